@@ -0,0 +1,280 @@
+//! Waiting for the asynchronous operations of VSS (`IVssAsync`).
+//!
+//! The status is polled until the operation is over, a deadline is reached or the user
+//! cancels it, in which case the operation is cancelled instead of being left to hang.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::error::VssError;
+
+/// The state of an asynchronous operation, as reported by `IVssAsync::QueryStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncStatus {
+    /// VSS_S_ASYNC_PENDING
+    Pending,
+    /// VSS_S_ASYNC_FINISHED
+    Finished,
+    /// VSS_S_ASYNC_CANCELLED
+    Cancelled,
+}
+
+impl AsyncStatus {
+    /// Whether the operation is over
+    pub fn is_done(&self) -> bool {
+        *self != AsyncStatus::Pending
+    }
+}
+
+/// An asynchronous operation in progress, `IVssAsync`
+pub trait AsyncOperation {
+    /// The status, without waiting. A failed operation is an error.
+    fn query_status(&mut self) -> Result<AsyncStatus, VssError>;
+
+    /// Request the cancellation and return the status it left the operation in,
+    /// `Finished` when it was over already
+    fn cancel(&mut self) -> Result<AsyncStatus, VssError>;
+}
+
+/// How the operation ended, see `AsyncOptions::wait`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncOutcome {
+    Finished,
+    /// Cancelled by VSS, or through the `CancelToken` of the options
+    Cancelled,
+    /// Cancelled after the timeout of the options
+    TimedOut(Duration),
+}
+
+impl AsyncOutcome {
+    /// Only a finished operation is a success
+    pub fn ok(self, operation: &'static str) -> Result<(), VssError> {
+        match self {
+            AsyncOutcome::Finished => Ok(()),
+            AsyncOutcome::Cancelled => Err(VssError::Cancelled { operation }),
+            AsyncOutcome::TimedOut(timeout) => Err(VssError::TimedOut { operation, timeout }),
+        }
+    }
+}
+
+impl fmt::Display for AsyncOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsyncOutcome::Finished => write!(f, "finished"),
+            AsyncOutcome::Cancelled => write!(f, "cancelled"),
+            AsyncOutcome::TimedOut(timeout) => {
+                write!(f, "cancelled after {}s", timeout.as_secs_f32())
+            }
+        }
+    }
+}
+
+/// A flag shared with whoever may cancel the operations, e.g. a Ctrl-C handler
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// How often `AsyncOptions::wait` polls the status by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How the asynchronous operations are waited for
+#[derive(Debug, Clone)]
+pub struct AsyncOptions {
+    /// The operation is cancelled when it lasts longer, never by default
+    pub timeout: Option<Duration>,
+    pub poll_interval: Duration,
+    pub cancel: CancelToken,
+}
+
+impl Default for AsyncOptions {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+impl AsyncOptions {
+    /// Poll the status of the operation until it is over. It is cancelled once the
+    /// timeout is reached or the token is cancelled, unless it finished meanwhile.
+    pub fn wait(&self, operation: &mut dyn AsyncOperation) -> Result<AsyncOutcome, VssError> {
+        let start = Instant::now();
+        loop {
+            match operation.query_status()? {
+                AsyncStatus::Finished => return Ok(AsyncOutcome::Finished),
+                AsyncStatus::Cancelled => return Ok(AsyncOutcome::Cancelled),
+                AsyncStatus::Pending => {}
+            }
+            let elapsed = start.elapsed();
+            let outcome = match self.timeout {
+                _ if self.cancel.is_cancelled() => Some(AsyncOutcome::Cancelled),
+                Some(timeout) if elapsed >= timeout => Some(AsyncOutcome::TimedOut(timeout)),
+                _ => None,
+            };
+            if let Some(outcome) = outcome {
+                tracing::warn!("Cancelling the asynchronous operation ...");
+                return match operation.cancel()? {
+                    AsyncStatus::Finished => Ok(AsyncOutcome::Finished),
+                    _ => Ok(outcome),
+                };
+            }
+            let remaining = self
+                .timeout
+                .map_or(self.poll_interval, |t| t.saturating_sub(elapsed));
+            thread::sleep(self.poll_interval.min(remaining));
+        }
+    }
+}
+
+/// Parse a timeout such as `90`, `90s`, `15m` or `2h`, in seconds without unit
+pub fn parse_timeout(s: &str) -> Result<Duration, VssError> {
+    let invalid = || {
+        VssError::InvalidArgument(format!(
+            "invalid timeout {}, expected a number of seconds, minutes or hours such as 15m",
+            s
+        ))
+    };
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 3600),
+        _ => (s, 1),
+    };
+    match number.parse::<u64>() {
+        Ok(number) if number > 0 => number
+            .checked_mul(unit)
+            .map(Duration::from_secs)
+            .ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fakebackend::FakeAsync;
+
+    fn options(timeout: Option<Duration>) -> AsyncOptions {
+        AsyncOptions {
+            timeout,
+            poll_interval: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_wait_finished() {
+        let mut operation = FakeAsync::new(Some(3));
+        let outcome = options(Some(Duration::from_secs(60)))
+            .wait(&mut operation)
+            .unwrap();
+        assert_eq!(outcome, AsyncOutcome::Finished);
+        assert_eq!(operation.polls, 3);
+        assert!(!operation.cancelled);
+        assert!(outcome.ok("DoSnapshotSet").is_ok());
+
+        let mut failed = FakeAsync::new(Some(1));
+        failed.error = Some(VssError::WriterErrorTimeout {
+            operation: "DoSnapshotSet",
+        });
+        assert!(matches!(
+            options(None).wait(&mut failed),
+            Err(VssError::WriterErrorTimeout { .. })
+        ));
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let timeout = Duration::from_millis(20);
+        let mut operation = FakeAsync::new(None);
+        let start = Instant::now();
+        let outcome = options(Some(timeout)).wait(&mut operation).unwrap();
+        assert!(start.elapsed() >= timeout);
+        assert_eq!(outcome, AsyncOutcome::TimedOut(timeout));
+        assert!(operation.cancelled);
+        assert_eq!(
+            outcome.ok("GatherWriterMetadata"),
+            Err(VssError::TimedOut {
+                operation: "GatherWriterMetadata",
+                timeout
+            })
+        );
+    }
+
+    #[test]
+    fn test_wait_cancel() {
+        let options = options(None);
+        options.cancel.cancel();
+        let mut operation = FakeAsync::new(None);
+        let outcome = options.wait(&mut operation).unwrap();
+        assert_eq!(outcome, AsyncOutcome::Cancelled);
+        assert!(operation.cancelled);
+        assert_eq!(operation.polls, 1);
+        assert_eq!(
+            outcome.ok("DoSnapshotSet"),
+            Err(VssError::Cancelled {
+                operation: "DoSnapshotSet"
+            })
+        );
+
+        // Cancelled by VSS
+        let mut operation = FakeAsync::new(None);
+        operation.cancelled = true;
+        assert_eq!(
+            AsyncOptions::default().wait(&mut operation).unwrap(),
+            AsyncOutcome::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_cancel_after_finish() {
+        // The operation finished between the last poll and the cancellation
+        struct Racing(FakeAsync);
+        impl AsyncOperation for Racing {
+            fn query_status(&mut self) -> Result<AsyncStatus, VssError> {
+                self.0.query_status()
+            }
+            fn cancel(&mut self) -> Result<AsyncStatus, VssError> {
+                self.0.polls = self.0.pending.unwrap();
+                self.0.cancel()
+            }
+        }
+        let options = options(None);
+        options.cancel.cancel();
+        let mut operation = Racing(FakeAsync::new(Some(5)));
+        assert_eq!(
+            options.wait(&mut operation).unwrap(),
+            AsyncOutcome::Finished
+        );
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_timeout("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_timeout("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_timeout("2h").unwrap(), Duration::from_secs(7200));
+        for invalid in ["", "0", "m", "-5s", "1.5h", "10d"] {
+            assert!(parse_timeout(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
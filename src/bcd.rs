@@ -0,0 +1,605 @@
+//! Model of the Backup Components Document, the XML saved with `IVssBackupComponents::SaveAsXML`.
+//!
+//! Attributes and elements without a typed field are kept in `other_attributes` and
+//! `other_elements`, so a parsed document serializes back without losing anything.
+
+use windows::{core::GUID, Win32::Storage::Vss::VSS_VOLSNAP_ATTR_TRANSPORTABLE};
+
+use crate::{
+    error::VssError,
+    xml::{Attributes, AttributesWriter, XmlElement, XmlNode},
+};
+
+/// The `BACKUP_COMPONENTS` root element
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupComponents {
+    pub version: Option<String>,
+    pub bootable_system_state_backup: Option<bool>,
+    pub select_components: Option<bool>,
+    /// "full", "differential", "incremental", "log" or "other"
+    pub backup_type: Option<String>,
+    pub partial_file_support: Option<bool>,
+    pub writer_components: Vec<WriterComponents>,
+    pub snapshot_sets: Vec<SnapshotSetDescription>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<XmlElement>,
+}
+
+/// The components of one writer included in the backup
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriterComponents {
+    pub instance_id: Option<GUID>,
+    pub writer_id: Option<GUID>,
+    pub components: Vec<Component>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<XmlElement>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Component {
+    pub logical_path: Option<String>,
+    pub component_name: String,
+    /// "database" or "filegroup"
+    pub component_type: Option<String>,
+    pub backup_succeeded: Option<bool>,
+    pub backup_options: Option<String>,
+    pub restore_options: Option<String>,
+    pub selected_for_restore: Option<bool>,
+    pub additional_restores: Option<bool>,
+    pub backup_stamp: Option<String>,
+    pub previous_backup_stamp: Option<String>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<XmlElement>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotSetDescription {
+    pub snapshot_set_id: GUID,
+    pub context: Option<i32>,
+    pub description: Option<String>,
+    pub metadata: Option<String>,
+    pub snapshots: Vec<SnapshotDescription>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<XmlElement>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDescription {
+    pub snapshot_id: GUID,
+    pub provider_id: Option<GUID>,
+    pub snapshot_attributes: Option<u32>,
+    pub originating_machine: Option<String>,
+    pub service_machine: Option<String>,
+    pub original_volume_name: Option<String>,
+    pub device_name: Option<String>,
+    pub exposed_name: Option<String>,
+    pub exposed_path: Option<String>,
+    pub timestamp: Option<i64>,
+    pub lun_mappings: Vec<LunMapping>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<XmlElement>,
+}
+
+/// The mapping of the original LUN to the shadow copy LUN of a hardware shadow copy
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LunMapping {
+    /// The `SOURCE_LUN` element and its LUN information
+    pub source_lun: Option<XmlElement>,
+    /// The `DESTINATION_LUN` element and its LUN information
+    pub destination_lun: Option<XmlElement>,
+    pub disk_extents: Vec<DiskExtent>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<XmlElement>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskExtent {
+    pub starting_offset: u64,
+    pub extent_length: u64,
+}
+
+fn check_name(e: &XmlElement, name: &str) -> Result<(), VssError> {
+    if e.name == name {
+        Ok(())
+    } else {
+        Err(VssError::Xml(format!(
+            "expected {}, found {}",
+            name, e.name
+        )))
+    }
+}
+
+fn element(name: &str, attributes: Vec<(String, String)>, children: Vec<XmlElement>) -> XmlElement {
+    XmlElement {
+        name: name.to_owned(),
+        attributes,
+        children: children.into_iter().map(XmlNode::Element).collect(),
+    }
+}
+
+/// Split the child elements, calling `typed` for each of them.
+/// The elements it gives back are kept in `other_elements`.
+fn children(
+    e: XmlElement,
+    mut typed: impl FnMut(XmlElement) -> Result<Option<XmlElement>, VssError>,
+) -> Result<Vec<XmlElement>, VssError> {
+    let mut other = Vec::new();
+    for child in e.children {
+        if let XmlNode::Element(child) = child {
+            if let Some(child) = typed(child)? {
+                other.push(child);
+            }
+        }
+    }
+    Ok(other)
+}
+
+impl BackupComponents {
+    /// Parse a document saved with `SaveAsXML`
+    pub fn from_xml(xml: &str) -> Result<Self, VssError> {
+        Self::from_element(XmlElement::parse(xml)?)
+    }
+
+    pub fn to_xml(&self) -> String {
+        self.to_element().to_xml()
+    }
+
+    pub fn from_element(mut e: XmlElement) -> Result<Self, VssError> {
+        check_name(&e, "BACKUP_COMPONENTS")?;
+        let mut attrs = Attributes::new("BACKUP_COMPONENTS", std::mem::take(&mut e.attributes));
+        let mut bc = BackupComponents {
+            version: attrs.take("version"),
+            bootable_system_state_backup: attrs.take_bool("bootableSystemStateBackup")?,
+            select_components: attrs.take_bool("selectComponents")?,
+            backup_type: attrs.take("backupType"),
+            partial_file_support: attrs.take_bool("partialFileSupport")?,
+            ..Default::default()
+        };
+        bc.other_attributes = attrs.into_rest();
+        bc.other_elements = children(e, |child| {
+            match child.name.as_str() {
+                "WRITER_COMPONENTS" => bc
+                    .writer_components
+                    .push(WriterComponents::from_element(child)?),
+                "SNAPSHOT_SET_DESCRIPTION" => bc
+                    .snapshot_sets
+                    .push(SnapshotSetDescription::from_element(child)?),
+                _ => return Ok(Some(child)),
+            }
+            Ok(None)
+        })?;
+        Ok(bc)
+    }
+
+    pub fn to_element(&self) -> XmlElement {
+        let attributes = AttributesWriter::default()
+            .str("version", self.version.as_deref())
+            .bool(
+                "bootableSystemStateBackup",
+                self.bootable_system_state_backup,
+            )
+            .bool("selectComponents", self.select_components)
+            .str("backupType", self.backup_type.as_deref())
+            .bool("partialFileSupport", self.partial_file_support)
+            .finish(&self.other_attributes);
+        let children = self
+            .writer_components
+            .iter()
+            .map(|w| w.to_element())
+            .chain(self.snapshot_sets.iter().map(|s| s.to_element()))
+            .chain(self.other_elements.iter().cloned())
+            .collect();
+        element("BACKUP_COMPONENTS", attributes, children)
+    }
+
+    /// All the shadow copies described by the document
+    pub fn snapshots(&self) -> impl Iterator<Item = &SnapshotDescription> {
+        self.snapshot_sets.iter().flat_map(|s| s.snapshots.iter())
+    }
+
+    /// The shadow copies `ImportSnapshots` can import
+    pub fn transportable_snapshots(&self) -> impl Iterator<Item = &SnapshotDescription> {
+        self.snapshot_sets
+            .iter()
+            .flat_map(|s| s.transportable_snapshots())
+    }
+
+    /// Refuse documents without any transportable shadow copy, there is nothing to import
+    pub fn check_importable(&self) -> Result<(), VssError> {
+        if self.transportable_snapshots().next().is_none() {
+            return Err(VssError::InvalidArgument(
+                "the Backup Components document contains no transportable shadow copy".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl WriterComponents {
+    pub fn from_element(mut e: XmlElement) -> Result<Self, VssError> {
+        check_name(&e, "WRITER_COMPONENTS")?;
+        let mut attrs = Attributes::new("WRITER_COMPONENTS", std::mem::take(&mut e.attributes));
+        let mut writer = WriterComponents {
+            instance_id: attrs.take_guid("instanceId")?,
+            writer_id: attrs.take_guid("writerId")?,
+            ..Default::default()
+        };
+        writer.other_attributes = attrs.into_rest();
+        writer.other_elements = children(e, |child| {
+            if child.name != "COMPONENT" {
+                return Ok(Some(child));
+            }
+            writer.components.push(Component::from_element(child)?);
+            Ok(None)
+        })?;
+        Ok(writer)
+    }
+
+    pub fn to_element(&self) -> XmlElement {
+        let attributes = AttributesWriter::default()
+            .guid("instanceId", self.instance_id)
+            .guid("writerId", self.writer_id)
+            .finish(&self.other_attributes);
+        let children = self
+            .components
+            .iter()
+            .map(|c| c.to_element())
+            .chain(self.other_elements.iter().cloned())
+            .collect();
+        element("WRITER_COMPONENTS", attributes, children)
+    }
+}
+
+impl Component {
+    pub fn from_element(mut e: XmlElement) -> Result<Self, VssError> {
+        check_name(&e, "COMPONENT")?;
+        let mut attrs = Attributes::new("COMPONENT", std::mem::take(&mut e.attributes));
+        let mut component = Component {
+            logical_path: attrs.take("logicalPath"),
+            component_name: attrs.required("componentName")?,
+            component_type: attrs.take("componentType"),
+            backup_succeeded: attrs.take_bool("backupSucceeded")?,
+            backup_options: attrs.take("backupOptions"),
+            restore_options: attrs.take("restoreOptions"),
+            selected_for_restore: attrs.take_bool("selectedForRestore")?,
+            additional_restores: attrs.take_bool("additionalRestores")?,
+            backup_stamp: attrs.take("backupStamp"),
+            previous_backup_stamp: attrs.take("previousBackupStamp"),
+            ..Default::default()
+        };
+        component.other_attributes = attrs.into_rest();
+        component.other_elements = children(e, |child| Ok(Some(child)))?;
+        Ok(component)
+    }
+
+    pub fn to_element(&self) -> XmlElement {
+        let attributes = AttributesWriter::default()
+            .str("logicalPath", self.logical_path.as_deref())
+            .str("componentName", Some(&self.component_name))
+            .str("componentType", self.component_type.as_deref())
+            .bool("backupSucceeded", self.backup_succeeded)
+            .str("backupOptions", self.backup_options.as_deref())
+            .str("restoreOptions", self.restore_options.as_deref())
+            .bool("selectedForRestore", self.selected_for_restore)
+            .bool("additionalRestores", self.additional_restores)
+            .str("backupStamp", self.backup_stamp.as_deref())
+            .str("previousBackupStamp", self.previous_backup_stamp.as_deref())
+            .finish(&self.other_attributes);
+        element("COMPONENT", attributes, self.other_elements.clone())
+    }
+
+    /// The full path of the component, `logical_path\component_name`
+    pub fn full_path(&self) -> String {
+        match self.logical_path.as_deref() {
+            Some(path) if !path.is_empty() => format!("{}\\{}", path, self.component_name),
+            _ => self.component_name.clone(),
+        }
+    }
+}
+
+impl SnapshotSetDescription {
+    pub fn from_element(mut e: XmlElement) -> Result<Self, VssError> {
+        check_name(&e, "SNAPSHOT_SET_DESCRIPTION")?;
+        let mut attrs = Attributes::new(
+            "SNAPSHOT_SET_DESCRIPTION",
+            std::mem::take(&mut e.attributes),
+        );
+        let mut set = SnapshotSetDescription {
+            snapshot_set_id: attrs.required_guid("snapshotSetId")?,
+            context: attrs.take_parse("context")?,
+            description: attrs.take("description"),
+            metadata: attrs.take("metadata"),
+            ..Default::default()
+        };
+        set.other_attributes = attrs.into_rest();
+        set.other_elements = children(e, |child| {
+            if child.name != "SNAPSHOT_DESCRIPTION" {
+                return Ok(Some(child));
+            }
+            set.snapshots
+                .push(SnapshotDescription::from_element(child)?);
+            Ok(None)
+        })?;
+        Ok(set)
+    }
+
+    pub fn to_element(&self) -> XmlElement {
+        let attributes = AttributesWriter::default()
+            .guid("snapshotSetId", Some(self.snapshot_set_id))
+            .display("context", self.context)
+            .str("description", self.description.as_deref())
+            .str("metadata", self.metadata.as_deref())
+            .finish(&self.other_attributes);
+        let children = self
+            .snapshots
+            .iter()
+            .map(|s| s.to_element())
+            .chain(self.other_elements.iter().cloned())
+            .collect();
+        element("SNAPSHOT_SET_DESCRIPTION", attributes, children)
+    }
+
+    /// The shadow copies created transportable, according to their attributes or the set context
+    pub fn transportable_snapshots(&self) -> impl Iterator<Item = &SnapshotDescription> {
+        let transportable = |attributes: i32| attributes & VSS_VOLSNAP_ATTR_TRANSPORTABLE.0 != 0;
+        let whole_set = self.context.is_some_and(transportable);
+        self.snapshots.iter().filter(move |s| {
+            whole_set
+                || s.snapshot_attributes
+                    .is_some_and(|a| transportable(a as i32))
+        })
+    }
+}
+
+impl SnapshotDescription {
+    pub fn from_element(mut e: XmlElement) -> Result<Self, VssError> {
+        check_name(&e, "SNAPSHOT_DESCRIPTION")?;
+        let mut attrs = Attributes::new("SNAPSHOT_DESCRIPTION", std::mem::take(&mut e.attributes));
+        let mut snapshot = SnapshotDescription {
+            snapshot_id: attrs.required_guid("snapshotId")?,
+            provider_id: attrs.take_guid("providerId")?,
+            snapshot_attributes: attrs.take_parse("snapshotAttributes")?,
+            originating_machine: attrs.take("originatingMachine"),
+            service_machine: attrs.take("serviceMachine"),
+            original_volume_name: attrs.take("originalVolumeName"),
+            device_name: attrs.take("deviceName"),
+            exposed_name: attrs.take("exposedName"),
+            exposed_path: attrs.take("exposedPath"),
+            timestamp: attrs.take_parse("timestamp")?,
+            ..Default::default()
+        };
+        snapshot.other_attributes = attrs.into_rest();
+        snapshot.other_elements = children(e, |child| {
+            if child.name != "LUN_MAPPING" {
+                return Ok(Some(child));
+            }
+            snapshot.lun_mappings.push(LunMapping::from_element(child)?);
+            Ok(None)
+        })?;
+        Ok(snapshot)
+    }
+
+    pub fn to_element(&self) -> XmlElement {
+        let attributes = AttributesWriter::default()
+            .guid("snapshotId", Some(self.snapshot_id))
+            .guid("providerId", self.provider_id)
+            .display("snapshotAttributes", self.snapshot_attributes)
+            .str("originatingMachine", self.originating_machine.as_deref())
+            .str("serviceMachine", self.service_machine.as_deref())
+            .str("originalVolumeName", self.original_volume_name.as_deref())
+            .str("deviceName", self.device_name.as_deref())
+            .str("exposedName", self.exposed_name.as_deref())
+            .str("exposedPath", self.exposed_path.as_deref())
+            .display("timestamp", self.timestamp)
+            .finish(&self.other_attributes);
+        let children = self
+            .lun_mappings
+            .iter()
+            .map(|l| l.to_element())
+            .chain(self.other_elements.iter().cloned())
+            .collect();
+        element("SNAPSHOT_DESCRIPTION", attributes, children)
+    }
+}
+
+impl LunMapping {
+    pub fn from_element(mut e: XmlElement) -> Result<Self, VssError> {
+        check_name(&e, "LUN_MAPPING")?;
+        let mut mapping = LunMapping {
+            other_attributes: std::mem::take(&mut e.attributes),
+            ..Default::default()
+        };
+        mapping.other_elements = children(e, |child| {
+            match child.name.as_str() {
+                "SOURCE_LUN" if mapping.source_lun.is_none() => mapping.source_lun = Some(child),
+                "DESTINATION_LUN" if mapping.destination_lun.is_none() => {
+                    mapping.destination_lun = Some(child)
+                }
+                "DISK_EXTENT" => mapping.disk_extents.push(DiskExtent::from_element(child)?),
+                _ => return Ok(Some(child)),
+            }
+            Ok(None)
+        })?;
+        Ok(mapping)
+    }
+
+    pub fn to_element(&self) -> XmlElement {
+        let children = self
+            .source_lun
+            .iter()
+            .chain(self.destination_lun.iter())
+            .cloned()
+            .chain(self.disk_extents.iter().map(|d| d.to_element()))
+            .chain(self.other_elements.iter().cloned())
+            .collect();
+        element("LUN_MAPPING", self.other_attributes.clone(), children)
+    }
+}
+
+impl DiskExtent {
+    pub fn from_element(e: XmlElement) -> Result<Self, VssError> {
+        check_name(&e, "DISK_EXTENT")?;
+        let mut attrs = Attributes::new("DISK_EXTENT", e.attributes);
+        let parse = |attrs: &mut Attributes, name| {
+            attrs
+                .take_parse(name)?
+                .ok_or_else(|| VssError::Xml(format!("DISK_EXTENT has no {} attribute", name)))
+        };
+        Ok(DiskExtent {
+            starting_offset: parse(&mut attrs, "startingOffset")?,
+            extent_length: parse(&mut attrs, "extentLength")?,
+        })
+    }
+
+    pub fn to_element(&self) -> XmlElement {
+        let attributes = AttributesWriter::default()
+            .display("startingOffset", Some(self.starting_offset))
+            .display("extentLength", Some(self.extent_length))
+            .finish(&[]);
+        element("DISK_EXTENT", attributes, Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0"?>
+<BACKUP_COMPONENTS xmlns="x-schema:#VssComponentMetadata" version="1.2" bootableSystemStateBackup="no" selectComponents="yes" backupType="full" partialFileSupport="no">
+    <WRITER_COMPONENTS instanceId="0bada1de-01a9-4625-8278-69e735f39dd2" writerId="a65faa63-5ea8-4ebc-9dbd-a0c4db26912a">
+        <COMPONENT logicalPath="Instance\Db" componentName="master" componentType="database" backupSucceeded="yes" backupStamp="stamp 1">
+            <ALTERNATE_LOCATION_MAPPING path="C:\db" filespec="*.mdf" alternatePath="D:\db"/>
+        </COMPONENT>
+    </WRITER_COMPONENTS>
+    <SNAPSHOT_SET_DESCRIPTION snapshotSetId="3808876b-c176-4e48-b7ae-04046e6cc752" context="0" description="nightly">
+        <SNAPSHOT_DESCRIPTION snapshotId="b5946137-7b9f-4925-af80-51abd60b20d5" providerId="b5946137-7b9f-4925-af80-51abd60b20d5" snapshotAttributes="4194304" originatingMachine="host.example.com" serviceMachine="host.example.com" originalVolumeName="\\?\Volume{c8a4e5a0-0000-0000-0000-100000000000}\" deviceName="\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy7" timestamp="133300000000000000" vendorSpecific="kept">
+            <LUN_MAPPING>
+                <SOURCE_LUN busType="11" deviceType="0" vendorId="ACME" productId="Disk"/>
+                <DESTINATION_LUN busType="11" deviceType="0" vendorId="ACME" productId="Clone"/>
+                <DISK_EXTENT startingOffset="1048576" extentLength="536870912"/>
+            </LUN_MAPPING>
+        </SNAPSHOT_DESCRIPTION>
+    </SNAPSHOT_SET_DESCRIPTION>
+</BACKUP_COMPONENTS>"#;
+
+    #[test]
+    fn test_parse() {
+        let bc = BackupComponents::from_xml(DOCUMENT).unwrap();
+        assert_eq!(bc.version.as_deref(), Some("1.2"));
+        assert_eq!(bc.select_components, Some(true));
+        assert_eq!(bc.bootable_system_state_backup, Some(false));
+        assert_eq!(bc.backup_type.as_deref(), Some("full"));
+        assert_eq!(
+            bc.other_attributes,
+            vec![(
+                "xmlns".to_owned(),
+                "x-schema:#VssComponentMetadata".to_owned()
+            )]
+        );
+
+        let writer = &bc.writer_components[0];
+        assert_eq!(
+            writer.writer_id,
+            Some(GUID::from_u128(0xa65faa63_5ea8_4ebc_9dbd_a0c4db26912a))
+        );
+        let component = &writer.components[0];
+        assert_eq!(component.full_path(), r"Instance\Db\master");
+        assert_eq!(component.backup_succeeded, Some(true));
+        assert_eq!(
+            component.other_elements[0].name,
+            "ALTERNATE_LOCATION_MAPPING"
+        );
+
+        let set = &bc.snapshot_sets[0];
+        assert_eq!(
+            set.snapshot_set_id,
+            GUID::from_u128(0x3808876b_c176_4e48_b7ae_04046e6cc752)
+        );
+        assert_eq!(set.context, Some(0));
+        assert_eq!(set.description.as_deref(), Some("nightly"));
+
+        let snapshot = bc.snapshots().next().unwrap();
+        assert_eq!(snapshot.snapshot_attributes, Some(0x0040_0000));
+        assert_eq!(snapshot.timestamp, Some(133300000000000000));
+        assert_eq!(
+            snapshot.device_name.as_deref(),
+            Some(r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy7")
+        );
+        assert_eq!(
+            snapshot.other_attributes,
+            vec![("vendorSpecific".to_owned(), "kept".to_owned())]
+        );
+        let mapping = &snapshot.lun_mappings[0];
+        assert_eq!(
+            mapping
+                .destination_lun
+                .as_ref()
+                .unwrap()
+                .attribute("productId"),
+            Some("Clone")
+        );
+        assert_eq!(
+            mapping.disk_extents,
+            vec![DiskExtent {
+                starting_offset: 1048576,
+                extent_length: 536870912
+            }]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bc = BackupComponents::from_xml(DOCUMENT).unwrap();
+        let xml = bc.to_xml();
+        assert_eq!(BackupComponents::from_xml(&xml).unwrap(), bc);
+        // The typed and generic trees agree on the serialized form
+        assert_eq!(XmlElement::parse(&xml).unwrap(), bc.to_element());
+        assert_eq!(BackupComponents::from_xml(&xml).unwrap().to_xml(), xml);
+    }
+
+    #[test]
+    fn test_check_importable() {
+        let bc = BackupComponents::from_xml(include_str!("../tests/fixtures/transportable.xml"))
+            .unwrap();
+        bc.check_importable().unwrap();
+        assert_eq!(bc.transportable_snapshots().count(), 2);
+
+        let bc =
+            BackupComponents::from_xml(include_str!("../tests/fixtures/not_transportable.xml"))
+                .unwrap();
+        assert!(matches!(
+            bc.check_importable(),
+            Err(VssError::InvalidArgument(_))
+        ));
+        // The test document has no transportable set context, but a transportable shadow copy
+        let mut bc = BackupComponents::from_xml(DOCUMENT).unwrap();
+        assert!(bc.check_importable().is_err());
+        bc.snapshot_sets[0].snapshots[0].snapshot_attributes = Some(0x20);
+        bc.check_importable().unwrap();
+        assert!(BackupComponents::default().check_importable().is_err());
+    }
+
+    #[test]
+    fn test_invalid_documents() {
+        assert!(matches!(
+            BackupComponents::from_xml("<WRITER_METADATA/>"),
+            Err(VssError::Xml(_))
+        ));
+        assert!(matches!(
+            BackupComponents::from_xml(
+                r#"<BACKUP_COMPONENTS><SNAPSHOT_SET_DESCRIPTION snapshotSetId="nope"/></BACKUP_COMPONENTS>"#
+            ),
+            Err(VssError::Xml(_))
+        ));
+        assert!(matches!(
+            BackupComponents::from_xml(r#"<BACKUP_COMPONENTS selectComponents="maybe"/>"#),
+            Err(VssError::Xml(_))
+        ));
+        assert!(matches!(
+            BackupComponents::from_xml(
+                r#"<BACKUP_COMPONENTS><WRITER_COMPONENTS><COMPONENT/></WRITER_COMPONENTS></BACKUP_COMPONENTS>"#
+            ),
+            Err(VssError::Xml(_))
+        ));
+    }
+}
@@ -0,0 +1,16 @@
+//! vshadow.exe in Rust. VSS only exists on Windows, the other targets build a stub
+//! so that the workspace builds everywhere.
+
+#[cfg(all(windows, feature = "com"))]
+mod vshadow;
+
+#[cfg(all(windows, feature = "com"))]
+fn main() {
+    vshadow::main()
+}
+
+#[cfg(not(all(windows, feature = "com")))]
+fn main() {
+    eprintln!("ERROR: the Volume Shadow Copy Service needs Windows and the com feature");
+    std::process::exit(1);
+}
@@ -0,0 +1,472 @@
+//! The vshadow commands, run against the COM backend
+
+use std::{path::Path, sync::OnceLock};
+
+use clap::CommandFactory;
+use vshadow_rs::{
+    asyncop::AsyncOptions,
+    cli::{
+        BreakArgs, CheckArgs, Cli, Command, CreateArgs, DeleteArgs, ExposeArgs, ImportArgs,
+        PruneArgs, QueryArgs, RestoreArgs, RevertArgs, StorageCommand, WritersArgs,
+    },
+    diffarea::ShadowStorage,
+    error::VssError,
+    exec::ShellRunner,
+    expose::ExposeTarget,
+    manifest::{load_document, manifest_path},
+    output::{write_snapshots, OutputFormat},
+    provider::set_provider_names,
+    restore::CommandRestorer,
+    revert::RevertStatus,
+    script::{generate_script, ScriptDialect},
+    utils::get_unique_volume_name_for_path,
+    vssclient::{BreakOptions, BrokenVolume, SnapshotSet, SnapshotSetOptions, VssClient},
+    vssprop::VSSProp,
+    writermetadata::WriterMetadata,
+};
+use windows::{
+    core::GUID,
+    Win32::{
+        Foundation::{BOOL, FALSE, TRUE},
+        Storage::Vss::{VSS_CTX_ALL, VSS_CTX_BACKUP},
+        System::Console::{SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT},
+    },
+};
+
+fn create(comm: &CreateArgs) -> Result<(VssClient, SnapshotSet), VssError> {
+    let options = SnapshotSetOptions {
+        persistent: comm.persistent,
+        no_writers: comm.no_writers,
+        differential: comm.differential,
+        plex: comm.plex,
+        transportable: comm.transportable.is_some(),
+        client_accessible: comm.client_accessible,
+        included_writers: comm.included_writers.clone(),
+        excluded_writers: comm.excluded_writers.clone(),
+        provider: comm.provider.clone(),
+        ..Default::default()
+    };
+    let volumes = comm
+        .volumes
+        .iter()
+        .map(|v| get_unique_volume_name_for_path(v))
+        .collect::<Result<Vec<_>, VssError>>()?;
+    let volumes = volumes.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+
+    let mut client = new_client();
+    client.initialize(options.context(), None, false)?;
+    let set = client.create_snapshot_set(&volumes, &options)?;
+    // Keep the client alive, non-persistent shadow copies are released with it
+    Ok((client, set))
+}
+
+fn delete(comm: &DeleteArgs) -> Result<(), VssError> {
+    let mut client = new_client();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    if comm.all {
+        tracing::debug!("(Option: Delete all shadow copies)");
+        client.delete_all_snapshots()
+    } else if let Some(snapshot_id) = comm.snapshot_id {
+        client.delete_snapshot(snapshot_id)
+    } else if let Some(snapshot_set_id) = comm.snapshot_set_id {
+        client.delete_snapshotset(snapshot_set_id)
+    } else {
+        Ok(())
+    }
+}
+
+/// Print the verdict of every shadow copy (--dry-run), or delete the ones not kept
+fn prune(comm: &PruneArgs) -> Result<(), VssError> {
+    let mut client = new_client();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    let plan = client.plan_retention(&comm.policy())?;
+    if comm.dry_run {
+        for (snapshot, verdict) in plan.decisions.iter() {
+            println!(
+                "* {:?} {} {}: {}",
+                snapshot.snapshot_id, snapshot.origin_vol_name, snapshot.create_time, verdict
+            );
+        }
+        return Ok(());
+    }
+    for snapshot_id in client.prune(&plan)? {
+        println!("* Deleted {:?}", snapshot_id);
+    }
+    Ok(())
+}
+
+fn storage(comm: &StorageCommand, format: OutputFormat) -> Result<(), VssError> {
+    let volume = |path: &str| get_unique_volume_name_for_path(path);
+    let storage = ShadowStorage::new()?;
+    match comm {
+        StorageCommand::List {
+            for_volume,
+            on_volume,
+        } => {
+            let for_volume = for_volume.as_deref().map(volume).transpose()?;
+            let on_volume = on_volume.as_deref().map(volume).transpose()?;
+            let diff_areas = storage.list(for_volume.as_deref(), on_volume.as_deref())?;
+            if diff_areas.is_empty() {
+                println!("No shadow copy storage associations were found");
+            }
+            for diff_area in diff_areas {
+                println!("{}\n", diff_area);
+            }
+        }
+        StorageCommand::Add(args) => storage.add(
+            &volume(&args.for_volume)?,
+            &volume(&args.on_volume)?,
+            args.max_size,
+        )?,
+        StorageCommand::Resize(args) => storage.resize(
+            &volume(&args.for_volume)?,
+            &volume(&args.on_volume)?,
+            args.max_size,
+        )?,
+        StorageCommand::Snapshots { volume: path } => {
+            print_snapshots(&storage.snapshots(&volume(path)?)?, format)?
+        }
+    }
+    Ok(())
+}
+
+fn list_providers() -> Result<(), VssError> {
+    let mut client = new_client();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    for provider in client.list_providers()? {
+        println!("{}\n", provider);
+    }
+    Ok(())
+}
+
+fn check_volumes(comm: &CheckArgs) -> Result<(), VssError> {
+    let mut client = new_client();
+    client.initialize(VSS_CTX_BACKUP, None, false)?;
+    let paths = comm.volumes.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+    let checks = client.check_volumes(&paths, comm.provider.as_ref())?;
+    for check in checks.iter() {
+        println!("* {}", check);
+    }
+    if !checks.iter().all(|c| c.is_supported()) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn query(comm: &QueryArgs) -> Result<Vec<VSSProp>, VssError> {
+    let mut client = new_client();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    let mut res = if let Some(snapshot_set_id) = comm.snapshot_set_id {
+        tracing::debug!("(Option: Query shadow copy set)");
+        client.query_snapshot_set(snapshot_set_id)?
+    } else if let Some(snapshot_id) = comm.snapshot_id {
+        tracing::debug!("(Option: Query shadow copy)");
+        let res = client.get_snapshot_properties(snapshot_id)?;
+        vec![res]
+    } else {
+        tracing::debug!("(Option: Query all shadow copies)");
+        client.query_snapshot_set(GUID::default())?
+    };
+    set_provider_names(&mut res, &client.list_providers()?);
+
+    Ok(res)
+}
+
+fn expose(comm: &ExposeArgs) -> Result<VSSProp, VssError> {
+    let target = match (&comm.local, &comm.remote) {
+        (Some(local), _) => ExposeTarget::local(local)?,
+        (None, Some(remote)) => ExposeTarget::remote(remote, comm.path.as_deref())?,
+        (None, None) => return Err(VssError::InvalidArgument("nothing to expose on".to_owned())),
+    };
+    let mut client = new_client();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    client.expose_snapshot(comm.snapshot_id, &target)
+}
+
+fn break_snapshot_set(comm: &BreakArgs) -> Result<Vec<BrokenVolume>, VssError> {
+    let options = BreakOptions {
+        writable: comm.writable,
+        revert_identity: comm.revert_identity,
+    };
+    let mut client = new_client();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    client.break_snapshot_set(comm.snapshot_set_id, &options)
+}
+
+fn import(comm: &ImportArgs) -> Result<Vec<VSSProp>, VssError> {
+    let xml = load_document(Path::new(&comm.file))?;
+    let mut client = new_client();
+    client.import_snapshots(&xml)
+}
+
+/// Revert the volume and print the progress until it is over
+fn revert(comm: &RevertArgs) -> Result<(), VssError> {
+    let mut client = new_client();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    for status in client.revert_to_snapshot(comm.snapshot_id, comm.force_dismount)? {
+        match status? {
+            RevertStatus::Pending => println!("- Revert in progress..."),
+            RevertStatus::Finished => println!("- Revert finished"),
+            RevertStatus::Cancelled => {
+                return Err(VssError::Cancelled {
+                    operation: "QueryRevertStatus",
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+fn writers() -> Result<Vec<WriterMetadata>, VssError> {
+    let mut client = new_client();
+    client.initialize(VSS_CTX_BACKUP, None, false)?;
+    client.initialize_writer_metadata()
+}
+
+fn print_writer_metadata(writer: &WriterMetadata, full: bool) {
+    println!("* WRITER \"{}\"", writer.writer_name);
+    println!("    - WriterId   = {:?}", writer.writer_id);
+    println!("    - InstanceId = {:?}", writer.instance_id);
+    println!(
+        "    - Supports restore events = {:?}",
+        writer.restore_method.writer_restore
+    );
+    println!("    - Usage type = {:?}", writer.usage_type);
+    println!("    - Source type = {:?}", writer.source_type);
+    println!("    - Restore method = {:?}", writer.restore_method.method);
+    for spec in writer.exclude_files.iter() {
+        println!("    - Excluded files: {}", spec);
+    }
+    for component in writer.components.iter() {
+        println!(
+            "    + Component \"{}\" ({:?})",
+            component.full_path(),
+            component.component_type
+        );
+        if !full {
+            continue;
+        }
+        for spec in component
+            .files
+            .iter()
+            .chain(component.database_files.iter())
+        {
+            println!("        - File: {}", spec);
+        }
+        for spec in component.log_files.iter() {
+            println!("        - Log file: {}", spec);
+        }
+        for dependency in component.dependencies.iter() {
+            println!("        - Depends on: {}", dependency);
+        }
+    }
+}
+
+fn list_writers(comm: &WritersArgs) -> Result<(), VssError> {
+    if comm.status {
+        let mut client = new_client();
+        client.initialize(VSS_CTX_BACKUP, None, false)?;
+        client.gather_writer_metadata()?;
+        for status in client.gather_writer_status()?.iter() {
+            println!("{}", status);
+        }
+    } else {
+        for writer in writers()?.iter() {
+            print_writer_metadata(writer, comm.full_metadata);
+        }
+    }
+    Ok(())
+}
+
+fn print_snapshots(props: &[VSSProp], format: OutputFormat) -> Result<(), VssError> {
+    Ok(write_snapshots(
+        &mut std::io::stdout().lock(),
+        props,
+        format,
+    )?)
+}
+
+/// Save the Backup Components document of the created set (-t, -bc) with its manifest
+fn save_backup_components(
+    comm: &CreateArgs,
+    client: &VssClient,
+    set: &SnapshotSet,
+) -> Result<(), VssError> {
+    let Some(path) = comm
+        .transportable
+        .as_ref()
+        .or(comm.backup_components.as_ref())
+    else {
+        return Ok(());
+    };
+    client.save_backup_components(Path::new(path), set)?;
+    eprintln!(
+        "Backup Components document saved into {}, manifest {}",
+        path,
+        manifest_path(Path::new(path)).display()
+    );
+    Ok(())
+}
+
+/// Save the document (-t, -bc), write the script (-script) and run the command (-exec)
+/// of the created set, stopping at the first error
+fn use_snapshot_set(
+    comm: &CreateArgs,
+    client: &VssClient,
+    set: &SnapshotSet,
+) -> Result<(), VssError> {
+    save_backup_components(comm, client, set)?;
+    write_script(comm, set)?;
+    if let Some(command) = &comm.exec {
+        client.exec_with_snapshot_set(set, command, &mut ShellRunner)?;
+    }
+    Ok(())
+}
+
+/// Write the -script file describing the created set
+fn write_script(comm: &CreateArgs, set: &SnapshotSet) -> Result<(), VssError> {
+    let Some(path) = &comm.script else {
+        return Ok(());
+    };
+    let dialect = comm
+        .script_format
+        .unwrap_or_else(|| ScriptDialect::from_path(Path::new(path)));
+    tracing::info!("- Generating the {:?} script file {}", dialect, path);
+    std::fs::write(path, generate_script(set.set_id, &set.snapshots, dialect))?;
+    Ok(())
+}
+
+fn wait_for_enter() {
+    println!("Press <ENTER> to continue...");
+    let mut line = String::new();
+    // A closed stdin just means there is nothing to wait for
+    let _ = std::io::stdin().read_line(&mut line);
+}
+
+/// Restore the document (-r), or only print what would be done (-rs)
+fn restore(comm: &RestoreArgs) -> Result<(), VssError> {
+    let xml = load_document(Path::new(&comm.file))?;
+    let mut client = new_client();
+    if comm.simulate {
+        for action in client.plan_restore(&xml)?.actions() {
+            println!("* {}", action);
+        }
+        return Ok(());
+    }
+    let mut restorer = comm.exec.as_ref().map(|command| CommandRestorer {
+        command: command.clone(),
+        runner: ShellRunner,
+    });
+    let report = client.restore(&xml, &mut restorer)?;
+    for status in report.components.iter() {
+        println!("* {}: {}", status.component, status.outcome);
+    }
+    if !report.is_success() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<(), VssError> {
+    match &cli.command {
+        Command::Create(comm) => {
+            let (client, set) = create(comm)?;
+            let used = use_snapshot_set(comm, &client, &set);
+            // As vshadow.exe, the backup is completed once the command has run, and as
+            // failed on the first error, so that the writers are never left waiting
+            let completed = client.complete_backup(used.is_ok());
+            used?;
+            completed?;
+            // Keep stdout machine-readable, the set id is in every shadow copy anyway
+            eprintln!("Snapshot set ID: {:?}", set.set_id);
+            print_snapshots(&set.snapshots, cli.output)?;
+            if cli.wait {
+                wait_for_enter();
+            }
+        }
+        Command::Query(comm) => {
+            let res = query(comm)?;
+            print_snapshots(&res, cli.output)?;
+        }
+        Command::Delete(comm) => delete(comm)?,
+        Command::Prune(comm) => prune(comm)?,
+        Command::Writers(comm) => list_writers(comm)?,
+        Command::Providers => list_providers()?,
+        Command::Check(comm) => check_volumes(comm)?,
+        Command::Storage(comm) => storage(comm, cli.output)?,
+        Command::Expose(comm) => {
+            let res = expose(comm)?;
+            print_snapshots(&[res], cli.output)?;
+        }
+        Command::Break(comm) => {
+            for volume in break_snapshot_set(comm)? {
+                println!(
+                    "* Shadow copy {:?} of {} is now a standalone volume (was {})",
+                    volume.snapshot_id, volume.original_volume, volume.snapshot_device_name
+                );
+            }
+        }
+        Command::Import(comm) => {
+            let res = import(comm)?;
+            print_snapshots(&res, cli.output)?;
+        }
+        Command::Restore(comm) => restore(comm)?,
+        Command::Revert(comm) => revert(comm)?,
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_owned();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+        }
+    }
+    Ok(())
+}
+
+/// How every client waits for the asynchronous VSS operations, set once in `main`
+static ASYNC_OPTIONS: OnceLock<AsyncOptions> = OnceLock::new();
+
+/// A client whose asynchronous operations are cancelled on timeout or Ctrl-C
+fn new_client() -> VssClient {
+    let mut client = VssClient::default();
+    if let Some(options) = ASYNC_OPTIONS.get() {
+        client.set_async_options(options.clone());
+    }
+    client
+}
+
+/// The first Ctrl-C cancels the pending VSS operation so the backup is aborted cleanly,
+/// the next one terminates the process
+unsafe extern "system" fn on_ctrl_c(ctrl_type: u32) -> BOOL {
+    match ASYNC_OPTIONS.get() {
+        Some(options)
+            if matches!(ctrl_type, CTRL_C_EVENT | CTRL_BREAK_EVENT)
+                && !options.cancel.is_cancelled() =>
+        {
+            eprintln!("Cancelling the VSS operation in progress...");
+            options.cancel.cancel();
+            TRUE
+        }
+        _ => FALSE,
+    }
+}
+
+pub fn main() {
+    let cli = Cli::parse_args(std::env::args()).unwrap_or_else(|e| e.exit());
+    ASYNC_OPTIONS.get_or_init(|| AsyncOptions {
+        timeout: cli.timeout,
+        ..Default::default()
+    });
+    if !unsafe { SetConsoleCtrlHandler(Some(on_ctrl_c), TRUE) }.as_bool() {
+        eprintln!("WARNING: Ctrl-C will not cancel the VSS operations");
+    }
+    if let Err(e) = run(&cli) {
+        eprintln!("ERROR: {}", e);
+        // Propagate the exit code of the -exec command
+        let code = match e {
+            VssError::CommandFailed {
+                code: Some(code), ..
+            } => code,
+            _ => 1,
+        };
+        std::process::exit(code);
+    }
+}
@@ -1,13 +1,47 @@
-use vshadow_rs::vssclient::VssClient;
+#[cfg(all(windows, feature = "com"))]
+use clap::Parser;
+#[cfg(all(windows, feature = "com"))]
+use vshadow_rs::{
+    error::VssError,
+    output::{write_snapshots, OutputFormat},
+    vssclient::VssClient,
+};
+#[cfg(all(windows, feature = "com"))]
 use windows::{core::GUID, Win32::Storage::Vss::VSS_CTX_ALL};
 
-fn main() {
+/// List all the shadow copies of the system
+#[cfg(all(windows, feature = "com"))]
+#[derive(Parser)]
+struct Args {
+    /// How the shadow copies are printed
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputFormat,
+}
+
+#[cfg(all(windows, feature = "com"))]
+fn run(args: &Args) -> Result<(), VssError> {
     let mut client = VssClient::default();
-    client.initialize(VSS_CTX_ALL, None, false).unwrap();
-    let props = client.query_snapshot_set(GUID::zeroed()).unwrap();
+    client.initialize(VSS_CTX_ALL, None, false)?;
+    let props = client.query_snapshot_set(GUID::zeroed())?;
+
+    Ok(write_snapshots(
+        &mut std::io::stdout().lock(),
+        &props,
+        args.output,
+    )?)
+}
 
-    for i in 0..props.len() {
-        println!("{i:05}--------------------------------------------------------------");
-        println!("{:#?}", props[i]);
+#[cfg(all(windows, feature = "com"))]
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = run(&args) {
+        eprintln!("ERROR: {}", e);
+        std::process::exit(1);
     }
 }
+
+#[cfg(not(all(windows, feature = "com")))]
+fn main() {
+    eprintln!("ERROR: the Volume Shadow Copy Service needs Windows and the com feature");
+    std::process::exit(1);
+}
@@ -0,0 +1,789 @@
+use std::str::FromStr;
+
+use chrono::Duration;
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
+use windows::core::GUID;
+
+use crate::{
+    asyncop::parse_timeout,
+    diffarea::MaxSize,
+    output::OutputFormat,
+    provider::ProviderSelector,
+    retention::{parse_age, RetentionPolicy, RetentionRules},
+    script::ScriptDialect,
+    utils::parse_guid,
+};
+
+/// Shadow copy management, compatible with the vshadow.exe options
+#[derive(Debug, Parser)]
+#[command(name = "vshadow", version, arg_required_else_help = true)]
+#[command(after_help = LEGACY_HELP)]
+pub struct Cli {
+    /// Wait for the user interaction before exiting. This will keep alive non-persistent shadows (-wait)
+    #[arg(long, global = true)]
+    pub wait: bool,
+    /// Verbose output, useful for diagnosis (-tracing)
+    #[arg(long, global = true)]
+    pub tracing: bool,
+    /// How the shadow copies are printed (-output)
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub output: OutputFormat,
+    /// Cancel the VSS operations lasting longer, e.g. 90s, 15m or 2h. Ctrl-C cancels them as well
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_timeout)]
+    pub timeout: Option<std::time::Duration>,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+const LEGACY_HELP: &str = "\
+The vshadow.exe options are accepted as well, e.g.:
+  vshadow -p -nw C: D:        create persistent shadow copies without writers
+  vshadow -q                  query all the shadow copies
+  vshadow -ds={SnapID}        delete a shadow copy
+  vshadow -el={SnapID},X:     expose a shadow copy as the X: drive";
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create a shadow copy set of the given volumes
+    Create(CreateArgs),
+    /// List the shadow copies (-q, -qx, -s)
+    Query(QueryArgs),
+    /// Delete shadow copies (-da, -dx, -ds)
+    Delete(DeleteArgs),
+    /// Delete the shadow copies a retention policy does not keep
+    Prune(PruneArgs),
+    /// Expose a shadow copy as a drive letter, a directory or a share (-el, -er)
+    Expose(ExposeArgs),
+    /// Break a shadow copy set into standalone volumes (-b, -bw)
+    Break(BreakArgs),
+    /// Import transportable shadow copies (-i)
+    Import(ImportArgs),
+    /// Restore the components of a backup (-r, -rs)
+    Restore(RestoreArgs),
+    /// Revert a volume to a shadow copy (-revert)
+    Revert(RevertArgs),
+    /// List the writers, their status or their metadata (-ws, -wm, -wm2)
+    Writers(WritersArgs),
+    /// List the registered shadow copy providers
+    Providers,
+    /// Check that the volumes can be shadow copied before creating a shadow copy set
+    Check(CheckArgs),
+    /// Manage the shadow copy storage of the system provider, like vssadmin
+    #[command(subcommand)]
+    Storage(StorageCommand),
+    /// Print the completion script of the given shell
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct CreateArgs {
+    /// Creates persistent shadow copies (-p)
+    #[arg(short, long)]
+    pub persistent: bool,
+    /// Creates shadow copies without involving writers (-nw)
+    #[arg(long)]
+    pub no_writers: bool,
+    /// Creates Differential Hardware shadow copies (-ad)
+    #[arg(long)]
+    pub differential: bool,
+    /// Creates Plex Hardware shadow copies (-ap)
+    #[arg(long)]
+    pub plex: bool,
+    /// Creates Shadow Copies for Shared Folders, client accessible (-scsf)
+    #[arg(long, conflicts_with_all = ["persistent", "no_writers"])]
+    pub client_accessible: bool,
+    /// Verifies that a writer or a `writer\component` is included (-wi)
+    #[arg(
+        long = "include-writer",
+        value_name = "WRITER",
+        conflicts_with = "no_writers"
+    )]
+    pub included_writers: Vec<String>,
+    /// The provider creating the shadow copies, by name or id, chosen by VSS by default
+    #[arg(long, value_name = "PROVIDER", value_parser = ProviderSelector::from_str)]
+    pub provider: Option<ProviderSelector>,
+    /// Excludes a writer or a `writer\component` from the shadow copy (-wx)
+    #[arg(
+        long = "exclude-writer",
+        value_name = "WRITER",
+        conflicts_with = "no_writers"
+    )]
+    pub excluded_writers: Vec<String>,
+    /// Creates a transportable shadow copy and saves the Backup Components document
+    /// into the given file, for a subsequent import or restore (-t)
+    #[arg(long, value_name = "FILE")]
+    pub transportable: Option<String>,
+    /// Saves the Backup Components document into the given file,
+    /// for a subsequent restore (-bc). Its manifest is saved into `FILE.json`.
+    #[arg(long, value_name = "FILE", conflicts_with = "transportable")]
+    pub backup_components: Option<String>,
+    /// Generates a CMD file containing environment variables related to the created
+    /// shadow copies (-script)
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<String>,
+    /// The language of the script, guessed from the file extension by default
+    #[arg(long, value_enum, requires = "script")]
+    pub script_format: Option<ScriptDialect>,
+    /// Executes a command between the shadow set creation and the program exit (-exec)
+    #[arg(long, value_name = "COMMAND")]
+    pub exec: Option<String>,
+    /// The volumes to shadow copy, e.g. C: or a mounted folder
+    #[arg(required = true)]
+    pub volumes: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// The provider that must support the volumes, any provider by default
+    #[arg(long, value_name = "PROVIDER", value_parser = ProviderSelector::from_str)]
+    pub provider: Option<ProviderSelector>,
+    /// The volumes, e.g. C:, a mounted folder or a \\server\share
+    #[arg(required = true)]
+    pub volumes: Vec<String>,
+}
+
+/// Which shadow copies to query, all of them by default
+#[derive(Debug, Args)]
+#[group(multiple = false)]
+pub struct QueryArgs {
+    /// All the shadow copies (-q)
+    #[arg(long)]
+    pub all: bool,
+    /// The shadow copies of the given set (-qx)
+    #[arg(long = "set", value_name = "SNAPSHOT_SET_ID", value_parser = parse_guid)]
+    pub snapshot_set_id: Option<GUID>,
+    /// The given shadow copy (-s)
+    #[arg(long = "snapshot", value_name = "SNAPSHOT_ID", value_parser = parse_guid)]
+    pub snapshot_id: Option<GUID>,
+}
+
+/// Which shadow copies to delete, one choice is required
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct DeleteArgs {
+    /// All the shadow copies (-da)
+    #[arg(long)]
+    pub all: bool,
+    /// The shadow copies of the given set (-dx)
+    #[arg(long = "set", value_name = "SNAPSHOT_SET_ID", value_parser = parse_guid)]
+    pub snapshot_set_id: Option<GUID>,
+    /// The given shadow copy (-ds)
+    #[arg(long = "snapshot", value_name = "SNAPSHOT_ID", value_parser = parse_guid)]
+    pub snapshot_id: Option<GUID>,
+}
+
+#[derive(Debug, Args)]
+pub struct PruneArgs {
+    /// Keep the given number of most recent shadow copies of each volume, whatever their age
+    #[arg(long, value_name = "N")]
+    pub keep_last: Option<usize>,
+    /// Keep the most recent shadow copy of the given number of last hours of each volume
+    #[arg(long, value_name = "N", default_value_t)]
+    pub keep_hourly: usize,
+    /// Keep the most recent shadow copy of the given number of last days of each volume
+    #[arg(long, value_name = "N", default_value_t)]
+    pub keep_daily: usize,
+    /// Keep the most recent shadow copy of the given number of last weeks of each volume
+    #[arg(long, value_name = "N", default_value_t)]
+    pub keep_weekly: usize,
+    /// Keep the most recent shadow copy of the given number of last months of each volume
+    #[arg(long, value_name = "N", default_value_t)]
+    pub keep_monthly: usize,
+    /// Delete the shadow copies older than the given age, e.g. 12h, 30d or 8w
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub max_age: Option<Duration>,
+    /// Never delete the shadow copies of the given provider
+    #[arg(long, value_name = "PROVIDER_ID", value_parser = parse_guid)]
+    pub keep_provider: Vec<GUID>,
+    /// Print what would be deleted without deleting anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl PruneArgs {
+    pub fn policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            rules: RetentionRules {
+                keep_last: self.keep_last,
+                keep_hourly: self.keep_hourly,
+                keep_daily: self.keep_daily,
+                keep_weekly: self.keep_weekly,
+                keep_monthly: self.keep_monthly,
+                max_age: self.max_age,
+            },
+            providers: self
+                .keep_provider
+                .iter()
+                .map(|id| (*id, RetentionRules::default()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ExposeArgs {
+    #[arg(value_name = "SNAPSHOT_ID", value_parser = parse_guid)]
+    pub snapshot_id: GUID,
+    /// An unused drive letter or an empty directory (-el)
+    #[arg(long, value_name = "DIR", required_unless_present = "remote")]
+    pub local: Option<String>,
+    /// An unused share name (-er)
+    #[arg(long, value_name = "SHARE", conflicts_with = "local")]
+    pub remote: Option<String>,
+    /// The path from the root of the shadow copy to share
+    #[arg(long, requires = "remote")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct BreakArgs {
+    #[arg(value_name = "SNAPSHOT_SET_ID", value_parser = parse_guid)]
+    pub snapshot_set_id: GUID,
+    /// Make the volumes writable (-bw)
+    #[arg(long)]
+    pub writable: bool,
+    /// Give back the volumes the disk identity of their original volume
+    #[arg(long)]
+    pub revert_identity: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// A Backup Components document created with the -t option,
+    /// checked against its manifest `FILE.json` if present
+    #[arg(value_name = "FILE")]
+    pub file: String,
+}
+
+#[derive(Debug, Args)]
+pub struct RevertArgs {
+    #[arg(value_name = "SNAPSHOT_ID", value_parser = parse_guid)]
+    pub snapshot_id: GUID,
+    /// Dismount the volume even when it is in use
+    #[arg(long)]
+    pub force_dismount: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    /// A Backup Components document created with the -t or -bc option,
+    /// checked against its manifest `FILE.json` if present
+    #[arg(value_name = "FILE")]
+    pub file: String,
+    /// Perform a simulated restore (-rs)
+    #[arg(long)]
+    pub simulate: bool,
+    /// Executes a command restoring the files of each component between PreRestore
+    /// and PostRestore, with the component described in its environment (-exec).
+    /// Without it the files must already be in place, only the writers are notified
+    #[arg(long, value_name = "COMMAND")]
+    pub exec: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StorageCommand {
+    /// List the shadow copy storage associations
+    List {
+        /// Only the storage of the shadow copies of the given volume
+        #[arg(long = "for", value_name = "VOLUME")]
+        for_volume: Option<String>,
+        /// Only the storage located on the given volume
+        #[arg(long = "on", value_name = "VOLUME")]
+        on_volume: Option<String>,
+    },
+    /// Store the shadow copies of a volume on another volume
+    Add(StorageArgs),
+    /// Change the maximum size of a shadow copy storage association
+    Resize(StorageArgs),
+    /// List the shadow copies of a volume created by the system provider
+    Snapshots {
+        #[arg(value_name = "VOLUME")]
+        volume: String,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct StorageArgs {
+    /// The volume whose shadow copies are stored
+    #[arg(long = "for", value_name = "VOLUME")]
+    pub for_volume: String,
+    /// The volume storing the shadow copies
+    #[arg(long = "on", value_name = "VOLUME")]
+    pub on_volume: String,
+    /// UNBOUNDED, a percentage of the storing volume such as 15%, or a size such as 10GB
+    #[arg(long, value_name = "SIZE", default_value = "UNBOUNDED", value_parser = MaxSize::from_str)]
+    pub max_size: MaxSize,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct WritersArgs {
+    /// List the writers status (-ws)
+    #[arg(long)]
+    pub status: bool,
+    /// List the writers metadata (-wm)
+    #[arg(long)]
+    pub metadata: bool,
+    /// List the writers detailed metadata, including the files (-wm2)
+    #[arg(long)]
+    pub full_metadata: bool,
+}
+
+impl Cli {
+    /// Parse the command line, the program name first.
+    ///
+    /// Both the subcommands and the vshadow.exe options are accepted;
+    /// the latter are translated to the former before parsing.
+    pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Cli, clap::Error> {
+        let args: Vec<String> = args.into_iter().collect();
+        if !is_legacy(&args) {
+            return Cli::try_parse_from(args);
+        }
+        Cli::try_parse_from(translate_legacy(&args)?)
+    }
+}
+
+fn error(kind: ErrorKind, message: String) -> clap::Error {
+    Cli::command().error(kind, message)
+}
+
+/// Whether the command line uses the vshadow.exe options rather than the subcommands
+fn is_legacy(args: &[String]) -> bool {
+    let Some(first) = args.get(1) else {
+        return false;
+    };
+    let subcommand = Cli::command()
+        .get_subcommands()
+        .any(|c| c.get_name() == first.as_str())
+        || first == "help";
+    !(subcommand || first.starts_with("--") || first == "-h" || first == "-V")
+}
+
+/// Split a `-key=value` option, an empty value is no value
+pub fn split_kv(kv: &str) -> (String, Option<String>) {
+    match kv.split_once('=') {
+        None => (kv.to_owned(), None),
+        Some((a, b)) => (
+            a.to_owned(),
+            if !b.is_empty() {
+                Some(b.to_owned())
+            } else {
+                None
+            },
+        ),
+    }
+}
+
+/// The subcommand built from the vshadow.exe options
+#[derive(Default)]
+struct Translation {
+    /// The subcommand and the vshadow.exe option which selected it
+    command: Option<(&'static str, String)>,
+    global: Vec<String>,
+    options: Vec<String>,
+    positionals: Vec<String>,
+}
+
+impl Translation {
+    /// Select the subcommand, only one operation can be performed at a time
+    fn command(&mut self, command: &'static str, option: &str) -> Result<&mut Self, clap::Error> {
+        match &self.command {
+            Some((c, first)) if *c != command => {
+                return Err(error(
+                    ErrorKind::ArgumentConflict,
+                    format!(
+                        "{} cannot be used with {}: only one operation can be performed at a time",
+                        option, first
+                    ),
+                ))
+            }
+            Some(_) => {}
+            None => self.command = Some((command, option.to_owned())),
+        }
+        Ok(self)
+    }
+
+    fn option(&mut self, option: &str) -> &mut Self {
+        self.options.push(option.to_owned());
+        self
+    }
+
+    /// An option with a value, in the `--key=value` form so that values starting with a dash
+    /// are not taken for options
+    fn value(&mut self, option: &str, value: &str) -> &mut Self {
+        self.options.push(format!("{}={}", option, value));
+        self
+    }
+
+    fn positional(&mut self, value: &str) -> &mut Self {
+        self.positionals.push(value.to_owned());
+        self
+    }
+}
+
+/// Translate the vshadow.exe options to the equivalent subcommand
+fn translate_legacy(args: &[String]) -> Result<Vec<String>, clap::Error> {
+    let mut t = Translation::default();
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "-?" | "/?" | "-h" => return Ok(vec![args[0].clone(), "--help".to_owned()]),
+            "-p" => t.command("create", arg)?.option("--persistent"),
+            "-nw" => t.command("create", arg)?.option("--no-writers"),
+            "-ad" => t.command("create", arg)?.option("--differential"),
+            "-ap" => t.command("create", arg)?.option("--plex"),
+            "-scsf" => t.command("create", arg)?.option("--client-accessible"),
+            "-q" => t.command("query", arg)?.option("--all"),
+            "-da" => t.command("delete", arg)?.option("--all"),
+            "-ws" => t.command("writers", arg)?.option("--status"),
+            "-wm" => t.command("writers", arg)?.option("--metadata"),
+            "-wm2" => t.command("writers", arg)?.option("--full-metadata"),
+            "-wait" => {
+                t.global.push("--wait".to_owned());
+                &mut t
+            }
+            "-tracing" => {
+                t.global.push("--tracing".to_owned());
+                &mut t
+            }
+            s if !s.starts_with('-') => t.command("create", "a volume")?.positional(s),
+            s => {
+                let (key, value) = split_kv(s);
+                let Some(v) = value else {
+                    return Err(match s.contains('=') {
+                        true => error(ErrorKind::InvalidValue, format!("{} requires a value", key)),
+                        false => error(
+                            ErrorKind::UnknownArgument,
+                            format!("unknown option {}", key),
+                        ),
+                    });
+                };
+                match key.as_str() {
+                    "-output" | "--output" => {
+                        t.global.push(format!("--output={}", v));
+                        &mut t
+                    }
+                    "-wi" => t.command("create", &key)?.value("--include-writer", &v),
+                    "-wx" => t.command("create", &key)?.value("--exclude-writer", &v),
+                    "-t" => t.command("create", &key)?.value("--transportable", &v),
+                    "-bc" => t.command("create", &key)?.value("--backup-components", &v),
+                    "-script" => t.command("create", &key)?.value("--script", &v),
+                    // -exec applies to -r as well, the command is given by the other options
+                    "-exec" => t.value("--exec", &v),
+                    "-qx" => t.command("query", &key)?.value("--set", &v),
+                    "-s" => t.command("query", &key)?.value("--snapshot", &v),
+                    "-dx" => t.command("delete", &key)?.value("--set", &v),
+                    "-ds" => t.command("delete", &key)?.value("--snapshot", &v),
+                    "-b" => t.command("break", &key)?.positional(&v),
+                    "-bw" => t
+                        .command("break", &key)?
+                        .option("--writable")
+                        .positional(&v),
+                    "-i" => t.command("import", &key)?.positional(&v),
+                    "-r" => t.command("restore", &key)?.positional(&v),
+                    "-revert" => t.command("revert", &key)?.positional(&v),
+                    "-rs" => t
+                        .command("restore", &key)?
+                        .option("--simulate")
+                        .positional(&v),
+                    "-el" => {
+                        // -el={SnapID},dir
+                        let (id, dir) = v.split_once(',').ok_or_else(|| {
+                            error(
+                                ErrorKind::InvalidValue,
+                                format!("{} requires {{SnapID}},dir", key),
+                            )
+                        })?;
+                        t.command("expose", &key)?
+                            .value("--local", dir)
+                            .positional(id)
+                    }
+                    "-er" => {
+                        // -er={SnapID},share[,path]
+                        let mut parts = v.splitn(3, ',');
+                        let id = parts.next().unwrap_or_default();
+                        let share = parts.next().ok_or_else(|| {
+                            error(
+                                ErrorKind::InvalidValue,
+                                format!("{} requires {{SnapID}},share[,path]", key),
+                            )
+                        })?;
+                        let t = t.command("expose", &key)?.value("--remote", share);
+                        match parts.next() {
+                            Some(path) => t.value("--path", path),
+                            None => t,
+                        }
+                        .positional(id)
+                    }
+                    _ => {
+                        return Err(error(
+                            ErrorKind::UnknownArgument,
+                            format!("unknown option {}", key),
+                        ))
+                    }
+                }
+            }
+        };
+    }
+
+    let mut args = vec![args[0].clone()];
+    args.append(&mut t.global);
+    if let Some((command, _)) = t.command {
+        args.push(command.to_owned());
+    }
+    args.append(&mut t.options);
+    if !t.positionals.is_empty() {
+        args.push("--".to_owned());
+        args.append(&mut t.positionals);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SET_ID: &str = "{3808876b-c176-4e48-b7ae-04046e6cc752}";
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::parse_args(
+            std::iter::once("vshadow")
+                .chain(args.iter().copied())
+                .map(|s| s.to_owned()),
+        )
+    }
+
+    #[test]
+    fn test_kv() {
+        assert_eq!(
+            split_kv("-bc={file.xml}"),
+            ("-bc".to_owned(), Some("{file.xml}".to_owned()))
+        );
+
+        assert_eq!(split_kv("-bc"), ("-bc".to_owned(), None));
+        assert_eq!(split_kv("-bc="), ("-bc".to_owned(), None));
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_legacy_create() {
+        let cli = parse(&["-p", "-nw", "-wait", "C:", "-script=-env.cmd", "D:"]).unwrap();
+        assert!(cli.wait);
+        assert_eq!(cli.output, OutputFormat::Table);
+        let Command::Create(create) = cli.command else {
+            panic!("{:?}", cli.command)
+        };
+        assert!(create.persistent && create.no_writers);
+        assert_eq!(create.script.as_deref(), Some("-env.cmd"));
+        assert_eq!(create.volumes, ["C:", "D:"]);
+
+        let cli = parse(&["-wi=Sql*", "-wx=System Writer", "-wx=x\\y", "C:"]).unwrap();
+        let Command::Create(create) = cli.command else {
+            panic!("{:?}", cli.command)
+        };
+        assert_eq!(create.included_writers, ["Sql*"]);
+        assert_eq!(create.excluded_writers, ["System Writer", "x\\y"]);
+    }
+
+    #[test]
+    fn test_legacy_commands() {
+        let guid = parse_guid(SET_ID).unwrap();
+        let query = |args: &[&str]| match parse(args).unwrap().command {
+            Command::Query(q) => q,
+            c => panic!("{:?}", c),
+        };
+        assert!(query(&["-q"]).all);
+        assert_eq!(
+            parse(&["-q", "-output=jsonl"]).unwrap().output,
+            OutputFormat::Jsonl
+        );
+        assert_eq!(
+            query(&[&format!("-qx={}", SET_ID)]).snapshot_set_id,
+            Some(guid)
+        );
+
+        match parse(&[&format!("-ds={}", SET_ID)]).unwrap().command {
+            Command::Delete(d) => assert_eq!(d.snapshot_id, Some(guid)),
+            c => panic!("{:?}", c),
+        }
+        match parse(&[&format!("-bw={}", SET_ID)]).unwrap().command {
+            Command::Break(b) => assert!(b.writable && b.snapshot_set_id == guid),
+            c => panic!("{:?}", c),
+        }
+        match parse(&[&format!("-er={},share,\\dir", SET_ID)])
+            .unwrap()
+            .command
+        {
+            Command::Expose(e) => {
+                assert_eq!(e.snapshot_id, guid);
+                assert_eq!(e.remote.as_deref(), Some("share"));
+                assert_eq!(e.path.as_deref(), Some("\\dir"));
+                assert_eq!(e.local, None);
+            }
+            c => panic!("{:?}", c),
+        }
+        match parse(&["-rs=backup.xml"]).unwrap().command {
+            Command::Restore(r) => assert!(r.simulate && r.file == "backup.xml"),
+            c => panic!("{:?}", c),
+        }
+        match parse(&["-exec=copy.cmd", "-r=backup.xml"]).unwrap().command {
+            Command::Restore(r) => assert_eq!(r.exec.as_deref(), Some("copy.cmd")),
+            c => panic!("{:?}", c),
+        }
+        match parse(&["-r=backup.xml"]).unwrap().command {
+            Command::Restore(r) => assert!(!r.simulate && r.exec.is_none()),
+            c => panic!("{:?}", c),
+        }
+        match parse(&[&format!("-revert={}", SET_ID)]).unwrap().command {
+            Command::Revert(r) => assert!(r.snapshot_id == guid && !r.force_dismount),
+            c => panic!("{:?}", c),
+        }
+        match parse(&["-wm2"]).unwrap().command {
+            Command::Writers(w) => assert!(w.full_metadata && !w.metadata),
+            c => panic!("{:?}", c),
+        }
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let cli = parse(&["create", "-p", "--exclude-writer", "Sql*", "C:"]).unwrap();
+        assert!(matches!(cli.command, Command::Create(c) if c.persistent && c.provider.is_none()));
+        let cli = parse(&["query", "--tracing"]).unwrap();
+        assert!(cli.tracing);
+        assert_eq!(cli.output, OutputFormat::Table);
+        assert!(matches!(cli.command, Command::Query(q) if !q.all));
+        assert!(matches!(
+            parse(&["expose", SET_ID, "--local", "X:"]).unwrap().command,
+            Command::Expose(_)
+        ));
+    }
+
+    #[test]
+    fn test_timeout() {
+        assert_eq!(parse(&["query"]).unwrap().timeout, None);
+        let cli = parse(&["create", "--timeout=15m", "C:"]).unwrap();
+        assert_eq!(cli.timeout, Some(std::time::Duration::from_secs(900)));
+    }
+
+    #[test]
+    fn test_check_subcommand() {
+        match parse(&["check", "C:", "D:\\mnt"]).unwrap().command {
+            Command::Check(c) => assert!(c.volumes == ["C:", "D:\\mnt"] && c.provider.is_none()),
+            c => panic!("{:?}", c),
+        }
+    }
+
+    #[test]
+    fn test_provider_option() {
+        let cli = parse(&["create", "--provider", "Contoso Provider", "C:"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Create(c) if c.provider == Some(ProviderSelector::Name("Contoso Provider".to_owned()))
+        ));
+        let cli = parse(&["create", &format!("--provider={}", SET_ID), "C:"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Create(c) if matches!(c.provider, Some(ProviderSelector::Id(_)))
+        ));
+    }
+
+    #[test]
+    fn test_providers_subcommand() {
+        assert!(matches!(
+            parse(&["providers"]).unwrap().command,
+            Command::Providers
+        ));
+    }
+
+    #[test]
+    fn test_storage_subcommand() {
+        match parse(&["storage", "add", "--for=C:", "--on", "D:", "--max-size=10%"])
+            .unwrap()
+            .command
+        {
+            Command::Storage(StorageCommand::Add(s)) => {
+                assert_eq!((s.for_volume.as_str(), s.on_volume.as_str()), ("C:", "D:"));
+                assert_eq!(s.max_size, MaxSize::Percent(10));
+            }
+            c => panic!("{:?}", c),
+        }
+        match parse(&["storage", "resize", "--for=C:", "--on=C:"])
+            .unwrap()
+            .command
+        {
+            Command::Storage(StorageCommand::Resize(s)) => {
+                assert_eq!(s.max_size, MaxSize::Unbounded)
+            }
+            c => panic!("{:?}", c),
+        }
+        assert!(matches!(
+            parse(&["storage", "list", "--on=D:"]).unwrap().command,
+            Command::Storage(StorageCommand::List {
+                for_volume: None,
+                on_volume: Some(_)
+            })
+        ));
+    }
+
+    #[test]
+    fn test_prune_subcommand() {
+        let cli = parse(&[
+            "prune",
+            "--keep-daily=7",
+            "--max-age=30d",
+            "--keep-provider",
+            SET_ID,
+            "--dry-run",
+        ])
+        .unwrap();
+        let Command::Prune(prune) = cli.command else {
+            panic!("{:?}", cli.command)
+        };
+        assert!(prune.dry_run);
+        let policy = prune.policy();
+        assert_eq!(policy.rules.keep_daily, 7);
+        assert_eq!(policy.rules.max_age, Some(Duration::days(30)));
+        assert_eq!(policy.providers.len(), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        let kind = |args: &[&str]| parse(args).unwrap_err().kind();
+        assert_eq!(kind(&["-q", "-da"]), ErrorKind::ArgumentConflict);
+        assert_eq!(kind(&["-p", "-ws"]), ErrorKind::ArgumentConflict);
+        assert_eq!(kind(&["-zz"]), ErrorKind::UnknownArgument);
+        assert_eq!(kind(&["-zz=1"]), ErrorKind::UnknownArgument);
+        assert_eq!(kind(&["-qx="]), ErrorKind::InvalidValue);
+        assert_eq!(kind(&["-qx=not-a-guid"]), ErrorKind::ValueValidation);
+        assert_eq!(kind(&["delete"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(
+            kind(&["delete", "--all", "--set", SET_ID]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            kind(&["-nw", "-wi=Sql*", "C:"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(kind(&["-p"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(kind(&["prune", "--max-age=30"]), ErrorKind::ValueValidation);
+        assert_eq!(kind(&["check"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(
+            kind(&["storage", "add", "--for=C:", "--on=D:", "--max-size=150%"]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            kind(&["storage", "resize", "--for=C:"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            kind(&["expose", SET_ID]),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+}
@@ -0,0 +1,1024 @@
+use std::{ffi::c_void, iter::once, ptr::null_mut};
+use tracing::debug;
+use windows::{
+    core::{ComInterface, Interface, Type, BSTR, GUID, HRESULT, PCWSTR},
+    Win32::{
+        Foundation::{BOOL, RPC_E_TOO_LATE, S_FALSE},
+        Storage::Vss::{
+            IVssAsync, IVssDifferentialSoftwareSnapshotMgmt, IVssEnumMgmtObject, IVssEnumObject,
+            IVssSnapshotMgmt, VssSnapshotMgmt, VSS_BACKUP_TYPE, VSS_CTX_ALL,
+            VSS_FILE_RESTORE_STATUS, VSS_HARDWARE_OPTIONS, VSS_MGMT_OBJECT_PROP, VSS_OBJECT_NONE,
+            VSS_OBJECT_PROVIDER, VSS_OBJECT_SNAPSHOT, VSS_OBJECT_TYPE, VSS_RESTORE_TYPE,
+            VSS_SNAPSHOT_CONTEXT, VSS_SNAPSHOT_PROP, VSS_S_ASYNC_CANCELLED, VSS_S_ASYNC_FINISHED,
+            VSS_S_ASYNC_PENDING, VSS_VOLUME_SNAPSHOT_ATTRIBUTES, VSS_WRITER_STATE,
+        },
+        System::Com::{
+            CoCreateInstance, CoInitialize, CoInitializeSecurity, CoTaskMemFree, CoUninitialize,
+            CLSCTX_ALL, EOAC_NONE, RPC_C_AUTHN_LEVEL_PKT_PRIVACY, RPC_C_IMP_LEVEL_IDENTIFY,
+        },
+    },
+};
+
+use crate::{
+    asyncop::{AsyncOperation, AsyncOptions, AsyncStatus},
+    diffarea::{DiffArea, DiffAreaBackend, DiffVolume, Volume, VSS_SWPRV_PROVIDER_ID},
+    error::{OperationContext, VssError},
+    provider::Provider,
+    restore::FileRestore,
+    revert::{RevertStatus, RevertStatusQuery},
+    utils::u16_to_string,
+    vssbackend::VssBackend,
+    vssbackupcomponent::{CreateVssBackupComponents, IVssBackupComponent},
+    vssbackupcomponentex::IVssBackupComponentEx3,
+    vssobject::{MgmtObjectProp, ProviderIter, SnapshotIter, SnapshotProp, VssDeallocator},
+    vssprop::VSSProp,
+    writerselection::SelectedComponent,
+    writerstatus::WriterStatus,
+};
+
+/// `VssBackend` implemented on top of the `IVssBackupComponents` COM object
+#[derive(Default)]
+pub struct ComBackend {
+    co_initialize_called: bool,
+    vss_object: Option<IVssBackupComponent>,
+    async_options: AsyncOptions,
+}
+
+impl Drop for ComBackend {
+    fn drop(&mut self) {
+        // Release the backup components before tearing down COM
+        self.vss_object.take();
+
+        if self.co_initialize_called {
+            unsafe {
+                CoUninitialize();
+            }
+        }
+    }
+}
+
+/// Initialize the COM infrastructure, only once per backend
+fn initialize_com(co_initialize_called: &mut bool) -> Result<(), VssError> {
+    if *co_initialize_called {
+        return Ok(());
+    }
+    unsafe {
+        // Initialize COM
+        CoInitialize(None).operation("CoInitialize")?;
+        *co_initialize_called = true;
+        // Initialize COM security
+        let result = CoInitializeSecurity(
+            None,                          //  Allow *all* VSS writers to communicate back!
+            -1,                            //  Default COM authentication service
+            None,                          //  Default COM authorization service
+            None,                          //  reserved parameter
+            RPC_C_AUTHN_LEVEL_PKT_PRIVACY, //  Strongest COM authentication level
+            RPC_C_IMP_LEVEL_IDENTIFY,      //  Minimal impersonation abilities
+            None,                          //  Default COM authentication settings
+            EOAC_NONE,                     //  No special options
+            None,                          //  Reserved parameter
+        );
+        // The security is already set when the process initialized COM before us
+        match result {
+            Err(e) if e.code() == RPC_E_TOO_LATE => Ok(()),
+            result => result.operation("CoInitializeSecurity"),
+        }
+    }
+}
+
+impl ComBackend {
+    /// The underlying backup components object
+    pub fn vss_object(&self, operation: &'static str) -> Result<&IVssBackupComponent, VssError> {
+        self.vss_object
+            .as_ref()
+            .ok_or(VssError::BadState { operation })
+    }
+}
+
+/// The `IVssAsync` returned by `operation`
+struct ComAsync {
+    p_async: IVssAsync,
+    operation: &'static str,
+}
+
+impl AsyncOperation for ComAsync {
+    fn query_status(&mut self) -> Result<AsyncStatus, VssError> {
+        let mut hr_result = HRESULT::default();
+        unsafe {
+            self.p_async
+                .QueryStatus(&mut hr_result, null_mut())
+                .operation("QueryStatus")?
+        };
+        match hr_result {
+            VSS_S_ASYNC_PENDING => Ok(AsyncStatus::Pending),
+            VSS_S_ASYNC_FINISHED => Ok(AsyncStatus::Finished),
+            VSS_S_ASYNC_CANCELLED => Ok(AsyncStatus::Cancelled),
+            hr => hr
+                .ok()
+                .operation(self.operation)
+                .map(|()| AsyncStatus::Finished),
+        }
+    }
+
+    fn cancel(&mut self) -> Result<AsyncStatus, VssError> {
+        // The success codes tell whether the operation was over already
+        let hr =
+            unsafe { (Interface::vtable(&self.p_async).Cancel)(Interface::as_raw(&self.p_async)) };
+        match hr {
+            VSS_S_ASYNC_FINISHED => Ok(AsyncStatus::Finished),
+            hr => hr.ok().operation("Cancel").map(|()| AsyncStatus::Cancelled),
+        }
+    }
+}
+
+/// Start an asynchronous operation of the backup components and wait for it to finish
+unsafe fn run_async(
+    operation: &'static str,
+    options: &AsyncOptions,
+    start: impl FnOnce(*mut *mut c_void) -> ::windows::core::Result<()>,
+) -> Result<(), VssError> {
+    let mut p_async = ::windows::core::zeroed::<IVssAsync>();
+    start(&mut p_async).operation(operation)?;
+    let p_async = IVssAsync::from_abi(p_async).operation(operation)?;
+    debug!("(Waiting for the asynchronous operation to finish...)");
+    options
+        .wait(&mut ComAsync { p_async, operation })?
+        .ok(operation)
+}
+
+/// A null-terminated UTF-16 copy of the string, for `PCWSTR` parameters
+fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(once(0)).collect()
+}
+
+/// The `PCWSTR` of an optional string converted with `to_wide`, null if missing
+fn to_pcwstr(s: &Option<Vec<u16>>) -> PCWSTR {
+    s.as_ref()
+        .map_or(PCWSTR::null(), |s| PCWSTR::from_raw(s.as_ptr()))
+}
+
+fn to_bstr(xml: Option<&str>) -> BSTR {
+    match xml {
+        Some(xml) => BSTR::from(xml),
+        None => BSTR::new(),
+    }
+}
+
+impl VssBackend for ComBackend {
+    fn create_backup_components(&mut self) -> Result<(), VssError> {
+        initialize_com(&mut self.co_initialize_called)?;
+        // Drop the previous instance before creating the new one
+        self.vss_object.take();
+        self.vss_object =
+            Some(unsafe { CreateVssBackupComponents().operation("CreateVssBackupComponents")? });
+        Ok(())
+    }
+
+    fn set_async_options(&mut self, options: AsyncOptions) {
+        self.async_options = options;
+    }
+
+    fn initialize_for_backup(&self, xml: Option<&str>) -> Result<(), VssError> {
+        const OP: &str = "InitializeForBackup";
+        unsafe {
+            self.vss_object(OP)?
+                .InitializeForBackup(to_bstr(xml))
+                .operation(OP)
+        }
+    }
+
+    fn initialize_for_restore(&self, xml: Option<&str>) -> Result<(), VssError> {
+        const OP: &str = "InitializeForRestore";
+        unsafe {
+            self.vss_object(OP)?
+                .InitializeForRestore(to_bstr(xml))
+                .operation(OP)
+        }
+    }
+
+    fn set_context(&self, context: VSS_SNAPSHOT_CONTEXT) -> Result<(), VssError> {
+        const OP: &str = "SetContext";
+        unsafe { self.vss_object(OP)?.SetContext(context).operation(OP) }
+    }
+
+    fn set_backup_state(
+        &self,
+        select_components: bool,
+        backup_bootable_system_state: bool,
+        backup_type: VSS_BACKUP_TYPE,
+        partial_file_support: bool,
+    ) -> Result<(), VssError> {
+        const OP: &str = "SetBackupState";
+        unsafe {
+            self.vss_object(OP)?
+                .SetBackupState(
+                    select_components,
+                    backup_bootable_system_state,
+                    backup_type,
+                    partial_file_support,
+                )
+                .operation(OP)
+        }
+    }
+
+    fn gather_writer_metadata(&self) -> Result<(), VssError> {
+        const OP: &str = "GatherWriterMetadata";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.GatherWriterMetadata(p_async)
+            })
+        }
+    }
+
+    fn get_writer_metadata_count(&self) -> Result<u32, VssError> {
+        let mut cnt_writer = 0;
+        const OP: &str = "GetWriterMetadataCount";
+        unsafe {
+            self.vss_object(OP)?
+                .GetWriterMetadataCount(&mut cnt_writer)
+                .operation(OP)?
+        };
+        Ok(cnt_writer)
+    }
+
+    fn get_writer_metadata(&self, index: u32) -> Result<String, VssError> {
+        const OP: &str = "GetWriterMetadata";
+        let mut instance_id = GUID::zeroed();
+        unsafe {
+            let metadata = self
+                .vss_object(OP)?
+                .GetWriterMetadata(index, &mut instance_id)
+                .operation(OP)?;
+            let xml = metadata.SaveAsXML().operation("SaveAsXML")?;
+            Ok(xml.to_string())
+        }
+    }
+
+    fn gather_writer_status(&self) -> Result<(), VssError> {
+        const OP: &str = "GatherWriterStatus";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.GatherWriterStatus(p_async)
+            })
+        }
+    }
+
+    fn get_writer_status_count(&self) -> Result<u32, VssError> {
+        const OP: &str = "GetWriterStatusCount";
+        let mut cnt_writer = 0;
+        unsafe {
+            self.vss_object(OP)?
+                .GetWriterStatusCount(&mut cnt_writer)
+                .operation(OP)?
+        };
+        Ok(cnt_writer)
+    }
+
+    fn get_writer_status(&self, index: u32) -> Result<WriterStatus, VssError> {
+        const OP: &str = "GetWriterStatus";
+        let vss_object = self.vss_object(OP)?;
+        let mut instance_id = GUID::zeroed();
+        let mut writer_id = GUID::zeroed();
+        let mut writer_name = BSTR::new();
+        let mut state = VSS_WRITER_STATE::default();
+        let mut failure = HRESULT::default();
+        let mut application_failure = HRESULT::default();
+        let mut application_message = BSTR::new();
+        unsafe {
+            // The application failure is only reported by IVssBackupComponentsEx3
+            match vss_object.cast::<IVssBackupComponentEx3>() {
+                Ok(ex3) => ex3.GetWriterStatus(
+                    index,
+                    &mut instance_id,
+                    &mut writer_id,
+                    &mut writer_name,
+                    &mut state,
+                    &mut failure,
+                    &mut application_failure,
+                    &mut application_message,
+                ),
+                Err(_) => vss_object.GetWriterStatus(
+                    index,
+                    &mut instance_id,
+                    &mut writer_id,
+                    &mut writer_name,
+                    &mut state,
+                    &mut failure,
+                ),
+            }
+            .operation(OP)?
+        };
+        Ok(WriterStatus {
+            writer_name: writer_name.to_string(),
+            writer_id,
+            instance_id,
+            state,
+            failure,
+            application_failure,
+            application_message: (!application_message.is_empty())
+                .then(|| application_message.to_string()),
+        })
+    }
+
+    fn free_writer_status(&self) -> Result<(), VssError> {
+        const OP: &str = "FreeWriterStatus";
+        unsafe { self.vss_object(OP)?.FreeWriterStatus().operation(OP) }
+    }
+
+    fn disable_writer_instances(&self, instance_ids: &[GUID]) -> Result<(), VssError> {
+        const OP: &str = "DisableWriterInstances";
+        unsafe {
+            self.vss_object(OP)?
+                .DisableWriterInstances(instance_ids)
+                .operation(OP)
+        }
+    }
+
+    fn add_component(&self, component: &SelectedComponent) -> Result<(), VssError> {
+        let logical_path = component.logical_path.as_deref().map(to_wide);
+        let name = to_wide(&component.name);
+        const OP: &str = "AddComponent";
+        unsafe {
+            self.vss_object(OP)?
+                .AddComponent(
+                    component.instance_id,
+                    component.writer_id,
+                    component.component_type.into(),
+                    to_pcwstr(&logical_path),
+                    PCWSTR::from_raw(name.as_ptr()),
+                )
+                .operation(OP)
+        }
+    }
+
+    fn set_restore_state(&self, restore_type: VSS_RESTORE_TYPE) -> Result<(), VssError> {
+        const OP: &str = "SetRestoreState";
+        unsafe {
+            self.vss_object(OP)?
+                .SetRestoreState(restore_type)
+                .operation(OP)
+        }
+    }
+
+    fn set_selected_for_restore(
+        &self,
+        component: &SelectedComponent,
+        selected: bool,
+    ) -> Result<(), VssError> {
+        let logical_path = component.logical_path.as_deref().map(to_wide);
+        let name = to_wide(&component.name);
+        const OP: &str = "SetSelectedForRestore";
+        unsafe {
+            self.vss_object(OP)?
+                .SetSelectedForRestore(
+                    component.writer_id,
+                    component.component_type.into(),
+                    to_pcwstr(&logical_path),
+                    PCWSTR::from_raw(name.as_ptr()),
+                    selected,
+                )
+                .operation(OP)
+        }
+    }
+
+    fn add_alternative_location_mapping(
+        &self,
+        component: &SelectedComponent,
+        file: &FileRestore,
+    ) -> Result<(), VssError> {
+        let logical_path = component.logical_path.as_deref().map(to_wide);
+        let name = to_wide(&component.name);
+        let path = to_wide(&file.path);
+        let filespec = to_wide(&file.filespec);
+        let destination = to_wide(file.destination());
+        const OP: &str = "AddAlternativeLocationMapping";
+        unsafe {
+            self.vss_object(OP)?
+                .AddAlternativeLocationMapping(
+                    component.writer_id,
+                    component.component_type.into(),
+                    to_pcwstr(&logical_path),
+                    PCWSTR::from_raw(name.as_ptr()),
+                    PCWSTR::from_raw(path.as_ptr()),
+                    PCWSTR::from_raw(filespec.as_ptr()),
+                    file.recursive,
+                    PCWSTR::from_raw(destination.as_ptr()),
+                )
+                .operation(OP)
+        }
+    }
+
+    fn pre_restore(&self) -> Result<(), VssError> {
+        const OP: &str = "PreRestore";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.PreRestore(p_async)
+            })
+        }
+    }
+
+    fn set_file_restore_status(
+        &self,
+        component: &SelectedComponent,
+        status: VSS_FILE_RESTORE_STATUS,
+    ) -> Result<(), VssError> {
+        let logical_path = component.logical_path.as_deref().map(to_wide);
+        let name = to_wide(&component.name);
+        const OP: &str = "SetFileRestoreStatus";
+        unsafe {
+            self.vss_object(OP)?
+                .SetFileRestoreStatus(
+                    component.writer_id,
+                    component.component_type.into(),
+                    to_pcwstr(&logical_path),
+                    PCWSTR::from_raw(name.as_ptr()),
+                    status,
+                )
+                .operation(OP)
+        }
+    }
+
+    fn post_restore(&self) -> Result<(), VssError> {
+        const OP: &str = "PostRestore";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.PostRestore(p_async)
+            })
+        }
+    }
+
+    fn start_snapshot_set(&self) -> Result<GUID, VssError> {
+        let mut set_id = GUID::zeroed();
+        const OP: &str = "StartSnapshotSet";
+        unsafe {
+            self.vss_object(OP)?
+                .StartSnapshotSet(&mut set_id)
+                .operation(OP)?
+        };
+        Ok(set_id)
+    }
+
+    fn add_to_snapshot_set(&self, volume: &str, provider_id: GUID) -> Result<GUID, VssError> {
+        let volume = to_wide(volume);
+        let mut snapshot_id = GUID::zeroed();
+        const OP: &str = "AddToSnapshotSet";
+        unsafe {
+            self.vss_object(OP)?
+                .AddToSnapshotSet(
+                    PCWSTR::from_raw(volume.as_ptr()),
+                    provider_id,
+                    &mut snapshot_id,
+                )
+                .operation(OP)?
+        };
+        Ok(snapshot_id)
+    }
+
+    fn is_volume_supported(&self, volume: &str, provider_id: GUID) -> Result<bool, VssError> {
+        const OP: &str = "IsVolumeSupported";
+        let volume = to_wide(volume);
+        let mut supported = BOOL::default();
+        unsafe {
+            self.vss_object(OP)?
+                .IsVolumeSupported(
+                    provider_id,
+                    PCWSTR::from_raw(volume.as_ptr()),
+                    &mut supported,
+                )
+                .operation(OP)?;
+        }
+        Ok(supported.as_bool())
+    }
+
+    fn prepare_for_backup(&self) -> Result<(), VssError> {
+        const OP: &str = "PrepareForBackup";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.PrepareForBackup(p_async)
+            })
+        }
+    }
+
+    fn do_snapshot_set(&self) -> Result<(), VssError> {
+        const OP: &str = "DoSnapshotSet";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.DoSnapshotSet(p_async)
+            })
+        }
+    }
+
+    fn set_backup_succeeded(
+        &self,
+        component: &SelectedComponent,
+        succeeded: bool,
+    ) -> Result<(), VssError> {
+        let logical_path = component.logical_path.as_deref().map(to_wide);
+        let name = to_wide(&component.name);
+        const OP: &str = "SetBackupSucceeded";
+        unsafe {
+            self.vss_object(OP)?
+                .SetBackupSucceeded(
+                    component.instance_id,
+                    component.writer_id,
+                    component.component_type.into(),
+                    to_pcwstr(&logical_path),
+                    PCWSTR::from_raw(name.as_ptr()),
+                    succeeded,
+                )
+                .operation(OP)
+        }
+    }
+
+    fn backup_complete(&self) -> Result<(), VssError> {
+        const OP: &str = "BackupComplete";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.BackupComplete(p_async)
+            })
+        }
+    }
+
+    fn abort_backup(&self) -> Result<(), VssError> {
+        const OP: &str = "AbortBackup";
+        unsafe { self.vss_object(OP)?.AbortBackup().operation(OP) }
+    }
+
+    fn query(&self) -> Result<Vec<VSSProp>, VssError> {
+        const OP: &str = "Query";
+        let mut p_ienum_snapshots = ::windows::core::zeroed::<IVssEnumObject>();
+        let hr_result = unsafe {
+            self.vss_object(OP)?.Query(
+                GUID::zeroed(),
+                VSS_OBJECT_NONE,
+                VSS_OBJECT_SNAPSHOT,
+                &mut p_ienum_snapshots,
+            )
+        };
+
+        let mut result = Vec::new();
+        // If there are no shadow copies, just return
+        if hr_result == S_FALSE {
+            return Ok(result);
+        }
+        hr_result.ok().operation(OP)?;
+
+        let p_ienum_snapshots = unsafe { IVssEnumObject::from_raw(p_ienum_snapshots) };
+        result = collect_snapshots(p_ienum_snapshots, OP)?;
+        Ok(result)
+    }
+
+    fn query_providers(&self) -> Result<Vec<Provider>, VssError> {
+        const OP: &str = "Query";
+        let mut p_ienum_providers = ::windows::core::zeroed::<IVssEnumObject>();
+        let hr_result = unsafe {
+            self.vss_object(OP)?.Query(
+                GUID::zeroed(),
+                VSS_OBJECT_NONE,
+                VSS_OBJECT_PROVIDER,
+                &mut p_ienum_providers,
+            )
+        };
+        let mut result = Vec::new();
+        // There is always the system provider, but be consistent with `query`
+        if hr_result == S_FALSE {
+            return Ok(result);
+        }
+        hr_result.ok().operation(OP)?;
+
+        let p_ienum_providers = unsafe { IVssEnumObject::from_raw(p_ienum_providers) };
+        for provider in ProviderIter::<_, VssDeallocator>::new(p_ienum_providers, OP) {
+            result.push(provider?.to_provider());
+        }
+        Ok(result)
+    }
+
+    fn delete_snapshots(
+        &self,
+        id: GUID,
+        object_type: VSS_OBJECT_TYPE,
+        force: bool,
+    ) -> Result<i32, VssError> {
+        let mut l_snapshot = 0;
+        let mut id_non_deleted_snapshot_id = GUID::default();
+        const OP: &str = "DeleteSnapshots";
+        let hr_result = unsafe {
+            self.vss_object(OP)?.DeleteSnapshots(
+                id,
+                object_type,
+                BOOL::from(force),
+                &mut l_snapshot,
+                &mut id_non_deleted_snapshot_id,
+            )
+        };
+
+        if hr_result.is_err() {
+            tracing::debug!(
+                "-Last shadow copy that could not be deleted:{:?}",
+                id_non_deleted_snapshot_id
+            );
+        }
+        hr_result.map(|_| l_snapshot).operation(OP)
+    }
+
+    fn get_snapshot_properties(&self, snapshot_id: GUID) -> Result<VSSProp, VssError> {
+        let mut prop = VSS_SNAPSHOT_PROP::default();
+        const OP: &str = "GetSnapshotProperties";
+        unsafe {
+            self.vss_object(OP)?
+                .GetSnapshotProperties(snapshot_id, &mut prop)
+                .operation(OP)?
+        };
+        let prop = unsafe { SnapshotProp::<VssDeallocator>::new(prop) };
+        Ok(prop.to_vssprop())
+    }
+
+    fn import_snapshots(&self) -> Result<(), VssError> {
+        const OP: &str = "ImportSnapshots";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            run_async(OP, &self.async_options, |p_async| {
+                vss_object.ImportSnapshots(p_async)
+            })
+        }
+    }
+
+    fn save_as_xml(&self) -> Result<String, VssError> {
+        const OP: &str = "SaveAsXML";
+        unsafe {
+            let xml = self.vss_object(OP)?.SaveAsXML().operation(OP)?;
+            Ok(xml.to_string())
+        }
+    }
+
+    fn break_snapshot_set(
+        &self,
+        set_id: GUID,
+        flags: VSS_HARDWARE_OPTIONS,
+    ) -> Result<(), VssError> {
+        const OP: &str = "BreakSnapshotSet";
+        const OP_EX: &str = "BreakSnapshotSetEx";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            // BreakSnapshotSetEx is only exposed by the newer interfaces
+            match vss_object.cast::<IVssBackupComponentEx3>() {
+                Ok(ex3) => run_async(OP_EX, &self.async_options, |p_async| {
+                    ex3.BreakSnapshotSetEx(set_id, flags.0 as u32, p_async)
+                }),
+                Err(_) if flags.0 == 0 => vss_object.BreakSnapshotSet(set_id).operation(OP),
+                Err(e) => Err(e).operation(OP_EX),
+            }
+        }
+    }
+
+    fn expose_snapshot(
+        &self,
+        snapshot_id: GUID,
+        path_from_root: Option<&str>,
+        attributes: VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+        expose: Option<&str>,
+    ) -> Result<String, VssError> {
+        let path_from_root = path_from_root.map(to_wide);
+        let expose = expose.map(to_wide);
+        const OP: &str = "ExposeSnapshot";
+        unsafe {
+            let exposed = self
+                .vss_object(OP)?
+                .ExposeSnapshot(
+                    snapshot_id,
+                    to_pcwstr(&path_from_root),
+                    attributes.0,
+                    to_pcwstr(&expose),
+                )
+                .operation(OP)?;
+            let name = u16_to_string(exposed.0);
+            CoTaskMemFree(Some(exposed.0 as *const c_void));
+            Ok(name)
+        }
+    }
+
+    fn revert_to_snapshot(&self, snapshot_id: GUID, force_dismount: bool) -> Result<(), VssError> {
+        const OP: &str = "RevertToSnapshot";
+        unsafe {
+            self.vss_object(OP)?
+                .RevertToSnapshot(snapshot_id, BOOL::from(force_dismount))
+                .operation(OP)
+        }
+    }
+
+    fn query_revert_status(&self, volume: &str) -> Result<Box<dyn RevertStatusQuery>, VssError> {
+        let volume = to_wide(volume);
+        const OP: &str = "QueryRevertStatus";
+        let vss_object = self.vss_object(OP)?;
+        unsafe {
+            let mut p_async = ::windows::core::zeroed::<IVssAsync>();
+            vss_object
+                .QueryRevertStatus(PCWSTR::from_raw(volume.as_ptr()), &mut p_async)
+                .operation(OP)?;
+            let p_async = IVssAsync::from_abi(p_async).operation(OP)?;
+            Ok(Box::new(ComRevertStatus(ComAsync {
+                p_async,
+                operation: OP,
+            })))
+        }
+    }
+}
+
+/// The `IVssAsync` of a revert in progress
+struct ComRevertStatus(ComAsync);
+
+impl RevertStatusQuery for ComRevertStatus {
+    fn query_status(&mut self) -> Result<RevertStatus, VssError> {
+        self.0.query_status()
+    }
+}
+
+/// The shadow copies of the enumeration of `Query` or `QuerySnapshotsByVolume`
+fn collect_snapshots(
+    p_ienum_snapshots: IVssEnumObject,
+    operation: &'static str,
+) -> Result<Vec<VSSProp>, VssError> {
+    SnapshotIter::<_, VssDeallocator>::new(p_ienum_snapshots, operation)
+        .map(|snapshot| snapshot.map(|s| s.to_vssprop()))
+        .collect()
+}
+
+/// `DiffAreaBackend` implemented on top of the `IVssSnapshotMgmt` COM object
+/// and the `IVssDifferentialSoftwareSnapshotMgmt` interface of the system provider
+pub struct ComDiffAreaBackend {
+    co_initialize_called: bool,
+    mgmt: Option<IVssSnapshotMgmt>,
+    diff: Option<IVssDifferentialSoftwareSnapshotMgmt>,
+}
+
+impl Drop for ComDiffAreaBackend {
+    fn drop(&mut self) {
+        // Release the interfaces before tearing down COM
+        self.diff.take();
+        self.mgmt.take();
+
+        if self.co_initialize_called {
+            unsafe {
+                CoUninitialize();
+            }
+        }
+    }
+}
+
+impl ComDiffAreaBackend {
+    pub fn new() -> Result<Self, VssError> {
+        let mut backend = Self {
+            co_initialize_called: false,
+            mgmt: None,
+            diff: None,
+        };
+        initialize_com(&mut backend.co_initialize_called)?;
+        unsafe {
+            let mgmt: IVssSnapshotMgmt = CoCreateInstance(&VssSnapshotMgmt, None, CLSCTX_ALL)
+                .operation("CoCreateInstance")?;
+            const OP: &str = "GetProviderMgmtInterface";
+            let diff = mgmt
+                .GetProviderMgmtInterface(
+                    VSS_SWPRV_PROVIDER_ID,
+                    &IVssDifferentialSoftwareSnapshotMgmt::IID,
+                )
+                .operation(OP)?
+                .cast::<IVssDifferentialSoftwareSnapshotMgmt>()
+                .operation(OP)?;
+            backend.mgmt = Some(mgmt);
+            backend.diff = Some(diff);
+        }
+        Ok(backend)
+    }
+
+    fn mgmt(&self, operation: &'static str) -> Result<&IVssSnapshotMgmt, VssError> {
+        self.mgmt.as_ref().ok_or(VssError::BadState { operation })
+    }
+
+    fn diff(
+        &self,
+        operation: &'static str,
+    ) -> Result<&IVssDifferentialSoftwareSnapshotMgmt, VssError> {
+        self.diff.as_ref().ok_or(VssError::BadState { operation })
+    }
+}
+
+/// The objects of an enumeration returned by a management query, converted by `convert`.
+/// The objects are freed once converted, or skipped when `convert` returns `None`.
+fn collect_mgmt_objects<T>(
+    p_enum: ::windows::core::Result<IVssEnumMgmtObject>,
+    operation: &'static str,
+    convert: impl Fn(&MgmtObjectProp<VssDeallocator>) -> Option<T>,
+) -> Result<Vec<T>, VssError> {
+    let p_enum = match p_enum {
+        Ok(p_enum) => p_enum,
+        // S_FALSE and no enumeration when there is nothing to return
+        Err(e) if e.code().is_ok() => return Ok(Vec::new()),
+        Err(e) => return Err(e).operation(operation),
+    };
+    let mut result = Vec::new();
+    loop {
+        let mut props = [VSS_MGMT_OBJECT_PROP::default(); 1];
+        let mut fetched = 0;
+        unsafe { p_enum.Next(&mut props, &mut fetched).operation(operation)? };
+        if fetched == 0 {
+            break;
+        }
+        let prop = unsafe { MgmtObjectProp::new(props[0]) };
+        result.extend(convert(&prop));
+    }
+    Ok(result)
+}
+
+impl DiffAreaBackend for ComDiffAreaBackend {
+    fn query_volumes_supported_for_snapshots(&self) -> Result<Vec<Volume>, VssError> {
+        const OP: &str = "QueryVolumesSupportedForSnapshots";
+        let p_enum = unsafe {
+            self.mgmt(OP)?
+                .QueryVolumesSupportedForSnapshots(VSS_SWPRV_PROVIDER_ID, VSS_CTX_ALL.0)
+        };
+        collect_mgmt_objects(p_enum, OP, MgmtObjectProp::to_volume)
+    }
+
+    fn query_volumes_supported_for_diff_areas(
+        &self,
+        volume: &str,
+    ) -> Result<Vec<DiffVolume>, VssError> {
+        const OP: &str = "QueryVolumesSupportedForDiffAreas";
+        let volume = to_wide(volume);
+        let p_enum = unsafe {
+            self.diff(OP)?
+                .QueryVolumesSupportedForDiffAreas(volume.as_ptr())
+        };
+        collect_mgmt_objects(p_enum, OP, MgmtObjectProp::to_diff_volume)
+    }
+
+    fn query_diff_areas_for_volume(&self, volume: &str) -> Result<Vec<DiffArea>, VssError> {
+        const OP: &str = "QueryDiffAreasForVolume";
+        let volume = to_wide(volume);
+        let p_enum = unsafe { self.diff(OP)?.QueryDiffAreasForVolume(volume.as_ptr()) };
+        collect_mgmt_objects(p_enum, OP, MgmtObjectProp::to_diff_area)
+    }
+
+    fn query_diff_areas_on_volume(&self, volume: &str) -> Result<Vec<DiffArea>, VssError> {
+        const OP: &str = "QueryDiffAreasOnVolume";
+        let volume = to_wide(volume);
+        let p_enum = unsafe { self.diff(OP)?.QueryDiffAreasOnVolume(volume.as_ptr()) };
+        collect_mgmt_objects(p_enum, OP, MgmtObjectProp::to_diff_area)
+    }
+
+    fn add_diff_area(
+        &self,
+        volume: &str,
+        diff_area_volume: &str,
+        maximum_space: i64,
+    ) -> Result<(), VssError> {
+        const OP: &str = "AddDiffArea";
+        let volume = to_wide(volume);
+        let diff_area_volume = to_wide(diff_area_volume);
+        unsafe {
+            self.diff(OP)?
+                .AddDiffArea(volume.as_ptr(), diff_area_volume.as_ptr(), maximum_space)
+                .operation(OP)
+        }
+    }
+
+    fn change_diff_area_maximum_size(
+        &self,
+        volume: &str,
+        diff_area_volume: &str,
+        maximum_space: i64,
+    ) -> Result<(), VssError> {
+        const OP: &str = "ChangeDiffAreaMaximumSize";
+        let volume = to_wide(volume);
+        let diff_area_volume = to_wide(diff_area_volume);
+        unsafe {
+            self.diff(OP)?
+                .ChangeDiffAreaMaximumSize(
+                    volume.as_ptr(),
+                    diff_area_volume.as_ptr(),
+                    maximum_space,
+                )
+                .operation(OP)
+        }
+    }
+
+    fn query_snapshots_by_volume(&self, volume: &str) -> Result<Vec<VSSProp>, VssError> {
+        const OP: &str = "QuerySnapshotsByVolume";
+        let volume = to_wide(volume);
+        let p_enum = unsafe {
+            self.mgmt(OP)?
+                .QuerySnapshotsByVolume(volume.as_ptr(), VSS_SWPRV_PROVIDER_ID)
+        };
+        match p_enum {
+            Ok(p_enum) => collect_snapshots(p_enum, OP),
+            // S_FALSE and no enumeration without shadow copies
+            Err(e) if e.code().is_ok() => Ok(Vec::new()),
+            Err(e) => Err(e).operation(OP),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, RefCell};
+
+    use windows::Win32::{
+        Foundation::{E_NOTIMPL, S_OK},
+        Storage::Vss::{VSS_BREAKEX_FLAG_MAKE_READ_WRITE, VSS_E_OBJECT_NOT_FOUND},
+    };
+
+    use super::*;
+
+    /// The methods of `IVssBackupComponentsEx3`, `IUnknown` included, as in vsbackup.h
+    const SLOTS: usize = 64;
+    const BREAK_SNAPSHOT_SET_EX: usize = 57;
+
+    /// A backup components object whose `BreakSnapshotSetEx` records its arguments and
+    /// fails with `VSS_E_OBJECT_NOT_FOUND`, every other method fails with `E_NOTIMPL`
+    #[repr(C)]
+    struct FakeEx3 {
+        vtable: *const [usize; SLOTS],
+        refs: Cell<u32>,
+        broken: RefCell<Vec<(GUID, u32)>>,
+    }
+
+    unsafe extern "system" fn query_interface(
+        this: *mut c_void,
+        _iid: *const GUID,
+        object: *mut *mut c_void,
+    ) -> HRESULT {
+        add_ref(this);
+        *object = this;
+        S_OK
+    }
+
+    unsafe extern "system" fn add_ref(this: *mut c_void) -> u32 {
+        let fake = &*(this as *const FakeEx3);
+        fake.refs.set(fake.refs.get() + 1);
+        fake.refs.get()
+    }
+
+    unsafe extern "system" fn release(this: *mut c_void) -> u32 {
+        let fake = &*(this as *const FakeEx3);
+        fake.refs.set(fake.refs.get() - 1);
+        fake.refs.get()
+    }
+
+    unsafe extern "system" fn not_implemented(_this: *mut c_void) -> HRESULT {
+        E_NOTIMPL
+    }
+
+    unsafe extern "system" fn break_snapshot_set_ex(
+        this: *mut c_void,
+        set_id: GUID,
+        flags: u32,
+        _p_async: *mut *mut c_void,
+    ) -> HRESULT {
+        let fake = &*(this as *const FakeEx3);
+        fake.broken.borrow_mut().push((set_id, flags));
+        VSS_E_OBJECT_NOT_FOUND
+    }
+
+    #[test]
+    fn test_break_snapshot_set_ex() {
+        let mut vtable = [not_implemented as *const () as usize; SLOTS];
+        vtable[0] = query_interface as *const () as usize;
+        vtable[1] = add_ref as *const () as usize;
+        vtable[2] = release as *const () as usize;
+        vtable[BREAK_SNAPSHOT_SET_EX] = break_snapshot_set_ex as *const () as usize;
+        let fake = FakeEx3 {
+            vtable: &vtable,
+            refs: Cell::new(1),
+            broken: RefCell::default(),
+        };
+        let backend = ComBackend {
+            co_initialize_called: false,
+            vss_object: Some(unsafe {
+                IVssBackupComponent::from_raw(&fake as *const FakeEx3 as *mut c_void)
+            }),
+            async_options: AsyncOptions::default(),
+        };
+
+        let set_id = GUID::from_u128(0x9a5cd9e1_93a4_4d18_8b2a_3f6c24b2e0a1);
+        assert_eq!(
+            backend.break_snapshot_set(set_id, VSS_BREAKEX_FLAG_MAKE_READ_WRITE),
+            Err(VssError::ObjectNotFound {
+                operation: "BreakSnapshotSetEx"
+            })
+        );
+        assert_eq!(
+            *fake.broken.borrow(),
+            [(set_id, VSS_BREAKEX_FLAG_MAKE_READ_WRITE.0 as u32)]
+        );
+        drop(backend);
+        assert_eq!(fake.refs.get(), 0);
+    }
+}
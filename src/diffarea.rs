@@ -0,0 +1,453 @@
+//! Shadow copy storage (diff area) management of the system provider (storage).
+//!
+//! It mirrors `vssadmin list/add/resize shadowstorage`: a diff area associates an original
+//! volume with the volume storing the copy-on-write data of its shadow copies.
+
+use std::{fmt, str::FromStr};
+
+use windows::core::GUID;
+
+#[cfg(all(windows, feature = "com"))]
+use crate::combackend::ComDiffAreaBackend;
+use crate::{error::VssError, vssprop::VSSProp};
+
+/// The id of the system provider, the only one with diff areas
+pub const VSS_SWPRV_PROVIDER_ID: GUID = GUID::from_u128(0xb5946137_7b9f_4925_af80_51abd60b20d5);
+
+/// The maximum space of a diff area without limit, `VSS_ASSOC_NO_MAX_SPACE`
+pub const NO_MAX_SPACE: i64 = -1;
+
+/// A volume the system provider can shadow copy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    pub volume_name: String,
+    pub display_name: String,
+}
+
+/// A volume that can store the diff area of another volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffVolume {
+    pub volume_name: String,
+    pub display_name: String,
+    pub free_space: u64,
+    pub total_space: u64,
+}
+
+/// The association of a volume with the volume storing its diff area
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffArea {
+    /// The shadow copied volume
+    pub volume_name: String,
+    /// The volume storing the diff area
+    pub diff_area_volume_name: String,
+    /// `None` when unbounded
+    pub maximum_space: Option<u64>,
+    pub allocated_space: u64,
+    pub used_space: u64,
+}
+
+impl fmt::Display for DiffArea {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Shadow Copy Storage association")?;
+        writeln!(f, "   For volume: {}", self.volume_name)?;
+        writeln!(
+            f,
+            "   Shadow Copy Storage volume: {}",
+            self.diff_area_volume_name
+        )?;
+        writeln!(
+            f,
+            "   Used Shadow Copy Storage space: {}",
+            format_size(self.used_space)
+        )?;
+        writeln!(
+            f,
+            "   Allocated Shadow Copy Storage space: {}",
+            format_size(self.allocated_space)
+        )?;
+        write!(
+            f,
+            "   Maximum Shadow Copy Storage space: {}",
+            self.maximum_space
+                .map_or_else(|| "UNBOUNDED".to_owned(), format_size)
+        )
+    }
+}
+
+/// The maximum size of a diff area, as given to `vssadmin /MaxSize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxSize {
+    Unbounded,
+    Bytes(u64),
+    /// A percentage of the total space of the volume storing the diff area
+    Percent(u8),
+}
+
+impl MaxSize {
+    /// The value given to VSS, for a diff area stored on a volume of `total_space` bytes
+    pub fn to_diff_space(self, total_space: u64) -> i64 {
+        match self {
+            MaxSize::Unbounded => NO_MAX_SPACE,
+            MaxSize::Bytes(bytes) => bytes.min(i64::MAX as u64) as i64,
+            MaxSize::Percent(percent) => {
+                (total_space as u128 * percent as u128 / 100).min(i64::MAX as u128) as i64
+            }
+        }
+    }
+}
+
+const UNITS: [(&str, u64); 6] = [
+    ("EB", 1 << 60),
+    ("PB", 1 << 50),
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+];
+
+impl FromStr for MaxSize {
+    type Err = VssError;
+
+    /// `UNBOUNDED`, a percentage such as `15%`, or a size in bytes with an optional
+    /// KB, MB, GB, TB, PB or EB suffix, e.g. `10GB` or `1.5TB`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            VssError::InvalidArgument(format!(
+                "invalid maximum size {}, expected UNBOUNDED, a percentage or a size such as 10GB",
+                s
+            ))
+        };
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("UNBOUNDED") {
+            return Ok(MaxSize::Unbounded);
+        }
+        if let Some(percent) = s.strip_suffix('%') {
+            return match percent.trim().parse::<u8>() {
+                Ok(percent) if (1..=100).contains(&percent) => Ok(MaxSize::Percent(percent)),
+                _ => Err(invalid()),
+            };
+        }
+        let upper = s.to_ascii_uppercase();
+        let (number, unit) = UNITS
+            .iter()
+            .find_map(|(suffix, unit)| upper.strip_suffix(suffix).map(|n| (n, *unit)))
+            .unwrap_or((upper.strip_suffix('B').unwrap_or(&upper), 1));
+        let number = number.trim();
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+            return Err(invalid());
+        }
+        let bytes = number.parse::<f64>().map_err(|_| invalid())? * unit as f64;
+        match bytes {
+            bytes if bytes >= 1.0 && bytes <= i64::MAX as f64 => Ok(MaxSize::Bytes(bytes as u64)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for MaxSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaxSize::Unbounded => write!(f, "UNBOUNDED"),
+            MaxSize::Bytes(bytes) => write!(f, "{}", format_size(*bytes)),
+            MaxSize::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// A size in bytes with the largest unit that keeps it above 1, like vssadmin prints it
+pub fn format_size(bytes: u64) -> String {
+    match UNITS.iter().find(|(_, unit)| bytes >= *unit) {
+        Some((suffix, unit)) => {
+            let value = bytes as f64 / *unit as f64;
+            let value = format!("{:.2}", value);
+            let value = value.trim_end_matches('0').trim_end_matches('.');
+            format!("{} {}", value, suffix)
+        }
+        None => format!("{} B", bytes),
+    }
+}
+
+/// The diff area operations of the system provider, through
+/// `IVssSnapshotMgmt` and `IVssDifferentialSoftwareSnapshotMgmt`.
+///
+/// The volumes are given by their unique names, `\\?\Volume{GUID}\`.
+pub trait DiffAreaBackend {
+    /// The volumes the system provider can shadow copy
+    fn query_volumes_supported_for_snapshots(&self) -> Result<Vec<Volume>, VssError>;
+
+    /// The volumes that can store the diff area of the volume
+    fn query_volumes_supported_for_diff_areas(
+        &self,
+        volume: &str,
+    ) -> Result<Vec<DiffVolume>, VssError>;
+
+    /// The diff areas of the volume, wherever they are stored
+    fn query_diff_areas_for_volume(&self, volume: &str) -> Result<Vec<DiffArea>, VssError>;
+
+    /// The diff areas stored on the volume
+    fn query_diff_areas_on_volume(&self, volume: &str) -> Result<Vec<DiffArea>, VssError>;
+
+    /// Store the diff area of `volume` on `diff_area_volume`, `maximum_space` is in bytes
+    /// or `NO_MAX_SPACE`
+    fn add_diff_area(
+        &self,
+        volume: &str,
+        diff_area_volume: &str,
+        maximum_space: i64,
+    ) -> Result<(), VssError>;
+
+    fn change_diff_area_maximum_size(
+        &self,
+        volume: &str,
+        diff_area_volume: &str,
+        maximum_space: i64,
+    ) -> Result<(), VssError>;
+
+    /// The shadow copies of the volume created by the system provider
+    fn query_snapshots_by_volume(&self, volume: &str) -> Result<Vec<VSSProp>, VssError>;
+}
+
+/// The shadow copy storage of the machine, `vssadmin list/add/resize shadowstorage`.
+/// The backend is the COM one by default, on Windows only.
+pub struct ShadowStorage<
+    #[cfg(all(windows, feature = "com"))] B: DiffAreaBackend = ComDiffAreaBackend,
+    #[cfg(not(all(windows, feature = "com")))] B: DiffAreaBackend,
+> {
+    backend: B,
+}
+
+#[cfg(all(windows, feature = "com"))]
+impl ShadowStorage {
+    /// Connect to the system provider through COM
+    pub fn new() -> Result<Self, VssError> {
+        Ok(Self::with_backend(ComDiffAreaBackend::new()?))
+    }
+}
+
+impl<B: DiffAreaBackend> ShadowStorage<B> {
+    pub fn with_backend(backend: B) -> Self {
+        ShadowStorage { backend }
+    }
+
+    /// The backend the shadow storage is working with
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// The diff areas of `for_volume` stored on `on_volume`, any of them when `None`,
+    /// as `vssadmin list shadowstorage /For= /On=`
+    pub fn list(
+        &self,
+        for_volume: Option<&str>,
+        on_volume: Option<&str>,
+    ) -> Result<Vec<DiffArea>, VssError> {
+        let same = |a: &str, b: &str| a.eq_ignore_ascii_case(b);
+        match (for_volume, on_volume) {
+            (Some(for_volume), on_volume) => Ok(self
+                .backend
+                .query_diff_areas_for_volume(for_volume)?
+                .into_iter()
+                .filter(|d| on_volume.is_none_or(|on| same(&d.diff_area_volume_name, on)))
+                .collect()),
+            (None, Some(on_volume)) => self.backend.query_diff_areas_on_volume(on_volume),
+            (None, None) => {
+                let mut diff_areas = Vec::new();
+                for volume in self.backend.query_volumes_supported_for_snapshots()? {
+                    diff_areas.extend(
+                        self.backend
+                            .query_diff_areas_for_volume(&volume.volume_name)?,
+                    );
+                }
+                Ok(diff_areas)
+            }
+        }
+    }
+
+    /// The maximum space given to VSS, checking that `on_volume` can store the diff area
+    fn diff_space(
+        &self,
+        for_volume: &str,
+        on_volume: &str,
+        max_size: MaxSize,
+    ) -> Result<i64, VssError> {
+        let diff_volume = self
+            .backend
+            .query_volumes_supported_for_diff_areas(for_volume)?
+            .into_iter()
+            .find(|v| v.volume_name.eq_ignore_ascii_case(on_volume))
+            .ok_or_else(|| {
+                VssError::InvalidArgument(format!(
+                    "{} cannot store the shadow copies of {}",
+                    on_volume, for_volume
+                ))
+            })?;
+        Ok(max_size.to_diff_space(diff_volume.total_space))
+    }
+
+    /// Store the shadow copies of `for_volume` on `on_volume`,
+    /// as `vssadmin add shadowstorage`
+    pub fn add(
+        &self,
+        for_volume: &str,
+        on_volume: &str,
+        max_size: MaxSize,
+    ) -> Result<(), VssError> {
+        let space = self.diff_space(for_volume, on_volume, max_size)?;
+        tracing::info!(
+            "- Adding the shadow copy storage of {} on {}, at most {}",
+            for_volume,
+            on_volume,
+            max_size
+        );
+        self.backend.add_diff_area(for_volume, on_volume, space)
+    }
+
+    /// Change the maximum size of the shadow copy storage of `for_volume` on `on_volume`,
+    /// as `vssadmin resize shadowstorage`
+    pub fn resize(
+        &self,
+        for_volume: &str,
+        on_volume: &str,
+        max_size: MaxSize,
+    ) -> Result<(), VssError> {
+        let space = self.diff_space(for_volume, on_volume, max_size)?;
+        tracing::info!(
+            "- Resizing the shadow copy storage of {} on {} to {}",
+            for_volume,
+            on_volume,
+            max_size
+        );
+        self.backend
+            .change_diff_area_maximum_size(for_volume, on_volume, space)
+    }
+
+    /// The shadow copies of the volume, which use its shadow copy storage
+    pub fn snapshots(&self, volume: &str) -> Result<Vec<VSSProp>, VssError> {
+        self.backend.query_snapshots_by_volume(volume)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Storage::Vss::VSS_CTX_BACKUP;
+
+    use super::*;
+    use crate::fakebackend::FakeBackend;
+
+    const C: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000c}\";
+    const D: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000d}\";
+    const E: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000e}\";
+    const GB: u64 = 1 << 30;
+
+    fn storage() -> ShadowStorage<FakeBackend> {
+        let backend = FakeBackend::new();
+        backend.add_volume(C, "C:\\", 100 * GB);
+        backend.add_volume(D, "D:\\", 400 * GB);
+        ShadowStorage::with_backend(backend)
+    }
+
+    #[test]
+    fn test_max_size() {
+        let parse = |s: &str| s.parse::<MaxSize>();
+        assert_eq!(parse("UNBOUNDED").unwrap(), MaxSize::Unbounded);
+        assert_eq!(parse("unbounded").unwrap(), MaxSize::Unbounded);
+        assert_eq!(parse("15%").unwrap(), MaxSize::Percent(15));
+        assert_eq!(parse("1024").unwrap(), MaxSize::Bytes(1024));
+        assert_eq!(parse("320MB").unwrap(), MaxSize::Bytes(320 << 20));
+        assert_eq!(parse("10gb").unwrap(), MaxSize::Bytes(10 * GB));
+        assert_eq!(parse("1.5TB").unwrap(), MaxSize::Bytes(3 << 39));
+        assert_eq!(parse("2 KB").unwrap(), MaxSize::Bytes(2048));
+        for invalid in ["", "0", "0%", "101%", "-5GB", "GB", "10XB", "1e3", "ten"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+
+        assert_eq!(MaxSize::Unbounded.to_diff_space(GB), NO_MAX_SPACE);
+        assert_eq!(MaxSize::Bytes(GB).to_diff_space(0), GB as i64);
+        assert_eq!(MaxSize::Percent(10).to_diff_space(100 * GB), 10 * GB as i64);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(320 << 20), "320 MB");
+        assert_eq!(format_size(3 << 29), "1.5 GB");
+        assert_eq!(format_size(GB + GB / 3), "1.33 GB");
+        assert_eq!(MaxSize::Bytes(10 * GB).to_string(), "10 GB");
+    }
+
+    #[test]
+    fn test_add_and_resize() {
+        let storage = storage();
+        storage.add(C, D, MaxSize::Percent(10)).unwrap();
+        storage.add(D, D, MaxSize::Unbounded).unwrap();
+        assert!(matches!(
+            storage.add(C, D, MaxSize::Unbounded),
+            Err(VssError::ObjectAlreadyExists { .. })
+        ));
+        // E: cannot store diff areas
+        assert!(matches!(
+            storage.add(C, E, MaxSize::Unbounded),
+            Err(VssError::InvalidArgument(_))
+        ));
+
+        let diff_areas = storage.list(Some(C), None).unwrap();
+        assert_eq!(diff_areas.len(), 1);
+        assert_eq!(diff_areas[0].diff_area_volume_name, D);
+        assert_eq!(diff_areas[0].maximum_space, Some(40 * GB));
+
+        storage.resize(C, D, MaxSize::Bytes(5 * GB)).unwrap();
+        assert_eq!(
+            storage.list(Some(C), Some(D)).unwrap()[0].maximum_space,
+            Some(5 * GB)
+        );
+        storage.resize(C, D, MaxSize::Unbounded).unwrap();
+        assert_eq!(storage.list(Some(C), None).unwrap()[0].maximum_space, None);
+        assert!(matches!(
+            storage.resize(D, C, MaxSize::Unbounded),
+            Err(VssError::ObjectNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_list() {
+        let storage = storage();
+        storage.add(C, D, MaxSize::Unbounded).unwrap();
+        storage.add(D, D, MaxSize::Bytes(GB)).unwrap();
+        let volumes = |diff_areas: Vec<DiffArea>| {
+            diff_areas
+                .into_iter()
+                .map(|d| d.volume_name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(volumes(storage.list(None, None).unwrap()), [C, D]);
+        assert_eq!(volumes(storage.list(None, Some(D)).unwrap()), [C, D]);
+        assert_eq!(volumes(storage.list(None, Some(C)).unwrap()), [""; 0]);
+        assert_eq!(
+            volumes(storage.list(Some(&C.to_lowercase()), Some(D)).unwrap()),
+            [C]
+        );
+        assert_eq!(volumes(storage.list(Some(C), Some(C)).unwrap()), [""; 0]);
+
+        storage.backend().set_diff_area_usage(C, D, 512 << 20, GB);
+        assert_eq!(
+            storage.list(Some(C), None).unwrap()[0].to_string(),
+            format!(
+                "Shadow Copy Storage association\n   For volume: {}\n   Shadow Copy Storage volume: {}\n   Used Shadow Copy Storage space: 512 MB\n   Allocated Shadow Copy Storage space: 1 GB\n   Maximum Shadow Copy Storage space: UNBOUNDED",
+                C, D
+            )
+        );
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let storage = storage();
+        let snapshot = VSSProp {
+            origin_vol_name: C.to_owned(),
+            ..Default::default()
+        };
+        storage.backend().insert_snapshot(snapshot, VSS_CTX_BACKUP);
+        assert_eq!(storage.snapshots(C).unwrap().len(), 1);
+        assert!(storage.snapshots(D).unwrap().is_empty());
+    }
+}
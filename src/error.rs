@@ -0,0 +1,308 @@
+use std::{fmt, time::Duration};
+
+use windows::core::HRESULT;
+
+macro_rules! vss_errors {
+    ($($variant:ident = $code:literal, $name:literal, $message:literal;)*) => {
+        /// The errors returned by the crate.
+        ///
+        /// Every known VSS or COM failure code has its own variant carrying the
+        /// operation that failed, e.g. `VssError::BadState { operation: "DoSnapshotSet" }`.
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum VssError {
+            $(
+                #[doc = $message]
+                $variant { operation: &'static str },
+            )*
+            /// A failure code without a dedicated variant
+            Hresult { operation: &'static str, code: HRESULT },
+            /// A string that is not a valid GUID
+            InvalidGuid(String),
+            /// An argument rejected before reaching VSS
+            InvalidArgument(String),
+            /// A malformed XML document
+            Xml(String),
+            /// A file or stream could not be read or written
+            Io(String),
+            /// The -exec command failed, `code` is its exit code if it has one
+            CommandFailed { command: String, code: Option<i32> },
+            /// An asynchronous operation was cancelled after lasting longer than `timeout`
+            TimedOut { operation: &'static str, timeout: Duration },
+            /// An asynchronous operation was cancelled by VSS or by the user
+            Cancelled { operation: &'static str },
+        }
+
+        impl VssError {
+            /// Map a failure code returned by `operation` to its variant
+            pub fn from_hresult(operation: &'static str, code: HRESULT) -> Self {
+                match code.0 as u32 {
+                    $($code => VssError::$variant { operation },)*
+                    _ => VssError::Hresult { operation, code },
+                }
+            }
+
+            /// The failure code, if the error comes from VSS or COM
+            pub fn hresult(&self) -> Option<HRESULT> {
+                match self {
+                    $(VssError::$variant { .. } => Some(HRESULT($code as u32 as i32)),)*
+                    VssError::Hresult { code, .. } => Some(*code),
+                    _ => None,
+                }
+            }
+
+            /// The symbolic name of the failure code, e.g. "VSS_E_BAD_STATE"
+            pub fn name(&self) -> Option<&'static str> {
+                match self {
+                    $(VssError::$variant { .. } => Some($name),)*
+                    _ => None,
+                }
+            }
+
+            /// The operation that failed, e.g. "DoSnapshotSet"
+            pub fn operation(&self) -> Option<&'static str> {
+                match self {
+                    $(VssError::$variant { operation } => Some(operation),)*
+                    VssError::Hresult { operation, .. } => Some(operation),
+                    VssError::TimedOut { operation, .. } => Some(operation),
+                    VssError::Cancelled { operation } => Some(operation),
+                    _ => None,
+                }
+            }
+
+            fn message(&self) -> Option<&'static str> {
+                match self {
+                    $(VssError::$variant { .. } => Some($message),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+vss_errors! {
+    BadState = 0x80042301, "VSS_E_BAD_STATE", "the backup components object is not initialized or the method was called out of sequence";
+    Unexpected = 0x80042302, "VSS_E_UNEXPECTED", "an unexpected error occurred in the VSS service";
+    ProviderAlreadyRegistered = 0x80042303, "VSS_E_PROVIDER_ALREADY_REGISTERED", "the provider is already registered";
+    ProviderNotRegistered = 0x80042304, "VSS_E_PROVIDER_NOT_REGISTERED", "the provider is not registered";
+    ProviderVeto = 0x80042306, "VSS_E_PROVIDER_VETO", "the provider vetoed the operation, check the event log for details";
+    ProviderInUse = 0x80042307, "VSS_E_PROVIDER_IN_USE", "the provider is currently in use";
+    ObjectNotFound = 0x80042308, "VSS_E_OBJECT_NOT_FOUND", "the specified object was not found";
+    VolumeNotSupported = 0x8004230C, "VSS_E_VOLUME_NOT_SUPPORTED", "shadow copies are not supported on the volume";
+    ObjectAlreadyExists = 0x8004230D, "VSS_E_OBJECT_ALREADY_EXISTS", "the object already exists, e.g. the volume is already in the shadow copy set";
+    VolumeNotSupportedByProvider = 0x8004230E, "VSS_E_VOLUME_NOT_SUPPORTED_BY_PROVIDER", "the volume is not supported by the specified provider";
+    UnexpectedProviderError = 0x8004230F, "VSS_E_UNEXPECTED_PROVIDER_ERROR", "the provider returned an unexpected error, check the event log for details";
+    CorruptXmlDocument = 0x80042310, "VSS_E_CORRUPT_XML_DOCUMENT", "the XML document is corrupt";
+    InvalidXmlDocument = 0x80042311, "VSS_E_INVALID_XML_DOCUMENT", "the XML document is not valid";
+    MaximumNumberOfVolumesReached = 0x80042312, "VSS_E_MAXIMUM_NUMBER_OF_VOLUMES_REACHED", "the maximum number of volumes has been added to the shadow copy set";
+    FlushWritesTimeout = 0x80042313, "VSS_E_FLUSH_WRITES_TIMEOUT", "the system was unable to flush I/O writes, try again later";
+    HoldWritesTimeout = 0x80042314, "VSS_E_HOLD_WRITES_TIMEOUT", "the system was unable to freeze the I/O writes, try again later";
+    UnexpectedWriterError = 0x80042315, "VSS_E_UNEXPECTED_WRITER_ERROR", "a writer returned an unexpected error";
+    SnapshotSetInProgress = 0x80042316, "VSS_E_SNAPSHOT_SET_IN_PROGRESS", "another shadow copy creation is already in progress";
+    MaximumNumberOfSnapshotsReached = 0x80042317, "VSS_E_MAXIMUM_NUMBER_OF_SNAPSHOTS_REACHED", "the volume has been added to the maximum number of shadow copy sets";
+    WriterInfrastructure = 0x80042318, "VSS_E_WRITER_INFRASTRUCTURE", "the writer infrastructure is not operating properly";
+    WriterNotResponding = 0x80042319, "VSS_E_WRITER_NOT_RESPONDING", "a writer did not respond";
+    WriterAlreadySubscribed = 0x8004231A, "VSS_E_WRITER_ALREADY_SUBSCRIBED", "the writer has already subscribed";
+    UnsupportedContext = 0x8004231B, "VSS_E_UNSUPPORTED_CONTEXT", "the context is not supported by the provider";
+    VolumeInUse = 0x8004231D, "VSS_E_VOLUME_IN_USE", "the volume is in use and cannot be locked";
+    MaximumDiffareaAssociationsReached = 0x8004231E, "VSS_E_MAXIMUM_DIFFAREA_ASSOCIATIONS_REACHED", "the maximum number of shadow copy storage associations has been reached";
+    InsufficientStorage = 0x8004231F, "VSS_E_INSUFFICIENT_STORAGE", "there is not enough shadow copy storage space";
+    NoSnapshotsImported = 0x80042320, "VSS_E_NO_SNAPSHOTS_IMPORTED", "no shadow copies were successfully imported";
+    SomeSnapshotsNotImported = 0x80042321, "VSS_E_SOME_SNAPSHOTS_NOT_IMPORTED", "some shadow copies were not successfully imported";
+    MaximumNumberOfRemoteMachinesReached = 0x80042322, "VSS_E_MAXIMUM_NUMBER_OF_REMOTE_MACHINES_REACHED", "the maximum number of remote machines has been reached";
+    RemoteServerUnavailable = 0x80042323, "VSS_E_REMOTE_SERVER_UNAVAILABLE", "the remote server is unavailable";
+    RemoteServerUnsupported = 0x80042324, "VSS_E_REMOTE_SERVER_UNSUPPORTED", "the remote server is running a version of VSS that does not support remote shadow copies";
+    RevertInProgress = 0x80042325, "VSS_E_REVERT_IN_PROGRESS", "a revert is currently in progress for the volume";
+    RevertVolumeLost = 0x80042326, "VSS_E_REVERT_VOLUME_LOST", "the volume being reverted was lost during the revert";
+    RebootRequired = 0x80042327, "VSS_E_REBOOT_REQUIRED", "a reboot is required";
+    TransactionFreezeTimeout = 0x80042328, "VSS_E_TRANSACTION_FREEZE_TIMEOUT", "a timeout occurred while freezing a transaction manager";
+    TransactionThawTimeout = 0x80042329, "VSS_E_TRANSACTION_THAW_TIMEOUT", "too much time elapsed between freezing and thawing a transaction manager";
+    UnselectedVolume = 0x8004232A, "VSS_E_UNSELECTED_VOLUME", "the volume is not part of the shadow copy set";
+    SnapshotNotInSet = 0x8004232B, "VSS_E_SNAPSHOT_NOT_IN_SET", "the shadow copy is not part of the shadow copy set";
+    NestedVolumeLimit = 0x8004232C, "VSS_E_NESTED_VOLUME_LIMIT", "the volume is nested too deeply";
+    VolumeNotLocal = 0x8004232D, "VSS_E_VOLUME_NOT_LOCAL", "the volume is not local";
+    ClusterTimeout = 0x8004232E, "VSS_E_CLUSTER_TIMEOUT", "a timeout occurred while preparing a cluster shared volume";
+    NotSupported = 0x8004232F, "VSS_E_NOT_SUPPORTED", "the requested operation is not supported";
+    WriterErrorPartialFailure = 0x80042336, "VSS_E_WRITERERROR_PARTIAL_FAILURE", "a writer reported a failure for some of its components";
+    WriterErrorInconsistentSnapshot = 0x800423F0, "VSS_E_WRITERERROR_INCONSISTENTSNAPSHOT", "the shadow copy contains only a subset of the volumes needed by a writer";
+    WriterErrorOutOfResources = 0x800423F1, "VSS_E_WRITERERROR_OUTOFRESOURCES", "a writer ran out of resources";
+    WriterErrorTimeout = 0x800423F2, "VSS_E_WRITERERROR_TIMEOUT", "a writer timed out between the freeze and thaw events";
+    WriterErrorRetryable = 0x800423F3, "VSS_E_WRITERERROR_RETRYABLE", "a writer failed with a transient error, the operation may succeed if retried";
+    WriterErrorNonRetryable = 0x800423F4, "VSS_E_WRITERERROR_NONRETRYABLE", "a writer failed with a non-transient error";
+    WriterErrorRecoveryFailed = 0x800423F5, "VSS_E_WRITERERROR_RECOVERY_FAILED", "a writer failed to recover the shadow copy volume";
+    BreakRevertIdFailed = 0x800423F6, "VSS_E_BREAK_REVERT_ID_FAILED", "the identity of the shadow copy volumes could not be reverted while breaking the set";
+    LegacyProvider = 0x800423F7, "VSS_E_LEGACY_PROVIDER", "the operation is not supported by a legacy provider";
+    MissingDisk = 0x800423F8, "VSS_E_MISSING_DISK", "a disk needed by the operation is missing";
+    MissingHiddenVolume = 0x800423F9, "VSS_E_MISSING_HIDDEN_VOLUME", "a hidden volume needed by the operation is missing";
+    MissingVolume = 0x800423FA, "VSS_E_MISSING_VOLUME", "a volume needed by the operation is missing";
+    AutorecoveryFailed = 0x800423FB, "VSS_E_AUTORECOVERY_FAILED", "the auto-recovery of the shadow copy failed";
+    DynamicDiskError = 0x800423FC, "VSS_E_DYNAMIC_DISK_ERROR", "an error occurred while processing a dynamic disk";
+    NontransportableBcd = 0x800423FD, "VSS_E_NONTRANSPORTABLE_BCD", "the Backup Components Document does not describe transportable shadow copies";
+    CannotRevertDiskId = 0x800423FE, "VSS_E_CANNOT_REVERT_DISKID", "the disk identifier could not be reverted";
+    ResyncInProgress = 0x800423FF, "VSS_E_RESYNC_IN_PROGRESS", "a LUN resynchronization is in progress";
+    ClusterError = 0x80042400, "VSS_E_CLUSTER_ERROR", "a cluster error occurred";
+    AsrDiskAssignmentFailed = 0x80042401, "VSS_E_ASRERROR_DISK_ASSIGNMENT_FAILED", "ASR: there are too few disks on the computer";
+    AsrDiskRecreationFailed = 0x80042402, "VSS_E_ASRERROR_DISK_RECREATION_FAILED", "ASR: a disk could not be recreated";
+    AsrNoArcPath = 0x80042403, "VSS_E_ASRERROR_NO_ARCPATH", "ASR: a critical disk has no ARC path";
+    AsrMissingDynDisk = 0x80042404, "VSS_E_ASRERROR_MISSING_DYNDISK", "ASR: a dynamic disk is missing";
+    AsrSharedCriticalDisk = 0x80042405, "VSS_E_ASRERROR_SHARED_CRIDISK", "ASR: a critical disk is a shared cluster disk";
+    AsrDataDiskRdisk0 = 0x80042406, "VSS_E_ASRERROR_DATADISK_RDISK0", "ASR: the system disk is a data disk";
+    AsrRdisk0TooSmall = 0x80042407, "VSS_E_ASRERROR_RDISK0_TOOSMALL", "ASR: the system disk is too small";
+    AsrCriticalDisksTooSmall = 0x80042408, "VSS_E_ASRERROR_CRITICAL_DISKS_TOO_SMALL", "ASR: the critical disks are too small";
+    WriterStatusNotAvailable = 0x80042409, "VSS_E_WRITER_STATUS_NOT_AVAILABLE", "the writer status is not available";
+    AsrDynamicVhdNotSupported = 0x8004240A, "VSS_E_ASRERROR_DYNAMIC_VHD_NOT_SUPPORTED", "ASR: a dynamic VHD is not supported";
+    CriticalVolumeOnInvalidDisk = 0x80042411, "VSS_E_CRITICAL_VOLUME_ON_INVALID_DISK", "a critical volume is on a disk that cannot be backed up";
+    AsrRdiskForSystemDiskNotFound = 0x80042412, "VSS_E_ASRERROR_RDISK_FOR_SYSTEM_DISK_NOT_FOUND", "ASR: no disk can be used to recreate the system disk";
+    AsrNoPhysicalDiskAvailable = 0x80042413, "VSS_E_ASRERROR_NO_PHYSICAL_DISK_AVAILABLE", "ASR: no physical disk is available";
+    AsrFixedPhysicalDiskAvailableAfterDiskExclusion = 0x80042414, "VSS_E_ASRERROR_FIXED_PHYSICAL_DISK_AVAILABLE_AFTER_DISK_EXCLUSION", "ASR: a fixed physical disk is available after disk exclusion";
+    AsrCriticalDiskCannotBeExcluded = 0x80042415, "VSS_E_ASRERROR_CRITICAL_DISK_CANNOT_BE_EXCLUDED", "ASR: a critical disk cannot be excluded";
+    AsrSystemPartitionHidden = 0x80042416, "VSS_E_ASRERROR_SYSTEM_PARTITION_HIDDEN", "ASR: the system partition is hidden";
+    FssTimeout = 0x80042417, "VSS_E_FSS_TIMEOUT", "a file share shadow copy operation timed out";
+    AccessDenied = 0x80070005, "E_ACCESSDENIED", "the caller is not an administrator or does not have the backup privilege";
+    InvalidArg = 0x80070057, "E_INVALIDARG", "one of the parameter values is not valid";
+    OutOfMemory = 0x8007000E, "E_OUTOFMEMORY", "the caller is out of memory or other system resources";
+    ComUnexpected = 0x8000FFFF, "E_UNEXPECTED", "unexpected error, check the application event log for details";
+}
+
+impl fmt::Display for VssError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VssError::InvalidGuid(s) => write!(f, "invalid GUID: {}", s),
+            VssError::InvalidArgument(s) => write!(f, "invalid argument: {}", s),
+            VssError::Xml(s) => write!(f, "invalid XML: {}", s),
+            VssError::Io(s) => write!(f, "I/O error: {}", s),
+            VssError::CommandFailed {
+                command,
+                code: Some(code),
+            } => write!(f, "{} failed with exit code {}", command, code),
+            VssError::CommandFailed {
+                command,
+                code: None,
+            } => write!(f, "{} was terminated without exit code", command),
+            VssError::TimedOut { operation, timeout } => write!(
+                f,
+                "{} timed out after {}s and was cancelled",
+                operation,
+                timeout.as_secs_f32()
+            ),
+            VssError::Cancelled { operation } => write!(f, "{} was cancelled", operation),
+            _ => {
+                let operation = self.operation().unwrap_or_default();
+                let code = self.hresult().unwrap_or_default().0 as u32;
+                match (self.name(), self.message()) {
+                    (Some(name), Some(message)) => write!(
+                        f,
+                        "{} failed with {} (0x{:08X}): {}",
+                        operation, name, code, message
+                    ),
+                    _ => write!(f, "{} failed with HRESULT 0x{:08X}", operation, code),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for VssError {}
+
+impl From<std::io::Error> for VssError {
+    fn from(e: std::io::Error) -> Self {
+        VssError::Io(e.to_string())
+    }
+}
+
+/// Attach the operation to the error of a COM call
+#[cfg(all(windows, feature = "com"))]
+pub(crate) trait OperationContext<T> {
+    fn operation(self, operation: &'static str) -> Result<T, VssError>;
+}
+
+#[cfg(all(windows, feature = "com"))]
+impl<T> OperationContext<T> for ::windows::core::Result<T> {
+    fn operation(self, operation: &'static str) -> Result<T, VssError> {
+        self.map_err(|e| VssError::from_hresult(operation, e.code()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Storage::Vss::{
+        VSS_E_BAD_STATE, VSS_E_FSS_TIMEOUT, VSS_E_MAXIMUM_NUMBER_OF_SNAPSHOTS_REACHED,
+        VSS_E_OBJECT_NOT_FOUND, VSS_E_UNEXPECTED_PROVIDER_ERROR, VSS_E_WRITERERROR_TIMEOUT,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_from_hresult() {
+        assert_eq!(
+            VssError::from_hresult("DoSnapshotSet", VSS_E_BAD_STATE),
+            VssError::BadState {
+                operation: "DoSnapshotSet"
+            }
+        );
+        assert_eq!(
+            VssError::from_hresult("Query", VSS_E_OBJECT_NOT_FOUND),
+            VssError::ObjectNotFound { operation: "Query" }
+        );
+        assert_eq!(
+            VssError::from_hresult(
+                "AddToSnapshotSet",
+                VSS_E_MAXIMUM_NUMBER_OF_SNAPSHOTS_REACHED
+            ),
+            VssError::MaximumNumberOfSnapshotsReached {
+                operation: "AddToSnapshotSet"
+            }
+        );
+        assert_eq!(
+            VssError::from_hresult("DoSnapshotSet", VSS_E_UNEXPECTED_PROVIDER_ERROR),
+            VssError::UnexpectedProviderError {
+                operation: "DoSnapshotSet"
+            }
+        );
+        assert_eq!(
+            VssError::from_hresult("GatherWriterStatus", VSS_E_WRITERERROR_TIMEOUT),
+            VssError::WriterErrorTimeout {
+                operation: "GatherWriterStatus"
+            }
+        );
+        assert_eq!(
+            VssError::from_hresult("Wait", VSS_E_FSS_TIMEOUT).name(),
+            Some("VSS_E_FSS_TIMEOUT")
+        );
+    }
+
+    #[test]
+    fn test_unknown_hresult() {
+        let err = VssError::from_hresult("CoInitialize", HRESULT(0x80041234u32 as i32));
+        assert_eq!(err.hresult(), Some(HRESULT(0x80041234u32 as i32)));
+        assert_eq!(err.operation(), Some("CoInitialize"));
+        assert_eq!(err.name(), None);
+        assert_eq!(
+            err.to_string(),
+            "CoInitialize failed with HRESULT 0x80041234"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let err = VssError::from_hresult("SetContext", HRESULT(0x8004231Bu32 as i32));
+        assert_eq!(err.hresult(), Some(HRESULT(0x8004231Bu32 as i32)));
+        assert_eq!(
+            VssError::from_hresult("SetContext", err.hresult().unwrap()),
+            err
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            VssError::from_hresult("StartSnapshotSet", VSS_E_BAD_STATE).to_string(),
+            "StartSnapshotSet failed with VSS_E_BAD_STATE (0x80042301): \
+             the backup components object is not initialized or the method was called out of sequence"
+        );
+        assert_eq!(
+            VssError::InvalidGuid("{1234}".to_owned()).to_string(),
+            "invalid GUID: {1234}"
+        );
+        assert_eq!(VssError::InvalidGuid("x".to_owned()).operation(), None);
+    }
+}
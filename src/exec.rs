@@ -0,0 +1,57 @@
+use std::process::Command;
+
+use crate::error::VssError;
+
+/// Runs the -exec command while the shadow copy set is alive
+pub trait CommandRunner {
+    /// Run the command line with the extra environment variables, its output going to ours,
+    /// and return its exit code. `None` means it was terminated without one, e.g. by a signal.
+    fn run(&mut self, command: &str, env: &[(String, String)]) -> Result<Option<i32>, VssError>;
+}
+
+/// Runs the command through the system shell: `cmd /C` on Windows, `sh -c` elsewhere
+#[derive(Debug, Default)]
+pub struct ShellRunner;
+
+impl CommandRunner for ShellRunner {
+    fn run(&mut self, command: &str, env: &[(String, String)]) -> Result<Option<i32>, VssError> {
+        let mut process = if cfg!(windows) {
+            let mut process = Command::new("cmd");
+            process.arg("/C").arg(command);
+            process
+        } else {
+            let mut process = Command::new("sh");
+            process.arg("-c").arg(command);
+            process
+        };
+        let status = process
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .status()
+            .map_err(|e| VssError::Io(format!("cannot run {}: {}", command, e)))?;
+        Ok(status.code())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shell_runner() {
+        let mut runner = ShellRunner;
+        assert_eq!(runner.run("exit 0", &[]).unwrap(), Some(0));
+        assert_eq!(runner.run("exit 3", &[]).unwrap(), Some(3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_runner_env() {
+        let env = [("SHADOW_SET_ID".to_owned(), "{x}".to_owned())];
+        assert_eq!(
+            ShellRunner
+                .run("test \"$SHADOW_SET_ID\" = '{x}'", &env)
+                .unwrap(),
+            Some(0)
+        );
+    }
+}
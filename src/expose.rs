@@ -0,0 +1,252 @@
+use windows::Win32::Storage::Vss::{
+    VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY, VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY,
+    VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+};
+
+use crate::error::VssError;
+
+/// Where a shadow copy is exposed by `VssClient::expose_snapshot`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExposeTarget {
+    /// An unused drive letter, e.g. `X:`
+    DriveLetter(char),
+    /// An empty directory of an NTFS volume, as an absolute path
+    Directory(String),
+    /// An unused share name, sharing the given path from the root of the shadow copy,
+    /// or the whole shadow copy
+    Share { name: String, path: Option<String> },
+}
+
+/// The characters not allowed in share names
+const INVALID_SHARE_CHARS: &str = "\"/\\[]:|<>+=;,?*";
+
+/// The maximum length of a share name
+const MAX_SHARE_NAME: usize = 80;
+
+impl ExposeTarget {
+    /// Parse the target of -el: a drive letter (`X`, `X:` or `X:\`) or an absolute directory path
+    pub fn local(target: &str) -> Result<Self, VssError> {
+        let invalid = |reason: &str| {
+            VssError::InvalidArgument(format!("cannot expose on {}: {}", target, reason))
+        };
+        let mut chars = target.chars();
+        let letter = chars.next().filter(|c| c.is_ascii_alphabetic());
+        match (letter, chars.as_str()) {
+            (Some(letter), "" | ":" | ":\\") => {
+                return Ok(ExposeTarget::DriveLetter(letter.to_ascii_uppercase()))
+            }
+            (Some(_), rest) if rest.starts_with(":\\") => {}
+            _ => return Err(invalid("not a drive letter nor an absolute path")),
+        }
+        if target.contains(['*', '?', '"', '<', '>', '|']) || target[2..].contains(':') {
+            return Err(invalid("invalid characters in the path"));
+        }
+        Ok(ExposeTarget::Directory(
+            target.trim_end_matches('\\').to_owned(),
+        ))
+    }
+
+    /// Parse the target of -er: a share name, and the path from the root of the shadow copy
+    pub fn remote(name: &str, path: Option<&str>) -> Result<Self, VssError> {
+        let invalid = |reason: &str| {
+            VssError::InvalidArgument(format!("invalid share name {}: {}", name, reason))
+        };
+        if name.is_empty() {
+            return Err(invalid("empty name"));
+        }
+        if name.chars().count() > MAX_SHARE_NAME {
+            return Err(invalid("longer than 80 characters"));
+        }
+        if name
+            .chars()
+            .any(|c| c.is_control() || INVALID_SHARE_CHARS.contains(c))
+        {
+            return Err(invalid("invalid characters"));
+        }
+        if name.ends_with('.') || name.trim() != name {
+            return Err(invalid("leading or trailing space or dot"));
+        }
+
+        let path = path.filter(|p| !p.is_empty() && *p != "\\");
+        if let Some(path) = path {
+            let relative = path.trim_start_matches('\\');
+            if relative.contains(':') || relative.split('\\').any(|p| p == "..") {
+                return Err(VssError::InvalidArgument(format!(
+                    "invalid path from the root of the shadow copy: {}",
+                    path
+                )));
+            }
+        }
+        Ok(ExposeTarget::Share {
+            name: name.to_owned(),
+            path: path.map(|p| p.to_owned()),
+        })
+    }
+
+    /// The `lAttributes` argument of `ExposeSnapshot`
+    pub fn attributes(&self) -> VSS_VOLUME_SNAPSHOT_ATTRIBUTES {
+        match self {
+            ExposeTarget::DriveLetter(_) | ExposeTarget::Directory(_) => {
+                VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY
+            }
+            ExposeTarget::Share { .. } => VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY,
+        }
+    }
+
+    /// The `wszExpose` argument of `ExposeSnapshot`
+    pub fn expose(&self) -> String {
+        match self {
+            ExposeTarget::DriveLetter(letter) => format!("{}:", letter),
+            ExposeTarget::Directory(path) => path.clone(),
+            ExposeTarget::Share { name, .. } => name.clone(),
+        }
+    }
+
+    /// The `wszPathFromRoot` argument of `ExposeSnapshot`, only used by shares
+    pub fn path_from_root(&self) -> Option<&str> {
+        match self {
+            ExposeTarget::Share { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Check that the drive letter is unused or that the directory exists and is empty.
+    /// Share names are checked by VSS.
+    pub fn validate<M: MountPoints>(&self, mount_points: &M) -> Result<(), VssError> {
+        match self {
+            ExposeTarget::DriveLetter(letter) if mount_points.is_drive_in_use(*letter)? => Err(
+                VssError::InvalidArgument(format!("drive {}: is already in use", letter)),
+            ),
+            ExposeTarget::Directory(path) => match mount_points.is_directory_empty(path)? {
+                None => Err(VssError::InvalidArgument(format!(
+                    "directory {} does not exist",
+                    path
+                ))),
+                Some(false) => Err(VssError::InvalidArgument(format!(
+                    "directory {} is not empty",
+                    path
+                ))),
+                Some(true) => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What the validation of an `ExposeTarget` needs to know about the machine
+pub trait MountPoints {
+    fn is_drive_in_use(&self, letter: char) -> Result<bool, VssError>;
+
+    /// Whether the directory is empty, `None` if it does not exist
+    fn is_directory_empty(&self, path: &str) -> Result<Option<bool>, VssError>;
+}
+
+/// The drives and directories of the local machine
+#[cfg(all(windows, feature = "com"))]
+#[derive(Debug, Default)]
+pub struct SystemMountPoints;
+
+#[cfg(all(windows, feature = "com"))]
+impl MountPoints for SystemMountPoints {
+    fn is_drive_in_use(&self, letter: char) -> Result<bool, VssError> {
+        let drives = unsafe { windows::Win32::Storage::FileSystem::GetLogicalDrives() };
+        let index = letter.to_ascii_uppercase() as u32 - 'A' as u32;
+        Ok(drives & (1 << index) != 0)
+    }
+
+    fn is_directory_empty(&self, path: &str) -> Result<Option<bool>, VssError> {
+        match std::fs::read_dir(path) {
+            Ok(mut entries) => Ok(Some(entries.next().is_none())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(VssError::Io(format!("cannot read {}: {}", path, e))),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Drives C: and D: are used, C:\empty is the only empty directory, C:\data is not empty
+    pub(crate) struct FakeMountPoints;
+
+    impl MountPoints for FakeMountPoints {
+        fn is_drive_in_use(&self, letter: char) -> Result<bool, VssError> {
+            Ok(matches!(letter, 'C' | 'D'))
+        }
+
+        fn is_directory_empty(&self, path: &str) -> Result<Option<bool>, VssError> {
+            Ok(match path.to_lowercase().as_str() {
+                "c:\\empty" => Some(true),
+                "c:\\data" => Some(false),
+                _ => None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_local() {
+        for target in ["x", "X:", "x:\\"] {
+            assert_eq!(
+                ExposeTarget::local(target).unwrap(),
+                ExposeTarget::DriveLetter('X')
+            );
+        }
+        let dir = ExposeTarget::local("C:\\mnt\\shadow\\").unwrap();
+        assert_eq!(dir, ExposeTarget::Directory("C:\\mnt\\shadow".to_owned()));
+        assert_eq!(dir.expose(), "C:\\mnt\\shadow");
+        assert_eq!(dir.attributes(), VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY);
+        assert_eq!(dir.path_from_root(), None);
+
+        for bad in ["", "1:", "XY", "mnt\\shadow", "C:mnt", "C:\\a*", "C:\\a:b"] {
+            assert!(
+                matches!(ExposeTarget::local(bad), Err(VssError::InvalidArgument(_))),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_remote() {
+        let share = ExposeTarget::remote("shadow$", Some("\\Users")).unwrap();
+        assert_eq!(share.expose(), "shadow$");
+        assert_eq!(share.path_from_root(), Some("\\Users"));
+        assert_eq!(share.attributes(), VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY);
+        assert_eq!(
+            ExposeTarget::remote("shadow", Some("\\")).unwrap(),
+            ExposeTarget::Share {
+                name: "shadow".to_owned(),
+                path: None
+            }
+        );
+
+        let long = "s".repeat(81);
+        for bad in [
+            "",
+            "a/b",
+            "a\\b",
+            "a:b",
+            "a*",
+            "a\tb",
+            " a",
+            "a.",
+            long.as_str(),
+        ] {
+            assert!(ExposeTarget::remote(bad, None).is_err(), "{:?}", bad);
+        }
+        assert!(ExposeTarget::remote("shadow", Some("\\..\\Windows")).is_err());
+        assert!(ExposeTarget::remote("shadow", Some("C:\\Users")).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let validate = |target: ExposeTarget| target.validate(&FakeMountPoints);
+        assert!(validate(ExposeTarget::DriveLetter('X')).is_ok());
+        assert!(validate(ExposeTarget::DriveLetter('C')).is_err());
+        assert!(validate(ExposeTarget::Directory("C:\\empty".to_owned())).is_ok());
+        assert!(validate(ExposeTarget::Directory("C:\\data".to_owned())).is_err());
+        assert!(validate(ExposeTarget::Directory("C:\\missing".to_owned())).is_err());
+        assert!(validate(ExposeTarget::remote("shadow", None).unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,1588 @@
+use std::{cell::RefCell, rc::Rc};
+
+use chrono::Utc;
+use windows::{
+    core::{GUID, HRESULT},
+    Win32::{
+        Foundation::E_INVALIDARG,
+        Storage::Vss::{
+            VSS_BACKUP_TYPE, VSS_CTX_ALL, VSS_CTX_BACKUP, VSS_E_OBJECT_ALREADY_EXISTS,
+            VSS_E_OBJECT_NOT_FOUND, VSS_E_PROVIDER_NOT_REGISTERED, VSS_E_REVERT_IN_PROGRESS,
+            VSS_E_UNSUPPORTED_CONTEXT, VSS_FILE_RESTORE_STATUS, VSS_HARDWARE_OPTIONS,
+            VSS_OBJECT_SNAPSHOT, VSS_OBJECT_SNAPSHOT_SET, VSS_OBJECT_TYPE, VSS_RESTORE_TYPE,
+            VSS_SNAPSHOT_CONTEXT, VSS_SS_CREATED, VSS_SS_PREPARING,
+            VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE, VSS_VOLSNAP_ATTR_IMPORTED,
+            VSS_VOLSNAP_ATTR_PERSISTENT, VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+        },
+    },
+};
+
+use crate::{
+    asyncop::{AsyncOperation, AsyncOptions, AsyncStatus},
+    bcd::{
+        BackupComponents, Component, SnapshotDescription, SnapshotSetDescription, WriterComponents,
+    },
+    diffarea::{DiffArea, DiffAreaBackend, DiffVolume, Volume, VSS_SWPRV_PROVIDER_ID},
+    error::VssError,
+    provider::{Provider, ProviderType},
+    restore::FileRestore,
+    revert::{RevertStatus, RevertStatusQuery},
+    utils::i64_to_date,
+    vssbackend::VssBackend,
+    vssprop::VSSProp,
+    writermetadata::ComponentType,
+    writerselection::SelectedComponent,
+    writerstatus::WriterStatus,
+};
+
+/// A shadow copy set known to the fake system
+#[derive(Debug, Clone)]
+pub struct FakeSnapshotSet {
+    pub set_id: GUID,
+    pub context: VSS_SNAPSHOT_CONTEXT,
+    pub snapshot_ids: Vec<GUID>,
+}
+
+/// The machine-wide state shared by every backend created through `FakeBackend::connect`
+#[derive(Debug)]
+struct FakeSystem {
+    machine: String,
+    next_id: u128,
+    next_device: u32,
+    snapshot_sets: Vec<FakeSnapshotSet>,
+    snapshots: Vec<VSSProp>,
+    /// The `WRITER_METADATA` documents of the registered writers
+    writers: Vec<String>,
+    writer_statuses: Vec<WriterStatus>,
+    /// The broken shadow copy sets, with the flags given to `break_snapshot_set`
+    broken_sets: Vec<(GUID, VSS_HARDWARE_OPTIONS)>,
+    providers: Vec<Provider>,
+    /// The volumes that a provider does not support
+    unsupported_volumes: Vec<(String, GUID)>,
+    /// The volumes of the machine, which can all store diff areas
+    volumes: Vec<DiffVolume>,
+    diff_areas: Vec<DiffArea>,
+    /// The reverted volumes, with the number of times their revert still reports pending
+    reverts: Vec<(String, u32)>,
+    /// The number of pending statuses of the next reverts
+    revert_polls: u32,
+    /// The asynchronous operations that report pending, with the number of polls,
+    /// `None` when they never finish
+    async_pending: Vec<(String, Option<u32>)>,
+    calls: Vec<String>,
+    failures: Vec<(String, HRESULT)>,
+}
+
+impl Default for FakeSystem {
+    fn default() -> Self {
+        Self {
+            machine: "localhost".to_owned(),
+            next_id: 1,
+            next_device: 1,
+            snapshot_sets: Vec::new(),
+            snapshots: Vec::new(),
+            writers: Vec::new(),
+            writer_statuses: Vec::new(),
+            broken_sets: Vec::new(),
+            providers: vec![Provider {
+                provider_id: VSS_SWPRV_PROVIDER_ID,
+                name: "Microsoft Software Shadow Copy provider 1.0".to_owned(),
+                provider_type: ProviderType::System,
+                version: "1.0.0.7".to_owned(),
+                version_id: GUID::from_u128(0x00000001_0000_0000_0000_000000000000),
+                class_id: GUID::from_u128(0x65ee1dba_8ff4_4a58_ac1c_3470ee2f376a),
+            }],
+            unsupported_volumes: Vec::new(),
+            volumes: Vec::new(),
+            diff_areas: Vec::new(),
+            reverts: Vec::new(),
+            revert_polls: 0,
+            async_pending: Vec::new(),
+            calls: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+}
+
+impl FakeSystem {
+    fn new_id(&mut self) -> GUID {
+        let id = GUID::from_u128(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    fn diff_area(
+        &mut self,
+        operation: &'static str,
+        volume: &str,
+        diff_area_volume: &str,
+    ) -> Result<&mut DiffArea, VssError> {
+        self.diff_areas
+            .iter_mut()
+            .find(|d| {
+                d.volume_name.eq_ignore_ascii_case(volume)
+                    && d.diff_area_volume_name
+                        .eq_ignore_ascii_case(diff_area_volume)
+            })
+            .ok_or_else(|| error(operation, VSS_E_OBJECT_NOT_FOUND))
+    }
+
+    fn check_volume(&self, operation: &'static str, volume: &str) -> Result<(), VssError> {
+        match self
+            .volumes
+            .iter()
+            .any(|v| v.volume_name.eq_ignore_ascii_case(volume))
+        {
+            true => Ok(()),
+            false => Err(error(operation, VSS_E_OBJECT_NOT_FOUND)),
+        }
+    }
+
+    fn remove_snapshot(&mut self, snapshot_id: GUID) {
+        self.snapshots.retain(|s| s.snapshot_id != snapshot_id);
+        for set in self.snapshot_sets.iter_mut() {
+            set.snapshot_ids.retain(|id| *id != snapshot_id);
+        }
+        self.snapshot_sets
+            .retain(|set| !set.snapshot_ids.is_empty());
+    }
+}
+
+/// The shadow copy set being built between `start_snapshot_set` and `do_snapshot_set`
+#[derive(Debug)]
+struct PendingSet {
+    set_id: GUID,
+    snapshots: Vec<VSSProp>,
+    prepared: bool,
+}
+
+/// The restore session of a backup components object initialized for restore
+#[derive(Debug, Default)]
+struct FakeRestore {
+    selected: Vec<SelectedComponent>,
+    alternate_locations: Vec<(SelectedComponent, FileRestore)>,
+    pre_restored: bool,
+    file_statuses: Vec<(SelectedComponent, VSS_FILE_RESTORE_STATUS)>,
+    post_restored: bool,
+}
+
+/// The state of one backup components object
+#[derive(Debug, Default)]
+struct FakeComponent {
+    created: bool,
+    initialized: bool,
+    context: Option<VSS_SNAPSHOT_CONTEXT>,
+    writer_metadata_gathered: bool,
+    writer_status_gathered: bool,
+    disabled_instances: Vec<GUID>,
+    components: Vec<SelectedComponent>,
+    /// The outcome reported for the added components by `SetBackupSucceeded`
+    backup_succeeded: Vec<(SelectedComponent, bool)>,
+    /// The Backup Components document given at initialization
+    document: Option<BackupComponents>,
+    restore: Option<FakeRestore>,
+    pending: Option<PendingSet>,
+    committed_set: Option<GUID>,
+}
+
+impl FakeComponent {
+    fn check_initialized(&self, operation: &'static str) -> Result<(), VssError> {
+        if self.created && self.initialized {
+            Ok(())
+        } else {
+            Err(bad_state(operation))
+        }
+    }
+
+    fn context(&self) -> VSS_SNAPSHOT_CONTEXT {
+        self.context.unwrap_or(VSS_CTX_BACKUP)
+    }
+
+    /// The restore session, only available after `initialize_for_restore`
+    fn restore(&mut self, operation: &'static str) -> Result<&mut FakeRestore, VssError> {
+        self.check_initialized(operation)?;
+        self.restore.as_mut().ok_or_else(|| bad_state(operation))
+    }
+
+    /// Whether the document given at initialization contains the component
+    fn has_component(&self, component: &SelectedComponent) -> bool {
+        let name = |path: Option<&str>, name: &str| match path.filter(|p| !p.is_empty()) {
+            Some(path) => format!("{}\\{}", path, name).to_lowercase(),
+            None => name.to_lowercase(),
+        };
+        let wanted = name(component.logical_path.as_deref(), &component.name);
+        self.document.iter().any(|document| {
+            document
+                .writer_components
+                .iter()
+                .filter(|w| w.writer_id == Some(component.writer_id))
+                .flat_map(|w| w.components.iter())
+                .any(|c| name(c.logical_path.as_deref(), &c.component_name) == wanted)
+        })
+    }
+}
+
+fn bad_state(operation: &'static str) -> VssError {
+    VssError::BadState { operation }
+}
+
+fn error(operation: &'static str, hr: HRESULT) -> VssError {
+    VssError::from_hresult(operation, hr)
+}
+
+/// In-memory `VssBackend` without any COM dependency.
+///
+/// Snapshot sets, snapshots, their attributes and states are kept in a fake
+/// "system" that can be shared by several backends, the same way all the
+/// backup components objects of a machine see the same shadow copies.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    system: Rc<RefCell<FakeSystem>>,
+    component: RefCell<FakeComponent>,
+    async_options: AsyncOptions,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A new backend working against the same fake system
+    pub fn connect(&self) -> Self {
+        Self {
+            system: self.system.clone(),
+            component: Default::default(),
+            async_options: Default::default(),
+        }
+    }
+
+    /// Set the machine name reported as originating and service machine
+    pub fn set_machine_name(&self, machine: &str) {
+        self.system.borrow_mut().machine = machine.to_owned();
+    }
+
+    /// All the committed shadow copies, in creation order
+    pub fn snapshots(&self) -> Vec<VSSProp> {
+        self.system.borrow().snapshots.clone()
+    }
+
+    /// All the committed shadow copy sets, in creation order
+    pub fn snapshot_sets(&self) -> Vec<FakeSnapshotSet> {
+        self.system.borrow().snapshot_sets.clone()
+    }
+
+    /// Add a committed shadow copy to the system, e.g. to seed a test.
+    /// The shadow copy set is created in the `VSS_CTX_*` context if needed, whatever the
+    /// attributes of the shadow copy.
+    pub fn insert_snapshot(&self, prop: VSSProp, context: VSS_SNAPSHOT_CONTEXT) {
+        let mut system = self.system.borrow_mut();
+        match system
+            .snapshot_sets
+            .iter_mut()
+            .find(|set| set.set_id == prop.shadow_copy_set_id)
+        {
+            Some(set) => set.snapshot_ids.push(prop.snapshot_id),
+            None => system.snapshot_sets.push(FakeSnapshotSet {
+                set_id: prop.shadow_copy_set_id,
+                context,
+                snapshot_ids: vec![prop.snapshot_id],
+            }),
+        }
+        system.snapshots.push(prop);
+    }
+
+    /// Register a provider next to the system provider
+    pub fn add_provider(&self, provider: Provider) {
+        self.system.borrow_mut().providers.push(provider);
+    }
+
+    /// Add a volume of `total_space` bytes, all free, to the machine
+    pub fn add_volume(&self, volume_name: &str, display_name: &str, total_space: u64) {
+        self.system.borrow_mut().volumes.push(DiffVolume {
+            volume_name: volume_name.to_owned(),
+            display_name: display_name.to_owned(),
+            free_space: total_space,
+            total_space,
+        });
+    }
+
+    /// Make the provider refuse to shadow copy the volume
+    pub fn set_volume_unsupported(&self, volume_name: &str, provider_id: GUID) {
+        self.system
+            .borrow_mut()
+            .unsupported_volumes
+            .push((volume_name.to_owned(), provider_id));
+    }
+
+    /// Make the next asynchronous `operation`, e.g. "DoSnapshotSet", report pending
+    /// for a number of polls, or forever when `None` to simulate a hung writer
+    pub fn set_async_pending(&self, operation: &str, polls: Option<u32>) {
+        self.system
+            .borrow_mut()
+            .async_pending
+            .push((operation.to_owned(), polls));
+    }
+
+    /// Set the space used and allocated by an existing diff area
+    pub fn set_diff_area_usage(
+        &self,
+        volume: &str,
+        diff_area_volume: &str,
+        used_space: u64,
+        allocated_space: u64,
+    ) {
+        let mut system = self.system.borrow_mut();
+        let diff_area = system
+            .diff_area("SetDiffAreaUsage", volume, diff_area_volume)
+            .unwrap();
+        diff_area.used_space = used_space;
+        diff_area.allocated_space = allocated_space;
+    }
+
+    /// Register a writer with its `WRITER_METADATA` document
+    pub fn add_writer(&self, metadata_xml: &str) {
+        self.system
+            .borrow_mut()
+            .writers
+            .push(metadata_xml.to_owned());
+    }
+
+    /// Set the status reported by a writer, replacing the previous status of the same instance
+    pub fn set_writer_status(&self, status: WriterStatus) {
+        let mut system = self.system.borrow_mut();
+        system
+            .writer_statuses
+            .retain(|s| s.instance_id != status.instance_id || s.writer_id != status.writer_id);
+        system.writer_statuses.push(status);
+    }
+
+    /// The context of the current backup components object
+    pub fn context(&self) -> VSS_SNAPSHOT_CONTEXT {
+        self.component.borrow().context()
+    }
+
+    /// The writer instances disabled on the current backup components object
+    pub fn disabled_writer_instances(&self) -> Vec<GUID> {
+        self.component.borrow().disabled_instances.clone()
+    }
+
+    /// The components added to the current backup components object
+    pub fn components(&self) -> Vec<SelectedComponent> {
+        self.component.borrow().components.clone()
+    }
+
+    /// The outcome reported for the components of the current backup components object
+    pub fn backup_succeeded(&self) -> Vec<(SelectedComponent, bool)> {
+        self.component.borrow().backup_succeeded.clone()
+    }
+
+    /// The components selected for restore on the current backup components object
+    pub fn selected_for_restore(&self) -> Vec<SelectedComponent> {
+        let component = self.component.borrow();
+        component
+            .restore
+            .as_ref()
+            .map(|r| r.selected.clone())
+            .unwrap_or_default()
+    }
+
+    /// The alternate locations added on the current backup components object
+    pub fn alternate_location_mappings(&self) -> Vec<(SelectedComponent, FileRestore)> {
+        let component = self.component.borrow();
+        component
+            .restore
+            .as_ref()
+            .map(|r| r.alternate_locations.clone())
+            .unwrap_or_default()
+    }
+
+    /// The file restore statuses set on the current backup components object
+    pub fn file_restore_statuses(&self) -> Vec<(SelectedComponent, VSS_FILE_RESTORE_STATUS)> {
+        let component = self.component.borrow();
+        component
+            .restore
+            .as_ref()
+            .map(|r| r.file_statuses.clone())
+            .unwrap_or_default()
+    }
+
+    /// The shadow copy sets broken so far, with their `VSS_BREAKEX_FLAG_*` flags
+    pub fn broken_snapshot_sets(&self) -> Vec<(GUID, VSS_HARDWARE_OPTIONS)> {
+        self.system.borrow().broken_sets.clone()
+    }
+
+    /// Make the next reverts report pending `polls` times before finishing
+    pub fn set_revert_polls(&self, polls: u32) {
+        self.system.borrow_mut().revert_polls = polls;
+    }
+
+    /// The volumes reverted so far
+    pub fn reverted_volumes(&self) -> Vec<String> {
+        let system = self.system.borrow();
+        system.reverts.iter().map(|(v, _)| v.clone()).collect()
+    }
+
+    /// Make the next call of the given operation fail with `hr`.
+    /// Operations are named after the `IVssBackupComponents` methods, e.g. "DoSnapshotSet".
+    pub fn fail_next(&self, operation: &str, hr: HRESULT) {
+        self.system
+            .borrow_mut()
+            .failures
+            .push((operation.to_owned(), hr));
+    }
+
+    /// The operations received so far, named after the `IVssBackupComponents` methods
+    pub fn calls(&self) -> Vec<String> {
+        self.system.borrow().calls.clone()
+    }
+
+    /// Record the call and return the failure injected for it, if any
+    fn call(&self, operation: &'static str) -> Result<(), VssError> {
+        let mut system = self.system.borrow_mut();
+        system.calls.push(operation.to_owned());
+        match system.failures.iter().position(|(op, _)| op == operation) {
+            Some(i) => Err(error(operation, system.failures.remove(i).1)),
+            None => Ok(()),
+        }
+    }
+
+    /// Wait for the asynchronous operation when it was set pending, it is not
+    /// carried out when it times out or is cancelled
+    fn wait_async(&self, operation: &'static str) -> Result<(), VssError> {
+        let pending = {
+            let mut system = self.system.borrow_mut();
+            match system
+                .async_pending
+                .iter()
+                .position(|(op, _)| op == operation)
+            {
+                Some(i) => system.async_pending.remove(i).1,
+                None => return Ok(()),
+            }
+        };
+        self.async_options
+            .wait(&mut FakeAsync::new(pending))?
+            .ok(operation)
+    }
+}
+
+impl VssBackend for FakeBackend {
+    fn create_backup_components(&mut self) -> Result<(), VssError> {
+        const OP: &str = "CreateVssBackupComponents";
+        self.call(OP)?;
+        *self.component.borrow_mut() = FakeComponent {
+            created: true,
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    fn set_async_options(&mut self, options: AsyncOptions) {
+        self.async_options = options;
+    }
+
+    fn initialize_for_backup(&self, xml: Option<&str>) -> Result<(), VssError> {
+        const OP: &str = "InitializeForBackup";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        if !component.created || component.initialized {
+            return Err(bad_state(OP));
+        }
+        if let Some(xml) = xml {
+            let document = BackupComponents::from_xml(xml).map_err(|_| error(OP, E_INVALIDARG))?;
+            component.document = Some(document);
+        }
+        component.initialized = true;
+        Ok(())
+    }
+
+    fn initialize_for_restore(&self, xml: Option<&str>) -> Result<(), VssError> {
+        const OP: &str = "InitializeForRestore";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        if !component.created || component.initialized {
+            return Err(bad_state(OP));
+        }
+        let document = xml
+            .and_then(|xml| BackupComponents::from_xml(xml).ok())
+            .ok_or_else(|| error(OP, E_INVALIDARG))?;
+        component.document = Some(document);
+        component.restore = Some(FakeRestore::default());
+        component.initialized = true;
+        Ok(())
+    }
+
+    fn set_context(&self, context: VSS_SNAPSHOT_CONTEXT) -> Result<(), VssError> {
+        const OP: &str = "SetContext";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        // The context can only be set before the first shadow copy set is started
+        if component.context.is_some() || component.pending.is_some() {
+            return Err(bad_state(OP));
+        }
+        component.context = Some(context);
+        Ok(())
+    }
+
+    fn set_backup_state(
+        &self,
+        _select_components: bool,
+        _backup_bootable_system_state: bool,
+        _backup_type: VSS_BACKUP_TYPE,
+        _partial_file_support: bool,
+    ) -> Result<(), VssError> {
+        const OP: &str = "SetBackupState";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)
+    }
+
+    fn gather_writer_metadata(&self) -> Result<(), VssError> {
+        const OP: &str = "GatherWriterMetadata";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        if component.writer_metadata_gathered {
+            return Err(bad_state(OP));
+        }
+        component.writer_metadata_gathered = true;
+        Ok(())
+    }
+
+    fn get_writer_metadata_count(&self) -> Result<u32, VssError> {
+        const OP: &str = "GetWriterMetadataCount";
+        self.call(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        if !component.writer_metadata_gathered {
+            return Err(bad_state(OP));
+        }
+        Ok(self.system.borrow().writers.len() as u32)
+    }
+
+    fn get_writer_metadata(&self, index: u32) -> Result<String, VssError> {
+        const OP: &str = "GetWriterMetadata";
+        self.call(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        if !component.writer_metadata_gathered {
+            return Err(bad_state(OP));
+        }
+        self.system
+            .borrow()
+            .writers
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| error(OP, E_INVALIDARG))
+    }
+
+    fn gather_writer_status(&self) -> Result<(), VssError> {
+        const OP: &str = "GatherWriterStatus";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        component.writer_status_gathered = true;
+        Ok(())
+    }
+
+    fn get_writer_status_count(&self) -> Result<u32, VssError> {
+        const OP: &str = "GetWriterStatusCount";
+        self.call(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        if !component.writer_status_gathered {
+            return Err(bad_state(OP));
+        }
+        Ok(self.system.borrow().writer_statuses.len() as u32)
+    }
+
+    fn get_writer_status(&self, index: u32) -> Result<WriterStatus, VssError> {
+        const OP: &str = "GetWriterStatus";
+        self.call(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        if !component.writer_status_gathered {
+            return Err(bad_state(OP));
+        }
+        self.system
+            .borrow()
+            .writer_statuses
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| error(OP, E_INVALIDARG))
+    }
+
+    fn free_writer_status(&self) -> Result<(), VssError> {
+        const OP: &str = "FreeWriterStatus";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        component.writer_status_gathered = false;
+        Ok(())
+    }
+
+    fn disable_writer_instances(&self, instance_ids: &[GUID]) -> Result<(), VssError> {
+        const OP: &str = "DisableWriterInstances";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        component.disabled_instances.extend_from_slice(instance_ids);
+        Ok(())
+    }
+
+    fn add_component(&self, added: &SelectedComponent) -> Result<(), VssError> {
+        const OP: &str = "AddComponent";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        if component.components.iter().any(|c| {
+            c.instance_id == added.instance_id
+                && c.logical_path == added.logical_path
+                && c.name == added.name
+        }) {
+            return Err(error(OP, VSS_E_OBJECT_ALREADY_EXISTS));
+        }
+        component.components.push(added.clone());
+        Ok(())
+    }
+
+    fn set_restore_state(&self, _restore_type: VSS_RESTORE_TYPE) -> Result<(), VssError> {
+        const OP: &str = "SetRestoreState";
+        self.call(OP)?;
+        match self.component.borrow_mut().restore(OP)? {
+            restore if restore.pre_restored => Err(bad_state(OP)),
+            _ => Ok(()),
+        }
+    }
+
+    fn set_selected_for_restore(
+        &self,
+        selected_component: &SelectedComponent,
+        selected: bool,
+    ) -> Result<(), VssError> {
+        const OP: &str = "SetSelectedForRestore";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        if !component.has_component(selected_component) {
+            return Err(error(OP, VSS_E_OBJECT_NOT_FOUND));
+        }
+        let restore = component.restore(OP)?;
+        if restore.pre_restored {
+            return Err(bad_state(OP));
+        }
+        restore.selected.retain(|c| c != selected_component);
+        if selected {
+            restore.selected.push(selected_component.clone());
+        }
+        Ok(())
+    }
+
+    fn add_alternative_location_mapping(
+        &self,
+        mapped_component: &SelectedComponent,
+        file: &FileRestore,
+    ) -> Result<(), VssError> {
+        const OP: &str = "AddAlternativeLocationMapping";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        if !component.has_component(mapped_component) {
+            return Err(error(OP, VSS_E_OBJECT_NOT_FOUND));
+        }
+        let restore = component.restore(OP)?;
+        if restore.pre_restored {
+            return Err(bad_state(OP));
+        }
+        restore
+            .alternate_locations
+            .push((mapped_component.clone(), file.clone()));
+        Ok(())
+    }
+
+    fn pre_restore(&self) -> Result<(), VssError> {
+        const OP: &str = "PreRestore";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let mut component = self.component.borrow_mut();
+        let gathered = component.writer_metadata_gathered;
+        let restore = component.restore(OP)?;
+        if restore.pre_restored || !gathered {
+            return Err(bad_state(OP));
+        }
+        restore.pre_restored = true;
+        Ok(())
+    }
+
+    fn set_file_restore_status(
+        &self,
+        restored: &SelectedComponent,
+        status: VSS_FILE_RESTORE_STATUS,
+    ) -> Result<(), VssError> {
+        const OP: &str = "SetFileRestoreStatus";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        let restore = component.restore(OP)?;
+        if !restore.pre_restored || restore.post_restored {
+            return Err(bad_state(OP));
+        }
+        if !restore.selected.contains(restored) {
+            return Err(error(OP, VSS_E_OBJECT_NOT_FOUND));
+        }
+        restore.file_statuses.push((restored.clone(), status));
+        Ok(())
+    }
+
+    fn post_restore(&self) -> Result<(), VssError> {
+        const OP: &str = "PostRestore";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let mut component = self.component.borrow_mut();
+        let restore = component.restore(OP)?;
+        if !restore.pre_restored || restore.post_restored {
+            return Err(bad_state(OP));
+        }
+        restore.post_restored = true;
+        Ok(())
+    }
+
+    fn start_snapshot_set(&self) -> Result<GUID, VssError> {
+        const OP: &str = "StartSnapshotSet";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        if component.pending.is_some() {
+            return Err(bad_state(OP));
+        }
+        let set_id = self.system.borrow_mut().new_id();
+        component.pending = Some(PendingSet {
+            set_id,
+            snapshots: Vec::new(),
+            prepared: false,
+        });
+        Ok(set_id)
+    }
+
+    fn add_to_snapshot_set(&self, volume: &str, provider_id: GUID) -> Result<GUID, VssError> {
+        const OP: &str = "AddToSnapshotSet";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        let context = component.context();
+        let pending = component.pending.as_mut().ok_or_else(|| bad_state(OP))?;
+        if pending.prepared {
+            return Err(bad_state(OP));
+        }
+        if pending
+            .snapshots
+            .iter()
+            .any(|s| s.origin_vol_name.eq_ignore_ascii_case(volume))
+        {
+            return Err(error(OP, VSS_E_OBJECT_ALREADY_EXISTS));
+        }
+
+        let mut system = self.system.borrow_mut();
+        // The system provider is chosen when none is given
+        let provider_id = match provider_id {
+            id if id == GUID::zeroed() => VSS_SWPRV_PROVIDER_ID,
+            id if system.providers.iter().any(|p| p.provider_id == id) => id,
+            _ => return Err(error(OP, VSS_E_PROVIDER_NOT_REGISTERED)),
+        };
+        let snapshot_id = system.new_id();
+        // The context bits are the implied snapshot attributes
+        let attrs = if context == VSS_CTX_ALL { 0 } else { context.0 };
+        pending.snapshots.push(VSSProp {
+            snapshot_id,
+            shadow_copy_set_id: pending.set_id,
+            origin_vol_name: volume.to_owned(),
+            origin_machine: system.machine.clone(),
+            origin_service: system.machine.clone(),
+            snapshot_attrs: VSS_VOLUME_SNAPSHOT_ATTRIBUTES(attrs),
+            provider_id,
+            state: VSS_SS_PREPARING,
+            ..Default::default()
+        });
+        Ok(snapshot_id)
+    }
+
+    fn is_volume_supported(&self, volume: &str, provider_id: GUID) -> Result<bool, VssError> {
+        const OP: &str = "IsVolumeSupported";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        let system = self.system.borrow();
+        system.check_volume(OP, volume)?;
+        let supports = |id: GUID| {
+            !system
+                .unsupported_volumes
+                .iter()
+                .any(|(v, p)| *p == id && v.eq_ignore_ascii_case(volume))
+        };
+        match provider_id {
+            // Any provider
+            id if id == GUID::zeroed() => {
+                Ok(system.providers.iter().any(|p| supports(p.provider_id)))
+            }
+            id if system.providers.iter().any(|p| p.provider_id == id) => Ok(supports(id)),
+            _ => Err(error(OP, VSS_E_PROVIDER_NOT_REGISTERED)),
+        }
+    }
+
+    fn prepare_for_backup(&self) -> Result<(), VssError> {
+        const OP: &str = "PrepareForBackup";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        match component.pending.as_mut() {
+            Some(pending) if !pending.snapshots.is_empty() && !pending.prepared => {
+                pending.prepared = true;
+                Ok(())
+            }
+            _ => Err(bad_state(OP)),
+        }
+    }
+
+    fn do_snapshot_set(&self) -> Result<(), VssError> {
+        const OP: &str = "DoSnapshotSet";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        let context = component.context();
+        let pending = match component.pending.take() {
+            Some(pending) if pending.prepared => pending,
+            other => {
+                component.pending = other;
+                return Err(bad_state(OP));
+            }
+        };
+
+        let mut system = self.system.borrow_mut();
+        let now = Utc::now();
+        let count = pending.snapshots.len() as i32;
+        let mut snapshot_ids = Vec::with_capacity(pending.snapshots.len());
+        for mut snapshot in pending.snapshots {
+            snapshot.snapshot_count = count;
+            snapshot.create_time = now;
+            snapshot.state = VSS_SS_CREATED;
+            snapshot.device_name = format!(
+                r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy{}",
+                system.next_device
+            );
+            system.next_device += 1;
+            snapshot_ids.push(snapshot.snapshot_id);
+            system.snapshots.push(snapshot);
+        }
+        system.snapshot_sets.push(FakeSnapshotSet {
+            set_id: pending.set_id,
+            context,
+            snapshot_ids,
+        });
+        component.committed_set = Some(pending.set_id);
+        Ok(())
+    }
+
+    fn set_backup_succeeded(
+        &self,
+        reported: &SelectedComponent,
+        succeeded: bool,
+    ) -> Result<(), VssError> {
+        const OP: &str = "SetBackupSucceeded";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        if component.committed_set.is_none() {
+            return Err(bad_state(OP));
+        }
+        if !component.components.contains(reported) {
+            return Err(error(OP, VSS_E_OBJECT_NOT_FOUND));
+        }
+        component.backup_succeeded.retain(|(c, _)| c != reported);
+        component
+            .backup_succeeded
+            .push((reported.clone(), succeeded));
+        Ok(())
+    }
+
+    fn backup_complete(&self) -> Result<(), VssError> {
+        const OP: &str = "BackupComplete";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        match component.committed_set {
+            Some(_) => Ok(()),
+            None => Err(bad_state(OP)),
+        }
+    }
+
+    fn abort_backup(&self) -> Result<(), VssError> {
+        const OP: &str = "AbortBackup";
+        self.call(OP)?;
+        let mut component = self.component.borrow_mut();
+        component.check_initialized(OP)?;
+        component.pending = None;
+        Ok(())
+    }
+
+    fn query_providers(&self) -> Result<Vec<Provider>, VssError> {
+        const OP: &str = "Query";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        Ok(self.system.borrow().providers.clone())
+    }
+
+    fn query(&self) -> Result<Vec<VSSProp>, VssError> {
+        const OP: &str = "Query";
+        self.call(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        let context = component.context();
+        let system = self.system.borrow();
+        // Only the shadow copies created in the current context are visible
+        let in_context = |s: &&VSSProp| {
+            context == VSS_CTX_ALL
+                || system
+                    .snapshot_sets
+                    .iter()
+                    .any(|set| set.set_id == s.shadow_copy_set_id && set.context == context)
+        };
+        Ok(system
+            .snapshots
+            .iter()
+            .filter(in_context)
+            .cloned()
+            .collect())
+    }
+
+    fn delete_snapshots(
+        &self,
+        id: GUID,
+        object_type: VSS_OBJECT_TYPE,
+        _force: bool,
+    ) -> Result<i32, VssError> {
+        const OP: &str = "DeleteSnapshots";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        let mut system = self.system.borrow_mut();
+        let ids = match object_type {
+            VSS_OBJECT_SNAPSHOT => system
+                .snapshots
+                .iter()
+                .filter(|s| s.snapshot_id == id)
+                .map(|s| s.snapshot_id)
+                .collect::<Vec<_>>(),
+            VSS_OBJECT_SNAPSHOT_SET => system
+                .snapshot_sets
+                .iter()
+                .find(|set| set.set_id == id)
+                .map(|set| set.snapshot_ids.clone())
+                .unwrap_or_default(),
+            _ => return Err(error(OP, E_INVALIDARG)),
+        };
+        if ids.is_empty() {
+            return Err(error(OP, VSS_E_OBJECT_NOT_FOUND));
+        }
+        for snapshot_id in ids.iter() {
+            system.remove_snapshot(*snapshot_id);
+        }
+        Ok(ids.len() as i32)
+    }
+
+    fn get_snapshot_properties(&self, snapshot_id: GUID) -> Result<VSSProp, VssError> {
+        const OP: &str = "GetSnapshotProperties";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        self.system
+            .borrow()
+            .snapshots
+            .iter()
+            .find(|s| s.snapshot_id == snapshot_id)
+            .cloned()
+            .ok_or_else(|| error(OP, VSS_E_OBJECT_NOT_FOUND))
+    }
+
+    fn import_snapshots(&self) -> Result<(), VssError> {
+        const OP: &str = "ImportSnapshots";
+        self.call(OP)?;
+        self.wait_async(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        let document = component.document.as_ref().ok_or_else(|| bad_state(OP))?;
+        let mut system = self.system.borrow_mut();
+        for set in document.snapshot_sets.iter() {
+            let snapshots = set.transportable_snapshots().collect::<Vec<_>>();
+            if snapshots.is_empty() {
+                continue;
+            }
+            if system
+                .snapshot_sets
+                .iter()
+                .any(|s| s.set_id == set.snapshot_set_id)
+            {
+                return Err(error(OP, VSS_E_OBJECT_ALREADY_EXISTS));
+            }
+            for snapshot in snapshots.iter() {
+                let mut prop = VSSProp {
+                    snapshot_id: snapshot.snapshot_id,
+                    shadow_copy_set_id: set.snapshot_set_id,
+                    snapshot_count: snapshots.len() as i32,
+                    origin_vol_name: snapshot.original_volume_name.clone().unwrap_or_default(),
+                    create_time: snapshot.timestamp.map_or_else(Utc::now, i64_to_date),
+                    device_name: format!(
+                        r"\\?\GLOBALROOT\Device\HarddiskVolumeShadowCopy{}",
+                        system.next_device
+                    ),
+                    origin_machine: snapshot.originating_machine.clone().unwrap_or_default(),
+                    origin_service: system.machine.clone(),
+                    provider_id: snapshot.provider_id.unwrap_or_default(),
+                    state: VSS_SS_CREATED,
+                    ..Default::default()
+                };
+                prop.snapshot_attrs = VSS_VOLUME_SNAPSHOT_ATTRIBUTES(
+                    snapshot.snapshot_attributes.unwrap_or_default() as i32
+                        | VSS_VOLSNAP_ATTR_IMPORTED.0,
+                );
+                system.next_device += 1;
+                system.snapshots.push(prop);
+            }
+            system.snapshot_sets.push(FakeSnapshotSet {
+                set_id: set.snapshot_set_id,
+                context: VSS_SNAPSHOT_CONTEXT(set.context.unwrap_or_default()),
+                snapshot_ids: snapshots.iter().map(|s| s.snapshot_id).collect(),
+            });
+        }
+        Ok(())
+    }
+
+    fn save_as_xml(&self) -> Result<String, VssError> {
+        const OP: &str = "SaveAsXML";
+        self.call(OP)?;
+        let component = self.component.borrow();
+        component.check_initialized(OP)?;
+        let mut document = component
+            .document
+            .clone()
+            .unwrap_or_else(|| BackupComponents {
+                version: Some("2.0".to_owned()),
+                ..Default::default()
+            });
+        for added in component.components.iter() {
+            let index = match document
+                .writer_components
+                .iter()
+                .position(|w| w.instance_id == Some(added.instance_id))
+            {
+                Some(index) => index,
+                None => {
+                    document.writer_components.push(WriterComponents {
+                        instance_id: Some(added.instance_id),
+                        writer_id: Some(added.writer_id),
+                        ..Default::default()
+                    });
+                    document.writer_components.len() - 1
+                }
+            };
+            let component_type = match added.component_type {
+                ComponentType::Database => "database",
+                ComponentType::FileGroup => "filegroup",
+            };
+            document.writer_components[index]
+                .components
+                .push(Component {
+                    logical_path: added.logical_path.clone(),
+                    component_name: added.name.clone(),
+                    component_type: Some(component_type.to_owned()),
+                    backup_succeeded: component
+                        .backup_succeeded
+                        .iter()
+                        .find(|(c, _)| c == added)
+                        .map(|(_, succeeded)| *succeeded),
+                    ..Default::default()
+                });
+        }
+
+        let system = self.system.borrow();
+        let committed = component
+            .committed_set
+            .and_then(|set_id| system.snapshot_sets.iter().find(|s| s.set_id == set_id));
+        if let Some(set) = committed {
+            let snapshots = system
+                .snapshots
+                .iter()
+                .filter(|s| set.snapshot_ids.contains(&s.snapshot_id))
+                .map(|s| SnapshotDescription {
+                    snapshot_id: s.snapshot_id,
+                    provider_id: Some(s.provider_id),
+                    snapshot_attributes: Some(s.snapshot_attrs.0 as u32),
+                    originating_machine: Some(s.origin_machine.clone()),
+                    service_machine: Some(s.origin_service.clone()),
+                    original_volume_name: Some(s.origin_vol_name.clone()),
+                    device_name: Some(s.device_name.clone()),
+                    ..Default::default()
+                })
+                .collect();
+            document.snapshot_sets.push(SnapshotSetDescription {
+                snapshot_set_id: set.set_id,
+                context: Some(set.context.0),
+                snapshots,
+                ..Default::default()
+            });
+        }
+        Ok(document.to_xml())
+    }
+
+    fn break_snapshot_set(
+        &self,
+        set_id: GUID,
+        flags: VSS_HARDWARE_OPTIONS,
+    ) -> Result<(), VssError> {
+        const OP: &str = "BreakSnapshotSet";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        let mut system = self.system.borrow_mut();
+        let set = system
+            .snapshot_sets
+            .iter()
+            .find(|set| set.set_id == set_id)
+            .cloned()
+            .ok_or_else(|| error(OP, VSS_E_OBJECT_NOT_FOUND))?;
+        // The volumes stay, but VSS does not know them as shadow copies anymore
+        for snapshot_id in set.snapshot_ids.iter() {
+            system.remove_snapshot(*snapshot_id);
+        }
+        system.broken_sets.push((set_id, flags));
+        Ok(())
+    }
+
+    fn expose_snapshot(
+        &self,
+        snapshot_id: GUID,
+        path_from_root: Option<&str>,
+        attributes: VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+        expose: Option<&str>,
+    ) -> Result<String, VssError> {
+        const OP: &str = "ExposeSnapshot";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        let mut system = self.system.borrow_mut();
+        let snapshot = system
+            .snapshots
+            .iter_mut()
+            .find(|s| s.snapshot_id == snapshot_id)
+            .ok_or_else(|| error(OP, VSS_E_OBJECT_NOT_FOUND))?;
+        if snapshot.exposed_name.is_some() {
+            return Err(error(OP, VSS_E_OBJECT_ALREADY_EXISTS));
+        }
+        let name = match expose {
+            Some(expose) => expose.to_owned(),
+            // VSS names the share after the shadow copy when none is given
+            None => format!("{:?}", snapshot_id),
+        };
+        snapshot.exposed_name = Some(name.clone());
+        snapshot.exposed_path = path_from_root.map(|p| p.to_owned());
+        snapshot.snapshot_attrs.0 |= attributes.0;
+        Ok(name)
+    }
+
+    fn revert_to_snapshot(&self, snapshot_id: GUID, _force_dismount: bool) -> Result<(), VssError> {
+        const OP: &str = "RevertToSnapshot";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        let mut system = self.system.borrow_mut();
+        let snapshot = system
+            .snapshots
+            .iter()
+            .find(|s| s.snapshot_id == snapshot_id)
+            .cloned()
+            .ok_or_else(|| error(OP, VSS_E_OBJECT_NOT_FOUND))?;
+        let attrs = snapshot.snapshot_attrs.0;
+        if attrs & VSS_VOLSNAP_ATTR_PERSISTENT.0 == 0
+            || attrs & VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0 != 0
+        {
+            return Err(error(OP, VSS_E_UNSUPPORTED_CONTEXT));
+        }
+        let volume = snapshot.origin_vol_name;
+        if system
+            .reverts
+            .iter()
+            .any(|(v, pending)| *v == volume && *pending > 0)
+        {
+            return Err(error(OP, VSS_E_REVERT_IN_PROGRESS));
+        }
+        // The shadow copy is consumed by the revert
+        system.remove_snapshot(snapshot_id);
+        let polls = system.revert_polls;
+        system.reverts.retain(|(v, _)| *v != volume);
+        system.reverts.push((volume, polls));
+        Ok(())
+    }
+
+    fn query_revert_status(&self, volume: &str) -> Result<Box<dyn RevertStatusQuery>, VssError> {
+        const OP: &str = "QueryRevertStatus";
+        self.call(OP)?;
+        self.component.borrow().check_initialized(OP)?;
+        if !self
+            .system
+            .borrow()
+            .reverts
+            .iter()
+            .any(|(v, _)| v == volume)
+        {
+            return Err(error(OP, VSS_E_OBJECT_NOT_FOUND));
+        }
+        Ok(Box::new(FakeRevertStatus {
+            system: self.system.clone(),
+            volume: volume.to_owned(),
+        }))
+    }
+}
+
+impl DiffAreaBackend for FakeBackend {
+    fn query_volumes_supported_for_snapshots(&self) -> Result<Vec<Volume>, VssError> {
+        const OP: &str = "QueryVolumesSupportedForSnapshots";
+        self.call(OP)?;
+        let system = self.system.borrow();
+        Ok(system
+            .volumes
+            .iter()
+            .map(|v| Volume {
+                volume_name: v.volume_name.clone(),
+                display_name: v.display_name.clone(),
+            })
+            .collect())
+    }
+
+    fn query_volumes_supported_for_diff_areas(
+        &self,
+        volume: &str,
+    ) -> Result<Vec<DiffVolume>, VssError> {
+        const OP: &str = "QueryVolumesSupportedForDiffAreas";
+        self.call(OP)?;
+        let system = self.system.borrow();
+        system.check_volume(OP, volume)?;
+        Ok(system.volumes.clone())
+    }
+
+    fn query_diff_areas_for_volume(&self, volume: &str) -> Result<Vec<DiffArea>, VssError> {
+        const OP: &str = "QueryDiffAreasForVolume";
+        self.call(OP)?;
+        let system = self.system.borrow();
+        system.check_volume(OP, volume)?;
+        Ok(system
+            .diff_areas
+            .iter()
+            .filter(|d| d.volume_name.eq_ignore_ascii_case(volume))
+            .cloned()
+            .collect())
+    }
+
+    fn query_diff_areas_on_volume(&self, volume: &str) -> Result<Vec<DiffArea>, VssError> {
+        const OP: &str = "QueryDiffAreasOnVolume";
+        self.call(OP)?;
+        let system = self.system.borrow();
+        system.check_volume(OP, volume)?;
+        Ok(system
+            .diff_areas
+            .iter()
+            .filter(|d| d.diff_area_volume_name.eq_ignore_ascii_case(volume))
+            .cloned()
+            .collect())
+    }
+
+    fn add_diff_area(
+        &self,
+        volume: &str,
+        diff_area_volume: &str,
+        maximum_space: i64,
+    ) -> Result<(), VssError> {
+        const OP: &str = "AddDiffArea";
+        self.call(OP)?;
+        let mut system = self.system.borrow_mut();
+        system.check_volume(OP, volume)?;
+        system.check_volume(OP, diff_area_volume)?;
+        // A volume has a single diff area
+        if system
+            .diff_areas
+            .iter()
+            .any(|d| d.volume_name.eq_ignore_ascii_case(volume))
+        {
+            return Err(error(OP, VSS_E_OBJECT_ALREADY_EXISTS));
+        }
+        system.diff_areas.push(DiffArea {
+            volume_name: volume.to_owned(),
+            diff_area_volume_name: diff_area_volume.to_owned(),
+            maximum_space: u64::try_from(maximum_space).ok(),
+            allocated_space: 0,
+            used_space: 0,
+        });
+        Ok(())
+    }
+
+    fn change_diff_area_maximum_size(
+        &self,
+        volume: &str,
+        diff_area_volume: &str,
+        maximum_space: i64,
+    ) -> Result<(), VssError> {
+        const OP: &str = "ChangeDiffAreaMaximumSize";
+        self.call(OP)?;
+        let mut system = self.system.borrow_mut();
+        let diff_area = system.diff_area(OP, volume, diff_area_volume)?;
+        diff_area.maximum_space = u64::try_from(maximum_space).ok();
+        Ok(())
+    }
+
+    fn query_snapshots_by_volume(&self, volume: &str) -> Result<Vec<VSSProp>, VssError> {
+        const OP: &str = "QuerySnapshotsByVolume";
+        self.call(OP)?;
+        let system = self.system.borrow();
+        system.check_volume(OP, volume)?;
+        Ok(system
+            .snapshots
+            .iter()
+            .filter(|s| s.origin_vol_name.eq_ignore_ascii_case(volume))
+            .cloned()
+            .collect())
+    }
+}
+
+/// The status of a revert of the fake system, pending `FakeBackend::set_revert_polls` times
+struct FakeRevertStatus {
+    system: Rc<RefCell<FakeSystem>>,
+    volume: String,
+}
+
+impl RevertStatusQuery for FakeRevertStatus {
+    fn query_status(&mut self) -> Result<RevertStatus, VssError> {
+        let mut system = self.system.borrow_mut();
+        let (_, pending) = system
+            .reverts
+            .iter_mut()
+            .find(|(v, _)| *v == self.volume)
+            .ok_or_else(|| error("QueryStatus", VSS_E_OBJECT_NOT_FOUND))?;
+        if *pending == 0 {
+            return Ok(RevertStatus::Finished);
+        }
+        *pending -= 1;
+        Ok(RevertStatus::Pending)
+    }
+}
+
+/// An operation pending for a given number of polls, then finished or failed,
+/// that can be cancelled while pending
+#[derive(Debug, Clone)]
+pub struct FakeAsync {
+    /// The number of polls still reporting pending, `None` when it never finishes
+    pub pending: Option<u32>,
+    /// The error the operation fails with once it is over
+    pub error: Option<VssError>,
+    pub polls: u32,
+    pub cancelled: bool,
+}
+
+impl FakeAsync {
+    pub fn new(pending: Option<u32>) -> Self {
+        Self {
+            pending,
+            error: None,
+            polls: 0,
+            cancelled: false,
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.pending.is_some_and(|pending| self.polls >= pending)
+    }
+}
+
+impl AsyncOperation for FakeAsync {
+    fn query_status(&mut self) -> Result<AsyncStatus, VssError> {
+        if self.cancelled {
+            return Ok(AsyncStatus::Cancelled);
+        }
+        if self.is_over() {
+            return match &self.error {
+                Some(e) => Err(e.clone()),
+                None => Ok(AsyncStatus::Finished),
+            };
+        }
+        self.polls += 1;
+        Ok(AsyncStatus::Pending)
+    }
+
+    fn cancel(&mut self) -> Result<AsyncStatus, VssError> {
+        if self.is_over() {
+            return Ok(AsyncStatus::Finished);
+        }
+        self.cancelled = true;
+        Ok(AsyncStatus::Cancelled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Storage::Vss::{
+        VSS_BT_FULL, VSS_CTX_APP_ROLLBACK, VSS_VOLSNAP_ATTR_DIFFERENTIAL,
+        VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE,
+    };
+
+    use super::*;
+
+    fn initialized() -> FakeBackend {
+        let mut backend = FakeBackend::new();
+        backend.create_backup_components().unwrap();
+        backend.initialize_for_backup(None).unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_requires_initialization() {
+        let backend = FakeBackend::new();
+        assert_eq!(
+            backend.start_snapshot_set().unwrap_err(),
+            VssError::BadState {
+                operation: "StartSnapshotSet"
+            }
+        );
+        assert_eq!(
+            backend.query().unwrap_err(),
+            VssError::BadState { operation: "Query" }
+        );
+    }
+
+    #[test]
+    fn test_snapshot_set_lifecycle() {
+        let backend = initialized();
+        backend.set_context(VSS_CTX_APP_ROLLBACK).unwrap();
+        backend
+            .set_backup_state(false, false, VSS_BT_FULL, false)
+            .unwrap();
+
+        let set_id = backend.start_snapshot_set().unwrap();
+        let c = backend
+            .add_to_snapshot_set(r"\\?\Volume{c}\", GUID::zeroed())
+            .unwrap();
+        let d = backend
+            .add_to_snapshot_set(r"\\?\Volume{d}\", GUID::zeroed())
+            .unwrap();
+        assert_eq!(
+            backend
+                .add_to_snapshot_set(r"\\?\Volume{C}\", GUID::zeroed())
+                .unwrap_err(),
+            VssError::ObjectAlreadyExists {
+                operation: "AddToSnapshotSet"
+            }
+        );
+        // Nothing is committed before DoSnapshotSet
+        assert!(backend.snapshots().is_empty());
+
+        backend.prepare_for_backup().unwrap();
+        backend.do_snapshot_set().unwrap();
+        let snapshots = backend.query().unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots.iter().all(|s| s.shadow_copy_set_id == set_id
+            && s.snapshot_count == 2
+            && s.state == VSS_SS_CREATED
+            && s.snapshot_attrs.0 == VSS_CTX_APP_ROLLBACK.0));
+
+        let sets = backend.snapshot_sets();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].snapshot_ids, vec![c, d]);
+        assert_ne!(
+            backend.get_snapshot_properties(c).unwrap().device_name,
+            backend.get_snapshot_properties(d).unwrap().device_name
+        );
+    }
+
+    #[test]
+    fn test_query_by_context() {
+        let mut backend = initialized();
+        let persistent = VSS_SNAPSHOT_CONTEXT(
+            VSS_CTX_BACKUP.0 | VSS_VOLSNAP_ATTR_PERSISTENT.0 | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0,
+        );
+        backend.set_context(persistent).unwrap();
+        let set_id = backend.start_snapshot_set().unwrap();
+        backend.add_to_snapshot_set("C:\\", GUID::zeroed()).unwrap();
+        backend.prepare_for_backup().unwrap();
+        backend.do_snapshot_set().unwrap();
+        assert_eq!(backend.snapshot_sets()[0].context, persistent);
+        // More attributes than the context, as recorded by a provider
+        backend.insert_snapshot(
+            VSSProp {
+                snapshot_id: GUID::from_u128(1),
+                shadow_copy_set_id: GUID::from_u128(2),
+                snapshot_attrs: VSS_VOLUME_SNAPSHOT_ATTRIBUTES(
+                    persistent.0 | VSS_VOLSNAP_ATTR_DIFFERENTIAL.0,
+                ),
+                ..Default::default()
+            },
+            persistent,
+        );
+        assert_eq!(backend.query().unwrap().len(), 2);
+
+        // Not visible from another context
+        backend.create_backup_components().unwrap();
+        backend.initialize_for_backup(None).unwrap();
+        assert!(backend.query().unwrap().is_empty());
+        backend.set_context(VSS_CTX_ALL).unwrap();
+        assert_eq!(
+            backend
+                .query()
+                .unwrap()
+                .iter()
+                .filter(|s| s.shadow_copy_set_id == set_id)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_delete_snapshots() {
+        let backend = initialized();
+        backend.start_snapshot_set().unwrap();
+        let c = backend.add_to_snapshot_set("C:\\", GUID::zeroed()).unwrap();
+        backend.add_to_snapshot_set("D:\\", GUID::zeroed()).unwrap();
+        backend.prepare_for_backup().unwrap();
+        backend.do_snapshot_set().unwrap();
+        let set_id = backend.start_snapshot_set().unwrap();
+        backend.add_to_snapshot_set("C:\\", GUID::zeroed()).unwrap();
+        backend.prepare_for_backup().unwrap();
+        backend.do_snapshot_set().unwrap();
+
+        assert_eq!(
+            backend
+                .delete_snapshots(c, VSS_OBJECT_SNAPSHOT, false)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            backend.get_snapshot_properties(c).unwrap_err(),
+            VssError::ObjectNotFound {
+                operation: "GetSnapshotProperties"
+            }
+        );
+        assert_eq!(
+            backend
+                .delete_snapshots(set_id, VSS_OBJECT_SNAPSHOT_SET, false)
+                .unwrap(),
+            1
+        );
+        assert_eq!(backend.snapshots().len(), 1);
+        assert_eq!(backend.snapshot_sets().len(), 1);
+    }
+
+    #[test]
+    fn test_connect_shares_system() {
+        let backend = initialized();
+        backend.start_snapshot_set().unwrap();
+        backend.add_to_snapshot_set("C:\\", GUID::zeroed()).unwrap();
+        backend.prepare_for_backup().unwrap();
+        backend.do_snapshot_set().unwrap();
+
+        let mut other = backend.connect();
+        other.create_backup_components().unwrap();
+        other.initialize_for_backup(None).unwrap();
+        assert_eq!(other.query().unwrap().len(), 1);
+    }
+}
@@ -1,5 +1,39 @@
+pub mod asyncop;
+pub mod bcd;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(all(windows, feature = "com"))]
+pub mod combackend;
+pub mod diffarea;
+pub mod error;
+pub mod exec;
+pub mod expose;
+pub mod fakebackend;
+#[cfg(feature = "serde")]
+pub mod manifest;
+pub mod output;
+pub mod provider;
+pub mod restore;
+pub mod retention;
+pub mod revert;
+pub mod script;
+pub mod snapshot;
 pub mod utils;
-#[allow(non_snake_case)]
+pub mod volume;
+pub mod vssbackend;
+#[cfg(all(windows, feature = "com"))]
+#[allow(non_snake_case, clippy::missing_safety_doc, clippy::too_many_arguments)]
 pub mod vssbackupcomponent;
+#[cfg(all(windows, feature = "com"))]
+#[allow(non_snake_case, clippy::missing_safety_doc, clippy::too_many_arguments)]
+pub mod vssbackupcomponentex;
 pub mod vssclient;
+#[cfg(all(windows, feature = "com"))]
+#[allow(non_snake_case, clippy::missing_safety_doc)]
+pub mod vssexaminewritermetadata;
+pub mod vssobject;
 pub mod vssprop;
+pub mod writermetadata;
+pub mod writerselection;
+pub mod writerstatus;
+pub mod xml;
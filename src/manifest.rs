@@ -0,0 +1,307 @@
+//! The Backup Components document saved after the creation of a shadow copy set (-t, -bc).
+//!
+//! The document is written next to a JSON manifest, `<document>.json`, recording the shadow
+//! copy set it was saved for. Both files are written atomically, so an interrupted save
+//! never leaves a truncated document behind. Before an import or a restore, the document
+//! is checked against its manifest.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use windows::core::GUID;
+
+use crate::{bcd::BackupComponents, error::VssError, snapshot::SnapshotContext, xml::format_guid};
+
+/// The shadow copy set a saved Backup Components document describes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BackupManifest {
+    #[serde(rename = "SnapshotSetId", with = "crate::vssprop::guid")]
+    pub set_id: GUID,
+    /// The original volumes of the shadow copies, in the order of the set
+    pub volumes: Vec<String>,
+    /// The context the shadow copies were created in, as the `VSS_CTX_*` value
+    #[serde(with = "context")]
+    pub context: SnapshotContext,
+    #[serde(rename = "CreationTimestamp")]
+    pub create_time: DateTime<Utc>,
+}
+
+/// Contexts by their `SetContext` value
+mod context {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use windows::Win32::Storage::Vss::VSS_SNAPSHOT_CONTEXT;
+
+    use crate::snapshot::SnapshotContext;
+
+    pub(crate) fn serialize<S: Serializer>(
+        context: &SnapshotContext,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.serialize_i32(context.value().0)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<SnapshotContext, D::Error> {
+        let value = i32::deserialize(d)?;
+        SnapshotContext::try_from(VSS_SNAPSHOT_CONTEXT(value)).map_err(D::Error::custom)
+    }
+}
+
+impl BackupManifest {
+    /// Check that the document describes the shadow copy set of the manifest,
+    /// with the same volumes and context
+    pub fn check(&self, document: &BackupComponents) -> Result<(), VssError> {
+        let mismatch = |reason: String| {
+            VssError::InvalidArgument(format!(
+                "the Backup Components document does not match its manifest: {}",
+                reason
+            ))
+        };
+        let set = document
+            .snapshot_sets
+            .iter()
+            .find(|s| s.snapshot_set_id == self.set_id)
+            .ok_or_else(|| mismatch(format!("no shadow copy set {}", format_guid(self.set_id))))?;
+
+        let sorted = |volumes: Vec<String>| {
+            let mut volumes = volumes
+                .into_iter()
+                .map(|v| v.to_lowercase())
+                .collect::<Vec<_>>();
+            volumes.sort();
+            volumes
+        };
+        let described = set
+            .snapshots
+            .iter()
+            .map(|s| s.original_volume_name.clone().unwrap_or_default())
+            .collect::<Vec<_>>();
+        if sorted(described.clone()) != sorted(self.volumes.clone()) {
+            return Err(mismatch(format!(
+                "the shadow copies are of {}, not {}",
+                described.join(", "),
+                self.volumes.join(", ")
+            )));
+        }
+
+        // VSS may record more attributes than the ones given to `SetContext`
+        let expected = self.context.value().0;
+        if let Some(context) = set.context.filter(|c| c & expected != expected) {
+            return Err(mismatch(format!(
+                "the context is 0x{:x}, not {}",
+                context, self.context
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The manifest of the document, the document path with `.json` appended
+pub fn manifest_path(document: &Path) -> PathBuf {
+    let mut path = document.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// Replace the file with the contents, or leave it untouched on failure: the contents are
+/// written to a temporary file of the same directory, then renamed over the file.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), VssError> {
+    let io_error =
+        |e: std::io::Error| VssError::Io(format!("cannot write {}: {}", path.display(), e));
+    let name = path
+        .file_name()
+        .ok_or_else(|| VssError::InvalidArgument(format!("invalid file {}", path.display())))?;
+    let mut temporary_name = std::ffi::OsString::from(".");
+    temporary_name.push(name);
+    temporary_name.push(format!(".{}.tmp", std::process::id()));
+    let temporary = path.with_file_name(temporary_name);
+
+    let written = fs::File::create(&temporary).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    match written.and_then(|_| fs::rename(&temporary, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temporary);
+            Err(io_error(e))
+        }
+    }
+}
+
+/// Write the document and then its manifest, see `manifest_path`
+pub fn save_document(path: &Path, xml: &str, manifest: &BackupManifest) -> Result<(), VssError> {
+    let json = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| VssError::InvalidArgument(format!("invalid manifest: {}", e)))?;
+    write_atomically(path, xml.as_bytes())?;
+    write_atomically(&manifest_path(path), &json)
+}
+
+/// Read the manifest of the document, `None` when there is none, as for the documents
+/// saved by vshadow.exe
+pub fn read_manifest(path: &Path) -> Result<Option<BackupManifest>, VssError> {
+    let manifest = manifest_path(path);
+    let json = match fs::read(&manifest) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(VssError::Io(format!(
+                "cannot read {}: {}",
+                manifest.display(),
+                e
+            )))
+        }
+    };
+    serde_json::from_slice(&json).map(Some).map_err(|e| {
+        VssError::InvalidArgument(format!("invalid manifest {}: {}", manifest.display(), e))
+    })
+}
+
+/// Read the document saved by `save_document`, checked against its manifest if it has one
+pub fn load_document(path: &Path) -> Result<String, VssError> {
+    let xml = fs::read_to_string(path)
+        .map_err(|e| VssError::Io(format!("cannot read {}: {}", path.display(), e)))?;
+    match read_manifest(path)? {
+        Some(manifest) => manifest.check(&BackupComponents::from_xml(&xml)?)?,
+        None => tracing::warn!(
+            "{} has no manifest, it cannot be checked against its shadow copy set",
+            path.display()
+        ),
+    }
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::bcd::{SnapshotDescription, SnapshotSetDescription};
+
+    const SET_ID: GUID = GUID::from_u128(0x9a5cd9e1_93a4_4d18_8b2a_3f6c24b2e0a1);
+
+    fn manifest() -> BackupManifest {
+        BackupManifest {
+            set_id: SET_ID,
+            volumes: vec!["C:\\".to_owned(), "D:\\".to_owned()],
+            context: SnapshotContext::builder().persistent(true).build().unwrap(),
+            create_time: Utc.with_ymd_and_hms(2023, 6, 1, 12, 30, 0).unwrap(),
+        }
+    }
+
+    fn document(volumes: &[&str], context: i32) -> BackupComponents {
+        BackupComponents {
+            snapshot_sets: vec![SnapshotSetDescription {
+                snapshot_set_id: SET_ID,
+                context: Some(context),
+                snapshots: volumes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| SnapshotDescription {
+                        snapshot_id: GUID::from_u128(i as u128 + 1),
+                        original_volume_name: Some(v.to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// A directory of its own for each test
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("vshadow-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn test_check() {
+        let manifest = manifest();
+        let context = manifest.context.value().0;
+        assert!(manifest
+            .check(&document(&["d:\\", "C:\\"], context))
+            .is_ok());
+        // More attributes recorded by VSS
+        assert!(manifest
+            .check(&document(&["C:\\", "D:\\"], context | 0x20))
+            .is_ok());
+
+        for wrong in [
+            document(&["C:\\"], context),
+            document(&["C:\\", "E:\\"], context),
+            document(&["C:\\", "D:\\"], 0),
+            BackupComponents::default(),
+        ] {
+            assert!(matches!(
+                manifest.check(&wrong),
+                Err(VssError::InvalidArgument(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let directory = directory("save");
+        let path = directory.join("backup.xml");
+        assert_eq!(manifest_path(&path), directory.join("backup.xml.json"));
+
+        let manifest = manifest();
+        let xml = document(&["C:\\", "D:\\"], manifest.context.value().0).to_xml();
+        save_document(&path, &xml, &manifest).unwrap();
+        assert_eq!(load_document(&path).unwrap(), xml);
+        assert_eq!(read_manifest(&path).unwrap(), Some(manifest.clone()));
+        let json: serde_json::Value =
+            serde_json::from_slice(&fs::read(manifest_path(&path)).unwrap()).unwrap();
+        assert_eq!(
+            json["SnapshotSetId"],
+            "9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1"
+        );
+        assert_eq!(json["Context"], manifest.context.value().0);
+        assert_eq!(json["CreationTimestamp"], "2023-06-01T12:30:00Z");
+        // Only the two files are left
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+        // Another document saved over the first one
+        let other = document(&["C:\\"], manifest.context.value().0).to_xml();
+        fs::write(&path, other).unwrap();
+        assert!(load_document(&path).is_err());
+
+        // A document without manifest
+        fs::remove_file(manifest_path(&path)).unwrap();
+        assert!(load_document(&path).is_ok());
+        assert_eq!(read_manifest(&path).unwrap(), None);
+
+        fs::write(manifest_path(&path), "{}").unwrap();
+        assert!(load_document(&path).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomically_failure() {
+        let directory = directory("atomic");
+        let path = directory.join("backup.xml");
+        fs::write(&path, "old").unwrap();
+        // The target cannot be replaced by a file
+        let blocked = directory.join("blocked");
+        fs::create_dir(&blocked).unwrap();
+        fs::write(blocked.join("file"), "").unwrap();
+        assert!(write_atomically(&blocked, b"new").is_err());
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(write_atomically(&directory.join("missing").join("backup.xml"), b"").is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use std::io::{self, Write};
+
+use crate::{
+    utils::{get_string_for_snapshot_state, volsnap_attrs_to_str},
+    vssprop::VSSProp,
+    xml::format_guid,
+};
+
+/// How the shadow copies are printed, only as a table without the serde feature
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// A JSON array
+    #[cfg(feature = "serde")]
+    Json,
+    /// One JSON object per line
+    #[cfg(feature = "serde")]
+    Jsonl,
+    /// A header line then one line per shadow copy, the attributes separated by spaces
+    #[cfg(feature = "serde")]
+    Csv,
+    /// Aligned columns for humans
+    #[default]
+    Table,
+    #[cfg(feature = "serde")]
+    Yaml,
+}
+
+/// The CSV columns, named like the serialized `VSSProp` fields
+const COLUMNS: [&str; 14] = [
+    "SnapshotId",
+    "SnapshotSetId",
+    "SnapshotsCount",
+    "OriginalVolumeName",
+    "CreationTimestamp",
+    "SnapshotDeviceObject",
+    "OriginatingMachine",
+    "ServiceMachine",
+    "SnapshotAttributes",
+    "ExposedName",
+    "ExposedPath",
+    "ProviderId",
+    "ProviderName",
+    "Status",
+];
+
+/// The columns of `COLUMNS` shown in a table
+const TABLE_COLUMNS: [usize; 7] = [0, 1, 3, 4, 5, 12, 13];
+
+fn record(prop: &VSSProp) -> [String; 14] {
+    [
+        format_guid(prop.snapshot_id),
+        format_guid(prop.shadow_copy_set_id),
+        prop.snapshot_count.to_string(),
+        prop.origin_vol_name.clone(),
+        prop.create_time.to_rfc3339(),
+        prop.device_name.clone(),
+        prop.origin_machine.clone(),
+        prop.origin_service.clone(),
+        volsnap_attrs_to_str(prop.snapshot_attrs.0).join(" "),
+        prop.exposed_name.clone().unwrap_or_default(),
+        prop.exposed_path.clone().unwrap_or_default(),
+        format_guid(prop.provider_id),
+        prop.provider_name.clone().unwrap_or_default(),
+        get_string_for_snapshot_state(prop.state),
+    ]
+}
+
+/// Write the shadow copies in the given format
+pub fn write_snapshots<W: Write>(
+    out: &mut W,
+    props: &[VSSProp],
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        #[cfg(feature = "serde")]
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, props)?;
+            writeln!(out)
+        }
+        #[cfg(feature = "serde")]
+        OutputFormat::Jsonl => {
+            for prop in props {
+                serde_json::to_writer(&mut *out, prop)?;
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+        #[cfg(feature = "serde")]
+        OutputFormat::Yaml => serde_yaml::to_writer(out, props).map_err(io::Error::other),
+        #[cfg(feature = "serde")]
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(COLUMNS)?;
+            for prop in props {
+                writer.write_record(record(prop))?;
+            }
+            writer.flush()
+        }
+        OutputFormat::Table => write_table(out, props),
+    }
+}
+
+fn write_table<W: Write>(out: &mut W, props: &[VSSProp]) -> io::Result<()> {
+    let rows = props
+        .iter()
+        .map(|p| {
+            let mut record = record(p);
+            // The id of the providers whose name is unknown
+            if record[12].is_empty() {
+                record[12] = record[11].clone();
+            }
+            TABLE_COLUMNS.map(|i| record[i].clone())
+        })
+        .collect::<Vec<_>>();
+    let mut widths = TABLE_COLUMNS.map(|i| COLUMNS[i].len());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = TABLE_COLUMNS.map(|i| COLUMNS[i].to_owned());
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use windows::{
+        core::GUID,
+        Win32::Storage::Vss::{
+            VSS_SS_CREATED, VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE, VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE,
+            VSS_VOLSNAP_ATTR_PERSISTENT, VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+        },
+    };
+
+    use super::*;
+
+    fn prop() -> VSSProp {
+        VSSProp {
+            snapshot_id: GUID::from_u128(0x3808876b_c176_4e48_b7ae_04046e6cc752),
+            shadow_copy_set_id: GUID::from_u128(0x9a5cd9e1_93a4_4d18_8b2a_3f6c24b2e0a1),
+            snapshot_count: 1,
+            origin_vol_name: "\\\\?\\Volume{1b2c}\\".to_owned(),
+            create_time: Utc.with_ymd_and_hms(2023, 6, 1, 12, 30, 0).unwrap(),
+            device_name: "\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy1".to_owned(),
+            origin_machine: "host".to_owned(),
+            origin_service: "host".to_owned(),
+            snapshot_attrs: VSS_VOLUME_SNAPSHOT_ATTRIBUTES(
+                VSS_VOLSNAP_ATTR_PERSISTENT.0
+                    | VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0
+                    | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0,
+            ),
+            exposed_name: Some("X:\\".to_owned()),
+            exposed_path: None,
+            provider_id: GUID::from_u128(0xb5946137_7b9f_4925_af80_51abd60b20d5),
+            provider_name: Some("Microsoft Software Shadow Copy provider 1.0".to_owned()),
+            state: VSS_SS_CREATED,
+        }
+    }
+
+    fn output(format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write_snapshots(&mut out, &[prop(), prop()], format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let json = serde_json::to_value(prop()).unwrap();
+        assert_eq!(json["SnapshotId"], "3808876b-c176-4e48-b7ae-04046e6cc752");
+        assert_eq!(json["CreationTimestamp"], "2023-06-01T12:30:00Z");
+        assert_eq!(
+            json["SnapshotAttributes"],
+            serde_json::json!(["Persistent", "Client_accessible", "No_Auto_Release"])
+        );
+        assert_eq!(json["Status"], "VSS_SS_CREATED");
+        assert_eq!(json["ExposedPath"], serde_json::Value::Null);
+        assert_eq!(
+            json["ProviderName"],
+            "Microsoft Software Shadow Copy provider 1.0"
+        );
+
+        let back: VSSProp = serde_json::from_value(json).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", prop()));
+
+        let yaml = output(OutputFormat::Yaml);
+        let back: Vec<VSSProp> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", vec![prop(), prop()]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_lines() {
+        let json = output(OutputFormat::Json);
+        assert!(json.starts_with("[\n"));
+        assert_eq!(
+            serde_json::from_str::<Vec<serde_json::Value>>(&json)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let jsonl = output(OutputFormat::Jsonl);
+        let lines = jsonl.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(serde_json::from_str::<VSSProp>(lines[1]).is_ok());
+    }
+
+    #[test]
+    fn test_csv_and_table() {
+        #[cfg(feature = "serde")]
+        {
+            let csv = output(OutputFormat::Csv);
+            let lines = csv.lines().collect::<Vec<_>>();
+            assert_eq!(lines.len(), 3);
+            assert!(lines[0].starts_with("SnapshotId,SnapshotSetId,SnapshotsCount,"));
+            assert!(lines[1].starts_with("3808876b-c176-4e48-b7ae-04046e6cc752,"));
+            assert!(lines[1].contains(",Persistent Client_accessible No_Auto_Release,X:\\,,"));
+        }
+
+        let table = output(OutputFormat::Table);
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("SnapshotId                            SnapshotSetId"));
+        assert!(lines[1].ends_with(
+            "HarddiskVolumeShadowCopy1  Microsoft Software Shadow Copy provider 1.0  VSS_SS_CREATED"
+        ));
+
+        let unnamed = VSSProp {
+            provider_name: None,
+            ..prop()
+        };
+        let mut out = Vec::new();
+        write_snapshots(&mut out, &[unnamed], OutputFormat::Table).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.contains("  b5946137-7b9f-4925-af80-51abd60b20d5  VSS_SS_CREATED"));
+    }
+}
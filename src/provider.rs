@@ -0,0 +1,252 @@
+//! The shadow copy providers registered on the machine (list providers).
+//!
+//! A provider is chosen for a shadow copy set by its name or its id, resolved against
+//! the providers returned by `VssClient::list_providers`.
+
+use std::{fmt, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::{
+    core::GUID,
+    Win32::Storage::Vss::{
+        VSS_PROVIDER_TYPE, VSS_PROV_FILESHARE, VSS_PROV_HARDWARE, VSS_PROV_SOFTWARE,
+        VSS_PROV_SYSTEM,
+    },
+};
+
+use crate::{error::VssError, utils::parse_guid, vssprop::VSSProp, xml::format_guid};
+
+/// The kind of a provider, `VSS_PROVIDER_TYPE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ProviderType {
+    /// The Microsoft Software Shadow Copy provider
+    System,
+    Software,
+    Hardware,
+    /// The provider of the shadow copies of file shares
+    FileShare,
+    Unknown,
+}
+
+impl From<VSS_PROVIDER_TYPE> for ProviderType {
+    fn from(provider_type: VSS_PROVIDER_TYPE) -> Self {
+        match provider_type {
+            VSS_PROV_SYSTEM => ProviderType::System,
+            VSS_PROV_SOFTWARE => ProviderType::Software,
+            VSS_PROV_HARDWARE => ProviderType::Hardware,
+            VSS_PROV_FILESHARE => ProviderType::FileShare,
+            _ => ProviderType::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for ProviderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProviderType::System => "system",
+            ProviderType::Software => "software",
+            ProviderType::Hardware => "hardware",
+            ProviderType::FileShare => "file share",
+            ProviderType::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The properties of a provider, `VSS_PROVIDER_PROP`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
+pub struct Provider {
+    #[cfg_attr(feature = "serde", serde(with = "crate::vssprop::guid"))]
+    pub provider_id: GUID,
+    #[cfg_attr(feature = "serde", serde(rename = "ProviderName"))]
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "ProviderType"))]
+    pub provider_type: ProviderType,
+    #[cfg_attr(feature = "serde", serde(rename = "ProviderVersion"))]
+    pub version: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "ProviderVersionId", with = "crate::vssprop::guid")
+    )]
+    pub version_id: GUID,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "ClassId", with = "crate::vssprop::guid")
+    )]
+    pub class_id: GUID,
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Provider name: '{}'", self.name)?;
+        writeln!(f, "   Provider type: {}", self.provider_type)?;
+        writeln!(f, "   Provider Id: {{{}}}", format_guid(self.provider_id))?;
+        writeln!(f, "   Version: {}", self.version)?;
+        write!(f, "   CLSID: {{{}}}", format_guid(self.class_id))
+    }
+}
+
+/// A provider chosen by the user, by id or by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderSelector {
+    Id(GUID),
+    /// Compared without case with the provider names
+    Name(String),
+}
+
+impl FromStr for ProviderSelector {
+    type Err = VssError;
+
+    /// A GUID, with or without braces, or else a provider name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(VssError::InvalidArgument("empty provider".to_owned()));
+        }
+        Ok(match parse_guid(s) {
+            Ok(id) => ProviderSelector::Id(id),
+            Err(_) => ProviderSelector::Name(s.to_owned()),
+        })
+    }
+}
+
+impl fmt::Display for ProviderSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderSelector::Id(id) => write!(f, "{{{}}}", format_guid(*id)),
+            ProviderSelector::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+impl ProviderSelector {
+    /// The id of the selected provider among the registered `providers`
+    pub fn resolve(&self, providers: &[Provider]) -> Result<GUID, VssError> {
+        let found = providers.iter().find(|p| match self {
+            ProviderSelector::Id(id) => p.provider_id == *id,
+            ProviderSelector::Name(name) => p.name.eq_ignore_ascii_case(name),
+        });
+        match found {
+            Some(provider) => Ok(provider.provider_id),
+            None => Err(VssError::InvalidArgument(format!(
+                "the provider {} is not registered, the registered providers are: {}",
+                self,
+                providers
+                    .iter()
+                    .map(|p| format!("'{}'", p.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+/// Set the provider name of the shadow copies created by one of the `providers`
+pub fn set_provider_names(snapshots: &mut [VSSProp], providers: &[Provider]) {
+    for snapshot in snapshots.iter_mut() {
+        snapshot.provider_name = providers
+            .iter()
+            .find(|p| p.provider_id == snapshot.provider_id)
+            .map(|p| p.name.clone());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diffarea::VSS_SWPRV_PROVIDER_ID;
+
+    const HARDWARE: GUID = GUID::from_u128(0x24e6c4f2_0ab3_4a1b_9c44_0f7d3e51a6b2);
+
+    fn providers() -> Vec<Provider> {
+        vec![
+            Provider {
+                provider_id: VSS_SWPRV_PROVIDER_ID,
+                name: "Microsoft Software Shadow Copy provider 1.0".to_owned(),
+                provider_type: ProviderType::System,
+                version: "1.0.0.7".to_owned(),
+                version_id: GUID::from_u128(1),
+                class_id: GUID::from_u128(0x65ee1dba_8ff4_4a58_ac1c_3470ee2f376a),
+            },
+            Provider {
+                provider_id: HARDWARE,
+                name: "Contoso Hardware Provider".to_owned(),
+                provider_type: ProviderType::Hardware,
+                version: "2.1".to_owned(),
+                version_id: GUID::from_u128(2),
+                class_id: GUID::from_u128(3),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_provider_type() {
+        assert_eq!(ProviderType::from(VSS_PROV_SYSTEM), ProviderType::System);
+        assert_eq!(
+            ProviderType::from(VSS_PROV_HARDWARE),
+            ProviderType::Hardware
+        );
+        assert_eq!(
+            ProviderType::from(VSS_PROVIDER_TYPE(42)),
+            ProviderType::Unknown
+        );
+        assert_eq!(ProviderType::Software.to_string(), "software");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let providers = providers();
+        let by_id = "{24E6C4F2-0AB3-4A1B-9C44-0F7D3E51A6B2}"
+            .parse::<ProviderSelector>()
+            .unwrap();
+        assert_eq!(by_id, ProviderSelector::Id(HARDWARE));
+        assert_eq!(by_id.resolve(&providers).unwrap(), HARDWARE);
+        let by_name = "contoso hardware provider"
+            .parse::<ProviderSelector>()
+            .unwrap();
+        assert_eq!(by_name.resolve(&providers).unwrap(), HARDWARE);
+
+        let unknown = ProviderSelector::Id(GUID::from_u128(4));
+        assert!(matches!(
+            unknown.resolve(&providers),
+            Err(VssError::InvalidArgument(m)) if m.contains("'Contoso Hardware Provider'")
+        ));
+        assert!(ProviderSelector::Name("Contoso".to_owned())
+            .resolve(&providers)
+            .is_err());
+        assert!(" ".parse::<ProviderSelector>().is_err());
+    }
+
+    #[test]
+    fn test_set_provider_names() {
+        let mut snapshots = vec![
+            VSSProp {
+                provider_id: HARDWARE,
+                ..Default::default()
+            },
+            VSSProp {
+                provider_id: GUID::from_u128(4),
+                provider_name: Some("stale".to_owned()),
+                ..Default::default()
+            },
+        ];
+        set_provider_names(&mut snapshots, &providers());
+        assert_eq!(
+            snapshots[0].provider_name.as_deref(),
+            Some("Contoso Hardware Provider")
+        );
+        assert_eq!(snapshots[1].provider_name, None);
+    }
+
+    #[test]
+    fn test_display() {
+        let text = providers()[0].to_string();
+        assert!(text.starts_with("Provider name: 'Microsoft Software Shadow Copy provider 1.0'\n"));
+        assert!(text.contains("   Provider Id: {b5946137-7b9f-4925-af80-51abd60b20d5}\n"));
+        assert!(text.ends_with("CLSID: {65ee1dba-8ff4-4a58-ac1c-3470ee2f376a}"));
+    }
+}
@@ -0,0 +1,454 @@
+//! Planning of a writer-involved restore from a Backup Components Document (-r, -rs).
+//!
+//! The plan only depends on the document and the writer metadata, `VssClient::restore`
+//! carries it out between `PreRestore` and `PostRestore`.
+
+use windows::core::GUID;
+
+use crate::{
+    bcd::{BackupComponents, Component},
+    error::VssError,
+    exec::CommandRunner,
+    writermetadata::{ComponentInfo, FileSpec, RestoreMethod, WriterMetadata},
+    writerselection::SelectedComponent,
+    writerstatus::WriterStatus,
+    xml::format_guid,
+};
+
+/// A file specification of a component and where its files are put back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRestore {
+    pub path: String,
+    pub filespec: String,
+    pub recursive: bool,
+    /// The alternate location mapped by the writer, `None` to restore in place
+    pub alternate_location: Option<String>,
+}
+
+impl FileRestore {
+    /// The directory the files are restored to
+    pub fn destination(&self) -> &str {
+        self.alternate_location.as_deref().unwrap_or(&self.path)
+    }
+}
+
+/// A component of the document the writer on this machine can restore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentRestore {
+    pub component: SelectedComponent,
+    pub writer_name: String,
+    pub restore_method: RestoreMethod,
+    pub files: Vec<FileRestore>,
+}
+
+impl ComponentRestore {
+    /// The full path of the component, `logical_path\name`
+    pub fn full_path(&self) -> String {
+        full_path(self.component.logical_path.as_deref(), &self.component.name)
+    }
+}
+
+/// A component of the document left out of the restore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedComponent {
+    pub writer_id: Option<GUID>,
+    pub component: String,
+    pub reason: String,
+}
+
+/// The components to restore, in the order of the document
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestorePlan {
+    pub components: Vec<ComponentRestore>,
+    pub skipped: Vec<SkippedComponent>,
+}
+
+fn full_path(logical_path: Option<&str>, name: &str) -> String {
+    match logical_path.filter(|p| !p.is_empty()) {
+        Some(path) => format!("{}\\{}", path, name),
+        None => name.to_owned(),
+    }
+}
+
+fn file_spec(file: &FileRestore) -> String {
+    format!("{}\\{}", file.path.trim_end_matches('\\'), file.filespec)
+}
+
+fn is_same_spec(a: &FileSpec, b: &FileSpec) -> bool {
+    a.path
+        .trim_end_matches('\\')
+        .eq_ignore_ascii_case(b.path.trim_end_matches('\\'))
+        && a.filespec.eq_ignore_ascii_case(&b.filespec)
+}
+
+/// The files of the component, mapped to the alternate locations of the writer
+/// when it restores to an alternate location
+fn files(writer: &WriterMetadata, info: &ComponentInfo) -> Vec<FileRestore> {
+    let to_alternate = writer.restore_method.method == RestoreMethod::RestoreToAlternateLocation;
+    info.files
+        .iter()
+        .chain(info.database_files.iter())
+        .chain(info.log_files.iter())
+        .map(|spec| FileRestore {
+            path: spec.path.clone(),
+            filespec: spec.filespec.clone(),
+            recursive: spec.recursive,
+            alternate_location: writer
+                .restore_method
+                .alternate_location_mappings
+                .iter()
+                .filter(|_| to_alternate)
+                .find(|mapping| is_same_spec(mapping, spec))
+                .and_then(|mapping| mapping.alternate_path.clone()),
+        })
+        .collect()
+}
+
+/// Find the writer of the document on this machine, by instance first
+fn find_writer(
+    writers: &[WriterMetadata],
+    writer_id: GUID,
+    instance_id: Option<GUID>,
+) -> Option<&WriterMetadata> {
+    writers
+        .iter()
+        .find(|w| w.writer_id == writer_id && Some(w.instance_id) == instance_id)
+        .or_else(|| writers.iter().find(|w| w.writer_id == writer_id))
+}
+
+fn find_component<'a>(
+    writer: &'a WriterMetadata,
+    component: &Component,
+) -> Option<&'a ComponentInfo> {
+    let path = full_path(component.logical_path.as_deref(), &component.component_name);
+    writer
+        .components
+        .iter()
+        .find(|c| c.full_path().eq_ignore_ascii_case(&path))
+}
+
+/// Match the components of the document with the writers running on this machine.
+///
+/// Components whose backup did not succeed, or which the writer does not report anymore,
+/// are skipped with the reason.
+pub fn plan_restore(document: &BackupComponents, writers: &[WriterMetadata]) -> RestorePlan {
+    let mut plan = RestorePlan::default();
+    for writer_components in document.writer_components.iter() {
+        let writer = writer_components
+            .writer_id
+            .and_then(|id| find_writer(writers, id, writer_components.instance_id));
+        for component in writer_components.components.iter() {
+            let skip = |reason: String| SkippedComponent {
+                writer_id: writer_components.writer_id,
+                component: full_path(component.logical_path.as_deref(), &component.component_name),
+                reason,
+            };
+            let Some(writer) = writer else {
+                plan.skipped.push(skip(match writer_components.writer_id {
+                    Some(id) => format!("writer {} is not running", format_guid(id)),
+                    None => "no writer id in the document".to_owned(),
+                }));
+                continue;
+            };
+            if component.backup_succeeded == Some(false) {
+                plan.skipped
+                    .push(skip("its backup did not succeed".to_owned()));
+                continue;
+            }
+            let Some(info) = find_component(writer, component) else {
+                plan.skipped.push(skip(format!(
+                    "writer {} does not report it",
+                    writer.writer_name
+                )));
+                continue;
+            };
+            plan.components.push(ComponentRestore {
+                component: SelectedComponent {
+                    instance_id: writer.instance_id,
+                    writer_id: writer.writer_id,
+                    component_type: info.component_type,
+                    logical_path: info.logical_path.clone(),
+                    name: info.name.clone(),
+                },
+                writer_name: writer.writer_name.clone(),
+                restore_method: writer.restore_method.method,
+                files: files(writer, info),
+            });
+        }
+    }
+    plan
+}
+
+impl RestorePlan {
+    /// The steps of the restore, in order, as printed by a simulated restore (-rs)
+    pub fn actions(&self) -> Vec<String> {
+        let mut actions = Vec::new();
+        for c in self.components.iter() {
+            actions.push(format!(
+                "Select component {} of writer {} for restore",
+                c.full_path(),
+                c.writer_name
+            ));
+            for file in c.files.iter() {
+                if let Some(alternate) = &file.alternate_location {
+                    actions.push(format!(
+                        "Map {} to the alternate location {}",
+                        file_spec(file),
+                        alternate
+                    ));
+                }
+            }
+        }
+        actions.push("PreRestore".to_owned());
+        for c in self.components.iter() {
+            for file in c.files.iter() {
+                actions.push(format!(
+                    "Restore {}{} to {}",
+                    file_spec(file),
+                    if file.recursive { " recursively" } else { "" },
+                    file.destination()
+                ));
+            }
+            actions.push(format!("Set the file restore status of {}", c.full_path()));
+        }
+        actions.push("PostRestore".to_owned());
+        for s in self.skipped.iter() {
+            actions.push(format!("Skip component {}: {}", s.component, s.reason));
+        }
+        actions
+    }
+}
+
+/// Puts the files of a component back, the pluggable step of the restore
+pub trait FileRestorer {
+    /// Restore the files of the component, called between `PreRestore` and `PostRestore`
+    fn restore_files(&mut self, component: &ComponentRestore) -> Result<(), VssError>;
+}
+
+/// Without a restorer the files are expected to be back already, as with vshadow.exe -r
+/// without -exec, and only the writers are notified
+impl<F: FileRestorer> FileRestorer for Option<F> {
+    fn restore_files(&mut self, component: &ComponentRestore) -> Result<(), VssError> {
+        match self {
+            Some(restorer) => restorer.restore_files(component),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The environment variables describing a component to restore:
+/// RESTORE_WRITER_ID, RESTORE_WRITER_NAME, RESTORE_COMPONENT, RESTORE_FILE_COUNT,
+/// then RESTORE_PATH_n, RESTORE_FILESPEC_n and RESTORE_DESTINATION_n for each file, from 1.
+pub fn restore_variables(component: &ComponentRestore) -> Vec<(String, String)> {
+    let mut variables = vec![
+        (
+            "RESTORE_WRITER_ID".to_owned(),
+            format!("{{{}}}", format_guid(component.component.writer_id)),
+        ),
+        (
+            "RESTORE_WRITER_NAME".to_owned(),
+            component.writer_name.clone(),
+        ),
+        ("RESTORE_COMPONENT".to_owned(), component.full_path()),
+        (
+            "RESTORE_FILE_COUNT".to_owned(),
+            component.files.len().to_string(),
+        ),
+    ];
+    for (i, file) in component.files.iter().enumerate() {
+        variables.push((format!("RESTORE_PATH_{}", i + 1), file.path.clone()));
+        variables.push((format!("RESTORE_FILESPEC_{}", i + 1), file.filespec.clone()));
+        variables.push((
+            format!("RESTORE_DESTINATION_{}", i + 1),
+            file.destination().to_owned(),
+        ));
+    }
+    variables
+}
+
+/// Runs a command for each component, with the `restore_variables` in its environment
+#[derive(Debug)]
+pub struct CommandRestorer<R: CommandRunner> {
+    pub command: String,
+    pub runner: R,
+}
+
+impl<R: CommandRunner> FileRestorer for CommandRestorer<R> {
+    fn restore_files(&mut self, component: &ComponentRestore) -> Result<(), VssError> {
+        match self
+            .runner
+            .run(&self.command, &restore_variables(component))?
+        {
+            Some(0) => Ok(()),
+            code => Err(VssError::CommandFailed {
+                command: self.command.clone(),
+                code,
+            }),
+        }
+    }
+}
+
+/// What happened to a component of the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreOutcome {
+    Restored,
+    Failed(String),
+    Skipped(String),
+}
+
+impl std::fmt::Display for RestoreOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RestoreOutcome::Restored => write!(f, "restored"),
+            RestoreOutcome::Failed(reason) => write!(f, "failed: {}", reason),
+            RestoreOutcome::Skipped(reason) => write!(f, "skipped: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentRestoreStatus {
+    pub writer_id: Option<GUID>,
+    pub component: String,
+    pub outcome: RestoreOutcome,
+}
+
+/// The result of `VssClient::restore`
+#[derive(Debug, Clone, Default)]
+pub struct RestoreReport {
+    pub components: Vec<ComponentRestoreStatus>,
+    /// The status of the writers after `PostRestore`
+    pub writers: Vec<WriterStatus>,
+}
+
+impl RestoreReport {
+    /// Whether no component failed, skipped components are not failures
+    pub fn is_success(&self) -> bool {
+        !self
+            .components
+            .iter()
+            .any(|c| matches!(c.outcome, RestoreOutcome::Failed(_)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::writermetadata::{
+        test::{SQL_WRITER, SYSTEM_WRITER},
+        ComponentType,
+    };
+
+    /// A writer restoring its files to an alternate location
+    pub(crate) const APP_WRITER: &str = r#"<WRITER_METADATA xmlns="x-schema:#VssWriterMetadataInfo" version="1.1">
+    <IDENTIFICATION writerId="00000000-0000-0000-0000-0000000000aa" instanceId="00000000-0000-0000-0000-0000000000a1" friendlyName="App Writer" usage="USER_DATA" dataSource="OTHER"/>
+    <BACKUP_LOCATIONS>
+        <FILE_GROUP logicalPath="" componentName="Config" selectable="yes" selectableForRestore="yes">
+            <FILE_LIST path="C:\App\config" filespec="*.xml" recursive="yes"/>
+        </FILE_GROUP>
+    </BACKUP_LOCATIONS>
+    <RESTORE_METHOD method="RESTORE_TO_ALTERNATE_LOCATION" writerRestore="never" rebootRequired="no">
+        <ALTERNATE_LOCATION_MAPPING path="C:\App\config\" filespec="*.xml" recursive="yes" alternatePath="C:\App\restored"/>
+    </RESTORE_METHOD>
+</WRITER_METADATA>"#;
+
+    pub(crate) const RESTORE_DOCUMENT: &str = include_str!("../tests/fixtures/restore.xml");
+
+    pub(crate) fn writers() -> Vec<WriterMetadata> {
+        [SYSTEM_WRITER, SQL_WRITER, APP_WRITER]
+            .iter()
+            .map(|xml| WriterMetadata::from_xml(xml).unwrap())
+            .collect()
+    }
+
+    fn plan() -> RestorePlan {
+        let document = BackupComponents::from_xml(RESTORE_DOCUMENT).unwrap();
+        plan_restore(&document, &writers())
+    }
+
+    #[test]
+    fn test_plan_restore() {
+        let plan = plan();
+        assert_eq!(
+            plan.components
+                .iter()
+                .map(|c| c.full_path())
+                .collect::<Vec<_>>(),
+            [r"HOST\MSSQLSERVER\master", "Config"]
+        );
+        let master = &plan.components[0];
+        assert_eq!(master.writer_name, "SqlServerWriter");
+        assert_eq!(master.component.component_type, ComponentType::Database);
+        assert_eq!(master.files.len(), 2);
+        assert_eq!(master.files[1].filespec, "mastlog.ldf");
+        assert_eq!(master.files[1].destination(), r"C:\Data");
+        let config = &plan.components[1];
+        assert_eq!(
+            config.restore_method,
+            RestoreMethod::RestoreToAlternateLocation
+        );
+        assert_eq!(config.files[0].destination(), r"C:\App\restored");
+
+        assert_eq!(
+            plan.skipped
+                .iter()
+                .map(|s| (s.component.as_str(), s.reason.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (r"HOST\MSSQLSERVER\sales", "its backup did not succeed"),
+                (
+                    r"HOST\MSSQLSERVER\gone",
+                    "writer SqlServerWriter does not report it"
+                ),
+                (
+                    "Files",
+                    "writer 00000000-0000-0000-0000-0000000000ff is not running"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_actions() {
+        assert_eq!(
+            plan().actions(),
+            [
+                r"Select component HOST\MSSQLSERVER\master of writer SqlServerWriter for restore",
+                "Select component Config of writer App Writer for restore",
+                r"Map C:\App\config\*.xml to the alternate location C:\App\restored",
+                "PreRestore",
+                r"Restore C:\Data\master.mdf to C:\Data",
+                r"Restore C:\Data\mastlog.ldf to C:\Data",
+                r"Set the file restore status of HOST\MSSQLSERVER\master",
+                r"Restore C:\App\config\*.xml recursively to C:\App\restored",
+                "Set the file restore status of Config",
+                "PostRestore",
+                r"Skip component HOST\MSSQLSERVER\sales: its backup did not succeed",
+                r"Skip component HOST\MSSQLSERVER\gone: writer SqlServerWriter does not report it",
+                "Skip component Files: writer 00000000-0000-0000-0000-0000000000ff is not running",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restore_variables() {
+        let plan = plan();
+        let variables = restore_variables(&plan.components[1]);
+        assert_eq!(
+            variables,
+            [
+                (
+                    "RESTORE_WRITER_ID",
+                    "{00000000-0000-0000-0000-0000000000aa}"
+                ),
+                ("RESTORE_WRITER_NAME", "App Writer"),
+                ("RESTORE_COMPONENT", "Config"),
+                ("RESTORE_FILE_COUNT", "1"),
+                ("RESTORE_PATH_1", r"C:\App\config"),
+                ("RESTORE_FILESPEC_1", "*.xml"),
+                ("RESTORE_DESTINATION_1", r"C:\App\restored"),
+            ]
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        );
+    }
+}
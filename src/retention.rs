@@ -0,0 +1,571 @@
+//! Retention policies deciding which shadow copies to prune (prune).
+//!
+//! The plan only depends on the shadow copy properties, the policy and the current time,
+//! `VssClient::prune` then deletes the shadow copies it selected.
+
+use std::{cmp::Reverse, collections::HashMap, fmt};
+
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+use windows::{
+    core::GUID,
+    Win32::Storage::Vss::{
+        VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY, VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY,
+        VSS_VOLSNAP_ATTR_IMPORTED,
+    },
+};
+
+use crate::{error::VssError, vssprop::VSSProp};
+
+/// The shadow copies to keep for each volume.
+///
+/// A shadow copy is kept when any rule selects it. Without any keep rule nor `max_age`,
+/// every shadow copy is kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionRules {
+    /// The number of most recent shadow copies to keep, whatever their age
+    pub keep_last: Option<usize>,
+    /// The number of hours, days, weeks and months for which the most recent shadow copy
+    /// is kept. The buckets are in UTC and only count periods that have a shadow copy.
+    pub keep_hourly: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+    /// The shadow copies older than this are deleted, unless kept by `keep_last`
+    pub max_age: Option<Duration>,
+}
+
+impl RetentionRules {
+    fn has_keep_rule(&self) -> bool {
+        self.keep_last.is_some()
+            || self.keep_hourly > 0
+            || self.keep_daily > 0
+            || self.keep_weekly > 0
+            || self.keep_monthly > 0
+    }
+}
+
+/// The rules applied to the shadow copies of each volume, by provider.
+///
+/// Exposed and imported shadow copies are never deleted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// The rules of the providers without their own rules
+    pub rules: RetentionRules,
+    /// The rules of the shadow copies created by the given providers
+    pub providers: Vec<(GUID, RetentionRules)>,
+}
+
+impl RetentionPolicy {
+    /// The rules of the shadow copies created by the provider
+    pub fn rules_for(&self, provider_id: GUID) -> &RetentionRules {
+        self.providers
+            .iter()
+            .find(|(id, _)| *id == provider_id)
+            .map_or(&self.rules, |(_, rules)| rules)
+    }
+}
+
+/// Why a shadow copy is kept, the first that applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepReason {
+    Exposed,
+    Imported,
+    Last,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    /// Younger than `max_age`, without any keep rule
+    Recent,
+    /// Neither a keep rule nor `max_age`
+    NoRule,
+}
+
+/// Why a shadow copy is deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteReason {
+    /// Older than `max_age`
+    Expired,
+    /// Selected by no keep rule
+    NotKept,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Keep(KeepReason),
+    Delete(DeleteReason),
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Verdict::Keep(KeepReason::Exposed) => "exposed",
+            Verdict::Keep(KeepReason::Imported) => "imported",
+            Verdict::Keep(KeepReason::Last) => "last",
+            Verdict::Keep(KeepReason::Hourly) => "hourly",
+            Verdict::Keep(KeepReason::Daily) => "daily",
+            Verdict::Keep(KeepReason::Weekly) => "weekly",
+            Verdict::Keep(KeepReason::Monthly) => "monthly",
+            Verdict::Keep(KeepReason::Recent) => "recent",
+            Verdict::Keep(KeepReason::NoRule) => "no rule",
+            Verdict::Delete(DeleteReason::Expired) => "expired",
+            Verdict::Delete(DeleteReason::NotKept) => "not kept",
+        };
+        match self {
+            Verdict::Keep(_) => write!(f, "keep ({})", reason),
+            Verdict::Delete(_) => write!(f, "delete ({})", reason),
+        }
+    }
+}
+
+/// The verdict of every shadow copy, newest first for each volume
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPlan {
+    pub decisions: Vec<(VSSProp, Verdict)>,
+}
+
+impl RetentionPlan {
+    /// The shadow copies to delete
+    pub fn deletions(&self) -> impl Iterator<Item = &VSSProp> {
+        self.decisions
+            .iter()
+            .filter(|(_, verdict)| matches!(verdict, Verdict::Delete(_)))
+            .map(|(snapshot, _)| snapshot)
+    }
+}
+
+fn is_protected(snapshot: &VSSProp) -> Option<KeepReason> {
+    let attrs = snapshot.snapshot_attrs.0;
+    let exposed = VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY.0 | VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY.0;
+    if snapshot.exposed_name.is_some() || attrs & exposed != 0 {
+        Some(KeepReason::Exposed)
+    } else if attrs & VSS_VOLSNAP_ATTR_IMPORTED.0 != 0 {
+        Some(KeepReason::Imported)
+    } else {
+        None
+    }
+}
+
+/// The newest shadow copy of each of the `count` most recent buckets, newest first
+fn keep_buckets<K: PartialEq>(
+    snapshots: &[&VSSProp],
+    count: usize,
+    bucket: impl Fn(&DateTime<Utc>) -> K,
+    kept: &mut [Option<KeepReason>],
+    reason: KeepReason,
+) {
+    let mut last = None;
+    let mut buckets = 0;
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        if buckets == count {
+            break;
+        }
+        let key = bucket(&snapshot.create_time);
+        if last.as_ref() != Some(&key) {
+            kept[i].get_or_insert(reason);
+            buckets += 1;
+            last = Some(key);
+        }
+    }
+}
+
+/// The verdicts of the shadow copies of one volume and provider, sorted newest first
+fn plan_volume(snapshots: &[&VSSProp], rules: &RetentionRules, now: DateTime<Utc>) -> Vec<Verdict> {
+    let mut kept = vec![None; snapshots.len()];
+    if let Some(last) = rules.keep_last {
+        for reason in kept.iter_mut().take(last) {
+            *reason = Some(KeepReason::Last);
+        }
+    }
+    let expired = |snapshot: &VSSProp| {
+        rules
+            .max_age
+            .is_some_and(|age| now - snapshot.create_time > age)
+    };
+    keep_buckets(
+        snapshots,
+        rules.keep_hourly,
+        |t| (t.date_naive(), t.hour()),
+        &mut kept,
+        KeepReason::Hourly,
+    );
+    keep_buckets(
+        snapshots,
+        rules.keep_daily,
+        |t| t.date_naive(),
+        &mut kept,
+        KeepReason::Daily,
+    );
+    keep_buckets(
+        snapshots,
+        rules.keep_weekly,
+        |t| t.iso_week(),
+        &mut kept,
+        KeepReason::Weekly,
+    );
+    keep_buckets(
+        snapshots,
+        rules.keep_monthly,
+        |t| (t.year(), t.month()),
+        &mut kept,
+        KeepReason::Monthly,
+    );
+
+    snapshots
+        .iter()
+        .zip(kept)
+        .map(|(snapshot, kept)| match (kept, expired(snapshot)) {
+            (Some(KeepReason::Last), _) => Verdict::Keep(KeepReason::Last),
+            (_, true) => Verdict::Delete(DeleteReason::Expired),
+            (Some(reason), false) => Verdict::Keep(reason),
+            (None, false) if !rules.has_keep_rule() => match rules.max_age {
+                Some(_) => Verdict::Keep(KeepReason::Recent),
+                None => Verdict::Keep(KeepReason::NoRule),
+            },
+            (None, false) => Verdict::Delete(DeleteReason::NotKept),
+        })
+        .collect()
+}
+
+/// Decide which shadow copies to keep and which to delete.
+///
+/// The shadow copies are grouped by volume and provider, each group with the rules of its
+/// provider. Exposed and imported shadow copies are always kept and are not counted by
+/// the keep rules.
+pub fn plan_retention(
+    snapshots: &[VSSProp],
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> RetentionPlan {
+    let mut groups: Vec<((String, GUID), Vec<&VSSProp>)> = Vec::new();
+    let mut index = HashMap::new();
+    let mut plan = RetentionPlan::default();
+    for snapshot in snapshots.iter() {
+        if let Some(reason) = is_protected(snapshot) {
+            plan.decisions
+                .push((snapshot.clone(), Verdict::Keep(reason)));
+            continue;
+        }
+        let key = (
+            snapshot.origin_vol_name.to_lowercase(),
+            snapshot.provider_id,
+        );
+        let i = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        groups[i].1.push(snapshot);
+    }
+
+    for ((_, provider_id), mut group) in groups {
+        group.sort_by_key(|s| Reverse(s.create_time));
+        let verdicts = plan_volume(&group, policy.rules_for(provider_id), now);
+        plan.decisions
+            .extend(group.into_iter().cloned().zip(verdicts));
+    }
+    plan
+}
+
+/// The oldest shadow copy of the volume, given by its unique name
+pub fn oldest_snapshot<'a>(snapshots: &'a [VSSProp], volume: &str) -> Option<&'a VSSProp> {
+    snapshots
+        .iter()
+        .filter(|s| s.origin_vol_name.eq_ignore_ascii_case(volume))
+        .min_by_key(|s| s.create_time)
+}
+
+/// Parse an age such as `12h`, `30d` or `8w`
+pub fn parse_age(s: &str) -> Result<Duration, VssError> {
+    let invalid = || {
+        VssError::InvalidArgument(format!(
+            "invalid age {}, expected a number of hours, days or weeks such as 30d",
+            s
+        ))
+    };
+    let s = s.trim();
+    let (count, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?);
+    let count = count.parse::<i64>().map_err(|_| invalid())?;
+    match unit {
+        "h" => Ok(Duration::hours(count)),
+        "d" => Ok(Duration::days(count)),
+        "w" => Ok(Duration::weeks(count)),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+    use windows::Win32::Storage::Vss::VSS_VOLUME_SNAPSHOT_ATTRIBUTES;
+
+    use super::*;
+
+    const C: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000c}\";
+    const D: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000d}\";
+    const HARDWARE: GUID = GUID::from_u128(0x4a);
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 6, 30, 12, 0, 0).unwrap()
+    }
+
+    /// A shadow copy of the volume taken `hours` before `now`
+    fn snapshot(id: u128, volume: &str, hours: i64) -> VSSProp {
+        VSSProp {
+            snapshot_id: GUID::from_u128(id),
+            origin_vol_name: volume.to_owned(),
+            create_time: now() - Duration::hours(hours),
+            ..Default::default()
+        }
+    }
+
+    /// The verdicts by shadow copy id, in the order of the plan
+    fn verdicts(snapshots: &[VSSProp], policy: &RetentionPolicy) -> Vec<(u128, String)> {
+        plan_retention(snapshots, policy, now())
+            .decisions
+            .iter()
+            .map(|(s, v)| (s.snapshot_id.to_u128(), v.to_string()))
+            .collect()
+    }
+
+    fn rules(rules: RetentionRules) -> RetentionPolicy {
+        RetentionPolicy {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    /// One shadow copy every 6 hours for 10 days, the newest first
+    fn every_six_hours() -> Vec<VSSProp> {
+        (0..40).map(|i| snapshot(i, C, i as i64 * 6)).collect()
+    }
+
+    fn kept(snapshots: &[VSSProp], policy: &RetentionPolicy) -> Vec<u128> {
+        verdicts(snapshots, policy)
+            .into_iter()
+            .filter(|(_, v)| v.starts_with("keep"))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn test_no_rule_keeps_everything() {
+        let snapshots = every_six_hours();
+        let plan = plan_retention(&snapshots, &RetentionPolicy::default(), now());
+        assert_eq!(plan.deletions().count(), 0);
+        assert!(plan
+            .decisions
+            .iter()
+            .all(|(_, v)| *v == Verdict::Keep(KeepReason::NoRule)));
+    }
+
+    #[test]
+    fn test_keep_last() {
+        let mut snapshots = every_six_hours();
+        // The order of the input does not matter
+        snapshots.reverse();
+        let policy = rules(RetentionRules {
+            keep_last: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(kept(&snapshots, &policy), [0, 1, 2]);
+        assert_eq!(
+            verdicts(&snapshots, &policy)[3],
+            (3, "delete (not kept)".to_owned())
+        );
+        let policy = rules(RetentionRules {
+            keep_last: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(kept(&snapshots, &policy), [0u128; 0]);
+    }
+
+    #[test]
+    fn test_keep_hourly_and_daily() {
+        let snapshots = every_six_hours();
+        let policy = rules(RetentionRules {
+            keep_hourly: 2,
+            ..Default::default()
+        });
+        assert_eq!(kept(&snapshots, &policy), [0, 1]);
+
+        // 12:00 today, then 18:00 of each previous day
+        let policy = rules(RetentionRules {
+            keep_daily: 3,
+            ..Default::default()
+        });
+        assert_eq!(kept(&snapshots, &policy), [0, 3, 7]);
+        assert_eq!(
+            verdicts(&snapshots, &policy)[3],
+            (3, "keep (daily)".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_keep_weekly_and_monthly() {
+        // Every day from 2023-06-30 (a Friday) back to 2023-04-30
+        let snapshots = (0..62)
+            .map(|i| snapshot(i, C, i as i64 * 24))
+            .collect::<Vec<_>>();
+        let policy = rules(RetentionRules {
+            keep_weekly: 3,
+            ..Default::default()
+        });
+        // The Friday, then the Sundays ending the previous ISO weeks
+        assert_eq!(kept(&snapshots, &policy), [0, 5, 12]);
+
+        let policy = rules(RetentionRules {
+            keep_monthly: 12,
+            ..Default::default()
+        });
+        // June, May and April
+        assert_eq!(kept(&snapshots, &policy), [0, 30, 61]);
+    }
+
+    #[test]
+    fn test_rules_are_combined() {
+        let snapshots = every_six_hours();
+        let policy = rules(RetentionRules {
+            keep_last: Some(1),
+            keep_hourly: 2,
+            keep_daily: 2,
+            ..Default::default()
+        });
+        let verdicts = verdicts(&snapshots, &policy);
+        assert_eq!(
+            verdicts[..4],
+            [
+                (0, "keep (last)".to_owned()),
+                (1, "keep (hourly)".to_owned()),
+                (2, "delete (not kept)".to_owned()),
+                (3, "keep (daily)".to_owned()),
+            ]
+        );
+        assert_eq!(
+            verdicts
+                .iter()
+                .filter(|(_, v)| v.starts_with("keep"))
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_max_age() {
+        let snapshots = every_six_hours();
+        let policy = rules(RetentionRules {
+            max_age: Some(Duration::days(1)),
+            ..Default::default()
+        });
+        assert_eq!(kept(&snapshots, &policy), [0, 1, 2, 3, 4]);
+        assert_eq!(
+            verdicts(&snapshots, &policy)[5],
+            (5, "delete (expired)".to_owned())
+        );
+
+        // keep_last wins over max_age, the buckets do not
+        let policy = rules(RetentionRules {
+            keep_last: Some(6),
+            keep_daily: 10,
+            max_age: Some(Duration::days(1)),
+            ..Default::default()
+        });
+        assert_eq!(kept(&snapshots, &policy), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_volumes_are_independent() {
+        let snapshots = [
+            snapshot(1, C, 1),
+            snapshot(2, D, 2),
+            snapshot(3, C, 3),
+            snapshot(4, &D.to_lowercase(), 4),
+        ];
+        let policy = rules(RetentionRules {
+            keep_last: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(kept(&snapshots, &policy), [1, 2]);
+    }
+
+    #[test]
+    fn test_provider_rules() {
+        let mut snapshots = every_six_hours();
+        for snapshot in snapshots.iter_mut().skip(20) {
+            snapshot.provider_id = HARDWARE;
+        }
+        let policy = RetentionPolicy {
+            rules: RetentionRules {
+                keep_last: Some(2),
+                ..Default::default()
+            },
+            // Never delete the hardware shadow copies
+            providers: vec![(HARDWARE, RetentionRules::default())],
+        };
+        let kept = kept(&snapshots, &policy);
+        assert_eq!(kept.len(), 22);
+        assert_eq!(kept[..3], [0, 1, 20]);
+        assert_eq!(policy.rules_for(GUID::zeroed()), &policy.rules);
+    }
+
+    #[test]
+    fn test_protected() {
+        let mut snapshots = every_six_hours();
+        snapshots[10].exposed_name = Some("X:".to_owned());
+        snapshots[11].snapshot_attrs = VSS_VOLUME_SNAPSHOT_ATTRIBUTES(VSS_VOLSNAP_ATTR_IMPORTED.0);
+        snapshots[12].snapshot_attrs =
+            VSS_VOLUME_SNAPSHOT_ATTRIBUTES(VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY.0);
+        // Protected shadow copies are not counted by the keep rules
+        snapshots[0].exposed_name = Some("Y:".to_owned());
+        let policy = rules(RetentionRules {
+            keep_last: Some(1),
+            max_age: Some(Duration::hours(1)),
+            ..Default::default()
+        });
+        let verdicts = verdicts(&snapshots, &policy);
+        let kept = verdicts
+            .iter()
+            .filter(|(_, v)| v.starts_with("keep"))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            [
+                (0, "keep (exposed)".to_owned()),
+                (10, "keep (exposed)".to_owned()),
+                (11, "keep (imported)".to_owned()),
+                (12, "keep (exposed)".to_owned()),
+                (1, "keep (last)".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oldest_snapshot() {
+        let snapshots = [snapshot(1, C, 1), snapshot(2, D, 9), snapshot(3, C, 3)];
+        assert_eq!(
+            oldest_snapshot(&snapshots, C)
+                .unwrap()
+                .snapshot_id
+                .to_u128(),
+            3
+        );
+        assert_eq!(
+            oldest_snapshot(&snapshots, &D.to_lowercase())
+                .unwrap()
+                .snapshot_id
+                .to_u128(),
+            2
+        );
+        assert!(oldest_snapshot(&snapshots, "E:").is_none());
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("12h").unwrap(), Duration::hours(12));
+        assert_eq!(parse_age("30d").unwrap(), Duration::days(30));
+        assert_eq!(parse_age(" 2w").unwrap(), Duration::weeks(2));
+        for invalid in ["", "30", "d", "3x", "-3d", "1.5d"] {
+            assert!(parse_age(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Reverting a volume to one of its shadow copies (-revert).
+//!
+//! The checks run before `RevertToSnapshot` only depend on the shadow copy properties,
+//! the progress is then polled from the `IVssAsync` returned by `QueryRevertStatus`.
+
+use std::{thread, time::Duration};
+
+use windows::Win32::Storage::Vss::{
+    VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE, VSS_VOLSNAP_ATTR_PERSISTENT,
+};
+
+use crate::{error::VssError, vssprop::VSSProp};
+
+/// The state of a revert, as reported by `QueryRevertStatus`
+pub use crate::asyncop::AsyncStatus as RevertStatus;
+
+/// How often `RevertOperation` polls the status by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The status of a revert in progress, queried without waiting
+pub trait RevertStatusQuery {
+    fn query_status(&mut self) -> Result<RevertStatus, VssError>;
+}
+
+/// A revert in progress, returned by `VssClient::revert_to_snapshot`.
+///
+/// Iterating polls the status every `interval` and yields it, up to and including
+/// the first status that is done or the first error.
+pub struct RevertOperation {
+    status: Box<dyn RevertStatusQuery>,
+    interval: Duration,
+    polled: bool,
+    done: bool,
+}
+
+impl RevertOperation {
+    pub fn new(status: Box<dyn RevertStatusQuery>, interval: Duration) -> Self {
+        Self {
+            status,
+            interval,
+            polled: false,
+            done: false,
+        }
+    }
+
+    /// Poll the status every `interval` instead
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Wait for the revert to be over and return its final status
+    pub fn wait(self) -> Result<RevertStatus, VssError> {
+        let mut last = RevertStatus::Pending;
+        for status in self {
+            last = status?;
+        }
+        Ok(last)
+    }
+}
+
+impl std::fmt::Debug for RevertOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RevertOperation")
+            .field("interval", &self.interval)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl Iterator for RevertOperation {
+    type Item = Result<RevertStatus, VssError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // The first status is queried right away
+        if self.polled {
+            thread::sleep(self.interval);
+        }
+        self.polled = true;
+        let status = self.status.query_status();
+        self.done = !matches!(status, Ok(RevertStatus::Pending));
+        Some(status)
+    }
+}
+
+fn not_revertable(snapshot: &VSSProp, reason: &str) -> VssError {
+    VssError::InvalidArgument(format!(
+        "shadow copy {:?} cannot be reverted to: {}",
+        snapshot.snapshot_id, reason
+    ))
+}
+
+/// Only persistent shadow copies can be reverted to
+pub fn check_persistent(snapshot: &VSSProp) -> Result<(), VssError> {
+    match snapshot.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_PERSISTENT.0 {
+        0 => Err(not_revertable(snapshot, "it is not persistent")),
+        _ => Ok(()),
+    }
+}
+
+/// Client-accessible shadow copies, the Shadow Copies for Shared Folders ones,
+/// cannot be reverted to
+pub fn check_not_client_accessible(snapshot: &VSSProp) -> Result<(), VssError> {
+    match snapshot.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0 {
+        0 => Ok(()),
+        _ => Err(not_revertable(snapshot, "it is client-accessible")),
+    }
+}
+
+/// The shadow copies of the volume, among `snapshots`, must all belong to the set
+/// of the shadow copy
+pub fn check_single_snapshot_set(
+    snapshot: &VSSProp,
+    snapshots: &[VSSProp],
+) -> Result<(), VssError> {
+    let other = snapshots.iter().find(|s| {
+        s.origin_vol_name
+            .eq_ignore_ascii_case(&snapshot.origin_vol_name)
+            && s.shadow_copy_set_id != snapshot.shadow_copy_set_id
+    });
+    match other {
+        Some(other) => Err(not_revertable(
+            snapshot,
+            &format!(
+                "the volume has shadow copies in another set, {:?}",
+                other.shadow_copy_set_id
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Check that the volume of the shadow copy can be reverted to it, given all the shadow
+/// copies of the machine
+pub fn check_revert(snapshot: &VSSProp, snapshots: &[VSSProp]) -> Result<(), VssError> {
+    check_persistent(snapshot)?;
+    check_not_client_accessible(snapshot)?;
+    check_single_snapshot_set(snapshot, snapshots)
+}
+
+#[cfg(test)]
+mod test {
+    use windows::{
+        core::GUID,
+        Win32::Storage::Vss::{VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE, VSS_VOLUME_SNAPSHOT_ATTRIBUTES},
+    };
+
+    use super::*;
+
+    fn snapshot(id: u128, set_id: u128, volume: &str, attrs: i32) -> VSSProp {
+        VSSProp {
+            snapshot_id: GUID::from_u128(id),
+            shadow_copy_set_id: GUID::from_u128(set_id),
+            origin_vol_name: volume.to_owned(),
+            snapshot_attrs: VSS_VOLUME_SNAPSHOT_ATTRIBUTES(attrs),
+            ..Default::default()
+        }
+    }
+
+    const C: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000c}\";
+    const D: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000d}\";
+    const PERSISTENT: i32 = VSS_VOLSNAP_ATTR_PERSISTENT.0 | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0;
+
+    #[test]
+    fn test_check_persistent() {
+        assert!(check_persistent(&snapshot(1, 10, C, PERSISTENT)).is_ok());
+        assert!(matches!(
+            check_persistent(&snapshot(1, 10, C, 0)),
+            Err(VssError::InvalidArgument(m)) if m.ends_with("it is not persistent")
+        ));
+    }
+
+    #[test]
+    fn test_check_not_client_accessible() {
+        assert!(check_not_client_accessible(&snapshot(1, 10, C, PERSISTENT)).is_ok());
+        let accessible = PERSISTENT | VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0;
+        assert!(check_not_client_accessible(&snapshot(1, 10, C, accessible)).is_err());
+        assert!(check_revert(&snapshot(1, 10, C, accessible), &[]).is_err());
+    }
+
+    #[test]
+    fn test_check_single_snapshot_set() {
+        let first = snapshot(1, 10, C, PERSISTENT);
+        let all = [
+            first.clone(),
+            snapshot(2, 10, D, PERSISTENT),
+            snapshot(3, 20, D, PERSISTENT),
+        ];
+        // The other set only has shadow copies of D:
+        check_revert(&first, &all).unwrap();
+        assert!(check_single_snapshot_set(&all[1], &all).is_err());
+        assert!(check_single_snapshot_set(&all[2], &all).is_err());
+        // The volume names are compared without case
+        let lower = snapshot(4, 30, &C.to_lowercase(), PERSISTENT);
+        assert!(check_single_snapshot_set(&first, &[first.clone(), lower]).is_err());
+    }
+
+    struct Countdown(Vec<Result<RevertStatus, VssError>>);
+
+    impl RevertStatusQuery for Countdown {
+        fn query_status(&mut self) -> Result<RevertStatus, VssError> {
+            self.0.remove(0)
+        }
+    }
+
+    #[test]
+    fn test_operation() {
+        let statuses = vec![
+            Ok(RevertStatus::Pending),
+            Ok(RevertStatus::Pending),
+            Ok(RevertStatus::Finished),
+            Ok(RevertStatus::Pending),
+        ];
+        let operation = RevertOperation::new(Box::new(Countdown(statuses)), Duration::ZERO);
+        assert_eq!(
+            operation.collect::<Result<Vec<_>, _>>().unwrap(),
+            [
+                RevertStatus::Pending,
+                RevertStatus::Pending,
+                RevertStatus::Finished
+            ]
+        );
+
+        let statuses = vec![
+            Ok(RevertStatus::Pending),
+            Err(VssError::BadState {
+                operation: "QueryStatus",
+            }),
+            Ok(RevertStatus::Finished),
+        ];
+        let mut operation = RevertOperation::new(Box::new(Countdown(statuses)), Duration::ZERO);
+        assert!(operation.next().unwrap().is_ok());
+        assert!(operation.next().unwrap().is_err());
+        assert!(operation.next().is_none());
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use windows::core::GUID;
+
+use crate::{vssprop::VSSProp, xml::format_guid};
+
+/// The language of the script generated with -script
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ScriptDialect {
+    /// A CMD file of `SET` commands, as generated by vshadow.exe
+    #[default]
+    Cmd,
+    /// A PowerShell script setting `$env:` variables
+    #[cfg_attr(feature = "cli", value(name = "powershell"))]
+    PowerShell,
+    /// A POSIX shell script of `export` commands
+    Sh,
+    /// A `.env` file of `NAME=value` lines
+    Env,
+}
+
+impl ScriptDialect {
+    /// Guess the dialect from the extension of the script file, CMD by default
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "ps1" => ScriptDialect::PowerShell,
+            "sh" => ScriptDialect::Sh,
+            "env" => ScriptDialect::Env,
+            _ if path.file_name().is_some_and(|n| n == ".env") => ScriptDialect::Env,
+            _ => ScriptDialect::Cmd,
+        }
+    }
+
+    fn line_ending(self) -> &'static str {
+        match self {
+            ScriptDialect::Cmd | ScriptDialect::PowerShell => "\r\n",
+            ScriptDialect::Sh | ScriptDialect::Env => "\n",
+        }
+    }
+}
+
+/// A GUID the way vshadow.exe prints it, lowercase between braces
+fn braced(guid: GUID) -> String {
+    format!("{{{}}}", format_guid(guid))
+}
+
+/// The environment variables describing a shadow copy set, named like vshadow.exe does:
+/// SHADOW_SET_ID, then SHADOW_ID_n and SHADOW_DEVICE_n for each shadow copy, from 1.
+pub fn script_variables(set_id: GUID, snapshots: &[VSSProp]) -> Vec<(String, String)> {
+    let mut variables = vec![("SHADOW_SET_ID".to_owned(), braced(set_id))];
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        variables.push((format!("SHADOW_ID_{}", i + 1), braced(snapshot.snapshot_id)));
+        variables.push((
+            format!("SHADOW_DEVICE_{}", i + 1),
+            snapshot.device_name.clone(),
+        ));
+    }
+    variables
+}
+
+/// Generate the script defining the `script_variables` of the set in the given dialect
+pub fn generate_script(set_id: GUID, snapshots: &[VSSProp], dialect: ScriptDialect) -> String {
+    let header = format!(
+        "This script is generated by VSHADOW for the shadow set {}",
+        braced(set_id)
+    );
+    let mut lines = match dialect {
+        ScriptDialect::Cmd => vec![
+            "@echo.".to_owned(),
+            format!("@echo [{}]", header),
+            "@echo.".to_owned(),
+            String::new(),
+        ],
+        ScriptDialect::PowerShell | ScriptDialect::Sh | ScriptDialect::Env => {
+            vec![format!("# {}", header), String::new()]
+        }
+    };
+    if dialect == ScriptDialect::Sh {
+        lines.insert(0, "#!/bin/sh".to_owned());
+    }
+
+    for (name, value) in script_variables(set_id, snapshots) {
+        lines.push(match dialect {
+            ScriptDialect::Cmd => format!("SET {}={}", name, value),
+            ScriptDialect::PowerShell => {
+                format!("$env:{} = '{}'", name, value.replace('\'', "''"))
+            }
+            ScriptDialect::Sh => format!("export {}='{}'", name, value.replace('\'', "'\\''")),
+            ScriptDialect::Env => format!("{}={}", name, value),
+        });
+    }
+
+    let line_ending = dialect.line_ending();
+    let mut script = lines.join(line_ending);
+    script.push_str(line_ending);
+    script
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshots() -> (GUID, Vec<VSSProp>) {
+        let set_id = GUID::from_u128(0x9a5cd9e1_93a4_4d18_8b2a_3f6c24b2e0a1);
+        let snapshots = (1..=2)
+            .map(|i| VSSProp {
+                snapshot_id: GUID::from_u128(0x3808876b_c176_4e48_b7ae_04046e6cc750 + i),
+                shadow_copy_set_id: set_id,
+                device_name: format!("\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy{}", i),
+                ..Default::default()
+            })
+            .collect();
+        (set_id, snapshots)
+    }
+
+    #[test]
+    fn test_cmd() {
+        let (set_id, snapshots) = snapshots();
+        assert_eq!(
+            generate_script(set_id, &snapshots, ScriptDialect::Cmd),
+            "@echo.\r\n\
+@echo [This script is generated by VSHADOW for the shadow set {9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}]\r\n\
+@echo.\r\n\
+\r\n\
+SET SHADOW_SET_ID={9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}\r\n\
+SET SHADOW_ID_1={3808876b-c176-4e48-b7ae-04046e6cc751}\r\n\
+SET SHADOW_DEVICE_1=\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy1\r\n\
+SET SHADOW_ID_2={3808876b-c176-4e48-b7ae-04046e6cc752}\r\n\
+SET SHADOW_DEVICE_2=\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy2\r\n"
+        );
+    }
+
+    #[test]
+    fn test_other_dialects() {
+        let (set_id, snapshots) = snapshots();
+        let snapshots = &snapshots[..1];
+        assert_eq!(
+            generate_script(set_id, snapshots, ScriptDialect::PowerShell),
+            "# This script is generated by VSHADOW for the shadow set {9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}\r\n\
+\r\n\
+$env:SHADOW_SET_ID = '{9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}'\r\n\
+$env:SHADOW_ID_1 = '{3808876b-c176-4e48-b7ae-04046e6cc751}'\r\n\
+$env:SHADOW_DEVICE_1 = '\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy1'\r\n"
+        );
+        assert_eq!(
+            generate_script(set_id, snapshots, ScriptDialect::Sh),
+            "#!/bin/sh\n\
+# This script is generated by VSHADOW for the shadow set {9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}\n\
+\n\
+export SHADOW_SET_ID='{9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}'\n\
+export SHADOW_ID_1='{3808876b-c176-4e48-b7ae-04046e6cc751}'\n\
+export SHADOW_DEVICE_1='\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy1'\n"
+        );
+        assert_eq!(
+            generate_script(set_id, snapshots, ScriptDialect::Env),
+            "# This script is generated by VSHADOW for the shadow set {9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}\n\
+\n\
+SHADOW_SET_ID={9a5cd9e1-93a4-4d18-8b2a-3f6c24b2e0a1}\n\
+SHADOW_ID_1={3808876b-c176-4e48-b7ae-04046e6cc751}\n\
+SHADOW_DEVICE_1=\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy1\n"
+        );
+    }
+
+    #[test]
+    fn test_dialect_from_path() {
+        for (path, dialect) in [
+            ("vars.cmd", ScriptDialect::Cmd),
+            ("vars.BAT", ScriptDialect::Cmd),
+            ("vars", ScriptDialect::Cmd),
+            ("C:\\vars.ps1", ScriptDialect::PowerShell),
+            ("/tmp/vars.sh", ScriptDialect::Sh),
+            ("vars.env", ScriptDialect::Env),
+            (".env", ScriptDialect::Env),
+        ] {
+            assert_eq!(
+                ScriptDialect::from_path(Path::new(path)),
+                dialect,
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,730 @@
+//! The attributes, states and contexts of the shadow copies.
+//!
+//! VSS exposes them as raw integers (`VSS_VOLUME_SNAPSHOT_ATTRIBUTES`, `VSS_SNAPSHOT_STATE`,
+//! `VSS_SNAPSHOT_CONTEXT`). The types here name them, order them and make sure that only
+//! the context values accepted by `SetContext` are built.
+
+use std::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+use windows::Win32::Storage::Vss::{
+    VSS_CTX_ALL, VSS_SNAPSHOT_CONTEXT, VSS_SNAPSHOT_STATE, VSS_SS_ABORTED, VSS_SS_COMMITTED,
+    VSS_SS_CREATED, VSS_SS_DELETED, VSS_SS_POSTCOMMITTED, VSS_SS_PRECOMMITTED,
+    VSS_SS_PREFINALCOMMITTED, VSS_SS_PREPARED, VSS_SS_PREPARING, VSS_SS_PROCESSING_COMMIT,
+    VSS_SS_PROCESSING_POSTCOMMIT, VSS_SS_PROCESSING_POSTFINALCOMMIT, VSS_SS_PROCESSING_PRECOMMIT,
+    VSS_SS_PROCESSING_PREFINALCOMMIT, VSS_SS_PROCESSING_PREPARE, VSS_SS_UNKNOWN,
+    VSS_VOLSNAP_ATTR_AUTORECOVER, VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE,
+    VSS_VOLSNAP_ATTR_DELAYED_POSTSNAPSHOT, VSS_VOLSNAP_ATTR_DIFFERENTIAL,
+    VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY, VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY,
+    VSS_VOLSNAP_ATTR_FILE_SHARE, VSS_VOLSNAP_ATTR_HARDWARE_ASSISTED, VSS_VOLSNAP_ATTR_IMPORTED,
+    VSS_VOLSNAP_ATTR_NOT_SURFACED, VSS_VOLSNAP_ATTR_NOT_TRANSACTED,
+    VSS_VOLSNAP_ATTR_NO_AUTORECOVERY, VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE,
+    VSS_VOLSNAP_ATTR_NO_WRITERS, VSS_VOLSNAP_ATTR_PERSISTENT, VSS_VOLSNAP_ATTR_PLEX,
+    VSS_VOLSNAP_ATTR_ROLLBACK_RECOVERY, VSS_VOLSNAP_ATTR_TRANSPORTABLE,
+    VSS_VOLSNAP_ATTR_TXF_RECOVERY, VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+};
+
+use crate::error::VssError;
+
+bitflags! {
+    /// The attributes of a shadow copy, `VSS_VOLUME_SNAPSHOT_ATTRIBUTES`
+    #[derive(Default)]
+    pub struct SnapshotAttributes: i32 {
+        const PERSISTENT = VSS_VOLSNAP_ATTR_PERSISTENT.0;
+        const NO_AUTORECOVERY = VSS_VOLSNAP_ATTR_NO_AUTORECOVERY.0;
+        const CLIENT_ACCESSIBLE = VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0;
+        const NO_AUTO_RELEASE = VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0;
+        const NO_WRITERS = VSS_VOLSNAP_ATTR_NO_WRITERS.0;
+        const TRANSPORTABLE = VSS_VOLSNAP_ATTR_TRANSPORTABLE.0;
+        const NOT_SURFACED = VSS_VOLSNAP_ATTR_NOT_SURFACED.0;
+        const NOT_TRANSACTED = VSS_VOLSNAP_ATTR_NOT_TRANSACTED.0;
+        const HARDWARE_ASSISTED = VSS_VOLSNAP_ATTR_HARDWARE_ASSISTED.0;
+        const DIFFERENTIAL = VSS_VOLSNAP_ATTR_DIFFERENTIAL.0;
+        const PLEX = VSS_VOLSNAP_ATTR_PLEX.0;
+        const IMPORTED = VSS_VOLSNAP_ATTR_IMPORTED.0;
+        const EXPOSED_LOCALLY = VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY.0;
+        const EXPOSED_REMOTELY = VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY.0;
+        const AUTORECOVER = VSS_VOLSNAP_ATTR_AUTORECOVER.0;
+        const ROLLBACK_RECOVERY = VSS_VOLSNAP_ATTR_ROLLBACK_RECOVERY.0;
+        const DELAYED_POSTSNAPSHOT = VSS_VOLSNAP_ATTR_DELAYED_POSTSNAPSHOT.0;
+        const TXF_RECOVERY = VSS_VOLSNAP_ATTR_TXF_RECOVERY.0;
+        const FILE_SHARE = VSS_VOLSNAP_ATTR_FILE_SHARE.0;
+    }
+}
+
+/// The names of the attributes as printed by vshadow.exe, in display order
+const ATTRIBUTE_NAMES: [(SnapshotAttributes, &str); 19] = [
+    (SnapshotAttributes::PERSISTENT, "Persistent"),
+    (SnapshotAttributes::NO_AUTORECOVERY, "No_AutoRecovery"),
+    (SnapshotAttributes::CLIENT_ACCESSIBLE, "Client_accessible"),
+    (SnapshotAttributes::NO_AUTO_RELEASE, "No_Auto_Release"),
+    (SnapshotAttributes::NO_WRITERS, "No_Writers"),
+    (SnapshotAttributes::TRANSPORTABLE, "Transportable"),
+    (SnapshotAttributes::NOT_SURFACED, "Not_Surfaced"),
+    (SnapshotAttributes::NOT_TRANSACTED, "Not_Transacted"),
+    (SnapshotAttributes::HARDWARE_ASSISTED, "Hardware"),
+    (SnapshotAttributes::DIFFERENTIAL, "Differential"),
+    (SnapshotAttributes::PLEX, "Plex"),
+    (SnapshotAttributes::IMPORTED, "Imported"),
+    (SnapshotAttributes::EXPOSED_LOCALLY, "Exposed_Locally"),
+    (SnapshotAttributes::EXPOSED_REMOTELY, "Exposed_Remotely"),
+    (SnapshotAttributes::AUTORECOVER, "Autorecover"),
+    (SnapshotAttributes::ROLLBACK_RECOVERY, "Rollback_Recovery"),
+    (
+        SnapshotAttributes::DELAYED_POSTSNAPSHOT,
+        "Delayed_Postsnapshot",
+    ),
+    (SnapshotAttributes::TXF_RECOVERY, "Txf_Recovery"),
+    (SnapshotAttributes::FILE_SHARE, "File_Share"),
+];
+
+/// The name vshadow.exe prints when `No_Auto_Release` is missing
+const AUTO_RELEASE: &str = "Auto_Release";
+
+impl SnapshotAttributes {
+    /// The names of the attributes that are set, in display order
+    pub fn names(&self) -> Vec<&'static str> {
+        ATTRIBUTE_NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// The names as printed by vshadow.exe, with "Auto_Release" in place of a missing
+    /// `No_Auto_Release`
+    pub fn vshadow_names(&self) -> Vec<&'static str> {
+        ATTRIBUTE_NAMES
+            .iter()
+            .filter_map(|(flag, name)| match self.contains(*flag) {
+                true => Some(*name),
+                false if *flag == SnapshotAttributes::NO_AUTO_RELEASE => Some(AUTO_RELEASE),
+                false => None,
+            })
+            .collect()
+    }
+
+    /// The attribute named `name` without case, `Auto_Release` being no attribute
+    fn from_name(name: &str) -> Result<Self, VssError> {
+        if name.eq_ignore_ascii_case(AUTO_RELEASE) || name.eq_ignore_ascii_case("None") {
+            return Ok(SnapshotAttributes::empty());
+        }
+        ATTRIBUTE_NAMES
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name))
+            .map(|(flag, _)| *flag)
+            .ok_or_else(|| {
+                VssError::InvalidArgument(format!("unknown snapshot attribute {}", name))
+            })
+    }
+}
+
+impl From<VSS_VOLUME_SNAPSHOT_ATTRIBUTES> for SnapshotAttributes {
+    /// The unknown bits are dropped
+    fn from(attributes: VSS_VOLUME_SNAPSHOT_ATTRIBUTES) -> Self {
+        SnapshotAttributes::from_bits_truncate(attributes.0)
+    }
+}
+
+impl From<SnapshotAttributes> for VSS_VOLUME_SNAPSHOT_ATTRIBUTES {
+    fn from(attributes: SnapshotAttributes) -> Self {
+        VSS_VOLUME_SNAPSHOT_ATTRIBUTES(attributes.bits())
+    }
+}
+
+impl fmt::Display for SnapshotAttributes {
+    /// The names separated by spaces, "None" without attribute
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.names().as_slice() {
+            [] => f.write_str("None"),
+            names => f.write_str(&names.join(" ")),
+        }
+    }
+}
+
+impl FromStr for SnapshotAttributes {
+    type Err = VssError;
+
+    /// Names separated by spaces, commas or `|`, as displayed or printed by vshadow.exe
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(|c: char| c.is_whitespace() || c == ',' || c == '|')
+            .filter(|name| !name.is_empty())
+            .try_fold(SnapshotAttributes::empty(), |attributes, name| {
+                Ok(attributes | SnapshotAttributes::from_name(name)?)
+            })
+    }
+}
+
+/// The state of a shadow copy, `VSS_SNAPSHOT_STATE`, ordered along its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SnapshotState {
+    Unknown,
+    Preparing,
+    ProcessingPrepare,
+    Prepared,
+    ProcessingPrecommit,
+    Precommitted,
+    ProcessingCommit,
+    Committed,
+    ProcessingPostcommit,
+    Postcommitted,
+    ProcessingPrefinalcommit,
+    Prefinalcommitted,
+    ProcessingPostfinalcommit,
+    Created,
+    Aborted,
+    Deleted,
+}
+
+/// The states with their `VSS_SS_*` values, in lifecycle order
+const STATES: [(SnapshotState, VSS_SNAPSHOT_STATE, &str); 16] = [
+    (SnapshotState::Unknown, VSS_SS_UNKNOWN, "VSS_SS_UNKNOWN"),
+    (
+        SnapshotState::Preparing,
+        VSS_SS_PREPARING,
+        "VSS_SS_PREPARING",
+    ),
+    (
+        SnapshotState::ProcessingPrepare,
+        VSS_SS_PROCESSING_PREPARE,
+        "VSS_SS_PROCESSING_PREPARE",
+    ),
+    (SnapshotState::Prepared, VSS_SS_PREPARED, "VSS_SS_PREPARED"),
+    (
+        SnapshotState::ProcessingPrecommit,
+        VSS_SS_PROCESSING_PRECOMMIT,
+        "VSS_SS_PROCESSING_PRECOMMIT",
+    ),
+    (
+        SnapshotState::Precommitted,
+        VSS_SS_PRECOMMITTED,
+        "VSS_SS_PRECOMMITTED",
+    ),
+    (
+        SnapshotState::ProcessingCommit,
+        VSS_SS_PROCESSING_COMMIT,
+        "VSS_SS_PROCESSING_COMMIT",
+    ),
+    (
+        SnapshotState::Committed,
+        VSS_SS_COMMITTED,
+        "VSS_SS_COMMITTED",
+    ),
+    (
+        SnapshotState::ProcessingPostcommit,
+        VSS_SS_PROCESSING_POSTCOMMIT,
+        "VSS_SS_PROCESSING_POSTCOMMIT",
+    ),
+    (
+        SnapshotState::Postcommitted,
+        VSS_SS_POSTCOMMITTED,
+        "VSS_SS_POSTCOMMITTED",
+    ),
+    (
+        SnapshotState::ProcessingPrefinalcommit,
+        VSS_SS_PROCESSING_PREFINALCOMMIT,
+        "VSS_SS_PROCESSING_PREFINALCOMMIT",
+    ),
+    (
+        SnapshotState::Prefinalcommitted,
+        VSS_SS_PREFINALCOMMITTED,
+        "VSS_SS_PREFINALCOMMITTED",
+    ),
+    (
+        SnapshotState::ProcessingPostfinalcommit,
+        VSS_SS_PROCESSING_POSTFINALCOMMIT,
+        "VSS_SS_PROCESSING_POSTFINALCOMMIT",
+    ),
+    (SnapshotState::Created, VSS_SS_CREATED, "VSS_SS_CREATED"),
+    (SnapshotState::Aborted, VSS_SS_ABORTED, "VSS_SS_ABORTED"),
+    (SnapshotState::Deleted, VSS_SS_DELETED, "VSS_SS_DELETED"),
+];
+
+impl SnapshotState {
+    fn entry(&self) -> &'static (SnapshotState, VSS_SNAPSHOT_STATE, &'static str) {
+        &STATES[*self as usize]
+    }
+
+    /// The name of the `VSS_SS_*` value, e.g. "VSS_SS_CREATED"
+    pub fn name(&self) -> &'static str {
+        self.entry().2
+    }
+
+    /// Whether the shadow copy is still being created
+    pub fn is_in_progress(&self) -> bool {
+        (SnapshotState::Preparing..SnapshotState::Created).contains(self)
+    }
+}
+
+impl From<VSS_SNAPSHOT_STATE> for SnapshotState {
+    /// The values outside of the lifecycle, such as `VSS_SS_COUNT`, are unknown
+    fn from(state: VSS_SNAPSHOT_STATE) -> Self {
+        STATES
+            .iter()
+            .find(|(_, value, _)| *value == state)
+            .map_or(SnapshotState::Unknown, |(state, _, _)| *state)
+    }
+}
+
+impl From<SnapshotState> for VSS_SNAPSHOT_STATE {
+    fn from(state: SnapshotState) -> Self {
+        state.entry().1
+    }
+}
+
+impl fmt::Display for SnapshotState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SnapshotState {
+    type Err = VssError;
+
+    /// The name of the `VSS_SS_*` value without case, with or without the prefix
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        STATES
+            .iter()
+            .find(|(_, _, name)| {
+                name.eq_ignore_ascii_case(s) || name["VSS_SS_".len()..].eq_ignore_ascii_case(s)
+            })
+            .map(|(state, _, _)| *state)
+            .ok_or_else(|| VssError::InvalidArgument(format!("unknown snapshot state {}", s)))
+    }
+}
+
+/// The attributes making up the contexts, besides the hardware ones
+const BASE_CONTEXT: SnapshotAttributes = SnapshotAttributes::from_bits_truncate(
+    SnapshotAttributes::PERSISTENT.bits()
+        | SnapshotAttributes::NO_AUTO_RELEASE.bits()
+        | SnapshotAttributes::NO_WRITERS.bits()
+        | SnapshotAttributes::CLIENT_ACCESSIBLE.bits(),
+);
+
+/// The hardware attributes that can be added to a context
+const HARDWARE_CONTEXT: SnapshotAttributes = SnapshotAttributes::from_bits_truncate(
+    SnapshotAttributes::DIFFERENTIAL.bits()
+        | SnapshotAttributes::PLEX.bits()
+        | SnapshotAttributes::TRANSPORTABLE.bits(),
+);
+
+/// The `VSS_CTX_*` values accepted by `SetContext`, by the attributes they are made of
+const CONTEXT_NAMES: [(i32, &str); 6] = [
+    (0, "VSS_CTX_BACKUP"),
+    (VSS_VOLSNAP_ATTR_NO_WRITERS.0, "VSS_CTX_FILE_SHARE_BACKUP"),
+    (
+        VSS_VOLSNAP_ATTR_PERSISTENT.0 | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0,
+        "VSS_CTX_APP_ROLLBACK",
+    ),
+    (
+        VSS_VOLSNAP_ATTR_PERSISTENT.0
+            | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0
+            | VSS_VOLSNAP_ATTR_NO_WRITERS.0,
+        "VSS_CTX_NAS_ROLLBACK",
+    ),
+    (
+        VSS_VOLSNAP_ATTR_PERSISTENT.0
+            | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0
+            | VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0,
+        "VSS_CTX_CLIENT_ACCESSIBLE_WRITERS",
+    ),
+    (
+        VSS_VOLSNAP_ATTR_PERSISTENT.0
+            | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0
+            | VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0
+            | VSS_VOLSNAP_ATTR_NO_WRITERS.0,
+        "VSS_CTX_CLIENT_ACCESSIBLE",
+    ),
+];
+
+/// A context accepted by `SetContext`: one of the `VSS_CTX_*` values, possibly with the
+/// `Differential` or `Plex` hardware attribute and the `Transportable` one. Built with
+/// `SnapshotContext::builder` or checked with `SnapshotContext::try_from`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotContext {
+    /// The attributes implied for the shadow copies, `None` for `VSS_CTX_ALL`
+    attributes: Option<SnapshotAttributes>,
+}
+
+impl SnapshotContext {
+    /// `VSS_CTX_BACKUP`, the default context
+    pub const BACKUP: SnapshotContext = SnapshotContext {
+        attributes: Some(SnapshotAttributes::empty()),
+    };
+    /// `VSS_CTX_ALL`, to query or delete the shadow copies of every context
+    pub const ALL: SnapshotContext = SnapshotContext { attributes: None };
+
+    pub fn builder() -> SnapshotContextBuilder {
+        SnapshotContextBuilder::default()
+    }
+
+    /// The attributes of the shadow copies created in this context, none for `VSS_CTX_ALL`
+    pub fn attributes(&self) -> SnapshotAttributes {
+        self.attributes.unwrap_or_default()
+    }
+
+    /// The value given to `SetContext`
+    pub fn value(&self) -> VSS_SNAPSHOT_CONTEXT {
+        match self.attributes {
+            Some(attributes) => {
+                VSS_SNAPSHOT_CONTEXT((attributes - SnapshotAttributes::FILE_SHARE).bits())
+            }
+            None => VSS_CTX_ALL,
+        }
+    }
+
+    /// The name of the `VSS_CTX_*` value, without the hardware attributes
+    pub fn name(&self) -> &'static str {
+        let base = match self.attributes {
+            Some(attributes) => (attributes & BASE_CONTEXT).bits(),
+            None => return "VSS_CTX_ALL",
+        };
+        CONTEXT_NAMES
+            .iter()
+            .find(|(value, _)| *value == base)
+            .map_or("VSS_CTX_UNKNOWN", |(_, name)| name)
+    }
+}
+
+impl Default for SnapshotContext {
+    fn default() -> Self {
+        SnapshotContext::BACKUP
+    }
+}
+
+impl TryFrom<VSS_SNAPSHOT_CONTEXT> for SnapshotContext {
+    type Error = VssError;
+
+    fn try_from(context: VSS_SNAPSHOT_CONTEXT) -> Result<Self, Self::Error> {
+        if context == VSS_CTX_ALL {
+            return Ok(SnapshotContext::ALL);
+        }
+        let invalid = |reason: &str| {
+            VssError::InvalidArgument(format!(
+                "invalid snapshot context 0x{:x}: {}",
+                context.0, reason
+            ))
+        };
+        let attributes = SnapshotAttributes::from_bits(context.0)
+            .filter(|attributes| (BASE_CONTEXT | HARDWARE_CONTEXT).contains(*attributes))
+            .ok_or_else(|| invalid("unexpected attributes"))?;
+        if !CONTEXT_NAMES
+            .iter()
+            .any(|(value, _)| *value == (attributes & BASE_CONTEXT).bits())
+        {
+            return Err(invalid("not a VSS_CTX_* value"));
+        }
+        if attributes.contains(SnapshotAttributes::DIFFERENTIAL | SnapshotAttributes::PLEX) {
+            return Err(invalid("Differential and Plex are exclusive"));
+        }
+        Ok(SnapshotContext {
+            attributes: Some(attributes),
+        })
+    }
+}
+
+impl From<SnapshotContext> for VSS_SNAPSHOT_CONTEXT {
+    fn from(context: SnapshotContext) -> Self {
+        context.value()
+    }
+}
+
+impl fmt::Display for SnapshotContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hardware = self.attributes() & HARDWARE_CONTEXT;
+        match hardware.is_empty() {
+            true => f.write_str(self.name()),
+            false => write!(f, "{} ({})", self.name(), hardware),
+        }
+    }
+}
+
+/// Builds a `SnapshotContext` from the kind of shadow copies wanted, see `build`
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotContextBuilder {
+    persistent: bool,
+    no_writers: bool,
+    client_accessible: bool,
+    file_share: bool,
+    differential: bool,
+    plex: bool,
+    transportable: bool,
+}
+
+impl SnapshotContextBuilder {
+    /// Persistent shadow copies, which are not released with the backup components
+    pub fn persistent(mut self, persistent: bool) -> Self {
+        self.persistent = persistent;
+        self
+    }
+
+    /// Shadow copies created without involving the writers
+    pub fn no_writers(mut self, no_writers: bool) -> Self {
+        self.no_writers = no_writers;
+        self
+    }
+
+    /// Shadow copies for shared folders, persistent and client accessible
+    pub fn client_accessible(mut self, client_accessible: bool) -> Self {
+        self.client_accessible = client_accessible;
+        self
+    }
+
+    /// Shadow copies of file shares, without writers
+    pub fn file_share(mut self, file_share: bool) -> Self {
+        self.file_share = file_share;
+        self
+    }
+
+    /// Differential hardware shadow copies
+    pub fn differential(mut self, differential: bool) -> Self {
+        self.differential = differential;
+        self
+    }
+
+    /// Plex hardware shadow copies
+    pub fn plex(mut self, plex: bool) -> Self {
+        self.plex = plex;
+        self
+    }
+
+    /// Hardware shadow copies that can be imported on another machine
+    pub fn transportable(mut self, transportable: bool) -> Self {
+        self.transportable = transportable;
+        self
+    }
+
+    /// The context with the attributes it implies: persistent shadow copies are never
+    /// auto-released, client accessible ones are persistent and file share ones have no
+    /// writers. The combinations VSS rejects are errors.
+    pub fn build(&self) -> Result<SnapshotContext, VssError> {
+        let invalid = |reason: &str| {
+            VssError::InvalidArgument(format!("invalid snapshot context: {}", reason))
+        };
+        if self.differential && self.plex {
+            return Err(invalid("differential and plex shadow copies are exclusive"));
+        }
+        let mut attributes = SnapshotAttributes::empty();
+        if self.file_share {
+            if self.persistent || self.client_accessible {
+                return Err(invalid(
+                    "the shadow copies of file shares are not persistent",
+                ));
+            }
+            if self.differential || self.plex || self.transportable {
+                return Err(invalid(
+                    "the shadow copies of file shares are not hardware shadow copies",
+                ));
+            }
+            attributes |= SnapshotAttributes::FILE_SHARE | SnapshotAttributes::NO_WRITERS;
+        }
+        if self.persistent || self.client_accessible {
+            attributes |= SnapshotAttributes::PERSISTENT | SnapshotAttributes::NO_AUTO_RELEASE;
+        }
+        if self.client_accessible {
+            attributes |= SnapshotAttributes::CLIENT_ACCESSIBLE;
+        }
+        if self.no_writers {
+            attributes |= SnapshotAttributes::NO_WRITERS;
+        }
+        if self.differential {
+            attributes |= SnapshotAttributes::DIFFERENTIAL;
+        }
+        if self.plex {
+            attributes |= SnapshotAttributes::PLEX;
+        }
+        if self.transportable {
+            attributes |= SnapshotAttributes::TRANSPORTABLE;
+        }
+        Ok(SnapshotContext {
+            attributes: Some(attributes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Storage::Vss::{
+        VSS_CTX_APP_ROLLBACK, VSS_CTX_BACKUP, VSS_CTX_CLIENT_ACCESSIBLE,
+        VSS_CTX_CLIENT_ACCESSIBLE_WRITERS, VSS_CTX_FILE_SHARE_BACKUP, VSS_CTX_NAS_ROLLBACK,
+        VSS_SS_COUNT,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_attributes_names() {
+        let attributes = SnapshotAttributes::PERSISTENT | SnapshotAttributes::NO_WRITERS;
+        assert_eq!(attributes.to_string(), "Persistent No_Writers");
+        assert_eq!(
+            attributes.vshadow_names(),
+            ["Persistent", "Auto_Release", "No_Writers"]
+        );
+        assert_eq!(SnapshotAttributes::empty().to_string(), "None");
+        assert_eq!(
+            SnapshotAttributes::empty().vshadow_names(),
+            ["Auto_Release"]
+        );
+
+        // Every attribute round-trips, and so does the vshadow.exe output
+        let all = SnapshotAttributes::all();
+        assert_eq!(all.to_string().parse::<SnapshotAttributes>().unwrap(), all);
+        for (flag, _) in ATTRIBUTE_NAMES {
+            assert_eq!(
+                flag.to_string().parse::<SnapshotAttributes>().unwrap(),
+                flag
+            );
+        }
+        assert_eq!(
+            "Persistent Auto_Release No_Writers"
+                .parse::<SnapshotAttributes>()
+                .unwrap(),
+            attributes
+        );
+        assert_eq!(
+            "persistent|no_writers"
+                .parse::<SnapshotAttributes>()
+                .unwrap(),
+            attributes
+        );
+        assert_eq!(
+            "None".parse::<SnapshotAttributes>().unwrap(),
+            SnapshotAttributes::empty()
+        );
+        assert!("Persistent Bogus".parse::<SnapshotAttributes>().is_err());
+
+        assert_eq!(
+            SnapshotAttributes::from(VSS_VOLUME_SNAPSHOT_ATTRIBUTES(
+                VSS_VOLSNAP_ATTR_PLEX.0 | 0x0000_0100
+            )),
+            SnapshotAttributes::PLEX
+        );
+    }
+
+    #[test]
+    fn test_state() {
+        assert_eq!(SnapshotState::from(VSS_SS_CREATED), SnapshotState::Created);
+        assert_eq!(SnapshotState::from(VSS_SS_COUNT), SnapshotState::Unknown);
+        for (state, value, name) in STATES {
+            assert_eq!(SnapshotState::from(value), state);
+            assert_eq!(VSS_SNAPSHOT_STATE::from(state), value);
+            assert_eq!(name.parse::<SnapshotState>().unwrap(), state);
+        }
+        assert_eq!(SnapshotState::Created.to_string(), "VSS_SS_CREATED");
+        assert_eq!(
+            "postcommitted".parse::<SnapshotState>().unwrap(),
+            SnapshotState::Postcommitted
+        );
+        assert!("VSS_SS_BOGUS".parse::<SnapshotState>().is_err());
+
+        // Along the lifecycle, not the values: VSS_SS_POSTCOMMITTED comes last
+        assert!(SnapshotState::Postcommitted < SnapshotState::ProcessingPrefinalcommit);
+        assert!(SnapshotState::Preparing < SnapshotState::Created);
+        assert!(SnapshotState::Committed.is_in_progress());
+        assert!(!SnapshotState::Created.is_in_progress());
+        assert!(!SnapshotState::Unknown.is_in_progress());
+    }
+
+    #[test]
+    fn test_context_builder() {
+        let build = |builder: SnapshotContextBuilder| builder.build().unwrap().value();
+        assert_eq!(build(SnapshotContext::builder()), VSS_CTX_BACKUP);
+        assert_eq!(
+            build(SnapshotContext::builder().persistent(true)),
+            VSS_CTX_APP_ROLLBACK
+        );
+        assert_eq!(
+            build(SnapshotContext::builder().persistent(true).no_writers(true)),
+            VSS_CTX_NAS_ROLLBACK
+        );
+        assert_eq!(
+            build(SnapshotContext::builder().client_accessible(true)),
+            VSS_CTX_CLIENT_ACCESSIBLE_WRITERS
+        );
+        assert_eq!(
+            build(
+                SnapshotContext::builder()
+                    .client_accessible(true)
+                    .no_writers(true)
+            ),
+            VSS_CTX_CLIENT_ACCESSIBLE
+        );
+
+        let file_share = SnapshotContext::builder().file_share(true).build().unwrap();
+        assert_eq!(file_share.value(), VSS_CTX_FILE_SHARE_BACKUP);
+        assert_eq!(
+            file_share.attributes(),
+            SnapshotAttributes::FILE_SHARE | SnapshotAttributes::NO_WRITERS
+        );
+        assert_eq!(file_share.to_string(), "VSS_CTX_FILE_SHARE_BACKUP");
+
+        let differential = SnapshotContext::builder()
+            .persistent(true)
+            .differential(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            differential.value().0,
+            VSS_CTX_APP_ROLLBACK.0 | VSS_VOLSNAP_ATTR_DIFFERENTIAL.0
+        );
+        assert_eq!(
+            differential.to_string(),
+            "VSS_CTX_APP_ROLLBACK (Differential)"
+        );
+
+        let transportable = SnapshotContext::builder()
+            .transportable(true)
+            .build()
+            .unwrap();
+        assert_eq!(transportable.value().0, VSS_VOLSNAP_ATTR_TRANSPORTABLE.0);
+        assert_eq!(transportable.to_string(), "VSS_CTX_BACKUP (Transportable)");
+
+        for invalid in [
+            SnapshotContext::builder().differential(true).plex(true),
+            SnapshotContext::builder().file_share(true).persistent(true),
+            SnapshotContext::builder().file_share(true).plex(true),
+            SnapshotContext::builder()
+                .file_share(true)
+                .transportable(true),
+        ] {
+            assert!(matches!(invalid.build(), Err(VssError::InvalidArgument(_))));
+        }
+    }
+
+    #[test]
+    fn test_context_try_from() {
+        for context in [
+            VSS_CTX_BACKUP,
+            VSS_CTX_FILE_SHARE_BACKUP,
+            VSS_CTX_APP_ROLLBACK,
+            VSS_CTX_NAS_ROLLBACK,
+            VSS_CTX_CLIENT_ACCESSIBLE,
+            VSS_CTX_CLIENT_ACCESSIBLE_WRITERS,
+            VSS_CTX_ALL,
+            VSS_SNAPSHOT_CONTEXT(VSS_CTX_CLIENT_ACCESSIBLE.0 | VSS_VOLSNAP_ATTR_PLEX.0),
+            VSS_SNAPSHOT_CONTEXT(VSS_VOLSNAP_ATTR_TRANSPORTABLE.0),
+            VSS_SNAPSHOT_CONTEXT(
+                VSS_CTX_APP_ROLLBACK.0
+                    | VSS_VOLSNAP_ATTR_DIFFERENTIAL.0
+                    | VSS_VOLSNAP_ATTR_TRANSPORTABLE.0,
+            ),
+        ] {
+            assert_eq!(SnapshotContext::try_from(context).unwrap().value(), context);
+        }
+        assert_eq!(
+            SnapshotContext::try_from(VSS_CTX_ALL).unwrap().to_string(),
+            "VSS_CTX_ALL"
+        );
+        for invalid in [
+            // Persistent shadow copies are never auto-released
+            VSS_VOLSNAP_ATTR_PERSISTENT.0,
+            VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0,
+            VSS_VOLSNAP_ATTR_DIFFERENTIAL.0 | VSS_VOLSNAP_ATTR_PLEX.0,
+            VSS_VOLSNAP_ATTR_TRANSPORTABLE.0 | VSS_VOLSNAP_ATTR_PERSISTENT.0,
+            0x0000_0100,
+        ] {
+            assert!(SnapshotContext::try_from(VSS_SNAPSHOT_CONTEXT(invalid)).is_err());
+        }
+    }
+}
@@ -1,51 +1,97 @@
 use chrono::{DateTime, Local, Utc};
-use std::{
-    iter::once,
-    time::{Duration, SystemTime},
+
+#[cfg(all(windows, feature = "com"))]
+use crate::{
+    error::OperationContext,
+    volume::{is_unc_path, normalize_volume_path},
 };
+use crate::{error::VssError, snapshot::SnapshotAttributes};
+#[cfg(all(windows, feature = "com"))]
+use std::iter::once;
+use std::time::{Duration, SystemTime};
 use windows::{
-    core::PCWSTR,
-    Win32::Storage::{
-        FileSystem::{GetVolumeNameForVolumeMountPointW, GetVolumePathNameW},
-        Vss::{
-            VSS_SNAPSHOT_STATE, VSS_SS_ABORTED, VSS_SS_COMMITTED, VSS_SS_COUNT, VSS_SS_CREATED,
-            VSS_SS_DELETED, VSS_SS_POSTCOMMITTED, VSS_SS_PRECOMMITTED, VSS_SS_PREFINALCOMMITTED,
-            VSS_SS_PREPARED, VSS_SS_PREPARING, VSS_SS_PROCESSING_COMMIT,
-            VSS_SS_PROCESSING_POSTCOMMIT, VSS_SS_PROCESSING_POSTFINALCOMMIT,
-            VSS_SS_PROCESSING_PRECOMMIT, VSS_SS_PROCESSING_PREFINALCOMMIT,
-            VSS_SS_PROCESSING_PREPARE, VSS_VOLSNAP_ATTR_AUTORECOVER,
-            VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE, VSS_VOLSNAP_ATTR_DELAYED_POSTSNAPSHOT,
-            VSS_VOLSNAP_ATTR_DIFFERENTIAL, VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY,
-            VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY, VSS_VOLSNAP_ATTR_FILE_SHARE,
-            VSS_VOLSNAP_ATTR_HARDWARE_ASSISTED, VSS_VOLSNAP_ATTR_IMPORTED,
-            VSS_VOLSNAP_ATTR_NOT_SURFACED, VSS_VOLSNAP_ATTR_NOT_TRANSACTED,
-            VSS_VOLSNAP_ATTR_NO_AUTORECOVERY, VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE,
-            VSS_VOLSNAP_ATTR_NO_WRITERS, VSS_VOLSNAP_ATTR_PERSISTENT, VSS_VOLSNAP_ATTR_PLEX,
-            VSS_VOLSNAP_ATTR_ROLLBACK_RECOVERY, VSS_VOLSNAP_ATTR_TRANSPORTABLE,
-            VSS_VOLSNAP_ATTR_TXF_RECOVERY,
-        },
+    core::GUID,
+    Win32::Storage::Vss::{
+        VSS_SNAPSHOT_STATE, VSS_SS_ABORTED, VSS_SS_COMMITTED, VSS_SS_COUNT, VSS_SS_CREATED,
+        VSS_SS_DELETED, VSS_SS_POSTCOMMITTED, VSS_SS_PRECOMMITTED, VSS_SS_PREFINALCOMMITTED,
+        VSS_SS_PREPARED, VSS_SS_PREPARING, VSS_SS_PROCESSING_COMMIT, VSS_SS_PROCESSING_POSTCOMMIT,
+        VSS_SS_PROCESSING_POSTFINALCOMMIT, VSS_SS_PROCESSING_PRECOMMIT,
+        VSS_SS_PROCESSING_PREFINALCOMMIT, VSS_SS_PROCESSING_PREPARE, VSS_WRITER_STATE,
+        VSS_WS_FAILED_AT_BACKUPSHUTDOWN, VSS_WS_FAILED_AT_BACKUP_COMPLETE, VSS_WS_FAILED_AT_FREEZE,
+        VSS_WS_FAILED_AT_IDENTIFY, VSS_WS_FAILED_AT_POST_RESTORE, VSS_WS_FAILED_AT_POST_SNAPSHOT,
+        VSS_WS_FAILED_AT_PREPARE_BACKUP, VSS_WS_FAILED_AT_PREPARE_SNAPSHOT,
+        VSS_WS_FAILED_AT_PRE_RESTORE, VSS_WS_FAILED_AT_THAW, VSS_WS_STABLE,
+        VSS_WS_WAITING_FOR_BACKUP_COMPLETE, VSS_WS_WAITING_FOR_FREEZE,
+        VSS_WS_WAITING_FOR_POST_SNAPSHOT, VSS_WS_WAITING_FOR_THAW,
     },
 };
+#[cfg(all(windows, feature = "com"))]
+use windows::{
+    core::PCWSTR,
+    Win32::Storage::FileSystem::{GetVolumeNameForVolumeMountPointW, GetVolumePathNameW},
+};
+
+/// The names of the snapshot states
+const SNAPSHOT_STATE_NAMES: [(VSS_SNAPSHOT_STATE, &str); 16] = [
+    (VSS_SS_PREPARING, "VSS_SS_PREPARING"),
+    (VSS_SS_PROCESSING_PREPARE, "VSS_SS_PROCESSING_PREPARE"),
+    (VSS_SS_PREPARED, "VSS_SS_PREPARED"),
+    (VSS_SS_PROCESSING_PRECOMMIT, "VSS_SS_PROCESSING_PRECOMMIT"),
+    (VSS_SS_PRECOMMITTED, "VSS_SS_PRECOMMITTED"),
+    (VSS_SS_PROCESSING_COMMIT, "VSS_SS_PROCESSING_COMMIT"),
+    (VSS_SS_COMMITTED, "VSS_SS_COMMITTED"),
+    (VSS_SS_PROCESSING_POSTCOMMIT, "VSS_SS_PROCESSING_POSTCOMMIT"),
+    (
+        VSS_SS_PROCESSING_PREFINALCOMMIT,
+        "VSS_SS_PROCESSING_PREFINALCOMMIT",
+    ),
+    (VSS_SS_PREFINALCOMMITTED, "VSS_SS_PREFINALCOMMITTED"),
+    (
+        VSS_SS_PROCESSING_POSTFINALCOMMIT,
+        "VSS_SS_PROCESSING_POSTFINALCOMMIT",
+    ),
+    (VSS_SS_CREATED, "VSS_SS_CREATED"),
+    (VSS_SS_ABORTED, "VSS_SS_ABORTED"),
+    (VSS_SS_DELETED, "VSS_SS_DELETED"),
+    (VSS_SS_POSTCOMMITTED, "VSS_SS_POSTCOMMITTED"),
+    (VSS_SS_COUNT, "VSS_SS_COUNT"),
+];
 
 pub fn get_string_for_snapshot_state(v: VSS_SNAPSHOT_STATE) -> String {
+    SNAPSHOT_STATE_NAMES
+        .iter()
+        .find(|(state, _)| *state == v)
+        .map_or("VSS_SS_UNKNOWN", |(_, name)| name)
+        .to_owned()
+}
+
+/// The snapshot state named by `get_string_for_snapshot_state`
+pub fn parse_snapshot_state(s: &str) -> Result<VSS_SNAPSHOT_STATE, VssError> {
+    SNAPSHOT_STATE_NAMES
+        .iter()
+        .find(|(_, name)| *name == s)
+        .map(|(state, _)| *state)
+        .ok_or_else(|| VssError::InvalidArgument(format!("unknown snapshot state {}", s)))
+}
+
+pub fn get_string_for_writer_state(v: VSS_WRITER_STATE) -> String {
     let res = match v {
-        VSS_SS_PREPARING => "VSS_SS_PREPARING",
-        VSS_SS_PROCESSING_PREPARE => "VSS_SS_PROCESSING_PREPARE",
-        VSS_SS_PREPARED => "VSS_SS_PREPARED",
-        VSS_SS_PROCESSING_PRECOMMIT => "VSS_SS_PROCESSING_PRECOMMIT",
-        VSS_SS_PRECOMMITTED => "VSS_SS_PRECOMMITTED",
-        VSS_SS_PROCESSING_COMMIT => "VSS_SS_PROCESSING_COMMIT",
-        VSS_SS_COMMITTED => "VSS_SS_COMMITTED",
-        VSS_SS_PROCESSING_POSTCOMMIT => "VSS_SS_PROCESSING_POSTCOMMIT",
-        VSS_SS_PROCESSING_PREFINALCOMMIT => "VSS_SS_PROCESSING_PREFINALCOMMIT",
-        VSS_SS_PREFINALCOMMITTED => "VSS_SS_PREFINALCOMMITTED",
-        VSS_SS_PROCESSING_POSTFINALCOMMIT => "VSS_SS_PROCESSING_POSTFINALCOMMIT",
-        VSS_SS_CREATED => "VSS_SS_CREATED",
-        VSS_SS_ABORTED => "VSS_SS_ABORTED",
-        VSS_SS_DELETED => "VSS_SS_DELETED",
-        VSS_SS_POSTCOMMITTED => "VSS_SS_POSTCOMMITTED",
-        VSS_SS_COUNT => "VSS_SS_COUNT",
-        _ => "VSS_SS_UNKNOWN",
+        VSS_WS_STABLE => "VSS_WS_STABLE",
+        VSS_WS_WAITING_FOR_FREEZE => "VSS_WS_WAITING_FOR_FREEZE",
+        VSS_WS_WAITING_FOR_THAW => "VSS_WS_WAITING_FOR_THAW",
+        VSS_WS_WAITING_FOR_POST_SNAPSHOT => "VSS_WS_WAITING_FOR_POST_SNAPSHOT",
+        VSS_WS_WAITING_FOR_BACKUP_COMPLETE => "VSS_WS_WAITING_FOR_BACKUP_COMPLETE",
+        VSS_WS_FAILED_AT_IDENTIFY => "VSS_WS_FAILED_AT_IDENTIFY",
+        VSS_WS_FAILED_AT_PREPARE_BACKUP => "VSS_WS_FAILED_AT_PREPARE_BACKUP",
+        VSS_WS_FAILED_AT_PREPARE_SNAPSHOT => "VSS_WS_FAILED_AT_PREPARE_SNAPSHOT",
+        VSS_WS_FAILED_AT_FREEZE => "VSS_WS_FAILED_AT_FREEZE",
+        VSS_WS_FAILED_AT_THAW => "VSS_WS_FAILED_AT_THAW",
+        VSS_WS_FAILED_AT_POST_SNAPSHOT => "VSS_WS_FAILED_AT_POST_SNAPSHOT",
+        VSS_WS_FAILED_AT_BACKUP_COMPLETE => "VSS_WS_FAILED_AT_BACKUP_COMPLETE",
+        VSS_WS_FAILED_AT_PRE_RESTORE => "VSS_WS_FAILED_AT_PRE_RESTORE",
+        VSS_WS_FAILED_AT_POST_RESTORE => "VSS_WS_FAILED_AT_POST_RESTORE",
+        VSS_WS_FAILED_AT_BACKUPSHUTDOWN => "VSS_WS_FAILED_AT_BACKUPSHUTDOWN",
+        _ => "VSS_WS_UNKNOWN",
     };
 
     res.to_owned()
@@ -66,7 +112,11 @@ pub(crate) fn i64_to_date(t: i64) -> DateTime<Utc> {
     DateTime::from(l)
 }
 
+/// Convert a null-terminated UTF-16 string, a null pointer is an empty string
 pub(crate) fn u16_to_string(ptr: *const u16) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
     let len = unsafe { (0..).take_while(|&i| *ptr.offset(i) != 0).count() };
     let slice = unsafe { std::slice::from_raw_parts(ptr, len) };
 
@@ -79,89 +129,34 @@ pub(crate) fn u16_to_string(ptr: *const u16) -> String {
     )
 }
 
-pub(crate) fn volsnap_attrs_to_str(attr: i32) -> Vec<String> {
-    let mut attrs = Vec::new();
-
-    if attr & VSS_VOLSNAP_ATTR_PERSISTENT.0 > 0 {
-        attrs.push("Persistent".to_owned());
-    }
-    if attr & VSS_VOLSNAP_ATTR_NO_AUTORECOVERY.0 > 0 {
-        attrs.push("No_AutoRecovery".to_owned());
-    }
-    if attr & VSS_VOLSNAP_ATTR_CLIENT_ACCESSIBLE.0 > 0 {
-        attrs.push("Client_accessible".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0 > 0 {
-        attrs.push("No_Auto_Release".to_owned());
-    } else {
-        attrs.push("Auto_Release".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_NO_WRITERS.0 > 0 {
-        attrs.push("No_Writers".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_TRANSPORTABLE.0 > 0 {
-        attrs.push("Transportable".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_NOT_SURFACED.0 > 0 {
-        attrs.push("Not_Surfaced".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_NOT_TRANSACTED.0 > 0 {
-        attrs.push("Not_Transacted".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_HARDWARE_ASSISTED.0 > 0 {
-        attrs.push("Hardware".to_owned());
-    }
-    if attr & VSS_VOLSNAP_ATTR_DIFFERENTIAL.0 > 0 {
-        attrs.push("Differential".to_owned());
-    }
-    if attr & VSS_VOLSNAP_ATTR_PLEX.0 > 0 {
-        attrs.push("Plex".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_IMPORTED.0 > 0 {
-        attrs.push("Imported".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY.0 > 0 {
-        attrs.push("Exposed_Locally".to_owned());
-    }
-    if attr & VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY.0 > 0 {
-        attrs.push("Exposed_Remotely".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_AUTORECOVER.0 > 0 {
-        attrs.push("Autorecover".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_ROLLBACK_RECOVERY.0 > 0 {
-        attrs.push("Rollback_Recovery".to_owned());
-    }
-
-    if attr & VSS_VOLSNAP_ATTR_DELAYED_POSTSNAPSHOT.0 > 0 {
-        attrs.push("Delayed_Postsnapshot".to_owned());
-    }
+/// Convert a null-terminated UTF-16 string that may be missing
+pub(crate) fn u16_to_option(ptr: *const u16) -> Option<String> {
+    (!ptr.is_null()).then(|| u16_to_string(ptr))
+}
 
-    if attr & VSS_VOLSNAP_ATTR_TXF_RECOVERY.0 > 0 {
-        attrs.push("Txf_Recovery".to_owned());
-    }
+/// The attribute names printed by vshadow.exe, see `SnapshotAttributes::vshadow_names`
+pub(crate) fn volsnap_attrs_to_str(attr: i32) -> Vec<&'static str> {
+    SnapshotAttributes::from_bits_truncate(attr).vshadow_names()
+}
 
-    if attr & VSS_VOLSNAP_ATTR_FILE_SHARE.0 > 0 {
-        attrs.push("File_Share".to_owned());
-    }
-    return attrs;
+/// The attributes named by `volsnap_attrs_to_str`
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) fn volsnap_attrs_from_str<S: AsRef<str>>(names: &[S]) -> Result<i32, VssError> {
+    names.iter().try_fold(0, |attrs, name| {
+        Ok(attrs | name.as_ref().parse::<SnapshotAttributes>()?.bits())
+    })
 }
 
-/// Get the unique volume name for the given path
+/// Get the unique volume name for the given path, or the share itself for a UNC path
+#[cfg(all(windows, feature = "com"))]
 #[inline]
-pub fn get_unique_volume_name_for_path(path: &str) -> ::windows::core::Result<String> {
-    assert!(path.len() > 0);
-    //todo: Add the backslash termination, if needed
+pub fn get_unique_volume_name_for_path(path: &str) -> Result<String, VssError> {
+    const OP: &str = "GetVolumeNameForVolumeMountPointW";
+    let path = normalize_volume_path(path)?;
+    // The shares are shadow copied by the file share provider, by their own name
+    if is_unc_path(&path) {
+        return Ok(path);
+    }
     let mut volume_root_path = [0; 260];
     let hr_res = unsafe {
         let file_name: Vec<u16> = path.encode_utf16().chain(once(0)).collect();
@@ -169,7 +164,7 @@ pub fn get_unique_volume_name_for_path(path: &str) -> ::windows::core::Result<St
     };
     if !hr_res.as_bool() {
         tracing::error!("failed to covert");
-        return hr_res.ok().map(|_| String::default());
+        return hr_res.ok().map(|_| String::default()).operation(OP);
     }
 
     tracing::debug!("- Path name: {}", u16_to_string(volume_root_path.as_ptr()));
@@ -183,7 +178,7 @@ pub fn get_unique_volume_name_for_path(path: &str) -> ::windows::core::Result<St
     };
     if !hr_res.as_bool() {
         tracing::error!("failed to covert");
-        return hr_res.ok().map(|_| String::default());
+        return hr_res.ok().map(|_| String::default()).operation(OP);
     }
 
     tracing::debug!(
@@ -200,7 +195,7 @@ pub fn get_unique_volume_name_for_path(path: &str) -> ::windows::core::Result<St
     };
     if !hr_res.as_bool() {
         tracing::error!("failed to covert");
-        return hr_res.ok().map(|_| String::default());
+        return hr_res.ok().map(|_| String::default()).operation(OP);
     }
     tracing::debug!(
         "-  Unique volume name: {}",
@@ -208,3 +203,79 @@ pub fn get_unique_volume_name_for_path(path: &str) -> ::windows::core::Result<St
     );
     Ok(u16_to_string(volume_unique_name.as_ptr()))
 }
+
+/// Parse a GUID written as `XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX`, with or without braces
+pub fn parse_guid(s: &str) -> Result<GUID, VssError> {
+    let invalid = || VssError::InvalidGuid(s.to_owned());
+    let trimmed = s.trim();
+    let inner = trimmed
+        .strip_prefix('{')
+        .and_then(|t| t.strip_suffix('}'))
+        .unwrap_or(trimmed);
+    let groups: Vec<&str> = inner.split('-').collect();
+    if groups.iter().map(|g| g.len()).ne([8, 4, 4, 4, 12])
+        || !groups
+            .iter()
+            .all(|g| g.bytes().all(|b| b.is_ascii_hexdigit()))
+    {
+        return Err(invalid());
+    }
+    u128::from_str_radix(&groups.concat(), 16)
+        .map(GUID::from_u128)
+        .map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Storage::Vss::{VSS_VOLSNAP_ATTR_NO_WRITERS, VSS_VOLSNAP_ATTR_PERSISTENT};
+
+    use super::*;
+
+    #[test]
+    fn test_volsnap_attrs_names() {
+        let attrs = VSS_VOLSNAP_ATTR_PERSISTENT.0 | VSS_VOLSNAP_ATTR_NO_WRITERS.0;
+        let names = volsnap_attrs_to_str(attrs);
+        assert_eq!(names, ["Persistent", "Auto_Release", "No_Writers"]);
+        assert_eq!(volsnap_attrs_from_str(&names).unwrap(), attrs);
+        assert!(volsnap_attrs_from_str(&["Bogus"]).is_err());
+
+        assert_eq!(
+            parse_snapshot_state(&get_string_for_snapshot_state(VSS_SS_CREATED)).unwrap(),
+            VSS_SS_CREATED
+        );
+        assert!(parse_snapshot_state("VSS_SS_UNKNOWN").is_err());
+    }
+
+    #[test]
+    fn test_parse_guid() {
+        let guid = GUID::from_u128(0x3808876b_c176_4e48_b7ae_04046e6cc752);
+        assert_eq!(
+            parse_guid("3808876b-c176-4e48-b7ae-04046e6cc752").unwrap(),
+            guid
+        );
+        assert_eq!(
+            parse_guid("{3808876B-C176-4E48-B7AE-04046E6CC752}").unwrap(),
+            guid
+        );
+        for bad in [
+            "",
+            "{}",
+            "3808876b-c176-4e48-b7ae",
+            "3808876bc1764e48b7ae04046e6cc752",
+            "{3808876b-c176-4e48-b7ae-04046e6cc752",
+            "3808876g-c176-4e48-b7ae-04046e6cc752",
+            "+808876b-c176-4e48-b7ae-04046e6cc752",
+        ] {
+            assert_eq!(parse_guid(bad), Err(VssError::InvalidGuid(bad.to_owned())));
+        }
+    }
+
+    #[cfg(all(windows, feature = "com"))]
+    #[test]
+    fn test_unique_volume_name_empty_path() {
+        assert!(matches!(
+            get_unique_volume_name_for_path(""),
+            Err(VssError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,363 @@
+//! Volume paths given by the user and the pre-flight checks of their support (check).
+//!
+//! The paths are normalized and grouped by volume in pure Rust, only the resolution of a
+//! path to its unique `\\?\Volume{GUID}\` name and `IsVolumeSupported` need the machine.
+
+use std::fmt;
+
+use crate::error::VssError;
+#[cfg(all(windows, feature = "com"))]
+use crate::utils::get_unique_volume_name_for_path;
+
+fn invalid(path: &str, reason: &str) -> VssError {
+    VssError::InvalidArgument(format!("invalid volume path {}: {}", path, reason))
+}
+
+/// The path with a single trailing backslash and its separators made backslashes,
+/// as `GetVolumeNameForVolumeMountPointW` and `AddToSnapshotSet` expect it.
+///
+/// - drive letters are uppercased: `c:` and `c:/` become `C:\`
+/// - mount points keep their case: `C:\mnt//data` becomes `C:\mnt\data\`
+/// - `\\?\` and `\\.\` prefixes are removed from drive paths, `\\?\UNC\server\share`
+///   becomes `\\server\share\`, the other prefixed paths such as the volume GUID paths
+///   are kept
+/// - UNC paths need a server and a share: `\\server\share\dir\`
+/// - relative paths, only completed by the trailing backslash, are resolved by Windows
+pub fn normalize_volume_path(path: &str) -> Result<String, VssError> {
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err(VssError::InvalidArgument("empty volume path".to_owned()));
+    }
+    let path_with_backslashes = trimmed.replace('/', "\\");
+    let mut rest = path_with_backslashes.as_str();
+
+    // The device namespace prefixes
+    let mut prefix = "";
+    for device in [r"\\?\", r"\\.\"] {
+        if let Some(stripped) = rest.strip_prefix(device) {
+            prefix = device;
+            rest = stripped;
+        }
+    }
+    let has_drive = |s: &str| {
+        let bytes = s.as_bytes();
+        bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+    };
+    let (root, rest) = if !prefix.is_empty() {
+        if let Some(unc) = strip_prefix_ignore_case(rest, r"UNC\") {
+            (r"\\".to_owned(), unc_share(path, unc)?)
+        } else if has_drive(rest) {
+            drive(path, rest)?
+        } else {
+            (format!(r"\\?\{}", first_component(rest)), skip_first(rest))
+        }
+    } else if let Some(unc) = rest.strip_prefix(r"\\") {
+        (r"\\".to_owned(), unc_share(path, unc)?)
+    } else if has_drive(rest) {
+        drive(path, rest)?
+    } else if rest.starts_with('\\') {
+        return Err(invalid(path, "a path from the root needs a drive letter"));
+    } else {
+        (String::new(), rest)
+    };
+
+    let mut normalized = root;
+    for component in rest.split('\\').filter(|c| !c.is_empty()) {
+        if !normalized.is_empty() && !normalized.ends_with('\\') {
+            normalized.push('\\');
+        }
+        normalized.push_str(component);
+    }
+    if !normalized.ends_with('\\') {
+        normalized.push('\\');
+    }
+    Ok(normalized)
+}
+
+/// Whether the normalized path is on a file share, `\\server\share\`
+pub fn is_unc_path(normalized: &str) -> bool {
+    normalized.starts_with(r"\\") && !normalized.starts_with(r"\\?\")
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    match s.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&s[prefix.len()..]),
+        _ => None,
+    }
+}
+
+fn first_component(s: &str) -> &str {
+    s.split('\\').next().unwrap_or_default()
+}
+
+fn skip_first(s: &str) -> &str {
+    s.split_once('\\').map_or("", |(_, rest)| rest)
+}
+
+/// The root `X:\` of a drive path, with the rest of the path
+fn drive<'a>(path: &str, s: &'a str) -> Result<(String, &'a str), VssError> {
+    let letter = s.as_bytes()[0].to_ascii_uppercase() as char;
+    let rest = &s[2..];
+    if !rest.is_empty() && !rest.starts_with('\\') {
+        return Err(invalid(path, "a path relative to a drive is ambiguous"));
+    }
+    Ok((format!(r"{}:\", letter), rest))
+}
+
+/// The `server\share` of a UNC path, the rest of the path stays after them
+fn unc_share<'a>(path: &str, s: &'a str) -> Result<&'a str, VssError> {
+    let mut components = s.split('\\');
+    match (components.next(), components.next()) {
+        (Some(server), Some(share)) if !server.is_empty() && !share.is_empty() => Ok(s),
+        _ => Err(invalid(
+            path,
+            r"a UNC path needs a server and a share, \\server\share",
+        )),
+    }
+}
+
+/// The resolution of paths to their unique volume names
+pub trait VolumeNames {
+    /// The unique `\\?\Volume{GUID}\` name of the volume of a normalized path,
+    /// or the share itself for a UNC path
+    fn unique_volume_name(&self, path: &str) -> Result<String, VssError>;
+}
+
+/// The volumes of the local machine
+#[cfg(all(windows, feature = "com"))]
+#[derive(Debug, Default)]
+pub struct SystemVolumeNames;
+
+#[cfg(all(windows, feature = "com"))]
+impl VolumeNames for SystemVolumeNames {
+    fn unique_volume_name(&self, path: &str) -> Result<String, VssError> {
+        get_unique_volume_name_for_path(path)
+    }
+}
+
+/// Why a volume cannot be shadow copied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unsupported {
+    /// The path is invalid or its volume could not be found
+    Unresolved(VssError),
+    /// The provider, or every provider when none was chosen, does not support the volume
+    NotSupported,
+    /// `IsVolumeSupported` failed
+    CheckFailed(VssError),
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unsupported::Unresolved(e) => write!(f, "the volume cannot be resolved: {}", e),
+            Unsupported::NotSupported => write!(f, "the provider does not support the volume"),
+            Unsupported::CheckFailed(e) => write!(f, "the support cannot be checked: {}", e),
+        }
+    }
+}
+
+/// The check of a volume, given by one or more of the paths
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeCheck {
+    /// The paths given for the volume, in their original order
+    pub paths: Vec<String>,
+    /// The unique name of the volume, `None` if it could not be resolved
+    pub volume: Option<String>,
+    pub result: Result<(), Unsupported>,
+}
+
+impl VolumeCheck {
+    pub fn is_supported(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl fmt::Display for VolumeCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.paths.join(", "))?;
+        if let Some(volume) = &self.volume {
+            write!(f, " ({})", volume)?;
+        }
+        match &self.result {
+            Ok(()) => write!(f, ": supported"),
+            Err(reason) => write!(f, ": not supported, {}", reason),
+        }
+    }
+}
+
+/// Resolve the paths to their volumes and group the paths of the same volume, in the order
+/// of their first path. Each path that cannot be resolved is kept apart with its error.
+pub fn resolve_volumes<N: VolumeNames>(paths: &[&str], names: &N) -> Vec<VolumeCheck> {
+    let mut checks: Vec<VolumeCheck> = Vec::new();
+    for path in paths {
+        let volume = normalize_volume_path(path).and_then(|p| names.unique_volume_name(&p));
+        let volume = match volume {
+            Ok(volume) => volume,
+            Err(e) => {
+                checks.push(VolumeCheck {
+                    paths: vec![path.to_string()],
+                    volume: None,
+                    result: Err(Unsupported::Unresolved(e)),
+                });
+                continue;
+            }
+        };
+        let same = checks.iter_mut().find(|c| {
+            c.volume
+                .as_deref()
+                .is_some_and(|v| v.eq_ignore_ascii_case(&volume))
+        });
+        match same {
+            Some(check) => check.paths.push(path.to_string()),
+            None => checks.push(VolumeCheck {
+                paths: vec![path.to_string()],
+                volume: Some(volume),
+                result: Ok(()),
+            }),
+        }
+    }
+    checks
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// C: with D: mounted on C:\mnt\d and the share \\nas\backup
+    pub(crate) struct FakeVolumeNames;
+
+    pub(crate) const C: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000c}\";
+    pub(crate) const D: &str = r"\\?\Volume{00000000-0000-0000-0000-00000000000d}\";
+
+    impl VolumeNames for FakeVolumeNames {
+        fn unique_volume_name(&self, path: &str) -> Result<String, VssError> {
+            let lower = path.to_lowercase();
+            if is_unc_path(path) {
+                return match lower.starts_with(r"\\nas\backup\") {
+                    true => Ok(r"\\nas\backup\".to_owned()),
+                    false => Err(VssError::ObjectNotFound {
+                        operation: "GetVolumePathNameW",
+                    }),
+                };
+            }
+            if lower.starts_with(r"c:\mnt\d\") || lower == D.to_lowercase() {
+                Ok(D.to_owned())
+            } else if lower.starts_with(r"c:\") || lower == C.to_lowercase() {
+                Ok(C.to_owned())
+            } else if lower.starts_with(r"d:\") {
+                Ok(D.to_owned())
+            } else {
+                Err(VssError::ObjectNotFound {
+                    operation: "GetVolumePathNameW",
+                })
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize_drive_letters() {
+        let normalize = |p: &str| normalize_volume_path(p).unwrap();
+        assert_eq!(normalize("c:"), r"C:\");
+        assert_eq!(normalize(r"C:\"), r"C:\");
+        assert_eq!(normalize("c:/"), r"C:\");
+        assert_eq!(normalize(r" D:\\ "), r"D:\");
+        assert_eq!(normalize(r"\\?\c:\"), r"C:\");
+        assert_eq!(normalize(r"\\.\E:"), r"E:\");
+        assert!(normalize_volume_path("C:dir").is_err());
+        assert!(normalize_volume_path("").is_err());
+        assert!(normalize_volume_path(r"\dir").is_err());
+    }
+
+    #[test]
+    fn test_normalize_mount_points() {
+        let normalize = |p: &str| normalize_volume_path(p).unwrap();
+        assert_eq!(normalize(r"c:\Mnt\Data"), r"C:\Mnt\Data\");
+        assert_eq!(normalize(r"C:\mnt\data\"), r"C:\mnt\data\");
+        assert_eq!(normalize("C:/mnt//data/"), r"C:\mnt\data\");
+        assert_eq!(normalize("data"), r"data\");
+        assert_eq!(
+            normalize(r"\\?\Volume{00000000-0000-0000-0000-00000000000c}"),
+            C
+        );
+        assert_eq!(normalize(C), C);
+    }
+
+    #[test]
+    fn test_normalize_unc_paths() {
+        let normalize = |p: &str| normalize_volume_path(p).unwrap();
+        assert_eq!(normalize(r"\\nas\backup"), r"\\nas\backup\");
+        assert_eq!(normalize(r"\\nas\backup\dir\"), r"\\nas\backup\dir\");
+        assert_eq!(normalize("//nas/backup"), r"\\nas\backup\");
+        assert_eq!(normalize(r"\\?\UNC\nas\backup"), r"\\nas\backup\");
+        assert_eq!(normalize(r"\\?\unc\nas\backup\"), r"\\nas\backup\");
+        assert!(is_unc_path(&normalize(r"\\nas\backup")));
+        assert!(!is_unc_path(C));
+        assert!(!is_unc_path(r"C:\"));
+        assert!(normalize_volume_path(r"\\nas").is_err());
+        assert!(normalize_volume_path(r"\\nas\").is_err());
+        assert!(normalize_volume_path(r"\\?\UNC\nas").is_err());
+    }
+
+    #[test]
+    fn test_resolve_volumes() {
+        let checks = resolve_volumes(
+            &[
+                "c:",
+                r"D:\",
+                r"C:\Windows",
+                r"C:\mnt\d",
+                r"\\nas\backup",
+                r"Z:\",
+                "C:relative",
+                r"//NAS/Backup/",
+            ],
+            &FakeVolumeNames,
+        );
+        let summary = checks
+            .iter()
+            .map(|c| (c.paths.clone(), c.volume.clone(), c.is_supported()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    vec!["c:".to_owned(), r"C:\Windows".to_owned()],
+                    Some(C.to_owned()),
+                    true
+                ),
+                (
+                    vec![r"D:\".to_owned(), r"C:\mnt\d".to_owned()],
+                    Some(D.to_owned()),
+                    true
+                ),
+                (
+                    vec![r"\\nas\backup".to_owned(), r"//NAS/Backup/".to_owned()],
+                    Some(r"\\nas\backup\".to_owned()),
+                    true
+                ),
+                (vec![r"Z:\".to_owned()], None, false),
+                (vec!["C:relative".to_owned()], None, false),
+            ]
+        );
+        assert!(matches!(
+            &checks[4].result,
+            Err(Unsupported::Unresolved(VssError::InvalidArgument(_)))
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        let check = VolumeCheck {
+            paths: vec!["c:".to_owned(), r"C:\Windows".to_owned()],
+            volume: Some(C.to_owned()),
+            result: Err(Unsupported::NotSupported),
+        };
+        assert_eq!(
+            check.to_string(),
+            format!(
+                r"c:, C:\Windows ({}): not supported, the provider does not support the volume",
+                C
+            )
+        );
+    }
+}
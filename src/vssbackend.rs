@@ -0,0 +1,182 @@
+use windows::{
+    core::GUID,
+    Win32::Storage::Vss::{
+        VSS_BACKUP_TYPE, VSS_FILE_RESTORE_STATUS, VSS_HARDWARE_OPTIONS, VSS_OBJECT_TYPE,
+        VSS_RESTORE_TYPE, VSS_SNAPSHOT_CONTEXT, VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+    },
+};
+
+use crate::{
+    asyncop::AsyncOptions, error::VssError, provider::Provider, restore::FileRestore,
+    revert::RevertStatusQuery, vssprop::VSSProp, writerselection::SelectedComponent,
+    writerstatus::WriterStatus,
+};
+
+/// The operations of a backup components object that `VssClient` relies on.
+///
+/// `ComBackend` forwards them to `IVssBackupComponents`, `FakeBackend` keeps
+/// snapshot sets and snapshots in memory so the client logic can run anywhere.
+pub trait VssBackend {
+    /// Create a fresh backup components object, dropping the previous one
+    fn create_backup_components(&mut self) -> Result<(), VssError>;
+
+    /// How the asynchronous operations (`IVssAsync`) are waited for: the timeout
+    /// after which they are cancelled and the token that cancels them
+    fn set_async_options(&mut self, options: AsyncOptions);
+
+    /// Initialize the backup components metadata in preparation for backup.
+    ///
+    /// - xml: the document saved with `SaveAsXML`, only used when importing transportable shadow copies.
+    fn initialize_for_backup(&self, xml: Option<&str>) -> Result<(), VssError>;
+
+    /// Initialize the backup components metadata in preparation for restore
+    fn initialize_for_restore(&self, xml: Option<&str>) -> Result<(), VssError>;
+
+    /// Set the context for subsequent shadow copy related operations
+    fn set_context(&self, context: VSS_SNAPSHOT_CONTEXT) -> Result<(), VssError>;
+
+    fn set_backup_state(
+        &self,
+        select_components: bool,
+        backup_bootable_system_state: bool,
+        backup_type: VSS_BACKUP_TYPE,
+        partial_file_support: bool,
+    ) -> Result<(), VssError>;
+
+    /// Gather the writers metadata and wait for the operation to finish.
+    /// WARNING: this call can be performed only once per backup components object!
+    fn gather_writer_metadata(&self) -> Result<(), VssError>;
+
+    fn get_writer_metadata_count(&self) -> Result<u32, VssError>;
+
+    /// The `WRITER_METADATA` document of the writer at the given index, see `get_writer_metadata_count`
+    fn get_writer_metadata(&self, index: u32) -> Result<String, VssError>;
+
+    /// Gather the status of the writers and wait for the operation to finish
+    fn gather_writer_status(&self) -> Result<(), VssError>;
+
+    fn get_writer_status_count(&self) -> Result<u32, VssError>;
+
+    /// The status of the writer at the given index, see `get_writer_status_count`
+    fn get_writer_status(&self, index: u32) -> Result<WriterStatus, VssError>;
+
+    /// Free the resources allocated by `gather_writer_status`
+    fn free_writer_status(&self) -> Result<(), VssError>;
+
+    /// Prevent the given writer instances from receiving any event
+    fn disable_writer_instances(&self, instance_ids: &[GUID]) -> Result<(), VssError>;
+
+    /// Explicitly add a component to the backup
+    fn add_component(&self, component: &SelectedComponent) -> Result<(), VssError>;
+
+    /// Define the kind of restore, after `initialize_for_restore`
+    fn set_restore_state(&self, restore_type: VSS_RESTORE_TYPE) -> Result<(), VssError>;
+
+    /// Select or deselect a component of the Backup Components document for restore
+    fn set_selected_for_restore(
+        &self,
+        component: &SelectedComponent,
+        selected: bool,
+    ) -> Result<(), VssError>;
+
+    /// Tell the writer the files of the component are restored to an alternate location
+    fn add_alternative_location_mapping(
+        &self,
+        component: &SelectedComponent,
+        file: &FileRestore,
+    ) -> Result<(), VssError>;
+
+    /// Let the writers prepare for the restore and wait for the operation to finish
+    fn pre_restore(&self) -> Result<(), VssError>;
+
+    /// Report whether all, some or none of the files of the component were restored
+    fn set_file_restore_status(
+        &self,
+        component: &SelectedComponent,
+        status: VSS_FILE_RESTORE_STATUS,
+    ) -> Result<(), VssError>;
+
+    /// Signal the writers that the restore is complete and wait for the operation to finish
+    fn post_restore(&self) -> Result<(), VssError>;
+
+    /// Create a new, empty shadow copy set and return its identifier
+    fn start_snapshot_set(&self) -> Result<GUID, VssError>;
+
+    /// Add a volume to the current shadow copy set and return the identifier of the added shadow copy.
+    /// A zeroed provider id selects the default provider.
+    fn add_to_snapshot_set(&self, volume: &str, provider_id: GUID) -> Result<GUID, VssError>;
+
+    /// Whether the provider supports shadow copies of the volume,
+    /// any provider when the provider id is zeroed
+    fn is_volume_supported(&self, volume: &str, provider_id: GUID) -> Result<bool, VssError>;
+
+    /// Let the writers prepare for the backup and wait for the operation to finish
+    fn prepare_for_backup(&self) -> Result<(), VssError>;
+
+    /// Commit all shadow copies in the current set and wait for the operation to finish
+    fn do_snapshot_set(&self) -> Result<(), VssError>;
+
+    /// Report whether the backup of the added component succeeded, after `do_snapshot_set`
+    fn set_backup_succeeded(
+        &self,
+        component: &SelectedComponent,
+        succeeded: bool,
+    ) -> Result<(), VssError>;
+
+    /// Signal the writers that the backup is complete and wait for the operation to finish
+    fn backup_complete(&self) -> Result<(), VssError>;
+
+    /// Abort the current backup, discarding the shadow copy set being created
+    fn abort_backup(&self) -> Result<(), VssError>;
+
+    /// Query all the shadow copies in the system, for the current context
+    fn query(&self) -> Result<Vec<VSSProp>, VssError>;
+
+    /// Query the registered providers
+    fn query_providers(&self) -> Result<Vec<Provider>, VssError>;
+
+    /// Delete a shadow copy or a shadow copy set, returning the number of deleted shadow copies
+    fn delete_snapshots(
+        &self,
+        id: GUID,
+        object_type: VSS_OBJECT_TYPE,
+        force: bool,
+    ) -> Result<i32, VssError>;
+
+    fn get_snapshot_properties(&self, snapshot_id: GUID) -> Result<VSSProp, VssError>;
+
+    /// Import the transportable shadow copies of the document given at initialization
+    /// and wait for the operation to finish
+    fn import_snapshots(&self) -> Result<(), VssError>;
+
+    /// The Backup Components document, `SaveAsXML`. It describes the selected components
+    /// and, once created, the shadow copy set.
+    fn save_as_xml(&self) -> Result<String, VssError>;
+
+    /// Break the shadow copy set: VSS forgets it and its hardware shadow copies become
+    /// independent volumes. Uses `BreakSnapshotSetEx` with the given flags where available;
+    /// without it only empty flags are supported.
+    fn break_snapshot_set(&self, set_id: GUID, flags: VSS_HARDWARE_OPTIONS)
+        -> Result<(), VssError>;
+
+    /// Expose a shadow copy as a drive letter, a mounted folder or a file share,
+    /// returning the name it was exposed as.
+    ///
+    /// - path_from_root: the shared directory of the shadow copy, only for file shares
+    /// - attributes: `VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY` or `VSS_VOLSNAP_ATTR_EXPOSED_REMOTELY`
+    /// - expose: the drive letter, directory or share name, `None` to let VSS pick a share name
+    fn expose_snapshot(
+        &self,
+        snapshot_id: GUID,
+        path_from_root: Option<&str>,
+        attributes: VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+        expose: Option<&str>,
+    ) -> Result<String, VssError>;
+
+    /// Start reverting the volume of the shadow copy to it, dismounting the volume
+    /// even when it is in use if `force_dismount` is set
+    fn revert_to_snapshot(&self, snapshot_id: GUID, force_dismount: bool) -> Result<(), VssError>;
+
+    /// The status of the revert in progress on the volume, given by its unique name
+    fn query_revert_status(&self, volume: &str) -> Result<Box<dyn RevertStatusQuery>, VssError>;
+}
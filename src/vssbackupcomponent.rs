@@ -3,13 +3,14 @@ use windows::{
     Win32::{
         Foundation::BOOL,
         Storage::Vss::{
-            IVssAsync, VSS_BACKUP_TYPE, VSS_COMPONENT_TYPE, VSS_FILE_RESTORE_STATUS,
-            VSS_OBJECT_TYPE, VSS_RESTORE_TYPE, VSS_SNAPSHOT_CONTEXT, VSS_SNAPSHOT_PROP,
-            VSS_WRITER_STATE,
+            VSS_BACKUP_TYPE, VSS_COMPONENT_TYPE, VSS_FILE_RESTORE_STATUS, VSS_OBJECT_TYPE,
+            VSS_RESTORE_TYPE, VSS_SNAPSHOT_CONTEXT, VSS_SNAPSHOT_PROP, VSS_WRITER_STATE,
         },
     },
 };
 
+use crate::vssexaminewritermetadata::IVssExamineWriterMetadata;
+
 #[repr(transparent)]
 pub struct IVssBackupComponent(::windows::core::IUnknown);
 
@@ -50,14 +51,27 @@ impl IVssBackupComponent {
         )
         .ok()
     }
-    // pub AddComponent: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     instanceId: ::windows::core::GUID,
-    //     writerId: ::windows::core::GUID,
-    //     ct: VSS_COMPONENT_TYPE,
-    //     wszLogicalPath: ::windows::core::PCWSTR,
-    //     wszComponentName: ::windows::core::PCWSTR,
-    // ) -> ::windows::core::HRESULT,
+
+    /// The AddComponent method is used to explicitly add to the backup set a component
+    /// that is to be backed up.
+    pub unsafe fn AddComponent(
+        &self,
+        instanceId: ::windows::core::GUID,
+        writerId: ::windows::core::GUID,
+        ct: VSS_COMPONENT_TYPE,
+        wszLogicalPath: ::windows::core::PCWSTR,
+        wszComponentName: ::windows::core::PCWSTR,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).AddComponent)(
+            ::windows::core::Interface::as_raw(self),
+            instanceId,
+            writerId,
+            ct,
+            wszLogicalPath,
+            wszComponentName,
+        )
+        .ok()
+    }
 
     // pub AddNewTarget: unsafe extern "system" fn(
     //     this: *mut ::core::ffi::c_void,
@@ -102,15 +116,46 @@ impl IVssBackupComponent {
         .ok()
     }
 
-    // pub BackupComplete: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     ppAsync: *mut *mut IVssAsync,
-    // ) -> ::windows::core::HRESULT,
+    /// The BackupComplete method causes VSS to generate a BackupComplete event,
+    /// which signals writers that the backup process has completed.
+    pub unsafe fn BackupComplete(
+        &self,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).BackupComplete)(
+            ::windows::core::Interface::as_raw(self),
+            ppAsync,
+        )
+        .ok()
+    }
 
-    // pub BreakSnapshotSet: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     SnapshotSetId: ::windows::core::PCWSTR,
-    // ) -> ::windows::core::HRESULT,
+    /// The ImportSnapshots method imports shadow copies transported from a different machine.
+    ///
+    /// The shadow copies are described by the document given to `InitializeForBackup`.
+    pub unsafe fn ImportSnapshots(
+        &self,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).ImportSnapshots)(
+            ::windows::core::Interface::as_raw(self),
+            ppAsync,
+        )
+        .ok()
+    }
+
+    /// The BreakSnapshotSet method causes the existence of a shadow copy set to be "forgotten" by VSS.
+    ///
+    /// Only hardware shadow copies can be broken, they become independent read-only volumes.
+    pub unsafe fn BreakSnapshotSet(
+        &self,
+        SnapshotSetId: ::windows::core::GUID,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).BreakSnapshotSet)(
+            ::windows::core::Interface::as_raw(self),
+            SnapshotSetId,
+        )
+        .ok()
+    }
 
     pub unsafe fn DeleteSnapshots(
         &self,
@@ -131,19 +176,33 @@ impl IVssBackupComponent {
         .ok()
     }
 
-    // pub DisableWriterClasses: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     rgWriterClassId: *const ::windows::core::GUID,
-    //     cClassId: u32,
-    // ) -> ::windows::core::HRESULT,
+    /// The DisableWriterClasses method prevents a specific class of writers
+    /// from receiving any events.
+    pub unsafe fn DisableWriterClasses(
+        &self,
+        rgWriterClassId: &[::windows::core::GUID],
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).DisableWriterClasses)(
+            ::windows::core::Interface::as_raw(self),
+            rgWriterClassId.as_ptr(),
+            rgWriterClassId.len() as u32,
+        )
+        .ok()
+    }
 
-    // /// [in] rgWriterInstanceId: An array containing one or more writer instance identifiers.
-    // /// [in] cInstanceId: The number of entries in the rgWriterInstanceId array.
-    // pub DisableWriterInstances: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     rgWriterInstanceId: *const ::windows::core::GUID,
-    //     cInstanceId: u32,
-    // ) -> ::windows::core::HRESULT,
+    /// The DisableWriterInstances method prevents a specified instance of a writer
+    /// from receiving any events.
+    pub unsafe fn DisableWriterInstances(
+        &self,
+        rgWriterInstanceId: &[::windows::core::GUID],
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).DisableWriterInstances)(
+            ::windows::core::Interface::as_raw(self),
+            rgWriterInstanceId.as_ptr(),
+            rgWriterInstanceId.len() as u32,
+        )
+        .ok()
+    }
 
     /// Commits all shadow copies in this set simultaneously.
     pub unsafe fn DoSnapshotSet(
@@ -157,57 +216,77 @@ impl IVssBackupComponent {
         .ok()
     }
 
-    // /// The EnableWriterClasses method enables the specified writers to receive all events.
-    // ///
-    // /// - \[in\] rgWriterClassId: An array containing one or more writer class identifiers.
-    // /// - \[in\] cClassId: The number of entries in the rgWriterClassId array.
-    // pub EnableWriterClasses: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     rgWriterClassId: *const ::windows::core::GUID,
-    //     cClassId: u32,
-    // ) -> ::windows::core::HRESULT,
+    /// The EnableWriterClasses method enables the specified writers to receive all events.
+    pub unsafe fn EnableWriterClasses(
+        &self,
+        rgWriterClassId: &[::windows::core::GUID],
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).EnableWriterClasses)(
+            ::windows::core::Interface::as_raw(self),
+            rgWriterClassId.as_ptr(),
+            rgWriterClassId.len() as u32,
+        )
+        .ok()
+    }
 
-    // /// The ExposeSnapshot method exposes a shadow copy as a drive letter, mounted folder, or file share.
-    // ///
-    // /// - \[in\] SnapshotId: Shadow copy identifier.
-    // /// - \[in\] wszPathFromRoot
-    // /// - \[in\] lAttributes
-    // /// - \[in\] wszExpose
-    // /// - \[out\] pwszExposed
-    // pub ExposeSnapshot: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     SnapshotId: ::windows::core::GUID,
-    //     wszPathFromRoot: ::windows::core::PCWSTR,
-    //     lAttributes: i32,
-    //     wszExpose: ::windows::core::PCWSTR,
-    //     pwszExposed: ::windows::core::PCWSTR,
-    // ) -> ::windows::core::HRESULT,
+    /// The ExposeSnapshot method exposes a shadow copy as a drive letter, mounted folder, or file share.
+    ///
+    /// The returned name must be freed with `CoTaskMemFree`.
+    pub unsafe fn ExposeSnapshot(
+        &self,
+        SnapshotId: ::windows::core::GUID,
+        wszPathFromRoot: ::windows::core::PCWSTR,
+        lAttributes: i32,
+        wszExpose: ::windows::core::PCWSTR,
+    ) -> ::windows::core::Result<::windows::core::PWSTR> {
+        let mut result__ = ::windows::core::PWSTR::null();
+        (::windows::core::Interface::vtable(self).ExposeSnapshot)(
+            ::windows::core::Interface::as_raw(self),
+            SnapshotId,
+            wszPathFromRoot,
+            lAttributes,
+            wszExpose,
+            &mut result__,
+        )
+        .ok()
+        .map(|()| result__)
+    }
 
     // /// The FreeWriterMetadata method frees system resources allocated
     // /// when IVssBackupComponents::GatherWriterMetadata was called.
     // pub FreeWriterMetadata:
     //     unsafe extern "system" fn(this: *mut ::core::ffi::c_void) -> ::windows::core::HRESULT,
 
-    // /// The FreeWriterStatus method frees system resources
-    // /// allocated during the call to IVssBackupComponents::GatherWriterStatus.
-    // pub FreeWriterStatus:
-    //     unsafe extern "system" fn(this: *mut ::core::ffi::c_void) -> ::windows::core::HRESULT,
+    /// The FreeWriterStatus method frees system resources
+    /// allocated during the call to IVssBackupComponents::GatherWriterStatus.
+    pub unsafe fn FreeWriterStatus(&self) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).FreeWriterStatus)(
+            ::windows::core::Interface::as_raw(self),
+        )
+        .ok()
+    }
 
     pub unsafe fn GatherWriterMetadata(
         &self,
-        mut pAsync: *mut IVssAsync,
+        ppAsync: *mut *mut ::core::ffi::c_void,
     ) -> ::windows::core::Result<()> {
         (::windows::core::Interface::vtable(self).GatherWriterMetadata)(
             ::windows::core::Interface::as_raw(self),
-            ::core::mem::transmute(&mut pAsync),
+            ppAsync,
         )
         .ok()
     }
 
-    // pub GatherWriterStatus: unsafe extern "system" fn(
-    //     this: *mut ::core::ffi::c_void,
-    //     ppAsync: *mut *mut IVssAsync,
-    // ) -> ::windows::core::HRESULT,
+    pub unsafe fn GatherWriterStatus(
+        &self,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).GatherWriterStatus)(
+            ::windows::core::Interface::as_raw(self),
+            ppAsync,
+        )
+        .ok()
+    }
 
     // pub GetWriterComponentsCount: unsafe extern "system" fn(
     //     this: *mut ::core::ffi::c_void,
@@ -217,17 +296,29 @@ impl IVssBackupComponent {
     /// The InitializeForBackup method initializes the backup components metadata in preparation for backup.
     ///
     /// - \[in\] bstrXML: Optional. During imports of transported shadow copies,
-    /// this parameter must be the original document generated when creating the
-    /// saved shadow copy and saved using IVssBackupComponents::SaveAsXML.
+    ///   this parameter must be the original document generated when creating the
+    ///   saved shadow copy and saved using IVssBackupComponents::SaveAsXML.
     pub unsafe fn InitializeForBackup(
         &self,
         bstrXML: ::windows::core::BSTR,
     ) -> ::windows::core::Result<()> {
-        let p = ::windows::core::Interface::as_raw(self);
-        let result = (::windows::core::Interface::vtable(self).InitializeForBackup)(p, bstrXML);
+        (::windows::core::Interface::vtable(self).InitializeForBackup)(
+            ::windows::core::Interface::as_raw(self),
+            bstrXML,
+        )
+        .ok()
+    }
 
-        println!("result in InitializeForBackup: {:018p} {}", p, result.0);
-        result.ok()
+    /// The SaveAsXML method saves the Backup Components Document, describing the
+    /// components and the shadow copy sets of the backup, as an XML string.
+    pub unsafe fn SaveAsXML(&self) -> ::windows::core::Result<BSTR> {
+        let mut pbstrXML = BSTR::new();
+        (::windows::core::Interface::vtable(self).SaveAsXML)(
+            ::windows::core::Interface::as_raw(self),
+            &mut pbstrXML,
+        )
+        .ok()?;
+        Ok(pbstrXML)
     }
 
     pub unsafe fn SetContext(&self, lContext: VSS_SNAPSHOT_CONTEXT) -> ::windows::core::Result<()> {
@@ -266,6 +357,29 @@ impl IVssBackupComponent {
         .ok()
     }
 
+    /// Indicate whether the backup of the component succeeded, after `DoSnapshotSet`
+    /// and before `BackupComplete`
+    pub unsafe fn SetBackupSucceeded(
+        &self,
+        instanceId: ::windows::core::GUID,
+        writerId: ::windows::core::GUID,
+        ct: VSS_COMPONENT_TYPE,
+        wszLogicalPath: ::windows::core::PCWSTR,
+        wszComponentName: ::windows::core::PCWSTR,
+        bSucceded: bool,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).SetBackupSucceeded)(
+            ::windows::core::Interface::as_raw(self),
+            instanceId,
+            writerId,
+            ct,
+            wszLogicalPath,
+            wszComponentName,
+            bSucceded,
+        )
+        .ok()
+    }
+
     pub unsafe fn PrepareForBackup(
         &self,
         ppAsync: *mut *mut ::core::ffi::c_void,
@@ -301,6 +415,134 @@ impl IVssBackupComponent {
         .ok()
     }
 
+    /// The SetRestoreState method defines an overall restore context for the current restore session.
+    pub unsafe fn SetRestoreState(
+        &self,
+        restoreType: VSS_RESTORE_TYPE,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).SetRestoreState)(
+            ::windows::core::Interface::as_raw(self),
+            restoreType,
+        )
+        .ok()
+    }
+
+    /// The SetSelectedForRestore method indicates whether the specified selectable component
+    /// is selected for restoration.
+    pub unsafe fn SetSelectedForRestore(
+        &self,
+        writerId: ::windows::core::GUID,
+        ct: VSS_COMPONENT_TYPE,
+        wszLogicalPath: ::windows::core::PCWSTR,
+        wszComponentName: ::windows::core::PCWSTR,
+        bSelectedForRestore: bool,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).SetSelectedForRestore)(
+            ::windows::core::Interface::as_raw(self),
+            writerId,
+            ct,
+            wszLogicalPath,
+            wszComponentName,
+            bSelectedForRestore,
+        )
+        .ok()
+    }
+
+    /// The PreRestore method will cause VSS to generate a PreRestore event,
+    /// signaling writers to prepare for an upcoming restore operation.
+    pub unsafe fn PreRestore(
+        &self,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).PreRestore)(
+            ::windows::core::Interface::as_raw(self),
+            ppAsync,
+        )
+        .ok()
+    }
+
+    /// The PostRestore method will cause VSS to generate a PostRestore event,
+    /// signaling writers that the current restore operation has finished.
+    pub unsafe fn PostRestore(
+        &self,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).PostRestore)(
+            ::windows::core::Interface::as_raw(self),
+            ppAsync,
+        )
+        .ok()
+    }
+
+    /// The SetFileRestoreStatus method indicates whether some, all, or no files were
+    /// successfully restored.
+    pub unsafe fn SetFileRestoreStatus(
+        &self,
+        writerId: ::windows::core::GUID,
+        ct: VSS_COMPONENT_TYPE,
+        wszLogicalPath: ::windows::core::PCWSTR,
+        wszComponentName: ::windows::core::PCWSTR,
+        status: VSS_FILE_RESTORE_STATUS,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).SetFileRestoreStatus)(
+            ::windows::core::Interface::as_raw(self),
+            writerId,
+            ct,
+            wszLogicalPath,
+            wszComponentName,
+            status,
+        )
+        .ok()
+    }
+
+    /// The RevertToSnapshot method reverts a volume to a previous shadow copy.
+    ///
+    /// Only persistent, non client-accessible shadow copies can be reverted to.
+    pub unsafe fn RevertToSnapshot(
+        &self,
+        SnapshotId: ::windows::core::GUID,
+        bForceDismount: BOOL,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).RevertToSnapshot)(
+            ::windows::core::Interface::as_raw(self),
+            SnapshotId,
+            bForceDismount,
+        )
+        .ok()
+    }
+
+    /// The QueryRevertStatus method returns an IVssAsync interface
+    /// to query the status of a revert operation on the volume.
+    pub unsafe fn QueryRevertStatus(
+        &self,
+        pwszVolume: ::windows::core::PCWSTR,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).QueryRevertStatus)(
+            ::windows::core::Interface::as_raw(self),
+            pwszVolume,
+            ppAsync,
+        )
+        .ok()
+    }
+
+    /// The IsVolumeSupported method determines whether the specified provider,
+    /// or any provider when GUID_NULL, supports shadow copies on the volume.
+    pub unsafe fn IsVolumeSupported(
+        &self,
+        ProviderId: ::windows::core::GUID,
+        pwszVolumeName: ::windows::core::PCWSTR,
+        pbSupportedByThisProvider: &mut BOOL,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).IsVolumeSupported)(
+            ::windows::core::Interface::as_raw(self),
+            ProviderId,
+            pwszVolumeName,
+            pbSupportedByThisProvider,
+        )
+        .ok()
+    }
+
     pub unsafe fn Query(
         &self,
         QueriedObjectId: ::windows::core::GUID,
@@ -327,6 +569,52 @@ impl IVssBackupComponent {
         )
         .ok()
     }
+
+    pub unsafe fn GetWriterStatusCount(&self, pcWriters: &mut u32) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).GetWriterStatusCount)(
+            ::windows::core::Interface::as_raw(self),
+            pcWriters,
+        )
+        .ok()
+    }
+
+    /// Return the status of the writer at the given index, see `GetWriterStatusCount`
+    pub unsafe fn GetWriterStatus(
+        &self,
+        iWriter: u32,
+        pidInstance: &mut ::windows::core::GUID,
+        pidWriter: &mut ::windows::core::GUID,
+        pbstrWriter: &mut BSTR,
+        pnStatus: &mut VSS_WRITER_STATE,
+        phResultFailure: &mut HRESULT,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).GetWriterStatus)(
+            ::windows::core::Interface::as_raw(self),
+            iWriter,
+            pidInstance,
+            pidWriter,
+            pbstrWriter,
+            pnStatus,
+            phResultFailure,
+        )
+        .ok()
+    }
+
+    /// Return the metadata of the writer at the given index, see `GetWriterMetadataCount`
+    pub unsafe fn GetWriterMetadata(
+        &self,
+        iWriter: u32,
+        pidInstance: &mut ::windows::core::GUID,
+    ) -> ::windows::core::Result<IVssExamineWriterMetadata> {
+        let mut result__ = ::windows::core::zeroed::<IVssExamineWriterMetadata>();
+        (::windows::core::Interface::vtable(self).GetWriterMetadata)(
+            ::windows::core::Interface::as_raw(self),
+            iWriter,
+            pidInstance,
+            &mut result__,
+        )
+        .from_abi(result__)
+    }
 }
 
 #[link(name = "vssapi")]
@@ -334,18 +622,20 @@ extern "system" {
     fn CreateVssBackupComponentsInternal(
         ppwriter: *mut *mut ::core::ffi::c_void,
     ) -> ::windows::core::HRESULT;
+    fn VssFreeSnapshotPropertiesInternal(pprop: *mut VSS_SNAPSHOT_PROP);
+}
+
+/// Free the strings of a `VSS_SNAPSHOT_PROP` returned by VSS
+#[inline]
+pub unsafe fn VssFreeSnapshotProperties(pprop: *mut VSS_SNAPSHOT_PROP) {
+    VssFreeSnapshotPropertiesInternal(pprop)
 }
 
 #[inline]
 pub unsafe fn CreateVssBackupComponents() -> ::windows::core::Result<IVssBackupComponent> {
     // ::windows_targets::link!("vssapi.dll" "system" fn CreateVssBackupComponentsInternal(ppwriter : *mut * mut::core::ffi::c_void) -> ::windows::core::HRESULT);
     let mut result__ = ::windows::core::zeroed::<IVssBackupComponent>();
-    // println!("&mut result: {:018p}", &mut result__);
     let result = CreateVssBackupComponentsInternal(&mut result__);
-    // println!("&mut result: {:018p}", &mut result__);
-    // println!("&result: {:018p}", &result__);
-    // println!("result: {:018p}", result__);
-    // println!("result value: {}", *result);
     result.from_abi(result__)
 }
 
@@ -365,7 +655,7 @@ pub struct IVssBackupComponent_Vtbl {
         ppWriter: *mut *mut ::core::ffi::c_void,
     ) -> ::windows::core::HRESULT,
 
-    pub InitializeForBackup: fn(
+    pub InitializeForBackup: unsafe extern "system" fn(
         this: *mut ::core::ffi::c_void,
         bstrXML: ::windows::core::BSTR,
     ) -> ::windows::core::HRESULT,
@@ -429,7 +719,7 @@ pub struct IVssBackupComponent_Vtbl {
 
     pub GatherWriterStatus: unsafe extern "system" fn(
         this: *mut ::core::ffi::c_void,
-        ppAsync: *mut *mut IVssAsync,
+        ppAsync: *mut *mut ::core::ffi::c_void,
     ) -> ::windows::core::HRESULT,
 
     pub GetWriterStatusCount: unsafe extern "system" fn(
@@ -514,7 +804,7 @@ pub struct IVssBackupComponent_Vtbl {
 
     pub BackupComplete: unsafe extern "system" fn(
         this: *mut ::core::ffi::c_void,
-        ppAsync: *mut *mut IVssAsync,
+        ppAsync: *mut *mut ::core::ffi::c_void,
     ) -> ::windows::core::HRESULT,
 
     pub AddAlternativeLocationMapping: unsafe extern "system" fn(
@@ -619,7 +909,7 @@ pub struct IVssBackupComponent_Vtbl {
 
     pub BreakSnapshotSet: unsafe extern "system" fn(
         this: *mut ::core::ffi::c_void,
-        SnapshotSetId: ::windows::core::PCWSTR,
+        SnapshotSetId: ::windows::core::GUID,
     ) -> ::windows::core::HRESULT,
 
     pub GetSnapshotProperties: unsafe extern "system" fn(
@@ -680,7 +970,7 @@ pub struct IVssBackupComponent_Vtbl {
         wszPathFromRoot: ::windows::core::PCWSTR,
         lAttributes: i32,
         wszExpose: ::windows::core::PCWSTR,
-        pwszExposed: ::windows::core::PCWSTR,
+        pwszExposed: *mut ::windows::core::PWSTR,
     ) -> ::windows::core::HRESULT,
 
     pub RevertToSnapshot: unsafe extern "system" fn(
@@ -729,7 +1019,8 @@ mod test {
     use windows::{
         core::{Type, BSTR, PCWSTR},
         Win32::Storage::Vss::{
-            VSS_BT_COPY, VSS_CTX_APP_ROLLBACK, VSS_CTX_BACKUP, VSS_CTX_CLIENT_ACCESSIBLE_WRITERS,
+            IVssAsync, VSS_BT_COPY, VSS_CTX_APP_ROLLBACK, VSS_CTX_BACKUP,
+            VSS_CTX_CLIENT_ACCESSIBLE_WRITERS,
         },
     };
 
@@ -750,8 +1041,9 @@ mod test {
                 ))
                 .unwrap();
 
-            let mut pAsync = IVssAsync::from_abi(::windows::core::zeroed::<IVssAsync>()).unwrap();
+            let mut pAsync = ::windows::core::zeroed::<IVssAsync>();
             vssBackup.GatherWriterMetadata(&mut pAsync).unwrap();
+            let pAsync = IVssAsync::from_abi(pAsync).unwrap();
 
             println!("Gathering metadata from writers...");
 
@@ -0,0 +1,163 @@
+use windows::{
+    core::{BSTR, HRESULT},
+    Win32::Storage::Vss::VSS_WRITER_STATE,
+};
+
+use crate::vssbackupcomponent::{IVssBackupComponent, IVssBackupComponent_Vtbl};
+
+/// `IVssBackupComponentsEx3`, available since Windows Vista.
+///
+/// Obtained with `IVssBackupComponent::cast`, only the methods the crate relies on are wrapped.
+#[repr(transparent)]
+pub struct IVssBackupComponentEx3(::windows::core::IUnknown);
+
+impl IVssBackupComponentEx3 {
+    /// Same as `IVssBackupComponent::GetWriterStatus`, with the failure reported by the application
+    pub unsafe fn GetWriterStatus(
+        &self,
+        iWriter: u32,
+        pidInstance: &mut ::windows::core::GUID,
+        pidWriter: &mut ::windows::core::GUID,
+        pbstrWriter: &mut BSTR,
+        pnStatus: &mut VSS_WRITER_STATE,
+        phrFailureWriter: &mut HRESULT,
+        phrApplication: &mut HRESULT,
+        pbstrApplicationMessage: &mut BSTR,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).GetWriterStatus)(
+            ::windows::core::Interface::as_raw(self),
+            iWriter,
+            pidInstance,
+            pidWriter,
+            pbstrWriter,
+            pnStatus,
+            phrFailureWriter,
+            phrApplication,
+            pbstrApplicationMessage,
+        )
+        .ok()
+    }
+
+    /// Same as `IVssBackupComponent::BreakSnapshotSet`, with `VSS_BREAKEX_FLAG_*` options
+    pub unsafe fn BreakSnapshotSetEx(
+        &self,
+        SnapshotSetId: ::windows::core::GUID,
+        dwBreakFlags: u32,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::Result<()> {
+        (::windows::core::Interface::vtable(self).BreakSnapshotSetEx)(
+            ::windows::core::Interface::as_raw(self),
+            SnapshotSetId,
+            dwBreakFlags,
+            ppAsync,
+        )
+        .ok()
+    }
+}
+
+#[repr(C)]
+#[doc(hidden)]
+pub struct IVssBackupComponentEx3_Vtbl {
+    pub base__: IVssBackupComponent_Vtbl,
+    // IVssBackupComponentsEx
+    pub GetWriterMetadataEx: usize,
+    pub SetSelectedForRestoreEx: usize,
+    // IVssBackupComponentsEx2
+    pub UnexposeSnapshot: usize,
+    pub SetAuthoritativeRestore: usize,
+    pub SetRollForward: usize,
+    pub SetRestoreName: usize,
+    pub BreakSnapshotSetEx: unsafe extern "system" fn(
+        this: *mut ::core::ffi::c_void,
+        SnapshotSetId: ::windows::core::GUID,
+        dwBreakFlags: u32,
+        ppAsync: *mut *mut ::core::ffi::c_void,
+    ) -> ::windows::core::HRESULT,
+    pub PreFastRecovery: usize,
+    pub FastRecovery: usize,
+    // IVssBackupComponentsEx3
+    pub GetWriterStatus: unsafe extern "system" fn(
+        this: *mut ::core::ffi::c_void,
+        iWriter: u32,
+        pidInstance: *mut ::windows::core::GUID,
+        pidWriter: *mut ::windows::core::GUID,
+        pbstrWriter: *mut BSTR,
+        pnStatus: *mut VSS_WRITER_STATE,
+        phrFailureWriter: *mut HRESULT,
+        phrApplication: *mut HRESULT,
+        pbstrApplicationMessage: *mut BSTR,
+    ) -> ::windows::core::HRESULT,
+    pub AddSnapshotToRecoverySet: usize,
+    pub RecoverSet: usize,
+    pub GetSessionId: usize,
+}
+
+impl ::core::cmp::PartialEq for IVssBackupComponentEx3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl ::core::cmp::Eq for IVssBackupComponentEx3 {}
+impl ::core::fmt::Debug for IVssBackupComponentEx3 {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        f.debug_tuple("IVssBackupComponentEx3")
+            .field(&self.0)
+            .finish()
+    }
+}
+unsafe impl ::windows::core::Interface for IVssBackupComponentEx3 {
+    type Vtable = IVssBackupComponentEx3_Vtbl;
+}
+impl ::core::clone::Clone for IVssBackupComponentEx3 {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+unsafe impl ::windows::core::ComInterface for IVssBackupComponentEx3 {
+    const IID: ::windows::core::GUID =
+        ::windows::core::GUID::from_u128(0xc191bfbc_b602_4675_8bd1_67d642f529d5);
+}
+
+::windows::imp::interface_hierarchy!(
+    IVssBackupComponentEx3,
+    ::windows::core::IUnknown,
+    IVssBackupComponent
+);
+
+#[cfg(test)]
+mod test {
+    use std::mem::{offset_of, size_of};
+
+    use super::*;
+
+    /// The index of the method at `offset` in the vtable
+    fn slot(offset: usize) -> usize {
+        offset / size_of::<usize>()
+    }
+
+    #[test]
+    fn test_vtable_slots() {
+        // As declared by vsbackup.h: 3 methods for IUnknown, 48 for IVssBackupComponents,
+        // then 2 for IVssBackupComponentsEx, 7 for Ex2 and 4 for Ex3
+        assert_eq!(
+            slot(offset_of!(IVssBackupComponent_Vtbl, QueryRevertStatus)),
+            50
+        );
+        assert_eq!(
+            slot(offset_of!(IVssBackupComponentEx3_Vtbl, GetWriterMetadataEx)),
+            51
+        );
+        assert_eq!(
+            slot(offset_of!(IVssBackupComponentEx3_Vtbl, BreakSnapshotSetEx)),
+            57
+        );
+        assert_eq!(
+            slot(offset_of!(IVssBackupComponentEx3_Vtbl, GetWriterStatus)),
+            60
+        );
+        assert_eq!(
+            slot(offset_of!(IVssBackupComponentEx3_Vtbl, GetSessionId)),
+            63
+        );
+    }
+}
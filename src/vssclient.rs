@@ -1,301 +1,779 @@
-use chrono::{DateTime, Local};
-use std::{iter::once, ptr::null_mut, time::SystemTime};
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+use chrono::Utc;
 use tracing::debug;
 use windows::{
-    core::{Interface, Type, BSTR, GUID, HRESULT},
-    Win32::{
-        Foundation::{FALSE, S_FALSE},
-        Storage::Vss::{
-            IVssAsync, IVssEnumObject, IVssExamineWriterMetadata, VSS_BT_FULL, VSS_CTX_BACKUP,
-            VSS_OBJECT_NONE, VSS_OBJECT_PROP, VSS_OBJECT_SNAPSHOT, VSS_OBJECT_SNAPSHOT_SET,
-            VSS_SNAPSHOT_CONTEXT, VSS_SNAPSHOT_PROP,
-        },
-        System::Com::{
-            CoInitialize, CoInitializeSecurity, CoUninitialize, EOAC_NONE,
-            RPC_C_AUTHN_LEVEL_PKT_PRIVACY, RPC_C_IMP_LEVEL_IDENTIFY,
-        },
+    core::GUID,
+    Win32::Storage::Vss::{
+        VSS_BACKUP_TYPE, VSS_BREAKEX_FLAG_MAKE_READ_WRITE, VSS_BREAKEX_FLAG_REVERT_IDENTITY_ALL,
+        VSS_BT_FULL, VSS_CTX_ALL, VSS_CTX_BACKUP, VSS_CTX_CLIENT_ACCESSIBLE, VSS_HARDWARE_OPTIONS,
+        VSS_OBJECT_SNAPSHOT, VSS_OBJECT_SNAPSHOT_SET, VSS_RS_ALL, VSS_RS_FAILED, VSS_RTYPE_BY_COPY,
+        VSS_SNAPSHOT_CONTEXT, VSS_SNAPSHOT_PROP, VSS_VOLSNAP_ATTR_DIFFERENTIAL,
+        VSS_VOLSNAP_ATTR_IMPORTED, VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE, VSS_VOLSNAP_ATTR_NO_WRITERS,
+        VSS_VOLSNAP_ATTR_PERSISTENT, VSS_VOLSNAP_ATTR_PLEX, VSS_VOLSNAP_ATTR_TRANSPORTABLE,
     },
 };
 
+#[cfg(feature = "serde")]
+use crate::manifest::{self, BackupManifest};
 use crate::{
-    utils::get_unique_volume_name_for_path,
-    vssbackupcomponent::{CreateVssBackupComponents, IVssBackupComponent},
+    asyncop::AsyncOptions,
+    bcd::BackupComponents,
+    error::VssError,
+    exec::CommandRunner,
+    expose::{ExposeTarget, MountPoints},
+    provider::{Provider, ProviderSelector},
+    restore::{
+        self, ComponentRestoreStatus, FileRestorer, RestoreOutcome, RestorePlan, RestoreReport,
+    },
+    retention::{self, RetentionPlan, RetentionPolicy},
+    revert::{self, RevertOperation},
+    script::script_variables,
+    snapshot::SnapshotContext,
+    volume::{self, Unsupported, VolumeCheck, VolumeNames},
+    vssbackend::VssBackend,
     vssprop::VSSProp,
+    writermetadata::WriterMetadata,
+    writerselection::{select_components, SelectedComponent, WriterSelection},
+    writerstatus::WriterStatus,
 };
+#[cfg(all(windows, feature = "com"))]
+use crate::{combackend::ComBackend, expose::SystemMountPoints, volume::SystemVolumeNames};
+
+/// How a shadow copy set is broken into standalone volumes (-b, -bw)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BreakOptions {
+    /// Make the volumes writable (-bw)
+    pub writable: bool,
+    /// Give back the volumes the disk identity of their original volume
+    pub revert_identity: bool,
+}
+
+impl BreakOptions {
+    /// The `VSS_BREAKEX_FLAG_*` flags of `BreakSnapshotSetEx`
+    pub fn flags(&self) -> VSS_HARDWARE_OPTIONS {
+        let mut flags = 0;
+        if self.writable {
+            flags |= VSS_BREAKEX_FLAG_MAKE_READ_WRITE.0;
+        }
+        if self.revert_identity {
+            flags |= VSS_BREAKEX_FLAG_REVERT_IDENTITY_ALL.0;
+        }
+        VSS_HARDWARE_OPTIONS(flags)
+    }
+}
+
+/// A shadow copy turned into a standalone volume by `VssClient::break_snapshot_set`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenVolume {
+    pub snapshot_id: GUID,
+    /// The volume the shadow copy was taken of
+    pub original_volume: String,
+    /// The device of the shadow copy before the break, the standalone volume
+    /// gets a device of its own once the set is broken
+    pub snapshot_device_name: String,
+}
+
+/// How a shadow copy set is created, mirroring the vshadow.exe creation flags
+#[derive(Debug, Clone)]
+pub struct SnapshotSetOptions {
+    /// Creates persistent shadow copies (-p)
+    pub persistent: bool,
+    /// Creates shadow copies without involving writers (-nw)
+    pub no_writers: bool,
+    /// Creates Differential Hardware shadow copies (-ad)
+    pub differential: bool,
+    /// Creates Plex Hardware shadow copies (-ap)
+    pub plex: bool,
+    /// Creates transportable shadow copies, to be imported on another machine (-t)
+    pub transportable: bool,
+    /// Creates Shadow Copies for Shared Folders, client accessible (-scsf)
+    pub client_accessible: bool,
+    /// The backup type announced to the writers
+    pub backup_type: VSS_BACKUP_TYPE,
+    /// Writers or components which must take part in the shadow copy (-wi)
+    pub included_writers: Vec<String>,
+    /// Writers or components left out of the shadow copy (-wx)
+    pub excluded_writers: Vec<String>,
+    /// The provider creating the shadow copies, chosen by VSS when `None`
+    pub provider: Option<ProviderSelector>,
+}
+
+impl Default for SnapshotSetOptions {
+    fn default() -> Self {
+        Self {
+            persistent: false,
+            no_writers: false,
+            differential: false,
+            plex: false,
+            transportable: false,
+            client_accessible: false,
+            backup_type: VSS_BT_FULL,
+            included_writers: Vec::new(),
+            excluded_writers: Vec::new(),
+            provider: None,
+        }
+    }
+}
+
+impl SnapshotSetOptions {
+    /// The context the client must be initialized with for these options
+    pub fn context(&self) -> VSS_SNAPSHOT_CONTEXT {
+        let mut context = if self.client_accessible {
+            VSS_CTX_CLIENT_ACCESSIBLE.0
+        } else {
+            let mut context = VSS_CTX_BACKUP.0;
+            if self.persistent {
+                context |= VSS_VOLSNAP_ATTR_PERSISTENT.0 | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0;
+            }
+            if self.no_writers {
+                context |= VSS_VOLSNAP_ATTR_NO_WRITERS.0;
+            }
+            context
+        };
+        if self.differential {
+            context |= VSS_VOLSNAP_ATTR_DIFFERENTIAL.0;
+        }
+        if self.plex {
+            context |= VSS_VOLSNAP_ATTR_PLEX.0;
+        }
+        if self.transportable {
+            context |= VSS_VOLSNAP_ATTR_TRANSPORTABLE.0;
+        }
+        VSS_SNAPSHOT_CONTEXT(context)
+    }
+
+    /// Whether the writers take part in the shadow copy creation
+    pub fn with_writers(&self) -> bool {
+        !self.no_writers && !self.client_accessible
+    }
+}
+
+/// A shadow copy set created by `VssClient::create_snapshot_set`
+#[derive(Debug, Clone)]
+pub struct SnapshotSet {
+    pub set_id: GUID,
+    /// The created shadow copies, in the order of the given volumes
+    pub snapshots: Vec<VSSProp>,
+}
 
-pub struct VssClient {
-    co_initialize_called: bool,
+/// The VSS requestor. The backend is the COM one by default, on Windows only.
+pub struct VssClient<
+    #[cfg(all(windows, feature = "com"))] B: VssBackend = ComBackend,
+    #[cfg(not(all(windows, feature = "com")))] B: VssBackend,
+> {
     context: VSS_SNAPSHOT_CONTEXT,
     latest_snapshot_set_id: Option<GUID>,
     during_restore: bool,
-    vss_object: Option<IVssBackupComponent>,
+    writer_metadata_gathered: bool,
+    writers: Vec<WriterMetadata>,
+    /// The components added to the backup by `select_writers`
+    components: Vec<SelectedComponent>,
+    backend: B,
 }
 
+#[cfg(all(windows, feature = "com"))]
 impl Default for VssClient {
     fn default() -> Self {
+        VssClient::with_backend(ComBackend::default())
+    }
+}
+
+impl<B: VssBackend> VssClient<B> {
+    /// Create a client on top of the given backend
+    pub fn with_backend(backend: B) -> Self {
         VssClient {
-            co_initialize_called: false,
             context: VSS_CTX_BACKUP,
             latest_snapshot_set_id: None,
             during_restore: false,
-            vss_object: None,
+            writer_metadata_gathered: false,
+            writers: Vec::new(),
+            components: Vec::new(),
+            backend,
         }
     }
-}
 
-impl Drop for VssClient {
-    fn drop(&mut self) {
-        if self.vss_object.is_some() {
-            self.vss_object.take();
-        }
+    /// The backend the client is working with
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
 
-        if self.co_initialize_called {
-            unsafe {
-                CoUninitialize();
-            }
-        }
+    /// Cancel the asynchronous operations, such as `DoSnapshotSet`, when they last
+    /// longer than the timeout or when the token of the options is cancelled
+    pub fn set_async_options(&mut self, options: AsyncOptions) {
+        self.backend.set_async_options(options);
     }
-}
 
-impl VssClient {
-    /// Initialize the COM infrastructure and the internal pointers
+    /// The context given at initialization
+    pub fn context(&self) -> VSS_SNAPSHOT_CONTEXT {
+        self.context
+    }
+
+    /// The identifier of the last shadow copy set created by this client
+    pub fn latest_snapshot_set_id(&self) -> Option<GUID> {
+        self.latest_snapshot_set_id
+    }
+
+    /// The writers enumerated by `initialize_writer_metadata`
+    pub fn writers(&self) -> &[WriterMetadata] {
+        &self.writers
+    }
+
+    /// Initialize the backend and the internal pointers
     pub fn initialize(
         &mut self,
         context: VSS_SNAPSHOT_CONTEXT,
         xml: Option<&str>,
         restore: bool,
-    ) -> ::windows::core::Result<()> {
-        unsafe {
-            // Initialize COM
-            CoInitialize(None).unwrap();
-            // Initialize COM security
-            CoInitializeSecurity(
-                None,                          //  Allow *all* VSS writers to communicate back!
-                -1,                            //  Default COM authentication service
-                None,                          //  Default COM authorization service
-                None,                          //  reserved parameter
-                RPC_C_AUTHN_LEVEL_PKT_PRIVACY, //  Strongest COM authentication level
-                RPC_C_IMP_LEVEL_IDENTIFY,      //  Minimal impersonation abilities
-                None,                          //  Default COM authentication settings
-                EOAC_NONE,                     //  No special options
-                None,                          //  Reserved parameter
-            )
-            .unwrap();
-        }
-        self.co_initialize_called = true;
-
+    ) -> Result<(), VssError> {
+        // Reject the contexts VSS does not know before creating anything
+        SnapshotContext::try_from(context)?;
         // Create the internal backup components object
-        let vss_backup = unsafe { CreateVssBackupComponents().unwrap() };
-        self.vss_object = Some(vss_backup);
+        self.backend.create_backup_components()?;
+        self.writer_metadata_gathered = false;
+        self.writers.clear();
+        self.components.clear();
         // We are during restore now?
         self.during_restore = restore;
 
         // Call either Initialize for backup or for restore
-        let xml = match xml {
-            Some(xml) => {
-                let xml = xml.encode_utf16().chain(once(0)).collect::<Vec<u16>>();
-                unsafe { BSTR::from_raw(xml.as_ptr()) }
-            }
-            None => BSTR::new(),
-        };
         if self.during_restore {
-            unsafe {
-                self.vss_object
-                    .as_ref()
-                    .unwrap()
-                    .InitializeForRestore(xml)
-                    .unwrap()
-            };
+            self.backend.initialize_for_restore(xml)?;
         } else {
-            unsafe {
-                self.vss_object
-                    .as_ref()
-                    .unwrap()
-                    .InitializeForBackup(xml)
-                    .unwrap()
-            };
+            self.backend.initialize_for_backup(xml)?;
 
             // Set the context, if different than the default context
             if context != VSS_CTX_BACKUP {
-                unsafe {
-                    self.vss_object
-                        .as_ref()
-                        .unwrap()
-                        .SetContext(context)
-                        .unwrap();
-                }
+                self.backend.set_context(context)?;
             }
         }
         // Keep the context
         self.context = context;
-
-        // Set various properties per backup components instance
-        unsafe {
-            self.vss_object
-                .as_ref()
-                .unwrap()
-                .SetBackupState(true, true, VSS_BT_FULL, false)
-                .unwrap();
-        }
         Ok(())
     }
 
     /// Gather writers metadata
-    pub fn gather_writer_metadata(&self) -> ::windows::core::Result<()> {
+    pub fn gather_writer_metadata(&mut self) -> Result<(), VssError> {
         tracing::info!("(Gathering writer metadata...)");
-        let mut p_async =
-            unsafe { IVssAsync::from_abi(::windows::core::zeroed::<IVssAsync>()).unwrap() };
-        unsafe {
-            // Gathers writer metadata
-            // WARNING: this call can be performed only once per IVssBackupComponents instance!
-            self.vss_object
-                .clone()
-                .as_ref()
-                .unwrap()
-                .GatherWriterMetadata(&mut p_async)?
-        };
-        self.wait_and_check_for_async_operation(&mut p_async)?;
+        // WARNING: this call can be performed only once per IVssBackupComponents instance!
+        self.backend.gather_writer_metadata()?;
+        self.writer_metadata_gathered = true;
         tracing::info!("Initialize writer metadata ...");
         // Initialize the internal metadata data structures
         Ok(())
     }
 
-    /// Initialize writer metadata
-    pub fn initialize_writer_metadata(&self) -> ::windows::core::Result<()> {
-        let mut cnt_writer = 0;
-        unsafe {
-            self.vss_object
-                .clone()
-                .as_ref()
-                .unwrap()
-                .GetWriterMetadataCount(&mut cnt_writer)?
+    /// Enumerate the writers and their metadata, gathering it first if needed
+    pub fn initialize_writer_metadata(&mut self) -> Result<Vec<WriterMetadata>, VssError> {
+        if !self.writer_metadata_gathered {
+            self.gather_writer_metadata()?;
+        }
+        let cnt_writer = self.backend.get_writer_metadata_count()?;
+        debug!("{} writers to examine", cnt_writer);
+        // Enumerate writers
+        self.writers = (0..cnt_writer)
+            .map(|i| {
+                let xml = self.backend.get_writer_metadata(i)?;
+                WriterMetadata::from_xml(&xml)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(self.writers.clone())
+    }
+
+    /// Gather the status of all the writers
+    pub fn gather_writer_status(&self) -> Result<Vec<WriterStatus>, VssError> {
+        tracing::info!("(Gathering writer status...)");
+        self.backend.gather_writer_status()?;
+        let cnt_writer = self.backend.get_writer_status_count()?;
+        debug!("{} writers to examine", cnt_writer);
+        let statuses = (0..cnt_writer)
+            .map(|i| self.backend.get_writer_status(i))
+            .collect::<Result<Vec<_>, _>>();
+        // Release the status even when one of them could not be read
+        self.backend.free_writer_status()?;
+        let statuses = statuses?;
+        for status in statuses.iter().filter(|s| s.is_failed()) {
+            tracing::error!(
+                "Writer {} failed ({:?}): 0x{:08X}",
+                status.writer_name,
+                status.failure_kind(),
+                status.failure.0
+            );
+        }
+        Ok(statuses)
+    }
+
+    /// Resolve the writer selectors against the writer metadata, enumerating it if needed,
+    /// then disable the excluded writers and add the selected components to the backup.
+    /// See `select_components` for the selector syntax.
+    pub fn select_writers(
+        &mut self,
+        included: &[String],
+        excluded: &[String],
+    ) -> Result<WriterSelection, VssError> {
+        if !self.writer_metadata_gathered || self.writers.is_empty() {
+            self.initialize_writer_metadata()?;
+        }
+        let selection = select_components(&self.writers, included, excluded)?;
+
+        if !selection.excluded_instances.is_empty() {
+            debug!(
+                "Disabling {} writer instances",
+                selection.excluded_instances.len()
+            );
+            self.backend
+                .disable_writer_instances(&selection.excluded_instances)?;
+        }
+        for component in selection.components.iter() {
+            debug!(
+                "- Adding component {}\\{}",
+                component.logical_path.as_deref().unwrap_or_default(),
+                component.name
+            );
+            self.backend.add_component(component)?;
+            self.components.push(component.clone());
+        }
+        Ok(selection)
+    }
+
+    /// Create a shadow copy set containing the given volumes.
+    ///
+    /// The client must have been initialized with `options.context()`.
+    /// The backup is aborted if any step fails, so no partial set is left behind.
+    /// Once the shadow copies are used, e.g. by the -exec command, the backup is
+    /// completed with `complete_backup`.
+    pub fn create_snapshot_set(
+        &mut self,
+        volumes: &[&str],
+        options: &SnapshotSetOptions,
+    ) -> Result<SnapshotSet, VssError> {
+        let provider_id = match &options.provider {
+            Some(provider) => provider.resolve(&self.list_providers()?)?,
+            None => GUID::zeroed(),
         };
+        // The components can only be selected once the backup state is set
+        self.backend
+            .set_backup_state(options.with_writers(), false, options.backup_type, false)?;
+        if options.with_writers() {
+            self.select_writers(&options.included_writers, &options.excluded_writers)?;
+        } else if !options.included_writers.is_empty() || !options.excluded_writers.is_empty() {
+            tracing::warn!("Writers are not involved, ignoring the writer selection");
+        }
 
-        for i in 0..cnt_writer {
-            // IVssExamineWriterMetadata
+        tracing::info!("Creating shadow set ...");
+        let set_id = self.backend.start_snapshot_set()?;
+        debug!("- Shadow copy set ID: {:?}", set_id);
+
+        match self.commit_snapshot_set(volumes, provider_id) {
+            Ok(snapshots) => {
+                self.latest_snapshot_set_id = Some(set_id);
+                Ok(SnapshotSet { set_id, snapshots })
+            }
+            Err(e) => {
+                tracing::error!("Error while creating the shadow copy set: {}", e);
+                tracing::info!("Aborting the backup...");
+                if let Err(abort) = self.backend.abort_backup() {
+                    tracing::error!("Error while aborting the backup: {}", abort);
+                }
+                Err(e)
+            }
         }
-        // Enumerate writers
-        Ok(())
     }
-    /// Waits for the completion of the asynchronous operation
-    pub fn wait_and_check_for_async_operation(
+
+    /// The Backup Components document of the backup, `SaveAsXML`. After
+    /// `create_snapshot_set` it describes the created shadow copy set.
+    pub fn save_as_xml(&self) -> Result<String, VssError> {
+        self.backend.save_as_xml()
+    }
+
+    /// Save the document describing the created set into the file, next to its manifest,
+    /// for a subsequent import or restore (-t, -bc). See `manifest::save_document`.
+    #[cfg(feature = "serde")]
+    pub fn save_backup_components(
+        &self,
+        path: &Path,
+        set: &SnapshotSet,
+    ) -> Result<BackupManifest, VssError> {
+        let xml = self.save_as_xml()?;
+        let manifest = BackupManifest {
+            set_id: set.set_id,
+            volumes: set
+                .snapshots
+                .iter()
+                .map(|s| s.origin_vol_name.clone())
+                .collect(),
+            context: SnapshotContext::try_from(self.context)?,
+            create_time: set
+                .snapshots
+                .first()
+                .map_or_else(Utc::now, |s| s.create_time),
+        };
+        manifest.check(&BackupComponents::from_xml(&xml)?)?;
+        tracing::info!(
+            "- Saving the Backup Components document into {} ...",
+            path.display()
+        );
+        manifest::save_document(path, &xml, &manifest)?;
+        Ok(manifest)
+    }
+
+    /// Add the volumes to the started set and commit it, returning the created shadow copies
+    fn commit_snapshot_set(
+        &self,
+        volumes: &[&str],
+        provider_id: GUID,
+    ) -> Result<Vec<VSSProp>, VssError> {
+        let mut snapshot_ids = Vec::with_capacity(volumes.len());
+        for volume in volumes {
+            tracing::info!("- Adding volume {} to the shadow set...", volume);
+            snapshot_ids.push(self.backend.add_to_snapshot_set(volume, provider_id)?);
+        }
+
+        tracing::info!("Preparing for backup ...");
+        self.backend.prepare_for_backup()?;
+
+        tracing::info!("Creating the shadow (DoSnapshotSet) ...");
+        self.backend.do_snapshot_set()?;
+
+        snapshot_ids
+            .into_iter()
+            .map(|id| self.backend.get_snapshot_properties(id))
+            .collect()
+    }
+
+    /// Complete the backup of the created set (BackupComplete), after the -exec command.
+    /// The components added to the backup are first reported as backed up, or as failed
+    /// when `succeeded` is false, e.g. when the command failed.
+    pub fn complete_backup(&self, succeeded: bool) -> Result<(), VssError> {
+        for component in self.components.iter() {
+            debug!(
+                "- Reporting the backup of {}\\{} as {}",
+                component.logical_path.as_deref().unwrap_or_default(),
+                component.name,
+                if succeeded { "succeeded" } else { "failed" }
+            );
+            self.backend.set_backup_succeeded(component, succeeded)?;
+        }
+        tracing::info!("Completing the backup (BackupComplete) ...");
+        self.backend.backup_complete()
+    }
+
+    /// Run the -exec command with the shadow copies of the set in its environment,
+    /// see `script_variables`.
+    ///
+    /// If the command fails the shadow copies are considered failed:
+    /// the non-persistent ones are released and `VssError::CommandFailed` is returned.
+    pub fn exec_with_snapshot_set<R: CommandRunner>(
         &self,
-        p_async: &mut IVssAsync,
-    ) -> ::windows::core::Result<()> {
-        debug!("(Waiting for the asynchronous operation to finish...)");
-        unsafe { p_async.Wait(u32::MAX).unwrap() };
+        set: &SnapshotSet,
+        command: &str,
+        runner: &mut R,
+    ) -> Result<(), VssError> {
+        tracing::info!("- Executing command '{}' ...", command);
+        let env = script_variables(set.set_id, &set.snapshots);
+        let result = match runner.run(command, &env) {
+            Ok(Some(0)) => return Ok(()),
+            Ok(code) => VssError::CommandFailed {
+                command: command.to_owned(),
+                code,
+            },
+            Err(e) => e,
+        };
 
-        // Check the result of the asynchronous operation
-        let mut hr_result = HRESULT::default();
-        unsafe { p_async.QueryStatus(&mut hr_result, null_mut()).unwrap() };
-        // Check if the async operation succeeded...
-        hr_result.ok()
+        tracing::error!("{}", result);
+        for snapshot in set
+            .snapshots
+            .iter()
+            .filter(|s| s.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_PERSISTENT.0 == 0)
+        {
+            if let Err(e) = self.delete_snapshot(snapshot.snapshot_id) {
+                tracing::error!(
+                    "Error while releasing the shadow copy {:?}: {}",
+                    snapshot.snapshot_id,
+                    e
+                );
+            }
+        }
+        Err(result)
     }
 
     /// Query all the shadow copies in the given set
     /// If snapshotSetID is zeroed, just query all shadow copies in the system
-    pub fn query_snapshot_set(&self, snapshot_id: GUID) -> ::windows::core::Result<Vec<VSSProp>> {
-        let mut p_ienum_snapshots = ::windows::core::zeroed::<IVssEnumObject>();
-        if snapshot_id == GUID::zeroed() {
+    pub fn query_snapshot_set(&self, snapshot_set_id: GUID) -> Result<Vec<VSSProp>, VssError> {
+        if snapshot_set_id == GUID::zeroed() {
             debug!("Querying all shadow copies in the system ...");
         } else {
             debug!(
                 "Querying all shadow copies with the SnapshotSetID, {:?}",
-                &snapshot_id
+                &snapshot_set_id
             );
         }
-        let hr_result = unsafe {
-            self.vss_object.as_ref().unwrap().Query(
-                snapshot_id,
-                VSS_OBJECT_NONE,
-                VSS_OBJECT_SNAPSHOT,
-                &mut p_ienum_snapshots,
-            )
-        };
+        let mut result = self.backend.query()?;
+        // Keep only the shadow copies of the set (if not filtered out)
+        if snapshot_set_id != GUID::zeroed() {
+            result.retain(|p| p.shadow_copy_set_id == snapshot_set_id);
+        }
 
-        println!("resutl: {:#?}", hr_result);
-        let mut result = Vec::new();
+        Ok(result)
+    }
+
+    pub fn get_snapshot_properties(&self, snapshot_id: GUID) -> Result<VSSProp, VssError> {
+        self.backend.get_snapshot_properties(snapshot_id)
+    }
+
+    /// Check that the volumes of the paths can be shadow copied by the provider, or by any
+    /// provider when `None`, before creating a shadow copy set.
+    ///
+    /// The paths of the same volume are checked once. The error is only returned when the
+    /// provider cannot be resolved, an unsupported volume is reported in its check.
+    #[cfg(all(windows, feature = "com"))]
+    pub fn check_volumes(
+        &self,
+        paths: &[&str],
+        provider: Option<&ProviderSelector>,
+    ) -> Result<Vec<VolumeCheck>, VssError> {
+        self.check_volumes_with(paths, provider, &SystemVolumeNames)
+    }
 
-        // If there are no shadow copies, just return
-        if hr_result == S_FALSE && snapshot_id == GUID::zeroed() {
-            return Ok(result);
+    /// `check_volumes` with the given resolution of the paths
+    pub fn check_volumes_with<N: VolumeNames>(
+        &self,
+        paths: &[&str],
+        provider: Option<&ProviderSelector>,
+        names: &N,
+    ) -> Result<Vec<VolumeCheck>, VssError> {
+        let provider_id = match provider {
+            Some(provider) => provider.resolve(&self.list_providers()?)?,
+            None => GUID::zeroed(),
+        };
+        let mut checks = volume::resolve_volumes(paths, names);
+        for check in checks.iter_mut() {
+            let Some(volume) = &check.volume else {
+                continue;
+            };
+            debug!("- Checking the support of {}", volume);
+            check.result = match self.backend.is_volume_supported(volume, provider_id) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(Unsupported::NotSupported),
+                Err(e) => Err(Unsupported::CheckFailed(e)),
+            };
         }
+        Ok(checks)
+    }
 
-        let p_ienum_snapshots = unsafe { IVssEnumObject::from_raw(p_ienum_snapshots) };
-        let mut props: [VSS_OBJECT_PROP; 1] = [VSS_OBJECT_PROP::default(); 1];
-        loop {
-            let mut fetched = 0;
-            // Get the next element
-            unsafe {
-                p_ienum_snapshots.Next(&mut props, &mut fetched).unwrap();
-            }
-            // We reached the end of list
-            if fetched == 0 {
-                break;
-            }
+    /// The providers registered on the machine
+    pub fn list_providers(&self) -> Result<Vec<Provider>, VssError> {
+        debug!("Querying the registered providers ...");
+        self.backend.query_providers()
+    }
+
+    /// Import the transportable shadow copies described by a Backup Components document,
+    /// saved with -t on the machine that created them, and return them.
+    ///
+    /// The document is checked before initializing the backend: it must describe at least
+    /// one transportable shadow copy.
+    pub fn import_snapshots(&mut self, xml: &str) -> Result<Vec<VSSProp>, VssError> {
+        let document = BackupComponents::from_xml(xml)?;
+        document.check_importable()?;
 
-            // Print the shadow copy (if not filtered out)
-            if snapshot_id == GUID::zeroed()
-                || unsafe { props[0].Obj.Snap.m_SnapshotId == snapshot_id }
-            {
-                let p = unsafe { props[0].Obj.Snap.clone() };
-                let p = VSSProp::from_props(&p);
-                result.push(p);
+        self.initialize(VSS_CTX_ALL, Some(xml), false)?;
+        tracing::info!("- Importing the transportable shadow copy set ...");
+        self.backend.import_snapshots()?;
+
+        let mut imported = Vec::new();
+        for snapshot in document.transportable_snapshots() {
+            let prop = self.get_snapshot_properties(snapshot.snapshot_id)?;
+            if prop.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_IMPORTED.0 == 0 {
+                tracing::warn!(
+                    "Shadow copy {:?} is not marked as imported",
+                    prop.snapshot_id
+                );
             }
+            imported.push(prop);
         }
+        Ok(imported)
+    }
 
-        Ok(result)
+    /// Initialize the backend for restoring the Backup Components document and plan the
+    /// restore against the writers running on this machine, without restoring anything.
+    pub fn plan_restore(&mut self, xml: &str) -> Result<RestorePlan, VssError> {
+        let document = BackupComponents::from_xml(xml)?;
+        self.initialize(VSS_CTX_BACKUP, Some(xml), true)?;
+        let writers = self.initialize_writer_metadata()?;
+        Ok(restore::plan_restore(&document, &writers))
     }
 
-    pub fn get_snapshot_properties(&self, snapshot_id: GUID) -> ::windows::core::Result<VSSProp> {
-        let mut prop = VSS_SNAPSHOT_PROP::default();
+    /// Restore the components of the Backup Components document.
+    ///
+    /// The components are selected for restore, then `restorer` puts the files of each one
+    /// back between `PreRestore` and `PostRestore`. A component whose files could not be
+    /// restored, or whose writer failed, is reported as failed instead of aborting the restore.
+    pub fn restore<F: FileRestorer>(
+        &mut self,
+        xml: &str,
+        restorer: &mut F,
+    ) -> Result<RestoreReport, VssError> {
+        let plan = self.plan_restore(xml)?;
+        self.backend.set_restore_state(VSS_RTYPE_BY_COPY)?;
+        for c in plan.components.iter() {
+            debug!("- Selecting component {} for restore", c.full_path());
+            self.backend.set_selected_for_restore(&c.component, true)?;
+            for file in c.files.iter().filter(|f| f.alternate_location.is_some()) {
+                self.backend
+                    .add_alternative_location_mapping(&c.component, file)?;
+            }
+        }
 
-        let res = unsafe {
-            self.vss_object
-                .as_ref()
-                .unwrap()
-                .GetSnapshotProperties(snapshot_id, &mut prop)
-        };
+        tracing::info!("(Calling PreRestore...)");
+        self.backend.pre_restore()?;
+        let mut report = RestoreReport::default();
+        for c in plan.components.iter() {
+            tracing::info!("- Restoring component {} ...", c.full_path());
+            let (status, outcome) = match restorer.restore_files(c) {
+                Ok(()) => (VSS_RS_ALL, RestoreOutcome::Restored),
+                Err(e) => {
+                    tracing::error!("Cannot restore component {}: {}", c.full_path(), e);
+                    (VSS_RS_FAILED, RestoreOutcome::Failed(e.to_string()))
+                }
+            };
+            self.backend.set_file_restore_status(&c.component, status)?;
+            report.components.push(ComponentRestoreStatus {
+                writer_id: Some(c.component.writer_id),
+                component: c.full_path(),
+                outcome,
+            });
+        }
+        tracing::info!("(Calling PostRestore...)");
+        self.backend.post_restore()?;
 
-        match res {
-            Err(e) => return Err(e),
-            Ok(_) => {
-                return Ok(VSSProp::from_props(&prop));
+        report.writers = self.gather_writer_status()?;
+        for status in report.components.iter_mut() {
+            let failed = report
+                .writers
+                .iter()
+                .find(|w| Some(w.writer_id) == status.writer_id && w.is_failed());
+            if let (Some(writer), RestoreOutcome::Restored) = (failed, &status.outcome) {
+                status.outcome = RestoreOutcome::Failed(format!(
+                    "writer {} failed ({:?})",
+                    writer.writer_name,
+                    writer.failure_kind()
+                ));
             }
         }
+        report
+            .components
+            .extend(plan.skipped.into_iter().map(|s| ComponentRestoreStatus {
+                writer_id: s.writer_id,
+                component: s.component,
+                outcome: RestoreOutcome::Skipped(s.reason),
+            }));
+        Ok(report)
+    }
+
+    /// Revert the volume of the shadow copy to it, and return the revert in progress.
+    ///
+    /// The shadow copy must be persistent, not client-accessible, and the only set
+    /// of its volume, see `revert::check_revert`. The client must have been initialized
+    /// with `VSS_CTX_ALL` so that all the shadow copies of the volume are seen.
+    pub fn revert_to_snapshot(
+        &self,
+        snapshot_id: GUID,
+        force_dismount: bool,
+    ) -> Result<RevertOperation, VssError> {
+        let snapshot = self.get_snapshot_properties(snapshot_id)?;
+        let snapshots = self.backend.query()?;
+        revert::check_revert(&snapshot, &snapshots)?;
+
+        tracing::info!(
+            "- Reverting {} to the shadow copy {:?} ...",
+            snapshot.origin_vol_name,
+            snapshot_id
+        );
+        self.backend
+            .revert_to_snapshot(snapshot_id, force_dismount)?;
+        let status = self
+            .backend
+            .query_revert_status(&snapshot.origin_vol_name)?;
+        Ok(RevertOperation::new(status, revert::DEFAULT_POLL_INTERVAL))
+    }
+
+    /// Break the shadow copy set and return the volumes its shadow copies became.
+    /// Only shadow copies of hardware providers can be broken.
+    pub fn break_snapshot_set(
+        &self,
+        set_id: GUID,
+        options: &BreakOptions,
+    ) -> Result<Vec<BrokenVolume>, VssError> {
+        // The shadow copies cannot be queried anymore once the set is broken
+        let snapshots = self.query_snapshot_set(set_id)?;
+        if snapshots.is_empty() {
+            return Err(VssError::InvalidArgument(format!(
+                "shadow copy set {:?} not found",
+                set_id
+            )));
+        }
+        tracing::info!("- Breaking shadow copy set {:?} ...", set_id);
+        self.backend.break_snapshot_set(set_id, options.flags())?;
+        Ok(snapshots
+            .into_iter()
+            .map(|s| BrokenVolume {
+                snapshot_id: s.snapshot_id,
+                original_volume: s.origin_vol_name,
+                snapshot_device_name: s.device_name,
+            })
+            .collect())
+    }
+
+    /// Expose the shadow copy on the given drive letter, directory or share,
+    /// and return its properties with `exposed_name` and `exposed_path` filled
+    #[cfg(all(windows, feature = "com"))]
+    pub fn expose_snapshot(
+        &self,
+        snapshot_id: GUID,
+        target: &ExposeTarget,
+    ) -> Result<VSSProp, VssError> {
+        self.expose_snapshot_with(snapshot_id, target, &SystemMountPoints)
+    }
+
+    /// `expose_snapshot`, validating the target against the given mount points
+    pub fn expose_snapshot_with<M: MountPoints>(
+        &self,
+        snapshot_id: GUID,
+        target: &ExposeTarget,
+        mount_points: &M,
+    ) -> Result<VSSProp, VssError> {
+        target.validate(mount_points)?;
+        let expose = target.expose();
+        tracing::info!("- Exposing shadow copy {:?} as {} ...", snapshot_id, expose);
+        let exposed = self.backend.expose_snapshot(
+            snapshot_id,
+            target.path_from_root(),
+            target.attributes(),
+            Some(&expose),
+        )?;
+        debug!("- Shadow copy exposed as {}", exposed);
+        self.get_snapshot_properties(snapshot_id)
     }
 
     /// Delete the given shadow copy
-    pub fn delete_snapshot(&self, vss_id: GUID) -> ::windows::core::Result<()> {
+    pub fn delete_snapshot(&self, vss_id: GUID) -> Result<(), VssError> {
         tracing::debug!("-Deleting shadow copy {:?}", vss_id);
-        let mut l_snapshot = 0;
-        let mut id_non_deleted_snapshot_id = GUID::default();
-        let hr_result = unsafe {
-            self.vss_object.as_ref().unwrap().DeleteSnapshots(
-                vss_id,
-                VSS_OBJECT_SNAPSHOT,
-                FALSE,
-                &mut l_snapshot,
-                &mut id_non_deleted_snapshot_id,
-            )
-        };
-
-        if hr_result.is_err() {
+        if let Err(e) = self
+            .backend
+            .delete_snapshots(vss_id, VSS_OBJECT_SNAPSHOT, false)
+        {
             tracing::debug!("Error while deleting shadow copies...");
-            tracing::debug!(
-                "-Last shadow copy that could not be deleted:{:?}",
-                id_non_deleted_snapshot_id
-            );
-            return hr_result;
+            return Err(e);
         }
         Ok(())
     }
 
     /// Delete all the shadow copies in the system
-    pub fn delete_all_snapshots(&self) -> ::windows::core::Result<()> {
+    pub fn delete_all_snapshots(&self) -> Result<(), VssError> {
         let all_snapshosts = self.query_snapshot_set(GUID::default())?;
 
-        if all_snapshosts.len() == 0 {
+        if all_snapshosts.is_empty() {
             tracing::debug!("There are no shadow copies on the system");
             return Ok(());
         }
@@ -317,55 +795,68 @@ impl VssClient {
     }
 
     /// Delete the given shadow copy set
-    pub fn delete_snapshotset(&self, set_id: GUID) -> ::windows::core::Result<()> {
+    pub fn delete_snapshotset(&self, set_id: GUID) -> Result<(), VssError> {
         tracing::debug!("- Deleting shadow copy set {:?}", set_id);
-        let mut l_snapshot = 0;
-        let mut id_non_deleted_snapshot_id = GUID::default();
-        let hr_result = unsafe {
-            self.vss_object.as_ref().unwrap().DeleteSnapshots(
-                set_id,
-                VSS_OBJECT_SNAPSHOT_SET,
-                FALSE,
-                &mut l_snapshot,
-                &mut id_non_deleted_snapshot_id,
-            )
-        };
-
-        if hr_result.is_err() {
+        if let Err(e) = self
+            .backend
+            .delete_snapshots(set_id, VSS_OBJECT_SNAPSHOT_SET, false)
+        {
             tracing::error!("Error while deleting shadow copies...");
-            tracing::error!(
-                "-Last shadow copy that could not be deleted:{:?}",
-                id_non_deleted_snapshot_id
-            );
-            return hr_result;
+            return Err(e);
         }
         Ok(())
     }
 
-    pub fn delete_oldest_snapshot(&self, vol_name: &str) -> ::windows::core::Result<()> {
-        let unique_volume = get_unique_volume_name_for_path(vol_name)?;
+    /// Delete the oldest shadow copy of the volume, if it has any
+    #[cfg(all(windows, feature = "com"))]
+    pub fn delete_oldest_snapshot(&self, vol_name: &str) -> Result<(), VssError> {
+        self.delete_oldest_snapshot_with(vol_name, &SystemVolumeNames)
+    }
+
+    /// `delete_oldest_snapshot` with the given resolution of the path
+    pub fn delete_oldest_snapshot_with<N: VolumeNames>(
+        &self,
+        vol_name: &str,
+        names: &N,
+    ) -> Result<(), VssError> {
+        let unique_volume = names.unique_volume_name(&volume::normalize_volume_path(vol_name)?)?;
 
         let all_snapshosts = self.query_snapshot_set(GUID::default())?;
 
-        if all_snapshosts.len() == 0 {
-            tracing::debug!("There are no shadow copies on the system");
-            return Ok(());
-        }
-        let mut oldest_id = GUID::default();
-        let mut oldest_time: DateTime<chrono::Utc> = DateTime::<Local>::MIN_UTC;
-        for i in all_snapshosts {
-            if i.origin_vol_name == unique_volume && i.create_time < oldest_time {
-                oldest_id = i.snapshot_id;
-                oldest_time = i.create_time;
+        match retention::oldest_snapshot(&all_snapshosts, &unique_volume) {
+            Some(oldest) => self.delete_snapshot(oldest.snapshot_id),
+            None => {
+                tracing::debug!("There are no specified shadow copies on the system");
+                Ok(())
             }
         }
+    }
 
-        if oldest_id != GUID::default() {
-            self.delete_snapshot(oldest_id)
-        } else {
-            tracing::debug!("There are no specified shadow copies on the system");
-            Ok(())
+    /// Plan the deletion of the shadow copies of the current context against the policy
+    pub fn plan_retention(&self, policy: &RetentionPolicy) -> Result<RetentionPlan, VssError> {
+        let all_snapshosts = self.query_snapshot_set(GUID::default())?;
+        Ok(retention::plan_retention(
+            &all_snapshosts,
+            policy,
+            Utc::now(),
+        ))
+    }
+
+    /// Delete the shadow copies the plan selected, and return them.
+    /// Stops at the first shadow copy that cannot be deleted.
+    pub fn prune(&self, plan: &RetentionPlan) -> Result<Vec<GUID>, VssError> {
+        let mut deleted = Vec::new();
+        for snapshot in plan.deletions() {
+            if let Err(e) = self.delete_snapshot(snapshot.snapshot_id) {
+                tracing::error!(
+                    "- Last shadow copy that could not be deleted: {:?}",
+                    snapshot.snapshot_id
+                );
+                return Err(e);
+            }
+            deleted.push(snapshot.snapshot_id);
         }
+        Ok(deleted)
     }
 }
 
@@ -392,3 +883,1052 @@ pub fn fmt_vss_snapshot_prop(
         .field("m_eStatus", &this.m_eStatus)
         .finish()
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use windows::Win32::Storage::Vss::{
+        VSS_CTX_APP_ROLLBACK, VSS_CTX_NAS_ROLLBACK, VSS_E_UNEXPECTED_PROVIDER_ERROR,
+        VSS_E_WRITERERROR_TIMEOUT, VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY, VSS_WS_FAILED_AT_FREEZE,
+    };
+
+    use super::*;
+    use crate::{
+        diffarea::VSS_SWPRV_PROVIDER_ID,
+        expose::test::FakeMountPoints,
+        fakebackend::FakeBackend,
+        provider::{set_provider_names, ProviderType},
+        restore::{
+            test::{APP_WRITER, RESTORE_DOCUMENT},
+            ComponentRestore,
+        },
+        retention::RetentionRules,
+        revert::RevertStatus,
+        volume::test::{FakeVolumeNames, C, D},
+        writermetadata::test::{SQL_WRITER, SYSTEM_WRITER},
+        writerstatus::FailureKind,
+    };
+
+    fn client_with_sets() -> (VssClient<FakeBackend>, GUID, GUID) {
+        let options = SnapshotSetOptions::default();
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(options.context(), None, false).unwrap();
+        let first = client
+            .create_snapshot_set(&["C:\\", "D:\\"], &options)
+            .unwrap()
+            .set_id;
+
+        let mut other = VssClient::with_backend(client.backend().connect());
+        other.initialize(options.context(), None, false).unwrap();
+        let second = other
+            .create_snapshot_set(&["C:\\"], &options)
+            .unwrap()
+            .set_id;
+        (client, first, second)
+    }
+
+    #[test]
+    fn test_options_context() {
+        assert_eq!(SnapshotSetOptions::default().context(), VSS_CTX_BACKUP);
+        let persistent = SnapshotSetOptions {
+            persistent: true,
+            ..Default::default()
+        };
+        assert_eq!(persistent.context(), VSS_CTX_APP_ROLLBACK);
+        let persistent_no_writers = SnapshotSetOptions {
+            persistent: true,
+            no_writers: true,
+            ..Default::default()
+        };
+        assert_eq!(persistent_no_writers.context(), VSS_CTX_NAS_ROLLBACK);
+        assert!(!persistent_no_writers.with_writers());
+        let scsf = SnapshotSetOptions {
+            client_accessible: true,
+            differential: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            scsf.context().0,
+            VSS_CTX_CLIENT_ACCESSIBLE.0 | VSS_VOLSNAP_ATTR_DIFFERENTIAL.0
+        );
+        assert!(!scsf.with_writers());
+    }
+
+    #[test]
+    fn test_initialize_rejects_invalid_context() {
+        let options = SnapshotSetOptions {
+            differential: true,
+            plex: true,
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        assert!(matches!(
+            client.initialize(options.context(), None, false),
+            Err(VssError::InvalidArgument(_))
+        ));
+        assert!(client.backend().calls().is_empty());
+    }
+
+    #[test]
+    fn test_create_snapshot_set() {
+        let options = SnapshotSetOptions {
+            persistent: true,
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(options.context(), None, false).unwrap();
+        let set = client
+            .create_snapshot_set(&["C:\\", "D:\\"], &options)
+            .unwrap();
+
+        assert_eq!(client.latest_snapshot_set_id(), Some(set.set_id));
+        assert_eq!(set.snapshots.len(), 2);
+        assert_eq!(set.snapshots[0].origin_vol_name, "C:\\");
+        assert_eq!(set.snapshots[1].origin_vol_name, "D:\\");
+        assert!(set
+            .snapshots
+            .iter()
+            .all(|s| s.shadow_copy_set_id == set.set_id
+                && s.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_PERSISTENT.0 != 0));
+
+        let calls = client.backend().calls();
+        let start = calls.iter().position(|c| c == "StartSnapshotSet").unwrap();
+        assert_eq!(
+            &calls[start..start + 5],
+            &[
+                "StartSnapshotSet",
+                "AddToSnapshotSet",
+                "AddToSnapshotSet",
+                "PrepareForBackup",
+                "DoSnapshotSet",
+            ]
+        );
+        // Completed once the shadow copies are used
+        assert!(!calls.contains(&"BackupComplete".to_owned()));
+        client.complete_backup(true).unwrap();
+        assert_eq!(client.backend().calls().last().unwrap(), "BackupComplete");
+        // Writers are involved, so their metadata is gathered first, once the backup
+        // state is set
+        let state = calls.iter().position(|c| c == "SetBackupState").unwrap();
+        let gather = calls
+            .iter()
+            .position(|c| c == "GatherWriterMetadata")
+            .unwrap();
+        assert!(state < gather && gather < start);
+        assert_eq!(calls.iter().filter(|c| *c == "SetBackupState").count(), 1);
+    }
+
+    #[test]
+    fn test_save_as_xml() {
+        let options = SnapshotSetOptions {
+            persistent: true,
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(options.context(), None, false).unwrap();
+        let set = client
+            .create_snapshot_set(&["C:\\", "D:\\"], &options)
+            .unwrap();
+
+        let document = BackupComponents::from_xml(&client.save_as_xml().unwrap()).unwrap();
+        assert_eq!(document.snapshot_sets.len(), 1);
+        let described = &document.snapshot_sets[0];
+        assert_eq!(described.snapshot_set_id, set.set_id);
+        assert_eq!(described.context, Some(options.context().0));
+        assert_eq!(
+            described
+                .snapshots
+                .iter()
+                .map(|s| (s.snapshot_id, s.original_volume_name.as_deref()))
+                .collect::<Vec<_>>(),
+            set.snapshots
+                .iter()
+                .map(|s| (s.snapshot_id, Some(s.origin_vol_name.as_str())))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(VssClient::with_backend(FakeBackend::new())
+            .save_as_xml()
+            .is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_save_backup_components() {
+        let (client, _, second) = client_with_sets();
+        let set = SnapshotSet {
+            set_id: client.latest_snapshot_set_id().unwrap(),
+            snapshots: client
+                .query_snapshot_set(client.latest_snapshot_set_id().unwrap())
+                .unwrap(),
+        };
+        assert_ne!(set.set_id, second);
+        let path = std::env::temp_dir().join(format!("vshadow-rs-bc-{}.xml", std::process::id()));
+        let saved = client.save_backup_components(&path, &set).unwrap();
+        assert_eq!(saved.set_id, set.set_id);
+        assert_eq!(saved.volumes, ["C:\\", "D:\\"]);
+        assert_eq!(saved.create_time, set.snapshots[0].create_time);
+        assert_eq!(manifest::read_manifest(&path).unwrap(), Some(saved));
+        assert_eq!(
+            manifest::load_document(&path).unwrap(),
+            client.save_as_xml().unwrap()
+        );
+
+        // The document of another set is not saved
+        let other = SnapshotSet {
+            set_id: second,
+            ..set
+        };
+        assert!(client.save_backup_components(&path, &other).is_err());
+        std::fs::remove_file(manifest::manifest_path(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_create_snapshot_set_with_provider() {
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        let hardware = Provider {
+            provider_id: GUID::from_u128(0x24e6c4f2_0ab3_4a1b_9c44_0f7d3e51a6b2),
+            name: "Contoso Hardware Provider".to_owned(),
+            provider_type: ProviderType::Hardware,
+            version: "2.1".to_owned(),
+            version_id: GUID::from_u128(1),
+            class_id: GUID::from_u128(2),
+        };
+        client.backend().add_provider(hardware.clone());
+        let mut options = SnapshotSetOptions {
+            no_writers: true,
+            provider: Some(ProviderSelector::Name(
+                "contoso hardware provider".to_owned(),
+            )),
+            ..Default::default()
+        };
+        client.initialize(options.context(), None, false).unwrap();
+
+        let providers = client.list_providers().unwrap();
+        assert_eq!(providers.len(), 2);
+        assert_eq!(providers[0].provider_id, VSS_SWPRV_PROVIDER_ID);
+        assert_eq!(providers[1], hardware);
+
+        let set = client.create_snapshot_set(&["C:\\"], &options).unwrap();
+        assert_eq!(set.snapshots[0].provider_id, hardware.provider_id);
+
+        // VSS chooses the provider by default
+        options.provider = None;
+        let mut snapshots = client
+            .create_snapshot_set(&["D:\\"], &options)
+            .unwrap()
+            .snapshots;
+        assert_eq!(snapshots[0].provider_id, VSS_SWPRV_PROVIDER_ID);
+        set_provider_names(&mut snapshots, &providers);
+        assert_eq!(
+            snapshots[0].provider_name.as_deref(),
+            Some("Microsoft Software Shadow Copy provider 1.0")
+        );
+
+        // An unknown provider is refused before starting the set
+        options.provider = Some(ProviderSelector::Id(GUID::from_u128(3)));
+        let calls = client.backend().calls().len();
+        assert!(matches!(
+            client.create_snapshot_set(&["E:\\"], &options),
+            Err(VssError::InvalidArgument(_))
+        ));
+        assert_eq!(client.backend().calls()[calls..], ["Query"]);
+    }
+
+    #[test]
+    fn test_check_volumes() {
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(VSS_CTX_BACKUP, None, false).unwrap();
+        client.backend().add_volume(C, r"C:\", 100);
+        client.backend().add_volume(D, r"D:\", 100);
+        client
+            .backend()
+            .set_volume_unsupported(D, VSS_SWPRV_PROVIDER_ID);
+
+        let checks = client
+            .check_volumes_with(
+                &["c:", r"C:\Windows", r"D:\", r"\\nas\backup", "Z:"],
+                None,
+                &FakeVolumeNames,
+            )
+            .unwrap();
+        assert_eq!(checks.len(), 4);
+        assert_eq!(checks[0].paths.len(), 2);
+        assert!(checks[0].is_supported());
+        assert_eq!(checks[1].result, Err(Unsupported::NotSupported));
+        // The share is not a volume of the fake machine
+        assert!(matches!(
+            &checks[2].result,
+            Err(Unsupported::CheckFailed(VssError::ObjectNotFound { .. }))
+        ));
+        assert!(matches!(&checks[3].result, Err(Unsupported::Unresolved(_))));
+        assert_eq!(
+            client
+                .backend()
+                .calls()
+                .iter()
+                .filter(|c| *c == "IsVolumeSupported")
+                .count(),
+            3
+        );
+
+        let system = ProviderSelector::Id(VSS_SWPRV_PROVIDER_ID);
+        let checks = client
+            .check_volumes_with(&[r"C:\mnt\d"], Some(&system), &FakeVolumeNames)
+            .unwrap();
+        assert_eq!(checks[0].volume.as_deref(), Some(D));
+        assert!(!checks[0].is_supported());
+        let unknown = ProviderSelector::Name("Contoso".to_owned());
+        assert!(client
+            .check_volumes_with(&["C:"], Some(&unknown), &FakeVolumeNames)
+            .is_err());
+    }
+
+    #[test]
+    fn test_create_snapshot_set_times_out() {
+        let options = SnapshotSetOptions {
+            no_writers: true,
+            ..Default::default()
+        };
+        let timeout = Duration::from_millis(20);
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.set_async_options(AsyncOptions {
+            timeout: Some(timeout),
+            poll_interval: Duration::from_millis(1),
+            ..Default::default()
+        });
+        client.initialize(options.context(), None, false).unwrap();
+
+        // A hung writer never lets DoSnapshotSet finish
+        client.backend().set_async_pending("DoSnapshotSet", None);
+        let err = client.create_snapshot_set(&["C:\\"], &options).unwrap_err();
+        assert_eq!(
+            err,
+            VssError::TimedOut {
+                operation: "DoSnapshotSet",
+                timeout
+            }
+        );
+        assert!(client.backend().snapshots().is_empty());
+        assert_eq!(client.backend().calls().last().unwrap(), "AbortBackup");
+
+        // A slow one finishes within the timeout
+        client.backend().set_async_pending("DoSnapshotSet", Some(3));
+        client.create_snapshot_set(&["C:\\"], &options).unwrap();
+        assert_eq!(client.backend().snapshots().len(), 1);
+    }
+
+    #[test]
+    fn test_gather_writer_metadata_cancelled() {
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        let options = AsyncOptions {
+            poll_interval: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let cancel = options.cancel.clone();
+        client.set_async_options(options);
+        client.initialize(VSS_CTX_BACKUP, None, false).unwrap();
+        client
+            .backend()
+            .set_async_pending("GatherWriterMetadata", None);
+        cancel.cancel();
+        assert_eq!(
+            client.gather_writer_metadata(),
+            Err(VssError::Cancelled {
+                operation: "GatherWriterMetadata"
+            })
+        );
+    }
+
+    #[test]
+    fn test_create_snapshot_set_aborts_on_failure() {
+        let options = SnapshotSetOptions {
+            no_writers: true,
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(options.context(), None, false).unwrap();
+        client
+            .backend()
+            .fail_next("DoSnapshotSet", VSS_E_UNEXPECTED_PROVIDER_ERROR);
+
+        let err = client.create_snapshot_set(&["C:\\"], &options).unwrap_err();
+        assert_eq!(
+            err,
+            VssError::UnexpectedProviderError {
+                operation: "DoSnapshotSet"
+            }
+        );
+        assert_eq!(client.latest_snapshot_set_id(), None);
+        assert!(client.backend().snapshots().is_empty());
+        assert_eq!(client.backend().calls().last().unwrap(), "AbortBackup");
+        assert!(!client
+            .backend()
+            .calls()
+            .contains(&"GatherWriterMetadata".to_owned()));
+
+        // The client can be used again after the abort
+        client.create_snapshot_set(&["C:\\"], &options).unwrap();
+    }
+
+    #[test]
+    fn test_create_snapshot_set_aborts_after_commit() {
+        let options = SnapshotSetOptions {
+            persistent: true,
+            no_writers: true,
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(options.context(), None, false).unwrap();
+        client
+            .backend()
+            .fail_next("GetSnapshotProperties", VSS_E_UNEXPECTED_PROVIDER_ERROR);
+
+        assert_eq!(
+            client.create_snapshot_set(&["C:\\"], &options).unwrap_err(),
+            VssError::UnexpectedProviderError {
+                operation: "GetSnapshotProperties"
+            }
+        );
+        assert_eq!(client.latest_snapshot_set_id(), None);
+        let calls = client.backend().calls();
+        assert!(calls.contains(&"DoSnapshotSet".to_owned()));
+        assert_eq!(calls.last().unwrap(), "AbortBackup");
+    }
+
+    #[test]
+    fn test_query_snapshot_set() {
+        let (client, first, second) = client_with_sets();
+        assert_eq!(client.query_snapshot_set(GUID::zeroed()).unwrap().len(), 3);
+        assert_eq!(client.query_snapshot_set(first).unwrap().len(), 2);
+        let res = client.query_snapshot_set(second).unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].origin_vol_name, "C:\\");
+    }
+
+    #[test]
+    fn test_delete() {
+        let (client, first, _) = client_with_sets();
+        client.delete_snapshotset(first).unwrap();
+        assert_eq!(client.query_snapshot_set(GUID::zeroed()).unwrap().len(), 1);
+        assert!(client.delete_snapshotset(first).is_err());
+
+        client.delete_all_snapshots().unwrap();
+        assert!(client.backend().snapshots().is_empty());
+    }
+
+    #[test]
+    fn test_delete_oldest_snapshot() {
+        // The fake shadow copies keep the volume paths as given
+        struct Paths;
+        impl VolumeNames for Paths {
+            fn unique_volume_name(&self, path: &str) -> Result<String, VssError> {
+                Ok(path.to_owned())
+            }
+        }
+        let (client, first, second) = client_with_sets();
+        client.delete_oldest_snapshot_with("c:", &Paths).unwrap();
+        let left = client.backend().snapshots();
+        assert_eq!(left.len(), 2);
+        assert!(left
+            .iter()
+            .all(|s| s.shadow_copy_set_id == second || s.origin_vol_name == "D:\\"));
+        assert_eq!(
+            client.query_snapshot_set(first).unwrap()[0].origin_vol_name,
+            "D:\\"
+        );
+
+        // A volume without shadow copies
+        client.delete_oldest_snapshot_with("E:", &Paths).unwrap();
+        assert_eq!(client.backend().snapshots().len(), 2);
+    }
+
+    #[test]
+    fn test_prune() {
+        let (client, first, second) = client_with_sets();
+        let policy = RetentionPolicy {
+            rules: RetentionRules {
+                keep_last: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let plan = client.plan_retention(&policy).unwrap();
+        assert_eq!(plan.decisions.len(), 3);
+        let deleted = client.prune(&plan).unwrap();
+        assert_eq!(deleted.len(), 1);
+        // One shadow copy of C: and the one of D: are left
+        let left = client.backend().snapshots();
+        assert_eq!(left.len(), 2);
+        assert!(left
+            .iter()
+            .all(|s| s.shadow_copy_set_id == first || s.shadow_copy_set_id == second));
+        assert!(!left.iter().any(|s| s.snapshot_id == deleted[0]));
+        assert_eq!(
+            client.plan_retention(&policy).unwrap().deletions().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_import_snapshots() {
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        let xml = include_str!("../tests/fixtures/transportable.xml");
+        let imported = client.import_snapshots(xml).unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!(
+            imported[0].shadow_copy_set_id,
+            GUID::from_u128(0x9a5cd9e1_93a4_4d18_8b2a_3f6c24b2e0a1)
+        );
+        assert!(imported
+            .iter()
+            .all(|p| p.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_IMPORTED.0 != 0));
+        assert_eq!(client.context(), VSS_CTX_ALL);
+        // The same set cannot be imported twice
+        assert!(client.import_snapshots(xml).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_import_transportable_set() {
+        let options = SnapshotSetOptions {
+            persistent: true,
+            transportable: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.context().0,
+            VSS_CTX_BACKUP.0
+                | VSS_VOLSNAP_ATTR_PERSISTENT.0
+                | VSS_VOLSNAP_ATTR_NO_AUTO_RELEASE.0
+                | VSS_VOLSNAP_ATTR_TRANSPORTABLE.0
+        );
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(options.context(), None, false).unwrap();
+        let set = client
+            .create_snapshot_set(&["C:\\", "D:\\"], &options)
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("vshadow-rs-t-{}.xml", std::process::id()));
+        client.save_backup_components(&path, &set).unwrap();
+        let xml = manifest::load_document(&path).unwrap();
+        std::fs::remove_file(manifest::manifest_path(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Imported on another machine
+        let mut other = VssClient::with_backend(FakeBackend::new());
+        let imported = other.import_snapshots(&xml).unwrap();
+        assert_eq!(
+            imported
+                .iter()
+                .map(|p| (p.snapshot_id, p.origin_vol_name.as_str()))
+                .collect::<Vec<_>>(),
+            set.snapshots
+                .iter()
+                .map(|s| (s.snapshot_id, s.origin_vol_name.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert!(imported.iter().all(|p| p.shadow_copy_set_id == set.set_id
+            && p.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_TRANSPORTABLE.0 != 0));
+    }
+
+    #[test]
+    fn test_import_refuses_non_transportable() {
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        let xml = include_str!("../tests/fixtures/not_transportable.xml");
+        assert!(matches!(
+            client.import_snapshots(xml),
+            Err(VssError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            client.import_snapshots("<WRITER_METADATA/>"),
+            Err(VssError::Xml(_))
+        ));
+        // Refused before touching the backend
+        assert!(client.backend().calls().is_empty());
+    }
+
+    #[test]
+    fn test_break_snapshot_set() {
+        let (client, first, second) = client_with_sets();
+        let options = BreakOptions {
+            writable: true,
+            ..Default::default()
+        };
+        let volumes = client.break_snapshot_set(first, &options).unwrap();
+        assert_eq!(
+            volumes
+                .iter()
+                .map(|v| v.original_volume.as_str())
+                .collect::<Vec<_>>(),
+            ["C:\\", "D:\\"]
+        );
+        assert!(volumes[0]
+            .snapshot_device_name
+            .contains("HarddiskVolumeShadowCopy"));
+        assert_eq!(
+            client.backend().broken_snapshot_sets(),
+            [(first, VSS_BREAKEX_FLAG_MAKE_READ_WRITE)]
+        );
+        // Only the other set is left
+        assert_eq!(client.query_snapshot_set(GUID::zeroed()).unwrap().len(), 1);
+        assert!(client.break_snapshot_set(first, &options).is_err());
+
+        let options = BreakOptions {
+            writable: true,
+            revert_identity: true,
+        };
+        client.break_snapshot_set(second, &options).unwrap();
+        assert_eq!(client.backend().broken_snapshot_sets()[1].1 .0, 6);
+    }
+
+    #[test]
+    fn test_expose_snapshot() {
+        let (client, first, _) = client_with_sets();
+        let snapshots = client.query_snapshot_set(first).unwrap();
+        let expose = |id, target: &str| {
+            let target = ExposeTarget::local(target).unwrap();
+            client.expose_snapshot_with(id, &target, &FakeMountPoints)
+        };
+
+        let prop = expose(snapshots[0].snapshot_id, "X:").unwrap();
+        assert_eq!(prop.exposed_name.as_deref(), Some("X:"));
+        assert_eq!(prop.exposed_path, None);
+        assert_ne!(
+            prop.snapshot_attrs.0 & VSS_VOLSNAP_ATTR_EXPOSED_LOCALLY.0,
+            0
+        );
+        // Already exposed, the drive is in use or the directory is not empty
+        assert!(expose(snapshots[0].snapshot_id, "Y:").is_err());
+        assert!(expose(snapshots[1].snapshot_id, "C:").is_err());
+        assert!(expose(snapshots[1].snapshot_id, "C:\\data").is_err());
+        assert_eq!(
+            client
+                .backend()
+                .calls()
+                .iter()
+                .filter(|c| *c == "ExposeSnapshot")
+                .count(),
+            2
+        );
+
+        let share = ExposeTarget::remote("shadow$", Some("\\Users")).unwrap();
+        let prop = client
+            .expose_snapshot_with(snapshots[1].snapshot_id, &share, &FakeMountPoints)
+            .unwrap();
+        assert_eq!(prop.exposed_name.as_deref(), Some("shadow$"));
+        assert_eq!(prop.exposed_path.as_deref(), Some("\\Users"));
+    }
+
+    #[test]
+    fn test_revert_to_snapshot() {
+        let options = SnapshotSetOptions {
+            persistent: true,
+            ..Default::default()
+        };
+        let backend = FakeBackend::new();
+        let create = |volumes: &[&str]| {
+            let mut client = VssClient::with_backend(backend.connect());
+            client.initialize(options.context(), None, false).unwrap();
+            client.create_snapshot_set(volumes, &options).unwrap()
+        };
+        let c = create(&["C:\\"]).snapshots[0].clone();
+        create(&["D:\\"]);
+        let d = create(&["D:\\"]).snapshots[0].clone();
+
+        backend.set_revert_polls(2);
+        let mut client = VssClient::with_backend(backend.connect());
+        client.initialize(VSS_CTX_ALL, None, false).unwrap();
+        // D: has shadow copies in two sets
+        assert!(matches!(
+            client.revert_to_snapshot(d.snapshot_id, false),
+            Err(VssError::InvalidArgument(_))
+        ));
+        assert!(!backend.calls().iter().any(|c| c == "RevertToSnapshot"));
+
+        let progress = client
+            .revert_to_snapshot(c.snapshot_id, true)
+            .unwrap()
+            .with_interval(Duration::ZERO)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            progress,
+            [
+                RevertStatus::Pending,
+                RevertStatus::Pending,
+                RevertStatus::Finished
+            ]
+        );
+        assert_eq!(backend.reverted_volumes(), [c.origin_vol_name]);
+        assert!(client.get_snapshot_properties(c.snapshot_id).is_err());
+    }
+
+    #[test]
+    fn test_revert_refuses_non_persistent() {
+        let (client, first, _) = client_with_sets();
+        let snapshot = client.query_snapshot_set(first).unwrap()[0].snapshot_id;
+        assert!(client.revert_to_snapshot(snapshot, false).is_err());
+        assert!(client.backend().reverted_volumes().is_empty());
+    }
+
+    #[test]
+    fn test_initialize_context() {
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(VSS_CTX_ALL, None, false).unwrap();
+        assert_eq!(client.context(), VSS_CTX_ALL);
+        assert_eq!(client.backend().context(), VSS_CTX_ALL);
+    }
+
+    #[test]
+    fn test_initialize_writer_metadata() {
+        let backend = FakeBackend::new();
+        backend.add_writer(SYSTEM_WRITER);
+        backend.add_writer(SQL_WRITER);
+        let mut client = VssClient::with_backend(backend);
+        client.initialize(VSS_CTX_BACKUP, None, false).unwrap();
+
+        let writers = client.initialize_writer_metadata().unwrap();
+        assert_eq!(
+            writers
+                .iter()
+                .map(|w| w.writer_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["System Writer", "SqlServerWriter"]
+        );
+        assert_eq!(writers[1].databases().count(), 2);
+        assert_eq!(client.writers(), &writers[..]);
+
+        // The metadata is gathered only once per backup components object
+        client.initialize_writer_metadata().unwrap();
+        let calls = client.backend().calls();
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|c| *c == "GatherWriterMetadata")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_gather_writer_status() {
+        let backend = FakeBackend::new();
+        backend.set_writer_status(WriterStatus {
+            writer_name: "System Writer".to_owned(),
+            writer_id: GUID::from_u128(1),
+            ..Default::default()
+        });
+        backend.set_writer_status(WriterStatus {
+            writer_name: "SqlServerWriter".to_owned(),
+            writer_id: GUID::from_u128(2),
+            state: VSS_WS_FAILED_AT_FREEZE,
+            failure: VSS_E_WRITERERROR_TIMEOUT,
+            ..Default::default()
+        });
+        let mut client = VssClient::with_backend(backend);
+        client.initialize(VSS_CTX_BACKUP, None, false).unwrap();
+
+        let statuses = client.gather_writer_status().unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0].failure_kind(), FailureKind::None);
+        assert_eq!(statuses[1].failure_kind(), FailureKind::Retryable);
+        assert_eq!(client.backend().calls().last().unwrap(), "FreeWriterStatus");
+    }
+
+    #[test]
+    fn test_create_snapshot_set_with_writer_selection() {
+        let backend = FakeBackend::new();
+        backend.add_writer(SYSTEM_WRITER);
+        backend.add_writer(SQL_WRITER);
+        let options = SnapshotSetOptions {
+            included_writers: vec!["SqlServerWriter\\*\\master".to_owned()],
+            excluded_writers: vec!["System Writer".to_owned(), "*\\sales".to_owned()],
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(backend);
+        client.initialize(options.context(), None, false).unwrap();
+        client.create_snapshot_set(&["C:\\"], &options).unwrap();
+
+        let writers = client.writers().to_vec();
+        assert_eq!(
+            client.backend().disabled_writer_instances(),
+            [writers[0].instance_id]
+        );
+        let components = client.backend().components();
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].name, "master");
+        assert_eq!(components[0].writer_id, writers[1].writer_id);
+
+        // The selection happens before the set is started
+        let calls = client.backend().calls();
+        let start = calls.iter().position(|c| c == "StartSnapshotSet").unwrap();
+        assert!(calls[..start].contains(&"AddComponent".to_owned()));
+    }
+
+    #[test]
+    fn test_create_snapshot_set_missing_included_writer() {
+        let backend = FakeBackend::new();
+        backend.add_writer(SYSTEM_WRITER);
+        let options = SnapshotSetOptions {
+            included_writers: vec!["SqlServerWriter".to_owned()],
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(backend);
+        client.initialize(options.context(), None, false).unwrap();
+        assert!(matches!(
+            client.create_snapshot_set(&["C:\\"], &options),
+            Err(VssError::InvalidArgument(_))
+        ));
+        assert!(!client
+            .backend()
+            .calls()
+            .contains(&"StartSnapshotSet".to_owned()));
+    }
+
+    /// Records the commands and their environment, and exits with `code`
+    struct FakeRunner {
+        code: Option<i32>,
+        runs: Vec<(String, Vec<(String, String)>)>,
+    }
+
+    impl CommandRunner for FakeRunner {
+        fn run(
+            &mut self,
+            command: &str,
+            env: &[(String, String)],
+        ) -> Result<Option<i32>, VssError> {
+            self.runs.push((command.to_owned(), env.to_vec()));
+            Ok(self.code)
+        }
+    }
+
+    fn exec(
+        persistent: bool,
+        code: Option<i32>,
+    ) -> (VssClient<FakeBackend>, FakeRunner, Result<(), VssError>) {
+        let options = SnapshotSetOptions {
+            persistent,
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(FakeBackend::new());
+        client.initialize(options.context(), None, false).unwrap();
+        let set = client
+            .create_snapshot_set(&["C:\\", "D:\\"], &options)
+            .unwrap();
+        let mut runner = FakeRunner {
+            code,
+            runs: Vec::new(),
+        };
+        let result = client.exec_with_snapshot_set(&set, "backup.cmd", &mut runner);
+        (client, runner, result)
+    }
+
+    #[test]
+    fn test_exec() {
+        let (client, runner, result) = exec(false, Some(0));
+        result.unwrap();
+        let snapshots = client.backend().snapshots();
+        assert_eq!(snapshots.len(), 2);
+
+        assert_eq!(runner.runs.len(), 1);
+        let (command, env) = &runner.runs[0];
+        assert_eq!(command, "backup.cmd");
+        let names = env.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "SHADOW_SET_ID",
+                "SHADOW_ID_1",
+                "SHADOW_DEVICE_1",
+                "SHADOW_ID_2",
+                "SHADOW_DEVICE_2"
+            ]
+        );
+        assert_eq!(env[4].1, snapshots[1].device_name);
+    }
+
+    #[test]
+    fn test_complete_backup_after_failed_exec() {
+        let backend = FakeBackend::new();
+        backend.add_writer(SYSTEM_WRITER);
+        backend.add_writer(SQL_WRITER);
+        let options = SnapshotSetOptions {
+            persistent: true,
+            included_writers: vec!["SqlServerWriter\\*\\master".to_owned()],
+            excluded_writers: vec!["System Writer".to_owned(), "*\\sales".to_owned()],
+            ..Default::default()
+        };
+        let mut client = VssClient::with_backend(backend);
+        client.initialize(options.context(), None, false).unwrap();
+        let set = client.create_snapshot_set(&["C:\\"], &options).unwrap();
+        let mut runner = FakeRunner {
+            code: Some(1),
+            runs: Vec::new(),
+        };
+        assert!(client
+            .exec_with_snapshot_set(&set, "backup.cmd", &mut runner)
+            .is_err());
+        client.complete_backup(false).unwrap();
+
+        let components = client.backend().components();
+        assert_eq!(
+            client.backend().backup_succeeded(),
+            [(components[0].clone(), false)]
+        );
+        let calls = client.backend().calls();
+        assert_eq!(
+            calls[calls.len() - 2..],
+            ["SetBackupSucceeded", "BackupComplete"]
+        );
+        let document = BackupComponents::from_xml(&client.save_as_xml().unwrap()).unwrap();
+        assert_eq!(document.writer_components.len(), 1);
+        assert_eq!(
+            document.writer_components[0].components[0].backup_succeeded,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_exec_failure_releases_non_persistent() {
+        let (client, _, result) = exec(false, Some(3));
+        assert_eq!(
+            result,
+            Err(VssError::CommandFailed {
+                command: "backup.cmd".to_owned(),
+                code: Some(3)
+            })
+        );
+        assert!(client.backend().snapshots().is_empty());
+
+        // Persistent shadow copies are kept, but the failure is still reported
+        let (client, _, result) = exec(true, None);
+        assert!(matches!(
+            result,
+            Err(VssError::CommandFailed { code: None, .. })
+        ));
+        assert_eq!(client.backend().snapshots().len(), 2);
+    }
+
+    struct FakeRestorer {
+        failing: &'static str,
+        restored: Vec<String>,
+    }
+
+    impl FileRestorer for FakeRestorer {
+        fn restore_files(&mut self, component: &ComponentRestore) -> Result<(), VssError> {
+            self.restored.push(component.full_path());
+            match component.full_path() == self.failing {
+                true => Err(VssError::CommandFailed {
+                    command: "restore.cmd".to_owned(),
+                    code: Some(2),
+                }),
+                false => Ok(()),
+            }
+        }
+    }
+
+    fn restore_client() -> VssClient<FakeBackend> {
+        let backend = FakeBackend::new();
+        for xml in [SYSTEM_WRITER, SQL_WRITER, APP_WRITER] {
+            backend.add_writer(xml);
+        }
+        VssClient::with_backend(backend)
+    }
+
+    #[test]
+    fn test_plan_restore() {
+        let mut client = restore_client();
+        let plan = client.plan_restore(RESTORE_DOCUMENT).unwrap();
+        assert_eq!(plan.components.len(), 2);
+        assert_eq!(plan.skipped.len(), 3);
+        // Planning does not touch the writers
+        let calls = client.backend().calls();
+        assert!(!calls
+            .iter()
+            .any(|c| c == "SetSelectedForRestore" || c == "PreRestore"));
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut client = restore_client();
+        let mut restorer = FakeRestorer {
+            failing: "Config",
+            restored: Vec::new(),
+        };
+        let report = client.restore(RESTORE_DOCUMENT, &mut restorer).unwrap();
+        assert_eq!(restorer.restored, [r"HOST\MSSQLSERVER\master", "Config"]);
+
+        let backend = client.backend();
+        assert_eq!(backend.selected_for_restore().len(), 2);
+        let mappings = backend.alternate_location_mappings();
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(mappings[0].0.name, "Config");
+        assert_eq!(
+            backend
+                .file_restore_statuses()
+                .iter()
+                .map(|(c, s)| (c.name.as_str(), *s))
+                .collect::<Vec<_>>(),
+            [("master", VSS_RS_ALL), ("Config", VSS_RS_FAILED)]
+        );
+        let calls = backend.calls();
+        let position = |op: &str| calls.iter().position(|c| c == op).unwrap();
+        assert!(position("SetSelectedForRestore") < position("PreRestore"));
+        assert!(position("SetFileRestoreStatus") < position("PostRestore"));
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report
+                .components
+                .iter()
+                .map(|c| c.outcome.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "restored",
+                "failed: restore.cmd failed with exit code 2",
+                "skipped: its backup did not succeed",
+                "skipped: writer SqlServerWriter does not report it",
+                "skipped: writer 00000000-0000-0000-0000-0000000000ff is not running",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restore_failed_writer() {
+        let mut client = restore_client();
+        client.backend().set_writer_status(WriterStatus {
+            writer_name: "SqlServerWriter".to_owned(),
+            writer_id: WriterMetadata::from_xml(SQL_WRITER).unwrap().writer_id,
+            state: VSS_WS_FAILED_AT_FREEZE,
+            failure: VSS_E_WRITERERROR_TIMEOUT,
+            ..Default::default()
+        });
+        let mut restorer = FakeRestorer {
+            failing: "",
+            restored: Vec::new(),
+        };
+        let report = client.restore(RESTORE_DOCUMENT, &mut restorer).unwrap();
+        assert!(!report.is_success());
+        assert!(matches!(
+            &report.components[0].outcome,
+            RestoreOutcome::Failed(reason) if reason.contains("SqlServerWriter")
+        ));
+        assert_eq!(report.components[1].outcome, RestoreOutcome::Restored);
+    }
+
+    #[test]
+    fn test_restore_without_restorer() {
+        let mut client = restore_client();
+        let report = client
+            .restore(RESTORE_DOCUMENT, &mut None::<FakeRestorer>)
+            .unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.components[0].outcome, RestoreOutcome::Restored);
+        assert_eq!(report.components[1].outcome, RestoreOutcome::Restored);
+        let calls = client.backend().calls();
+        assert!(calls.iter().any(|c| c == "PreRestore"));
+        assert!(calls.iter().any(|c| c == "PostRestore"));
+    }
+}
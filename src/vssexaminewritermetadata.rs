@@ -0,0 +1,68 @@
+use windows::core::BSTR;
+
+/// The metadata of one writer, returned by `IVssBackupComponent::GetWriterMetadata`.
+///
+/// Only `SaveAsXML` is wrapped, the document is parsed into `WriterMetadata` instead of
+/// walking the `IVssWMComponent`/`IVssWMFiledesc` objects one by one.
+#[repr(transparent)]
+pub struct IVssExamineWriterMetadata(::windows::core::IUnknown);
+
+impl IVssExamineWriterMetadata {
+    /// Save the writer metadata as an XML document
+    pub unsafe fn SaveAsXML(&self) -> ::windows::core::Result<BSTR> {
+        let mut result__ = ::windows::core::zeroed::<BSTR>();
+        (::windows::core::Interface::vtable(self).SaveAsXML)(
+            ::windows::core::Interface::as_raw(self),
+            &mut result__,
+        )
+        .from_abi(result__)
+    }
+}
+
+#[repr(C)]
+#[doc(hidden)]
+pub struct IVssExamineWriterMetadata_Vtbl {
+    pub base__: ::windows::core::IUnknown_Vtbl,
+    pub GetIdentity: usize,
+    pub GetFileCounts: usize,
+    pub GetIncludeFile: usize,
+    pub GetExcludeFile: usize,
+    pub GetComponent: usize,
+    pub GetRestoreMethod: usize,
+    pub GetAlternateLocationMapping: usize,
+    pub GetBackupSchema: usize,
+    pub GetDocument: usize,
+    pub SaveAsXML: unsafe extern "system" fn(
+        this: *mut ::core::ffi::c_void,
+        pbstrXML: *mut ::std::mem::MaybeUninit<BSTR>,
+    ) -> ::windows::core::HRESULT,
+    pub LoadFromXML: usize,
+}
+
+impl ::core::cmp::PartialEq for IVssExamineWriterMetadata {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl ::core::cmp::Eq for IVssExamineWriterMetadata {}
+impl ::core::fmt::Debug for IVssExamineWriterMetadata {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        f.debug_tuple("IVssExamineWriterMetadata")
+            .field(&self.0)
+            .finish()
+    }
+}
+unsafe impl ::windows::core::Interface for IVssExamineWriterMetadata {
+    type Vtable = IVssExamineWriterMetadata_Vtbl;
+}
+impl ::core::clone::Clone for IVssExamineWriterMetadata {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+unsafe impl ::windows::core::ComInterface for IVssExamineWriterMetadata {
+    const IID: ::windows::core::GUID =
+        ::windows::core::GUID::from_u128(0x902fcf7f_b7fd_42f8_81f1_b2e400b1e5bd);
+}
+
+::windows::imp::interface_hierarchy!(IVssExamineWriterMetadata, ::windows::core::IUnknown);
@@ -0,0 +1,663 @@
+//! Ownership of the property structs returned by VSS (`VSS_OBJECT_PROP`,
+//! `VSS_MGMT_OBJECT_PROP`).
+//!
+//! The strings of `VSS_SNAPSHOT_PROP`, `VSS_PROVIDER_PROP` and of the management objects
+//! are allocated by VSS and belong to the caller. The wrappers free them when dropped and convert them without
+//! assuming that any of them is set. Only `VssDeallocator` and the `IVssEnumObject`
+//! enumeration need the COM backend, the conversions build on every target.
+
+use std::marker::PhantomData;
+
+#[cfg(all(windows, feature = "com"))]
+use windows::Win32::{Storage::Vss::IVssEnumObject, System::Com::CoTaskMemFree};
+use windows::{
+    core::HRESULT,
+    Win32::Storage::Vss::{
+        VSS_MGMT_OBJECT_DIFF_AREA, VSS_MGMT_OBJECT_DIFF_VOLUME, VSS_MGMT_OBJECT_PROP,
+        VSS_MGMT_OBJECT_TYPE, VSS_MGMT_OBJECT_VOLUME, VSS_OBJECT_PROP, VSS_OBJECT_PROVIDER,
+        VSS_OBJECT_SNAPSHOT, VSS_OBJECT_TYPE, VSS_PROVIDER_PROP, VSS_SNAPSHOT_PROP,
+    },
+};
+
+#[cfg(all(windows, feature = "com"))]
+use crate::vssbackupcomponent::VssFreeSnapshotProperties;
+use crate::{
+    diffarea::{DiffArea, DiffVolume, Volume},
+    error::VssError,
+    provider::{Provider, ProviderType},
+    utils::u16_to_string,
+    vssprop::VSSProp,
+};
+
+/// How the strings of the property structs are freed
+pub trait Deallocator {
+    /// Free a string, never called with a null pointer
+    ///
+    /// # Safety
+    /// The string must have been allocated by the matching allocator and not be used anymore.
+    unsafe fn free_string(s: *mut u16);
+
+    /// Free all the strings of a shadow copy properties struct
+    ///
+    /// # Safety
+    /// See `free_string`
+    unsafe fn free_snapshot(prop: &mut VSS_SNAPSHOT_PROP) {
+        for s in [
+            &mut prop.m_pwszSnapshotDeviceObject,
+            &mut prop.m_pwszOriginalVolumeName,
+            &mut prop.m_pwszOriginatingMachine,
+            &mut prop.m_pwszServiceMachine,
+            &mut prop.m_pwszExposedName,
+            &mut prop.m_pwszExposedPath,
+        ] {
+            if !s.is_null() {
+                Self::free_string(*s);
+                *s = std::ptr::null_mut();
+            }
+        }
+    }
+}
+
+/// The strings allocated by VSS, freed with `CoTaskMemFree` and `VssFreeSnapshotProperties`
+#[cfg(all(windows, feature = "com"))]
+#[derive(Debug)]
+pub struct VssDeallocator;
+
+#[cfg(all(windows, feature = "com"))]
+impl Deallocator for VssDeallocator {
+    unsafe fn free_string(s: *mut u16) {
+        CoTaskMemFree(Some(s as *const std::ffi::c_void));
+    }
+
+    unsafe fn free_snapshot(prop: &mut VSS_SNAPSHOT_PROP) {
+        VssFreeSnapshotProperties(prop);
+    }
+}
+
+/// An owned `VSS_SNAPSHOT_PROP`, its strings are freed on drop
+pub struct SnapshotProp<D: Deallocator> {
+    prop: VSS_SNAPSHOT_PROP,
+    deallocator: PhantomData<D>,
+}
+
+impl<D: Deallocator> SnapshotProp<D> {
+    /// Take the ownership of the strings of `prop`
+    ///
+    /// # Safety
+    /// The strings must be null or null-terminated, allocated for `D` and owned by nobody else.
+    pub unsafe fn new(prop: VSS_SNAPSHOT_PROP) -> Self {
+        Self {
+            prop,
+            deallocator: PhantomData,
+        }
+    }
+
+    pub fn as_raw(&self) -> &VSS_SNAPSHOT_PROP {
+        &self.prop
+    }
+
+    pub fn to_vssprop(&self) -> VSSProp {
+        VSSProp::from_props(&self.prop)
+    }
+}
+
+impl<D: Deallocator> Drop for SnapshotProp<D> {
+    fn drop(&mut self) {
+        unsafe { D::free_snapshot(&mut self.prop) };
+    }
+}
+
+impl<D: Deallocator> std::fmt::Debug for SnapshotProp<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_vssprop().fmt(f)
+    }
+}
+
+/// An owned `VSS_PROVIDER_PROP`, its strings are freed on drop
+pub struct ProviderProp<D: Deallocator> {
+    prop: VSS_PROVIDER_PROP,
+    deallocator: PhantomData<D>,
+}
+
+impl<D: Deallocator> ProviderProp<D> {
+    /// Take the ownership of the strings of `prop`
+    ///
+    /// # Safety
+    /// See `SnapshotProp::new`
+    pub unsafe fn new(prop: VSS_PROVIDER_PROP) -> Self {
+        Self {
+            prop,
+            deallocator: PhantomData,
+        }
+    }
+
+    pub fn as_raw(&self) -> &VSS_PROVIDER_PROP {
+        &self.prop
+    }
+
+    pub fn to_provider(&self) -> Provider {
+        Provider {
+            provider_id: self.prop.m_ProviderId,
+            name: u16_to_string(self.prop.m_pwszProviderName),
+            provider_type: ProviderType::from(self.prop.m_eProviderType),
+            version: u16_to_string(self.prop.m_pwszProviderVersion),
+            version_id: self.prop.m_ProviderVersionId,
+            class_id: self.prop.m_ClassId,
+        }
+    }
+}
+
+impl<D: Deallocator> Drop for ProviderProp<D> {
+    fn drop(&mut self) {
+        for s in [
+            self.prop.m_pwszProviderName,
+            self.prop.m_pwszProviderVersion,
+        ] {
+            if !s.is_null() {
+                unsafe { D::free_string(s) };
+            }
+        }
+    }
+}
+
+impl<D: Deallocator> std::fmt::Debug for ProviderProp<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_provider().fmt(f)
+    }
+}
+
+/// An owned `VSS_MGMT_OBJECT_PROP`, the strings of its object are freed on drop, whatever
+/// the conversion asked for
+pub struct MgmtObjectProp<D: Deallocator> {
+    prop: VSS_MGMT_OBJECT_PROP,
+    deallocator: PhantomData<D>,
+}
+
+impl<D: Deallocator> MgmtObjectProp<D> {
+    /// Take the ownership of the strings of `prop`
+    ///
+    /// # Safety
+    /// `prop.Type` must match the union member that is set, see `SnapshotProp::new`
+    /// for its strings.
+    pub unsafe fn new(prop: VSS_MGMT_OBJECT_PROP) -> Self {
+        Self {
+            prop,
+            deallocator: PhantomData,
+        }
+    }
+
+    pub fn object_type(&self) -> VSS_MGMT_OBJECT_TYPE {
+        self.prop.Type
+    }
+
+    /// The volume, `None` for the other object types
+    pub fn to_volume(&self) -> Option<Volume> {
+        if self.prop.Type != VSS_MGMT_OBJECT_VOLUME {
+            return None;
+        }
+        let vol = unsafe { self.prop.Obj.Vol };
+        Some(Volume {
+            volume_name: u16_to_string(vol.m_pwszVolumeName),
+            display_name: u16_to_string(vol.m_pwszVolumeDisplayName),
+        })
+    }
+
+    /// The volume that can store diff areas, `None` for the other object types
+    pub fn to_diff_volume(&self) -> Option<DiffVolume> {
+        if self.prop.Type != VSS_MGMT_OBJECT_DIFF_VOLUME {
+            return None;
+        }
+        let vol = unsafe { self.prop.Obj.DiffVol };
+        Some(DiffVolume {
+            volume_name: u16_to_string(vol.m_pwszVolumeName),
+            display_name: u16_to_string(vol.m_pwszVolumeDisplayName),
+            free_space: to_size(vol.m_llVolumeFreeSpace),
+            total_space: to_size(vol.m_llVolumeTotalSpace),
+        })
+    }
+
+    /// The diff area, `None` for the other object types
+    pub fn to_diff_area(&self) -> Option<DiffArea> {
+        if self.prop.Type != VSS_MGMT_OBJECT_DIFF_AREA {
+            return None;
+        }
+        let area = unsafe { self.prop.Obj.DiffArea };
+        Some(DiffArea {
+            volume_name: u16_to_string(area.m_pwszVolumeName),
+            diff_area_volume_name: u16_to_string(area.m_pwszDiffAreaVolumeName),
+            maximum_space: u64::try_from(area.m_llMaximumDiffSpace).ok(),
+            allocated_space: to_size(area.m_llAllocatedDiffSpace),
+            used_space: to_size(area.m_llUsedDiffSpace),
+        })
+    }
+}
+
+fn to_size(size: i64) -> u64 {
+    u64::try_from(size).unwrap_or_default()
+}
+
+impl<D: Deallocator> Drop for MgmtObjectProp<D> {
+    fn drop(&mut self) {
+        let strings = unsafe {
+            match self.prop.Type {
+                VSS_MGMT_OBJECT_VOLUME => [
+                    self.prop.Obj.Vol.m_pwszVolumeName,
+                    self.prop.Obj.Vol.m_pwszVolumeDisplayName,
+                ],
+                VSS_MGMT_OBJECT_DIFF_VOLUME => [
+                    self.prop.Obj.DiffVol.m_pwszVolumeName,
+                    self.prop.Obj.DiffVol.m_pwszVolumeDisplayName,
+                ],
+                VSS_MGMT_OBJECT_DIFF_AREA => [
+                    self.prop.Obj.DiffArea.m_pwszVolumeName,
+                    self.prop.Obj.DiffArea.m_pwszDiffAreaVolumeName,
+                ],
+                // Unknown objects, none of the union members can be trusted
+                _ => return,
+            }
+        };
+        for s in strings {
+            if !s.is_null() {
+                unsafe { D::free_string(s) };
+            }
+        }
+    }
+}
+
+impl<D: Deallocator> std::fmt::Debug for MgmtObjectProp<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MgmtObjectProp")
+            .field("object_type", &self.prop.Type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An owned `VSS_OBJECT_PROP`
+#[derive(Debug)]
+pub enum ObjectProp<D: Deallocator> {
+    Snapshot(SnapshotProp<D>),
+    Provider(ProviderProp<D>),
+    /// An object type without strings, such as a shadow copy set
+    Other(VSS_OBJECT_TYPE),
+}
+
+impl<D: Deallocator> ObjectProp<D> {
+    /// Take the ownership of the object of `prop`, see `SnapshotProp::new`
+    ///
+    /// # Safety
+    /// `prop.Type` must match the union member that is set.
+    pub unsafe fn new(prop: VSS_OBJECT_PROP) -> Self {
+        match prop.Type {
+            VSS_OBJECT_SNAPSHOT => ObjectProp::Snapshot(SnapshotProp::new(prop.Obj.Snap)),
+            VSS_OBJECT_PROVIDER => ObjectProp::Provider(ProviderProp::new(prop.Obj.Prov)),
+            object_type => ObjectProp::Other(object_type),
+        }
+    }
+}
+
+/// An enumeration of `VSS_OBJECT_PROP`, such as `IVssEnumObject`
+pub trait ObjectEnum {
+    /// The next object, `None` at the end. Its strings then belong to the caller.
+    fn next_object(&mut self) -> Result<Option<VSS_OBJECT_PROP>, HRESULT>;
+}
+
+#[cfg(all(windows, feature = "com"))]
+impl ObjectEnum for IVssEnumObject {
+    fn next_object(&mut self) -> Result<Option<VSS_OBJECT_PROP>, HRESULT> {
+        let mut props = [VSS_OBJECT_PROP::default(); 1];
+        let mut fetched = 0;
+        unsafe { self.Next(&mut props, &mut fetched) }.map_err(|e| e.code())?;
+        Ok((fetched == 1).then_some(props[0]))
+    }
+}
+
+impl<E: ObjectEnum> ObjectEnum for &mut E {
+    fn next_object(&mut self) -> Result<Option<VSS_OBJECT_PROP>, HRESULT> {
+        (**self).next_object()
+    }
+}
+
+/// The objects of an enumeration, fetched one at a time.
+///
+/// The iteration ends after the first error. The objects that are not consumed
+/// are neither fetched nor leaked.
+pub struct ObjectIter<E: ObjectEnum, D: Deallocator> {
+    enumerator: E,
+    operation: &'static str,
+    done: bool,
+    deallocator: PhantomData<D>,
+}
+
+impl<E: ObjectEnum, D: Deallocator> ObjectIter<E, D> {
+    /// Iterate over the enumeration returned by `operation`, e.g. "Query"
+    pub fn new(enumerator: E, operation: &'static str) -> Self {
+        Self {
+            enumerator,
+            operation,
+            done: false,
+            deallocator: PhantomData,
+        }
+    }
+}
+
+impl<E: ObjectEnum, D: Deallocator> Iterator for ObjectIter<E, D> {
+    type Item = Result<ObjectProp<D>, VssError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.enumerator.next_object() {
+            Ok(Some(prop)) => Some(Ok(unsafe { ObjectProp::new(prop) })),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(code) => {
+                self.done = true;
+                Some(Err(VssError::from_hresult(self.operation, code)))
+            }
+        }
+    }
+}
+
+/// The shadow copies of an enumeration, the other objects are skipped
+pub struct SnapshotIter<E: ObjectEnum, D: Deallocator>(ObjectIter<E, D>);
+
+impl<E: ObjectEnum, D: Deallocator> SnapshotIter<E, D> {
+    pub fn new(enumerator: E, operation: &'static str) -> Self {
+        Self(ObjectIter::new(enumerator, operation))
+    }
+}
+
+impl<E: ObjectEnum, D: Deallocator> Iterator for SnapshotIter<E, D> {
+    type Item = Result<SnapshotProp<D>, VssError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|object| match object {
+            Ok(ObjectProp::Snapshot(snapshot)) => Some(Ok(snapshot)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+}
+
+/// The providers of an enumeration, the other objects are skipped
+pub struct ProviderIter<E: ObjectEnum, D: Deallocator>(ObjectIter<E, D>);
+
+impl<E: ObjectEnum, D: Deallocator> ProviderIter<E, D> {
+    pub fn new(enumerator: E, operation: &'static str) -> Self {
+        Self(ObjectIter::new(enumerator, operation))
+    }
+}
+
+impl<E: ObjectEnum, D: Deallocator> Iterator for ProviderIter<E, D> {
+    type Item = Result<ProviderProp<D>, VssError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|object| match object {
+            Ok(ObjectProp::Provider(provider)) => Some(Ok(provider)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, collections::VecDeque, iter::once, ptr::null_mut};
+
+    use windows::{
+        core::GUID,
+        Win32::Storage::Vss::{
+            VSS_DIFF_AREA_PROP, VSS_E_UNEXPECTED_PROVIDER_ERROR, VSS_MGMT_OBJECT_UNION,
+            VSS_MGMT_OBJECT_UNKNOWN, VSS_OBJECT_SNAPSHOT_SET, VSS_OBJECT_UNION, VSS_PROV_SOFTWARE,
+            VSS_SS_CREATED, VSS_VOLSNAP_ATTR_PERSISTENT, VSS_VOLUME_PROP,
+        },
+    };
+
+    use super::*;
+
+    thread_local! {
+        /// The strings freed by `TestDeallocator` on this thread
+        static FREED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Frees the strings allocated by `alloc`
+    struct TestDeallocator;
+
+    impl Deallocator for TestDeallocator {
+        unsafe fn free_string(s: *mut u16) {
+            let len = (0..).take_while(|&i| *s.add(i) != 0).count();
+            let s = Box::from_raw(std::ptr::slice_from_raw_parts_mut(s, len + 1));
+            FREED.with(|freed| freed.borrow_mut().push(String::from_utf16_lossy(&s[..len])));
+        }
+    }
+
+    fn alloc(s: &str) -> *mut u16 {
+        let s = s.encode_utf16().chain(once(0)).collect::<Box<[u16]>>();
+        Box::into_raw(s) as *mut u16
+    }
+
+    fn freed() -> Vec<String> {
+        FREED.with(|freed| freed.take())
+    }
+
+    fn snapshot(volume: &str) -> VSS_SNAPSHOT_PROP {
+        VSS_SNAPSHOT_PROP {
+            m_SnapshotId: GUID::from_u128(1),
+            m_SnapshotSetId: GUID::from_u128(2),
+            m_lSnapshotsCount: 1,
+            m_pwszSnapshotDeviceObject: alloc(
+                "\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy1",
+            ),
+            m_pwszOriginalVolumeName: alloc(volume),
+            m_pwszOriginatingMachine: alloc("host"),
+            // Not always set, e.g. for a shadow copy being created
+            m_pwszServiceMachine: null_mut(),
+            m_pwszExposedName: null_mut(),
+            m_pwszExposedPath: null_mut(),
+            m_lSnapshotAttributes: VSS_VOLSNAP_ATTR_PERSISTENT.0,
+            m_eStatus: VSS_SS_CREATED,
+            ..Default::default()
+        }
+    }
+
+    fn provider() -> VSS_PROVIDER_PROP {
+        VSS_PROVIDER_PROP {
+            m_ProviderId: GUID::from_u128(3),
+            m_pwszProviderName: alloc("Contoso Provider"),
+            m_eProviderType: VSS_PROV_SOFTWARE,
+            m_pwszProviderVersion: null_mut(),
+            ..Default::default()
+        }
+    }
+
+    /// An `IVssEnumObject` returning the given results in order
+    struct FakeEnum(VecDeque<Result<VSS_OBJECT_PROP, HRESULT>>);
+
+    impl ObjectEnum for FakeEnum {
+        fn next_object(&mut self) -> Result<Option<VSS_OBJECT_PROP>, HRESULT> {
+            self.0.pop_front().transpose()
+        }
+    }
+
+    impl Drop for FakeEnum {
+        // The objects never fetched are still owned by the enumeration
+        fn drop(&mut self) {
+            for prop in self.0.drain(..).flatten() {
+                drop(unsafe { ObjectProp::<TestDeallocator>::new(prop) });
+            }
+        }
+    }
+
+    fn objects() -> FakeEnum {
+        FakeEnum(VecDeque::from([
+            Ok(VSS_OBJECT_PROP {
+                Type: VSS_OBJECT_SNAPSHOT,
+                Obj: VSS_OBJECT_UNION {
+                    Snap: snapshot("C:\\"),
+                },
+            }),
+            Ok(VSS_OBJECT_PROP {
+                Type: VSS_OBJECT_PROVIDER,
+                Obj: VSS_OBJECT_UNION { Prov: provider() },
+            }),
+            Ok(VSS_OBJECT_PROP {
+                Type: VSS_OBJECT_SNAPSHOT_SET,
+                Obj: VSS_OBJECT_UNION {
+                    Snap: VSS_SNAPSHOT_PROP::default(),
+                },
+            }),
+            Ok(VSS_OBJECT_PROP {
+                Type: VSS_OBJECT_SNAPSHOT,
+                Obj: VSS_OBJECT_UNION {
+                    Snap: snapshot("D:\\"),
+                },
+            }),
+        ]))
+    }
+
+    #[test]
+    fn test_snapshot_prop() {
+        let prop = unsafe { SnapshotProp::<TestDeallocator>::new(snapshot("C:\\")) };
+        let vssprop = prop.to_vssprop();
+        assert_eq!(vssprop.origin_vol_name, "C:\\");
+        assert_eq!(vssprop.origin_machine, "host");
+        assert_eq!(vssprop.origin_service, "");
+        assert_eq!(vssprop.exposed_name, None);
+        assert_eq!(vssprop.snapshot_attrs, VSS_VOLSNAP_ATTR_PERSISTENT);
+        assert!(freed().is_empty());
+
+        drop(prop);
+        assert_eq!(
+            freed(),
+            [
+                "\\\\?\\GLOBALROOT\\Device\\HarddiskVolumeShadowCopy1",
+                "C:\\",
+                "host"
+            ]
+        );
+
+        // Nothing to free when no string is set
+        drop(unsafe { SnapshotProp::<TestDeallocator>::new(VSS_SNAPSHOT_PROP::default()) });
+        assert!(freed().is_empty());
+    }
+
+    #[test]
+    fn test_provider_prop() {
+        let prop = unsafe { ProviderProp::<TestDeallocator>::new(provider()) };
+        let provider = prop.to_provider();
+        assert_eq!(provider.provider_id, GUID::from_u128(3));
+        assert_eq!(provider.name, "Contoso Provider");
+        assert_eq!(provider.provider_type, ProviderType::Software);
+        assert_eq!(provider.version, "");
+        drop(prop);
+        assert_eq!(freed(), ["Contoso Provider"]);
+    }
+
+    #[test]
+    fn test_mgmt_object_prop() {
+        let volume = VSS_MGMT_OBJECT_PROP {
+            Type: VSS_MGMT_OBJECT_VOLUME,
+            Obj: VSS_MGMT_OBJECT_UNION {
+                Vol: VSS_VOLUME_PROP {
+                    m_pwszVolumeName: alloc("\\\\?\\Volume{1}\\"),
+                    m_pwszVolumeDisplayName: alloc("C:\\"),
+                },
+            },
+        };
+        let prop = unsafe { MgmtObjectProp::<TestDeallocator>::new(volume) };
+        assert_eq!(
+            prop.to_volume(),
+            Some(Volume {
+                volume_name: "\\\\?\\Volume{1}\\".to_owned(),
+                display_name: "C:\\".to_owned(),
+            })
+        );
+        drop(prop);
+        assert_eq!(freed(), ["\\\\?\\Volume{1}\\", "C:\\"]);
+
+        let diff_area = VSS_MGMT_OBJECT_PROP {
+            Type: VSS_MGMT_OBJECT_DIFF_AREA,
+            Obj: VSS_MGMT_OBJECT_UNION {
+                DiffArea: VSS_DIFF_AREA_PROP {
+                    m_pwszVolumeName: alloc("C:\\"),
+                    m_pwszDiffAreaVolumeName: alloc("D:\\"),
+                    m_llMaximumDiffSpace: -1,
+                    m_llAllocatedDiffSpace: 4096,
+                    m_llUsedDiffSpace: 1024,
+                },
+            },
+        };
+        let prop = unsafe { MgmtObjectProp::<TestDeallocator>::new(diff_area) };
+        let area = prop.to_diff_area().unwrap();
+        assert_eq!(area.diff_area_volume_name, "D:\\");
+        assert_eq!(area.maximum_space, None);
+        assert_eq!(area.allocated_space, 4096);
+        // Another object type is not converted, but still freed
+        assert_eq!(prop.to_volume(), None);
+        assert_eq!(prop.to_diff_volume(), None);
+        assert!(freed().is_empty());
+        drop(prop);
+        assert_eq!(freed(), ["C:\\", "D:\\"]);
+
+        let unknown = VSS_MGMT_OBJECT_PROP {
+            Type: VSS_MGMT_OBJECT_UNKNOWN,
+            ..Default::default()
+        };
+        let prop = unsafe { MgmtObjectProp::<TestDeallocator>::new(unknown) };
+        assert_eq!(prop.object_type(), VSS_MGMT_OBJECT_UNKNOWN);
+        assert_eq!(prop.to_diff_area(), None);
+        drop(prop);
+        assert!(freed().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_iter() {
+        let snapshots = SnapshotIter::<_, TestDeallocator>::new(objects(), "Query")
+            .map(|s| s.map(|s| s.to_vssprop().origin_vol_name))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(snapshots, ["C:\\", "D:\\"]);
+        // The skipped provider is freed as well
+        let freed = freed();
+        assert_eq!(freed.len(), 7);
+        assert!(freed.contains(&"Contoso Provider".to_owned()));
+
+        let providers = ProviderIter::<_, TestDeallocator>::new(objects(), "Query")
+            .map(|p| p.unwrap().to_provider().name)
+            .collect::<Vec<_>>();
+        assert_eq!(providers, ["Contoso Provider"]);
+        assert_eq!(self::freed().len(), 7);
+    }
+
+    #[test]
+    fn test_snapshot_iter_is_lazy() {
+        let mut enumerator = objects();
+        let first = SnapshotIter::<_, TestDeallocator>::new(&mut enumerator, "Query")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.to_vssprop().origin_vol_name, "C:\\");
+        assert_eq!(enumerator.0.len(), 3);
+        drop(first);
+        drop(enumerator);
+        assert_eq!(freed().len(), 7);
+    }
+
+    #[test]
+    fn test_snapshot_iter_error() {
+        let mut enumerator = objects();
+        enumerator.0.insert(1, Err(VSS_E_UNEXPECTED_PROVIDER_ERROR));
+        let mut snapshots = SnapshotIter::<_, TestDeallocator>::new(enumerator, "Query");
+        assert!(snapshots.next().unwrap().is_ok());
+        assert_eq!(
+            snapshots.next().unwrap().unwrap_err(),
+            VssError::UnexpectedProviderError { operation: "Query" }
+        );
+        assert!(snapshots.next().is_none());
+        drop(snapshots);
+        assert_eq!(freed().len(), 7);
+    }
+}
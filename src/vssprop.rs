@@ -1,31 +1,125 @@
 use chrono::{DateTime, Utc};
-
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use windows::{
     core::GUID,
     Win32::Storage::Vss::{VSS_SNAPSHOT_PROP, VSS_SNAPSHOT_STATE, VSS_VOLUME_SNAPSHOT_ATTRIBUTES},
 };
 
-use crate::utils::{
-    get_string_for_snapshot_state, i64_to_date, u16_to_string, volsnap_attrs_to_str,
+use crate::{
+    snapshot::{SnapshotAttributes, SnapshotState},
+    utils::{
+        get_string_for_snapshot_state, i64_to_date, u16_to_option, u16_to_string,
+        volsnap_attrs_to_str,
+    },
 };
 
+/// The properties of a shadow copy.
+///
+/// With the serde feature, it serializes with the vshadow.exe field names, the GUIDs as
+/// strings, the creation time in RFC 3339 and the attributes as a list of flag names.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "PascalCase"))]
 pub struct VSSProp {
+    #[cfg_attr(feature = "serde", serde(with = "guid"))]
     pub snapshot_id: GUID,
+    #[cfg_attr(feature = "serde", serde(rename = "SnapshotSetId", with = "guid"))]
     pub shadow_copy_set_id: GUID,
+    #[cfg_attr(feature = "serde", serde(rename = "SnapshotsCount"))]
     pub snapshot_count: i32,
+    #[cfg_attr(feature = "serde", serde(rename = "OriginalVolumeName"))]
     pub origin_vol_name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "CreationTimestamp"))]
     pub create_time: DateTime<Utc>,
+    #[cfg_attr(feature = "serde", serde(rename = "SnapshotDeviceObject"))]
     pub device_name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "OriginatingMachine"))]
     pub origin_machine: String,
+    #[cfg_attr(feature = "serde", serde(rename = "ServiceMachine"))]
     pub origin_service: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "SnapshotAttributes", with = "attributes")
+    )]
     pub snapshot_attrs: VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
     pub exposed_name: Option<String>,
     pub exposed_path: Option<String>,
+    #[cfg_attr(feature = "serde", serde(with = "guid"))]
     pub provider_id: GUID,
+    /// Only known when the providers were queried as well, see `set_provider_names`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub provider_name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(rename = "Status", with = "state"))]
     pub state: VSS_SNAPSHOT_STATE,
 }
 
+/// GUIDs as lowercase strings, without braces
+#[cfg(feature = "serde")]
+pub(crate) mod guid {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use windows::core::GUID;
+
+    use crate::{utils::parse_guid, xml::format_guid};
+
+    pub(crate) fn serialize<S: Serializer>(guid: &GUID, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&format_guid(*guid))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<GUID, D::Error> {
+        let s = String::deserialize(d)?;
+        parse_guid(&s).map_err(D::Error::custom)
+    }
+}
+
+/// Shadow copy attributes as a list of flag names
+#[cfg(feature = "serde")]
+mod attributes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use windows::Win32::Storage::Vss::VSS_VOLUME_SNAPSHOT_ATTRIBUTES;
+
+    use crate::utils::{volsnap_attrs_from_str, volsnap_attrs_to_str};
+
+    pub(crate) fn serialize<S: Serializer>(
+        attrs: &VSS_VOLUME_SNAPSHOT_ATTRIBUTES,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.collect_seq(volsnap_attrs_to_str(attrs.0))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<VSS_VOLUME_SNAPSHOT_ATTRIBUTES, D::Error> {
+        let names = Vec::<String>::deserialize(d)?;
+        volsnap_attrs_from_str(&names)
+            .map(VSS_VOLUME_SNAPSHOT_ATTRIBUTES)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Snapshot states by name, e.g. "VSS_SS_CREATED"
+#[cfg(feature = "serde")]
+mod state {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use windows::Win32::Storage::Vss::VSS_SNAPSHOT_STATE;
+
+    use crate::utils::{get_string_for_snapshot_state, parse_snapshot_state};
+
+    pub(crate) fn serialize<S: Serializer>(
+        state: &VSS_SNAPSHOT_STATE,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&get_string_for_snapshot_state(*state))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<VSS_SNAPSHOT_STATE, D::Error> {
+        let s = String::deserialize(d)?;
+        parse_snapshot_state(&s).map_err(D::Error::custom)
+    }
+}
+
 impl Default for VSSProp {
     fn default() -> Self {
         Self {
@@ -41,30 +135,30 @@ impl Default for VSSProp {
             exposed_name: Default::default(),
             exposed_path: Default::default(),
             provider_id: Default::default(),
+            provider_name: Default::default(),
             state: Default::default(),
         }
     }
 }
 
 impl VSSProp {
+    /// Copy the properties returned by VSS, any of the strings may be null.
+    /// The strings of `prop` are not freed, see `vssobject::SnapshotProp`.
     pub fn from_props(prop: &VSS_SNAPSHOT_PROP) -> Self {
-        let mut ret = VSSProp::default();
-        ret.snapshot_id = prop.m_SnapshotId;
-        ret.shadow_copy_set_id = prop.m_SnapshotSetId;
-        ret.snapshot_count = prop.m_lSnapshotsCount;
-        ret.origin_vol_name = u16_to_string(prop.m_pwszOriginalVolumeName);
-        //time
-        ret.create_time = i64_to_date(prop.m_tsCreationTimestamp);
-
-        ret.device_name = u16_to_string(prop.m_pwszSnapshotDeviceObject);
-        ret.origin_machine = u16_to_string(prop.m_pwszOriginatingMachine);
-        ret.origin_service = u16_to_string(prop.m_pwszServiceMachine);
-        if !prop.m_pwszExposedName.is_null() {
-            ret.exposed_name = Some(u16_to_string(prop.m_pwszExposedName));
-        }
-        if !prop.m_pwszExposedPath.is_null() {
-            ret.exposed_path = Some(u16_to_string(prop.m_pwszExposedPath))
-        }
+        let mut ret = VSSProp {
+            snapshot_id: prop.m_SnapshotId,
+            shadow_copy_set_id: prop.m_SnapshotSetId,
+            snapshot_count: prop.m_lSnapshotsCount,
+            origin_vol_name: u16_to_string(prop.m_pwszOriginalVolumeName),
+            //time
+            create_time: i64_to_date(prop.m_tsCreationTimestamp),
+            device_name: u16_to_string(prop.m_pwszSnapshotDeviceObject),
+            origin_machine: u16_to_string(prop.m_pwszOriginatingMachine),
+            origin_service: u16_to_string(prop.m_pwszServiceMachine),
+            exposed_name: u16_to_option(prop.m_pwszExposedName),
+            exposed_path: u16_to_option(prop.m_pwszExposedPath),
+            ..Default::default()
+        };
         ret.snapshot_attrs = windows::Win32::Storage::Vss::VSS_VOLUME_SNAPSHOT_ATTRIBUTES(
             prop.m_lSnapshotAttributes,
         );
@@ -72,7 +166,15 @@ impl VSSProp {
         ret.provider_id = prop.m_ProviderId;
         ret.state = prop.m_eStatus;
 
-        return ret;
+        ret
+    }
+
+    pub fn attributes(&self) -> SnapshotAttributes {
+        SnapshotAttributes::from(self.snapshot_attrs)
+    }
+
+    pub fn snapshot_state(&self) -> SnapshotState {
+        SnapshotState::from(self.state)
     }
 }
 
@@ -89,6 +191,7 @@ impl ::core::fmt::Debug for VSSProp {
             .field("ExposedName", &self.exposed_name)
             .field("ExposedPath", &self.exposed_path)
             .field("ProviderId", &self.provider_id)
+            .field("ProviderName", &self.provider_name)
             .field(
                 "SnapshotAttributes",
                 &volsnap_attrs_to_str(self.snapshot_attrs.0).join(" "),
@@ -0,0 +1,468 @@
+use windows::{
+    core::GUID,
+    Win32::Storage::Vss::{VSS_COMPONENT_TYPE, VSS_CT_DATABASE, VSS_CT_FILEGROUP},
+};
+
+use crate::{
+    error::VssError,
+    xml::{Attributes, XmlElement},
+};
+
+/// How the data of a writer is used by the system
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UsageType {
+    #[default]
+    Undefined,
+    BootableSystemState,
+    SystemService,
+    UserData,
+    Other,
+}
+
+/// The kind of data a writer manages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SourceType {
+    #[default]
+    Undefined,
+    TransactedDb,
+    NonTransactedDb,
+    Other,
+}
+
+/// How the files of a writer must be restored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestoreMethod {
+    #[default]
+    Undefined,
+    RestoreIfNotThere,
+    RestoreIfCanReplace,
+    StopRestart,
+    RestoreToAlternateLocation,
+    RestoreAtReboot,
+    RestoreAtRebootIfCannotReplace,
+    Custom,
+    RestoreStopStart,
+}
+
+/// Whether the writer must be involved in the restore
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriterRestore {
+    #[default]
+    Undefined,
+    Never,
+    IfReplaceFails,
+    Always,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ComponentType {
+    #[default]
+    FileGroup,
+    Database,
+}
+
+impl From<ComponentType> for VSS_COMPONENT_TYPE {
+    fn from(value: ComponentType) -> Self {
+        match value {
+            ComponentType::FileGroup => VSS_CT_FILEGROUP,
+            ComponentType::Database => VSS_CT_DATABASE,
+        }
+    }
+}
+
+/// Parse an enumerated attribute with the given (value, variant) table
+fn parse_enum<T: Copy + Default>(
+    attrs: &mut Attributes,
+    name: &str,
+    values: &[(&str, T)],
+) -> Result<T, VssError> {
+    match attrs.take(name) {
+        None => Ok(T::default()),
+        Some(v) => values
+            .iter()
+            .find(|(s, _)| s.eq_ignore_ascii_case(&v))
+            .map(|(_, t)| *t)
+            .ok_or_else(|| VssError::Xml(format!("invalid {} attribute: {}", name, v))),
+    }
+}
+
+/// A file specification: the files matching `filespec` under `path`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileSpec {
+    pub path: String,
+    pub filespec: String,
+    pub recursive: bool,
+    pub alternate_path: Option<String>,
+}
+
+impl FileSpec {
+    fn from_element(e: &XmlElement) -> Result<Self, VssError> {
+        let mut attrs = Attributes::new("file specification", e.attributes.clone());
+        Ok(FileSpec {
+            path: attrs.take("path").unwrap_or_default(),
+            filespec: attrs.take("filespec").unwrap_or_default(),
+            recursive: attrs.take_bool("recursive")?.unwrap_or_default(),
+            alternate_path: attrs.take("alternatePath"),
+        })
+    }
+}
+
+impl std::fmt::Display for FileSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sep = if self.path.is_empty() || self.path.ends_with('\\') {
+            ""
+        } else {
+            "\\"
+        };
+        write!(f, "{}{}{}", self.path, sep, self.filespec)?;
+        if self.recursive {
+            write!(f, " (recursive)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The restore method declared by a writer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreMethodInfo {
+    pub method: RestoreMethod,
+    pub service: Option<String>,
+    pub user_procedure: Option<String>,
+    pub writer_restore: WriterRestore,
+    pub reboot_required: bool,
+    pub alternate_location_mappings: Vec<FileSpec>,
+}
+
+impl RestoreMethodInfo {
+    fn from_element(e: &XmlElement) -> Result<Self, VssError> {
+        let mut attrs = Attributes::new("RESTORE_METHOD", e.attributes.clone());
+        Ok(RestoreMethodInfo {
+            method: parse_enum(
+                &mut attrs,
+                "method",
+                &[
+                    ("RESTORE_IF_NONE_THERE", RestoreMethod::RestoreIfNotThere),
+                    (
+                        "RESTORE_IF_CAN_BE_REPLACED",
+                        RestoreMethod::RestoreIfCanReplace,
+                    ),
+                    ("STOP_RESTART_SERVICE", RestoreMethod::StopRestart),
+                    (
+                        "RESTORE_TO_ALTERNATE_LOCATION",
+                        RestoreMethod::RestoreToAlternateLocation,
+                    ),
+                    ("REPLACE_AT_REBOOT", RestoreMethod::RestoreAtReboot),
+                    (
+                        "REPLACE_AT_REBOOT_IF_CANNOT_REPLACE",
+                        RestoreMethod::RestoreAtRebootIfCannotReplace,
+                    ),
+                    ("CUSTOM", RestoreMethod::Custom),
+                    ("RESTORE_STOP_START", RestoreMethod::RestoreStopStart),
+                ],
+            )?,
+            service: attrs.take("service").filter(|s| !s.is_empty()),
+            user_procedure: attrs.take("userProcedure").filter(|s| !s.is_empty()),
+            writer_restore: parse_enum(
+                &mut attrs,
+                "writerRestore",
+                &[
+                    ("never", WriterRestore::Never),
+                    ("ifReplaceFails", WriterRestore::IfReplaceFails),
+                    ("always", WriterRestore::Always),
+                ],
+            )?,
+            reboot_required: attrs.take_bool("rebootRequired")?.unwrap_or_default(),
+            alternate_location_mappings: e
+                .elements()
+                .filter(|c| c.name == "ALTERNATE_LOCATION_MAPPING")
+                .map(FileSpec::from_element)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// A component declared by a writer, either a file group or a database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComponentInfo {
+    pub component_type: ComponentType,
+    pub logical_path: Option<String>,
+    pub name: String,
+    pub caption: Option<String>,
+    pub restore_metadata: bool,
+    pub notify_on_backup_complete: bool,
+    pub selectable: bool,
+    pub selectable_for_restore: bool,
+    pub component_flags: u32,
+    /// The files of a file group
+    pub files: Vec<FileSpec>,
+    /// The data files of a database
+    pub database_files: Vec<FileSpec>,
+    /// The log files of a database
+    pub log_files: Vec<FileSpec>,
+    /// The components this component depends on, by logical path
+    pub dependencies: Vec<String>,
+}
+
+impl ComponentInfo {
+    fn from_element(e: &XmlElement) -> Result<Self, VssError> {
+        let component_type = match e.name.as_str() {
+            "DATABASE" => ComponentType::Database,
+            _ => ComponentType::FileGroup,
+        };
+        let mut attrs = Attributes::new("component", e.attributes.clone());
+        let specs = |name: &str| {
+            e.elements()
+                .filter(|c| c.name == name)
+                .map(FileSpec::from_element)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(ComponentInfo {
+            component_type,
+            logical_path: attrs.take("logicalPath").filter(|s| !s.is_empty()),
+            name: attrs.required("componentName")?,
+            caption: attrs.take("caption").filter(|s| !s.is_empty()),
+            restore_metadata: attrs.take_bool("restoreMetadata")?.unwrap_or_default(),
+            notify_on_backup_complete: attrs
+                .take_bool("notifyOnBackupComplete")?
+                .unwrap_or_default(),
+            selectable: attrs.take_bool("selectable")?.unwrap_or_default(),
+            selectable_for_restore: attrs.take_bool("selectableForRestore")?.unwrap_or_default(),
+            component_flags: attrs.take_parse("componentFlags")?.unwrap_or_default(),
+            files: specs("FILE_LIST")?,
+            database_files: specs("DATABASE_FILES")?,
+            log_files: specs("DATABASE_LOGFILES")?,
+            dependencies: e
+                .elements()
+                .filter(|c| c.name == "DEPENDENCY")
+                .filter_map(|c| {
+                    let path = c.attribute("logicalPath").unwrap_or_default();
+                    let name = c.attribute("componentName")?;
+                    Some(full_path(path, name))
+                })
+                .collect(),
+        })
+    }
+
+    /// The full path of the component, `logical_path\name`
+    pub fn full_path(&self) -> String {
+        full_path(self.logical_path.as_deref().unwrap_or_default(), &self.name)
+    }
+}
+
+fn full_path(logical_path: &str, name: &str) -> String {
+    if logical_path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}\\{}", logical_path, name)
+    }
+}
+
+/// The metadata of one writer, as saved by `IVssExamineWriterMetadata::SaveAsXML`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriterMetadata {
+    pub writer_id: GUID,
+    pub instance_id: GUID,
+    pub writer_name: String,
+    pub instance_name: Option<String>,
+    pub usage_type: UsageType,
+    pub source_type: SourceType,
+    pub restore_method: RestoreMethodInfo,
+    pub components: Vec<ComponentInfo>,
+    pub include_files: Vec<FileSpec>,
+    pub exclude_files: Vec<FileSpec>,
+}
+
+impl WriterMetadata {
+    /// Parse the `WRITER_METADATA` document of a writer
+    pub fn from_xml(xml: &str) -> Result<Self, VssError> {
+        Self::from_element(&XmlElement::parse(xml)?)
+    }
+
+    pub fn from_element(e: &XmlElement) -> Result<Self, VssError> {
+        if e.name != "WRITER_METADATA" {
+            return Err(VssError::Xml(format!(
+                "expected WRITER_METADATA, found {}",
+                e.name
+            )));
+        }
+        let identification = e
+            .elements()
+            .find(|c| c.name == "IDENTIFICATION")
+            .ok_or_else(|| VssError::Xml("WRITER_METADATA has no IDENTIFICATION".to_owned()))?;
+        let mut attrs = Attributes::new("IDENTIFICATION", identification.attributes.clone());
+
+        let mut metadata = WriterMetadata {
+            writer_id: attrs.required_guid("writerId")?,
+            instance_id: attrs.take_guid("instanceId")?.unwrap_or_default(),
+            writer_name: attrs.take("friendlyName").unwrap_or_default(),
+            instance_name: attrs.take("instanceName").filter(|s| !s.is_empty()),
+            usage_type: parse_enum(
+                &mut attrs,
+                "usage",
+                &[
+                    ("BOOTABLE_SYSTEM_STATE", UsageType::BootableSystemState),
+                    ("SYSTEM_SERVICE", UsageType::SystemService),
+                    ("USER_DATA", UsageType::UserData),
+                    ("OTHER", UsageType::Other),
+                ],
+            )?,
+            source_type: parse_enum(
+                &mut attrs,
+                "dataSource",
+                &[
+                    ("TRANSACTION_DB", SourceType::TransactedDb),
+                    ("NONTRANSACTION_DB", SourceType::NonTransactedDb),
+                    ("OTHER", SourceType::Other),
+                ],
+            )?,
+            ..Default::default()
+        };
+
+        for child in e.elements() {
+            match child.name.as_str() {
+                "BACKUP_LOCATIONS" => {
+                    for component in child
+                        .elements()
+                        .filter(|c| c.name == "FILE_GROUP" || c.name == "DATABASE")
+                    {
+                        metadata
+                            .components
+                            .push(ComponentInfo::from_element(component)?);
+                    }
+                }
+                "RESTORE_METHOD" => {
+                    metadata.restore_method = RestoreMethodInfo::from_element(child)?
+                }
+                "INCLUDE_FILES" => metadata.include_files.push(FileSpec::from_element(child)?),
+                "EXCLUDE_FILES" => metadata.exclude_files.push(FileSpec::from_element(child)?),
+                _ => {}
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// The components that are file groups
+    pub fn file_groups(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.components
+            .iter()
+            .filter(|c| c.component_type == ComponentType::FileGroup)
+    }
+
+    /// The components that are databases
+    pub fn databases(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.components
+            .iter()
+            .filter(|c| c.component_type == ComponentType::Database)
+    }
+
+    /// The log files of all the databases
+    pub fn log_files(&self) -> impl Iterator<Item = &FileSpec> {
+        self.components.iter().flat_map(|c| c.log_files.iter())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    pub(crate) const SYSTEM_WRITER: &str = r#"<?xml version="1.0"?>
+<WRITER_METADATA xmlns="x-schema:#VssWriterMetadataInfo" version="1.1">
+    <IDENTIFICATION writerId="e8132975-6f93-4464-a53e-1050253ae220" instanceId="d8cd8b7b-5d1b-4c1b-a6c1-0d4f9a4fa5c6" friendlyName="System Writer" usage="BOOTABLE_SYSTEM_STATE" dataSource="OTHER"/>
+    <BACKUP_LOCATIONS>
+        <FILE_GROUP logicalPath="" componentName="System Files" caption="" restoreMetadata="no" notifyOnBackupComplete="no" selectable="yes" selectableForRestore="no" componentFlags="0">
+            <FILE_LIST path="C:\Windows\system32" filespec="*.dll" recursive="no"/>
+            <FILE_LIST path="C:\Windows\system32\drivers" filespec="*.sys" recursive="yes"/>
+        </FILE_GROUP>
+    </BACKUP_LOCATIONS>
+    <RESTORE_METHOD method="REPLACE_AT_REBOOT" service="" userProcedure="" writerRestore="always" rebootRequired="yes"/>
+    <EXCLUDE_FILES path="C:\Windows\Temp" filespec="*" recursive="yes"/>
+</WRITER_METADATA>"#;
+
+    pub(crate) const SQL_WRITER: &str = r#"<WRITER_METADATA xmlns="x-schema:#VssWriterMetadataInfo" version="1.1">
+    <IDENTIFICATION writerId="a65faa63-5ea8-4ebc-9dbd-a0c4db26912a" instanceId="0bada1de-01a9-4625-8278-69e735f39dd2" friendlyName="SqlServerWriter" instanceName="MSSQLSERVER" usage="USER_DATA" dataSource="TRANSACTION_DB"/>
+    <BACKUP_LOCATIONS>
+        <DATABASE logicalPath="HOST\MSSQLSERVER" componentName="master" caption="" restoreMetadata="no" notifyOnBackupComplete="no" selectable="yes" selectableForRestore="yes" componentFlags="0">
+            <DATABASE_FILES path="C:\Data" filespec="master.mdf" recursive="no"/>
+            <DATABASE_LOGFILES path="C:\Data" filespec="mastlog.ldf" recursive="no"/>
+        </DATABASE>
+        <DATABASE logicalPath="HOST\MSSQLSERVER" componentName="sales" restoreMetadata="no" notifyOnBackupComplete="no" selectable="yes" selectableForRestore="yes" componentFlags="0">
+            <DATABASE_FILES path="D:\Data" filespec="sales.mdf" recursive="no"/>
+            <DATABASE_LOGFILES path="E:\Logs" filespec="sales_log.ldf" recursive="no"/>
+            <DEPENDENCY logicalPath="HOST\MSSQLSERVER" componentName="master"/>
+        </DATABASE>
+    </BACKUP_LOCATIONS>
+    <RESTORE_METHOD method="RESTORE_IF_CAN_BE_REPLACED" writerRestore="never" rebootRequired="no"/>
+</WRITER_METADATA>"#;
+
+    #[test]
+    fn test_system_writer() {
+        let writer = WriterMetadata::from_xml(SYSTEM_WRITER).unwrap();
+        assert_eq!(
+            writer.writer_id,
+            GUID::from_u128(0xe8132975_6f93_4464_a53e_1050253ae220)
+        );
+        assert_eq!(writer.writer_name, "System Writer");
+        assert_eq!(writer.instance_name, None);
+        assert_eq!(writer.usage_type, UsageType::BootableSystemState);
+        assert_eq!(writer.source_type, SourceType::Other);
+        assert_eq!(writer.restore_method.method, RestoreMethod::RestoreAtReboot);
+        assert_eq!(writer.restore_method.writer_restore, WriterRestore::Always);
+        assert!(writer.restore_method.reboot_required);
+        assert_eq!(writer.restore_method.service, None);
+
+        let groups = writer.file_groups().collect::<Vec<_>>();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].full_path(), "System Files");
+        assert!(groups[0].selectable);
+        assert_eq!(
+            groups[0].files[1].to_string(),
+            r"C:\Windows\system32\drivers\*.sys (recursive)"
+        );
+        assert_eq!(writer.databases().count(), 0);
+        assert_eq!(writer.exclude_files[0].path, r"C:\Windows\Temp");
+        assert!(writer.include_files.is_empty());
+    }
+
+    #[test]
+    fn test_database_writer() {
+        let writer = WriterMetadata::from_xml(SQL_WRITER).unwrap();
+        assert_eq!(writer.instance_name.as_deref(), Some("MSSQLSERVER"));
+        assert_eq!(writer.usage_type, UsageType::UserData);
+        assert_eq!(writer.source_type, SourceType::TransactedDb);
+        assert_eq!(
+            writer.restore_method.method,
+            RestoreMethod::RestoreIfCanReplace
+        );
+
+        let databases = writer.databases().collect::<Vec<_>>();
+        assert_eq!(databases.len(), 2);
+        assert_eq!(databases[1].full_path(), r"HOST\MSSQLSERVER\sales");
+        assert_eq!(databases[1].database_files[0].filespec, "sales.mdf");
+        assert_eq!(databases[1].dependencies, vec![r"HOST\MSSQLSERVER\master"]);
+        assert_eq!(
+            writer
+                .log_files()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>(),
+            vec![r"C:\Data\mastlog.ldf", r"E:\Logs\sales_log.ldf"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_metadata() {
+        assert!(matches!(
+            WriterMetadata::from_xml("<BACKUP_COMPONENTS/>"),
+            Err(VssError::Xml(_))
+        ));
+        assert!(matches!(
+            WriterMetadata::from_xml("<WRITER_METADATA/>"),
+            Err(VssError::Xml(_))
+        ));
+        assert!(matches!(
+            WriterMetadata::from_xml(
+                r#"<WRITER_METADATA><IDENTIFICATION writerId="e8132975-6f93-4464-a53e-1050253ae220" usage="SOMETIMES"/></WRITER_METADATA>"#
+            ),
+            Err(VssError::Xml(_))
+        ));
+    }
+}
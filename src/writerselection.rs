@@ -0,0 +1,381 @@
+use std::collections::HashSet;
+
+use windows::core::GUID;
+
+use crate::{
+    error::VssError,
+    utils::parse_guid,
+    writermetadata::{ComponentType, WriterMetadata},
+};
+
+/// A component to add to the backup with `IVssBackupComponents::AddComponent`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectedComponent {
+    pub instance_id: GUID,
+    pub writer_id: GUID,
+    pub component_type: ComponentType,
+    pub logical_path: Option<String>,
+    pub name: String,
+}
+
+/// The writers and components taking part in a backup, resolved from the -wi / -wx selectors
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriterSelection {
+    /// The writer instances to disable with `IVssBackupComponents::DisableWriterInstances`
+    pub excluded_instances: Vec<GUID>,
+    /// The components to add to the backup
+    pub components: Vec<SelectedComponent>,
+}
+
+/// What a selector matched in the writer metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// A whole writer, by index
+    Writer(usize),
+    /// A component, by writer and component index
+    Component(usize, usize),
+}
+
+/// Match `text` against a `*` / `?` wildcard pattern, ignoring the case
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // The position of the last '*' in the pattern and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last '*' match one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether `writer` is designated by `pattern`: its writer or instance id,
+/// or a wildcard on its writer or instance name
+fn writer_matches(pattern: &str, writer: &WriterMetadata) -> bool {
+    if let Ok(guid) = parse_guid(pattern) {
+        return guid == writer.writer_id || guid == writer.instance_id;
+    }
+    wildcard_match(pattern, &writer.writer_name)
+        || writer
+            .instance_name
+            .as_deref()
+            .is_some_and(|name| wildcard_match(pattern, name))
+}
+
+/// Split a `writer\component` or `writer:\component` selector
+fn split_selector(selector: &str) -> (&str, Option<&str>) {
+    if let Some((writer, path)) = selector.split_once(":\\") {
+        return (writer, Some(path));
+    }
+    match selector.split_once('\\') {
+        Some((writer, path)) => (writer, Some(path)),
+        None => (selector, None),
+    }
+}
+
+/// Resolve a selector to the writers or components it designates
+fn resolve(selector: &str, writers: &[WriterMetadata]) -> Vec<Target> {
+    let (writer_pattern, path_pattern) = split_selector(selector.trim());
+    let mut targets = Vec::new();
+    for (w, writer) in writers.iter().enumerate() {
+        if !writer_matches(writer_pattern, writer) {
+            continue;
+        }
+        match path_pattern {
+            None => targets.push(Target::Writer(w)),
+            Some(path_pattern) => targets.extend(
+                writer
+                    .components
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| {
+                        wildcard_match(path_pattern, &c.full_path())
+                            || (!path_pattern.contains('\\')
+                                && wildcard_match(path_pattern, &c.name))
+                    })
+                    .map(|(c, _)| Target::Component(w, c)),
+            ),
+        }
+    }
+    targets
+}
+
+/// Whether the component at `path` is `ancestor` or one of its sub-components
+fn is_under(path: &str, ancestor: &str) -> bool {
+    path.eq_ignore_ascii_case(ancestor)
+        || (path.len() > ancestor.len()
+            && path.is_char_boundary(ancestor.len())
+            && path[..ancestor.len()].eq_ignore_ascii_case(ancestor)
+            && path[ancestor.len()..].starts_with('\\'))
+}
+
+/// Resolve the -wi (`included`) and -wx (`excluded`) selectors against the gathered writers.
+///
+/// A selector is a writer name, a writer or instance id, or a `writer\component` path;
+/// names and paths may contain `*` and `?` wildcards.
+/// Every component of the remaining writers is added, except the excluded components and
+/// their sub-components. Excluding a component which is not selectable for backup excludes its
+/// whole writer.
+/// It fails if an included selector matches nothing or something excluded.
+pub fn select_components(
+    writers: &[WriterMetadata],
+    included: &[String],
+    excluded: &[String],
+) -> Result<WriterSelection, VssError> {
+    let mut excluded_writers = HashSet::new();
+    let mut excluded_components = HashSet::new();
+    for selector in excluded {
+        let targets = resolve(selector, writers);
+        if targets.is_empty() {
+            tracing::warn!("Excluded writer or component {} not found", selector);
+        }
+        for target in targets {
+            match target {
+                Target::Writer(w) => {
+                    excluded_writers.insert(w);
+                }
+                Target::Component(w, c) if !writers[w].components[c].selectable => {
+                    tracing::info!(
+                        "Component {} is not selectable, excluding the writer {}",
+                        writers[w].components[c].full_path(),
+                        writers[w].writer_name
+                    );
+                    excluded_writers.insert(w);
+                }
+                Target::Component(w, c) => {
+                    excluded_components.insert((w, c));
+                }
+            }
+        }
+    }
+
+    // The full paths of the components, per writer
+    let paths: Vec<Vec<String>> = writers
+        .iter()
+        .map(|w| w.components.iter().map(|c| c.full_path()).collect())
+        .collect();
+    let is_excluded = |w: usize, c: usize| {
+        excluded_writers.contains(&w)
+            || excluded_components
+                .iter()
+                .any(|&(ew, ec)| ew == w && is_under(&paths[w][c], &paths[w][ec]))
+    };
+    // Whether some sub-components of the component are excluded
+    let has_excluded_descendant = |w: usize, c: usize| {
+        excluded_components
+            .iter()
+            .any(|&(ew, ec)| ew == w && ec != c && is_under(&paths[w][ec], &paths[w][c]))
+    };
+
+    for selector in included {
+        let targets = resolve(selector, writers);
+        if targets.is_empty() {
+            return Err(VssError::InvalidArgument(format!(
+                "included writer or component {} not found",
+                selector
+            )));
+        }
+        for target in targets {
+            let conflict = match target {
+                Target::Writer(w) => excluded_writers.contains(&w),
+                Target::Component(w, c) => is_excluded(w, c),
+            };
+            if conflict {
+                return Err(VssError::InvalidArgument(format!(
+                    "{} is both included and excluded",
+                    selector
+                )));
+            }
+        }
+    }
+
+    let mut selection = WriterSelection::default();
+    for (w, writer) in writers.iter().enumerate() {
+        if excluded_writers.contains(&w) {
+            selection.excluded_instances.push(writer.instance_id);
+            continue;
+        }
+        for (c, component) in writer.components.iter().enumerate() {
+            // Add the largest sub-trees without excluded components: a component is added
+            // when all its ancestors had to be split because of an excluded descendant.
+            let covered_by_ancestor = (0..writer.components.len()).any(|a| {
+                a != c && is_under(&paths[w][c], &paths[w][a]) && !has_excluded_descendant(w, a)
+            });
+            if is_excluded(w, c) || has_excluded_descendant(w, c) || covered_by_ancestor {
+                continue;
+            }
+            selection.components.push(SelectedComponent {
+                instance_id: writer.instance_id,
+                writer_id: writer.writer_id,
+                component_type: component.component_type,
+                logical_path: component.logical_path.clone(),
+                name: component.name.clone(),
+            });
+        }
+    }
+    Ok(selection)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::writermetadata::test::{SQL_WRITER, SYSTEM_WRITER};
+
+    fn writers() -> Vec<WriterMetadata> {
+        vec![
+            WriterMetadata::from_xml(SYSTEM_WRITER).unwrap(),
+            WriterMetadata::from_xml(SQL_WRITER).unwrap(),
+        ]
+    }
+
+    fn strings(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn names(selection: &WriterSelection) -> Vec<String> {
+        selection
+            .components
+            .iter()
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("sql*", "SqlServerWriter"));
+        assert!(wildcard_match("*Writer", "SqlServerWriter"));
+        assert!(wildcard_match("S?lServer*r", "SqlServerWriter"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("HOST\\*\\m*", "HOST\\MSSQLSERVER\\master"));
+        assert!(!wildcard_match("sql", "SqlServerWriter"));
+        assert!(!wildcard_match("?", ""));
+        assert!(!wildcard_match("*x", "SqlServerWriter"));
+    }
+
+    #[test]
+    fn test_select_all() {
+        let writers = writers();
+        let selection = select_components(&writers, &[], &[]).unwrap();
+        assert!(selection.excluded_instances.is_empty());
+        assert_eq!(names(&selection), ["System Files", "master", "sales"]);
+        assert_eq!(selection.components[1].writer_id, writers[1].writer_id);
+        assert_eq!(
+            selection.components[1].logical_path.as_deref(),
+            Some("HOST\\MSSQLSERVER")
+        );
+        assert_eq!(
+            selection.components[1].component_type,
+            ComponentType::Database
+        );
+    }
+
+    #[test]
+    fn test_exclude() {
+        let writers = writers();
+        // By name, with wildcards
+        let selection = select_components(&writers, &[], &strings(&["system*"])).unwrap();
+        assert_eq!(selection.excluded_instances, [writers[0].instance_id]);
+        assert_eq!(names(&selection), ["master", "sales"]);
+
+        // By writer and instance id
+        let selection = select_components(
+            &writers,
+            &[],
+            &strings(&["{A65FAA63-5EA8-4EBC-9DBD-A0C4DB26912A}"]),
+        )
+        .unwrap();
+        assert_eq!(selection.excluded_instances, [writers[1].instance_id]);
+        let selection = select_components(
+            &writers,
+            &[],
+            &strings(&["0bada1de-01a9-4625-8278-69e735f39dd2"]),
+        )
+        .unwrap();
+        assert_eq!(selection.excluded_instances, [writers[1].instance_id]);
+
+        // A component, by logical path or name
+        for selector in [
+            "SqlServerWriter\\HOST\\MSSQLSERVER\\sales",
+            "SqlServerWriter:\\HOST\\*\\s*",
+            "MSSQLSERVER\\sales",
+        ] {
+            let selection = select_components(&writers, &[], &strings(&[selector])).unwrap();
+            assert!(selection.excluded_instances.is_empty());
+            assert_eq!(names(&selection), ["System Files", "master"]);
+        }
+
+        // Unknown exclusions are ignored
+        let selection = select_components(&writers, &[], &strings(&["Exchange*"])).unwrap();
+        assert_eq!(names(&selection).len(), 3);
+    }
+
+    #[test]
+    fn test_exclude_sub_component() {
+        let mut writers = writers();
+        // Turn the databases into sub-components of a MSSQLSERVER component
+        let mut instance = writers[1].components[0].clone();
+        instance.logical_path = Some("HOST".to_owned());
+        instance.name = "MSSQLSERVER".to_owned();
+        writers[1].components.insert(0, instance);
+
+        let selection = select_components(&writers, &[], &[]).unwrap();
+        assert_eq!(names(&selection), ["System Files", "MSSQLSERVER"]);
+
+        let selection =
+            select_components(&writers, &[], &strings(&["SqlServerWriter\\*\\sales"])).unwrap();
+        assert_eq!(names(&selection), ["System Files", "master"]);
+
+        // A non selectable component can't be left out of its writer
+        writers[1].components[2].selectable = false;
+        let selection =
+            select_components(&writers, &[], &strings(&["SqlServerWriter\\*\\sales"])).unwrap();
+        assert_eq!(selection.excluded_instances, [writers[1].instance_id]);
+        assert_eq!(names(&selection), ["System Files"]);
+    }
+
+    #[test]
+    fn test_include() {
+        let writers = writers();
+        let selection = select_components(
+            &writers,
+            &strings(&[
+                "System Writer",
+                "SqlServerWriter\\HOST\\MSSQLSERVER\\master",
+            ]),
+            &strings(&["SqlServerWriter\\*\\sales"]),
+        )
+        .unwrap();
+        assert_eq!(names(&selection), ["System Files", "master"]);
+
+        assert!(matches!(
+            select_components(&writers, &strings(&["Exchange Writer"]), &[]),
+            Err(VssError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            select_components(&writers, &strings(&["SqlServerWriter\\msdb"]), &[]),
+            Err(VssError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            select_components(
+                &writers,
+                &strings(&["SqlServerWriter\\*\\sales"]),
+                &strings(&["Sql*"])
+            ),
+            Err(VssError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+use windows::{
+    core::{GUID, HRESULT},
+    Win32::Storage::Vss::{
+        VSS_E_WRITERERROR_INCONSISTENTSNAPSHOT, VSS_E_WRITERERROR_OUTOFRESOURCES,
+        VSS_E_WRITERERROR_RETRYABLE, VSS_E_WRITERERROR_TIMEOUT, VSS_WRITER_STATE,
+        VSS_WS_FAILED_AT_BACKUPSHUTDOWN, VSS_WS_FAILED_AT_BACKUP_COMPLETE, VSS_WS_FAILED_AT_FREEZE,
+        VSS_WS_FAILED_AT_IDENTIFY, VSS_WS_FAILED_AT_POST_RESTORE, VSS_WS_FAILED_AT_POST_SNAPSHOT,
+        VSS_WS_FAILED_AT_PREPARE_BACKUP, VSS_WS_FAILED_AT_PREPARE_SNAPSHOT,
+        VSS_WS_FAILED_AT_PRE_RESTORE, VSS_WS_FAILED_AT_THAW, VSS_WS_STABLE,
+    },
+};
+
+use crate::{error::VssError, utils::get_string_for_writer_state};
+
+/// Whether a writer failure may go away when the shadow copy is retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The writer did not fail
+    None,
+    /// The writer reported a transient error, e.g. a timeout or an inconsistent shadow copy
+    Retryable,
+    /// Retrying will fail the same way until the writer problem is fixed
+    NonRetryable,
+}
+
+/// The status of one writer, as returned by `IVssBackupComponents::GetWriterStatus`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriterStatus {
+    pub writer_name: String,
+    pub writer_id: GUID,
+    pub instance_id: GUID,
+    pub state: VSS_WRITER_STATE,
+    /// The failure reported by the writer, `S_OK` if none
+    pub failure: HRESULT,
+    /// The failure reported by the application behind the writer, `S_OK` if none
+    pub application_failure: HRESULT,
+    pub application_message: Option<String>,
+}
+
+impl Default for WriterStatus {
+    fn default() -> Self {
+        Self {
+            writer_name: Default::default(),
+            writer_id: Default::default(),
+            instance_id: Default::default(),
+            state: VSS_WS_STABLE,
+            failure: Default::default(),
+            application_failure: Default::default(),
+            application_message: Default::default(),
+        }
+    }
+}
+
+impl WriterStatus {
+    /// Whether the writer is in one of the `VSS_WS_FAILED_AT_*` states or reported a failure
+    pub fn is_failed(&self) -> bool {
+        matches!(
+            self.state,
+            VSS_WS_FAILED_AT_IDENTIFY
+                | VSS_WS_FAILED_AT_PREPARE_BACKUP
+                | VSS_WS_FAILED_AT_PREPARE_SNAPSHOT
+                | VSS_WS_FAILED_AT_FREEZE
+                | VSS_WS_FAILED_AT_THAW
+                | VSS_WS_FAILED_AT_POST_SNAPSHOT
+                | VSS_WS_FAILED_AT_BACKUP_COMPLETE
+                | VSS_WS_FAILED_AT_PRE_RESTORE
+                | VSS_WS_FAILED_AT_POST_RESTORE
+                | VSS_WS_FAILED_AT_BACKUPSHUTDOWN
+        ) || self.failure.is_err()
+    }
+
+    /// Classify the failure the same way vshadow.exe does: only the inconsistent shadow copy,
+    /// out of resources, timeout and retryable writer errors are worth another attempt.
+    pub fn failure_kind(&self) -> FailureKind {
+        if !self.is_failed() {
+            return FailureKind::None;
+        }
+        match self.failure {
+            VSS_E_WRITERERROR_INCONSISTENTSNAPSHOT
+            | VSS_E_WRITERERROR_OUTOFRESOURCES
+            | VSS_E_WRITERERROR_TIMEOUT
+            | VSS_E_WRITERERROR_RETRYABLE => FailureKind::Retryable,
+            _ => FailureKind::NonRetryable,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.failure_kind() == FailureKind::Retryable
+    }
+
+    /// The writer failure as an error, if any
+    pub fn error(&self) -> Option<VssError> {
+        self.failure
+            .is_err()
+            .then(|| VssError::from_hresult("GetWriterStatus", self.failure))
+    }
+}
+
+impl std::fmt::Display for WriterStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "* WRITER \"{}\"", self.writer_name)?;
+        writeln!(
+            f,
+            "   - Status: {}",
+            get_string_for_writer_state(self.state)
+        )?;
+        match self.error() {
+            Some(e) => writeln!(f, "   - Writer Failure code: {}", e)?,
+            None => writeln!(f, "   - Writer Failure code: 0x{:08X}", self.failure.0)?,
+        }
+        if self.application_failure.is_err() || self.application_message.is_some() {
+            writeln!(
+                f,
+                "   - Application Failure code: 0x{:08X} {}",
+                self.application_failure.0,
+                self.application_message.as_deref().unwrap_or_default()
+            )?;
+        }
+        writeln!(f, "   - Writer ID: {:?}", self.writer_id)?;
+        write!(f, "   - Instance ID: {:?}", self.instance_id)
+    }
+}
+
+/// Whether retrying the shadow copy may succeed: at least one writer failed and all the
+/// failures are retryable
+pub fn is_retryable(statuses: &[WriterStatus]) -> bool {
+    statuses.iter().any(|s| s.is_failed())
+        && statuses
+            .iter()
+            .all(|s| s.failure_kind() != FailureKind::NonRetryable)
+}
+
+#[cfg(test)]
+mod test {
+    use windows::Win32::Storage::Vss::{
+        VSS_E_WRITERERROR_NONRETRYABLE, VSS_E_WRITER_NOT_RESPONDING,
+        VSS_WS_WAITING_FOR_BACKUP_COMPLETE,
+    };
+
+    use super::*;
+
+    fn status(state: VSS_WRITER_STATE, failure: HRESULT) -> WriterStatus {
+        WriterStatus {
+            writer_name: "SqlServerWriter".to_owned(),
+            state,
+            failure,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_failure_kind() {
+        let ok = status(VSS_WS_STABLE, HRESULT(0));
+        assert!(!ok.is_failed());
+        assert_eq!(ok.failure_kind(), FailureKind::None);
+        assert_eq!(
+            status(VSS_WS_WAITING_FOR_BACKUP_COMPLETE, HRESULT(0)).failure_kind(),
+            FailureKind::None
+        );
+
+        for hr in [
+            VSS_E_WRITERERROR_INCONSISTENTSNAPSHOT,
+            VSS_E_WRITERERROR_OUTOFRESOURCES,
+            VSS_E_WRITERERROR_TIMEOUT,
+            VSS_E_WRITERERROR_RETRYABLE,
+        ] {
+            assert!(status(VSS_WS_FAILED_AT_FREEZE, hr).is_retryable());
+        }
+        for hr in [
+            VSS_E_WRITERERROR_NONRETRYABLE,
+            VSS_E_WRITER_NOT_RESPONDING,
+            HRESULT(0),
+        ] {
+            assert_eq!(
+                status(VSS_WS_FAILED_AT_PREPARE_SNAPSHOT, hr).failure_kind(),
+                FailureKind::NonRetryable
+            );
+        }
+        // A failure code is a failure, whatever the state
+        assert!(status(VSS_WS_STABLE, VSS_E_WRITERERROR_TIMEOUT).is_retryable());
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let ok = status(VSS_WS_STABLE, HRESULT(0));
+        let timeout = status(VSS_WS_FAILED_AT_FREEZE, VSS_E_WRITERERROR_TIMEOUT);
+        let broken = status(VSS_WS_FAILED_AT_THAW, VSS_E_WRITERERROR_NONRETRYABLE);
+        assert!(!is_retryable(&[]));
+        assert!(is_retryable(&[ok.clone(), timeout.clone()]));
+        assert!(!is_retryable(&[ok, timeout, broken]));
+    }
+
+    #[test]
+    fn test_display() {
+        let s = status(VSS_WS_FAILED_AT_FREEZE, VSS_E_WRITERERROR_TIMEOUT).to_string();
+        assert!(s.starts_with("* WRITER \"SqlServerWriter\"\n"));
+        assert!(s.contains("Status: VSS_WS_FAILED_AT_FREEZE"));
+        assert!(s.contains("VSS_E_WRITERERROR_TIMEOUT"));
+    }
+}
@@ -0,0 +1,295 @@
+use std::str::FromStr;
+
+use quick_xml::{escape::escape, events::Event, Reader};
+use windows::core::GUID;
+
+use crate::{error::VssError, utils::parse_guid};
+
+/// A node of an XML document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+}
+
+/// A generic XML element, used to keep the parts of the VSS documents without a typed model
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmlElement {
+    pub name: String,
+    /// Attributes in document order
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+}
+
+fn xml_error(e: impl std::fmt::Display) -> VssError {
+    VssError::Xml(e.to_string())
+}
+
+impl XmlElement {
+    pub fn new(name: &str) -> Self {
+        XmlElement {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Parse the root element of a document.
+    /// The declaration, comments and processing instructions are skipped.
+    pub fn parse(xml: &str) -> Result<Self, VssError> {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+
+        // The elements being parsed, the root first
+        let mut stack: Vec<XmlElement> = Vec::new();
+        loop {
+            match reader.read_event().map_err(xml_error)? {
+                Event::Start(e) => stack.push(Self::from_start(&e)?),
+                Event::Empty(e) => {
+                    let element = Self::from_start(&e)?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(XmlNode::Element(element)),
+                        None => return Ok(element),
+                    }
+                }
+                Event::End(_) => {
+                    let element = stack.pop().ok_or_else(|| xml_error("unexpected end tag"))?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(XmlNode::Element(element)),
+                        None => return Ok(element),
+                    }
+                }
+                Event::Text(t) => {
+                    let text = t.unescape().map_err(xml_error)?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(XmlNode::Text(text.into_owned()));
+                    }
+                }
+                Event::CData(t) => {
+                    let text = String::from_utf8(t.into_inner().into_owned()).map_err(xml_error)?;
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(XmlNode::Text(text));
+                    }
+                }
+                Event::Eof => return Err(xml_error("no root element")),
+                Event::Decl(_) | Event::Comment(_) | Event::PI(_) | Event::DocType(_) => {}
+            }
+        }
+    }
+
+    fn from_start(e: &quick_xml::events::BytesStart) -> Result<Self, VssError> {
+        let name = String::from_utf8(e.name().as_ref().to_vec()).map_err(xml_error)?;
+        let mut attributes = Vec::new();
+        for attr in e.attributes() {
+            let attr = attr.map_err(xml_error)?;
+            let key = String::from_utf8(attr.key.as_ref().to_vec()).map_err(xml_error)?;
+            let value = attr.unescape_value().map_err(xml_error)?;
+            attributes.push((key, value.into_owned()));
+        }
+        Ok(XmlElement {
+            name,
+            attributes,
+            children: Vec::new(),
+        })
+    }
+
+    /// Serialize the element and its children, without declaration nor indentation
+    pub fn to_xml(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(&self.name);
+        for (key, value) in self.attributes.iter() {
+            out.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+        if self.children.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        for child in self.children.iter() {
+            match child {
+                XmlNode::Element(e) => e.write(out),
+                XmlNode::Text(t) => out.push_str(&escape(t)),
+            }
+        }
+        out.push_str(&format!("</{}>", self.name));
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The child elements, skipping the text nodes
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|c| match c {
+            XmlNode::Element(e) => Some(e),
+            XmlNode::Text(_) => None,
+        })
+    }
+
+    /// The concatenated text of the direct children
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|c| match c {
+                XmlNode::Text(t) => Some(t.as_str()),
+                XmlNode::Element(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Attributes of an element being converted to a typed struct.
+/// The typed fields are taken out, the remaining attributes are kept as they are.
+pub(crate) struct Attributes {
+    element: &'static str,
+    attributes: Vec<(String, String)>,
+}
+
+impl Attributes {
+    pub(crate) fn new(element: &'static str, attributes: Vec<(String, String)>) -> Self {
+        Attributes {
+            element,
+            attributes,
+        }
+    }
+
+    pub(crate) fn take(&mut self, name: &str) -> Option<String> {
+        let i = self.attributes.iter().position(|(k, _)| k == name)?;
+        Some(self.attributes.remove(i).1)
+    }
+
+    pub(crate) fn required(&mut self, name: &str) -> Result<String, VssError> {
+        self.take(name)
+            .ok_or_else(|| VssError::Xml(format!("{} has no {} attribute", self.element, name)))
+    }
+
+    /// A "yes"/"no" attribute
+    pub(crate) fn take_bool(&mut self, name: &str) -> Result<Option<bool>, VssError> {
+        self.take(name)
+            .map(|v| match v.as_str() {
+                "yes" => Ok(true),
+                "no" => Ok(false),
+                _ => Err(self.invalid(name, &v)),
+            })
+            .transpose()
+    }
+
+    pub(crate) fn take_guid(&mut self, name: &str) -> Result<Option<GUID>, VssError> {
+        self.take(name)
+            .map(|v| parse_guid(&v).map_err(|_| self.invalid(name, &v)))
+            .transpose()
+    }
+
+    pub(crate) fn required_guid(&mut self, name: &str) -> Result<GUID, VssError> {
+        let v = self.required(name)?;
+        parse_guid(&v).map_err(|_| self.invalid(name, &v))
+    }
+
+    pub(crate) fn take_parse<T: FromStr>(&mut self, name: &str) -> Result<Option<T>, VssError> {
+        self.take(name)
+            .map(|v| v.parse().map_err(|_| self.invalid(name, &v)))
+            .transpose()
+    }
+
+    fn invalid(&self, name: &str, value: &str) -> VssError {
+        VssError::Xml(format!(
+            "invalid {} attribute of {}: {}",
+            name, self.element, value
+        ))
+    }
+
+    /// The attributes without a typed field
+    pub(crate) fn into_rest(self) -> Vec<(String, String)> {
+        self.attributes
+    }
+}
+
+/// Builds the attribute list of a typed struct being serialized
+#[derive(Default)]
+pub(crate) struct AttributesWriter(Vec<(String, String)>);
+
+impl AttributesWriter {
+    pub(crate) fn str(mut self, name: &str, value: Option<&str>) -> Self {
+        if let Some(value) = value {
+            self.0.push((name.to_owned(), value.to_owned()));
+        }
+        self
+    }
+
+    pub(crate) fn bool(self, name: &str, value: Option<bool>) -> Self {
+        self.str(name, value.map(|v| if v { "yes" } else { "no" }))
+    }
+
+    pub(crate) fn guid(self, name: &str, value: Option<GUID>) -> Self {
+        self.display(name, value.map(format_guid))
+    }
+
+    pub(crate) fn display<T: ToString>(self, name: &str, value: Option<T>) -> Self {
+        let value = value.map(|v| v.to_string());
+        self.str(name, value.as_deref())
+    }
+
+    pub(crate) fn finish(mut self, rest: &[(String, String)]) -> Vec<(String, String)> {
+        self.0.extend(rest.iter().cloned());
+        self.0
+    }
+}
+
+/// Format a GUID the way VSS writes it in the XML documents, lowercase and without braces
+pub fn format_guid(guid: GUID) -> String {
+    format!("{:?}", guid).to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let xml = r#"<?xml version="1.0"?>
+<!-- comment -->
+<ROOT a="1" b="x &amp; y">
+    <CHILD name="c"/>
+    <TEXT>hello &lt;world&gt;</TEXT>
+</ROOT>"#;
+        let root = XmlElement::parse(xml).unwrap();
+        assert_eq!(root.name, "ROOT");
+        assert_eq!(root.attribute("b"), Some("x & y"));
+        assert_eq!(root.attribute("c"), None);
+        let children = root.elements().collect::<Vec<_>>();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].attribute("name"), Some("c"));
+        assert_eq!(children[1].text(), "hello <world>");
+
+        assert_eq!(
+            root.to_xml(),
+            r#"<ROOT a="1" b="x &amp; y"><CHILD name="c"/><TEXT>hello &lt;world&gt;</TEXT></ROOT>"#
+        );
+        assert_eq!(XmlElement::parse(&root.to_xml()).unwrap(), root);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(XmlElement::parse(""), Err(VssError::Xml(_))));
+        assert!(matches!(
+            XmlElement::parse("<A><B></A>"),
+            Err(VssError::Xml(_))
+        ));
+        assert!(matches!(XmlElement::parse("<A>"), Err(VssError::Xml(_))));
+    }
+
+    #[test]
+    fn test_format_guid() {
+        let guid = GUID::from_u128(0x3808876b_c176_4e48_b7ae_04046e6cc752);
+        assert_eq!(format_guid(guid), "3808876b-c176-4e48-b7ae-04046e6cc752");
+        assert_eq!(parse_guid(&format_guid(guid)).unwrap(), guid);
+    }
+}